@@ -16,8 +16,11 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
+import logging
 import os
 import stat
+import subprocess
+from typing import Optional
 
 
 __version__ = (0, 0, 9)
@@ -88,6 +91,35 @@ class Requirement(object):
     def met(self, session):
         raise NotImplementedError(self)
 
+    def try_met(self, session) -> Optional[bool]:
+        """Check whether this requirement is met, without raising.
+
+        Returns: whether the requirement is met, or None if that could not
+            be determined, e.g. because there is no check for this kind of
+            requirement or because the tool to check with is missing
+        """
+        try:
+            return self.met(session)
+        except NotImplementedError:
+            return None
+        except (OSError, subprocess.CalledProcessError) as e:
+            logging.debug("Unable to check whether %s is met: %s", self, e)
+            return None
+
+    def json(self):
+        """Serialize this requirement.
+
+        Returns: dictionary with the family and a payload, which
+            ognibuild.requirements.requirement_from_json turns back into
+            a requirement
+        """
+        payload = {k: v for (k, v) in vars(self).items() if k != "family"}
+        return {"family": self.family, "payload": payload}
+
+    @classmethod
+    def from_json(cls, payload):
+        return cls(**payload)
+
 
 class UpstreamOutput(object):
     def __init__(self, family):
@@ -15,6 +15,7 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
+import json
 import logging
 import os
 import shlex
@@ -25,8 +26,11 @@ from .buildlog import (
     ExplainInstallFixer,
     ExplainInstall,
     install_missing_reqs,
+    problem_to_upstream_requirement,
 )
-from .buildsystem import NoBuildToolsFound, detect_buildsystems
+from .buildsystem import BUILD_PROFILES, KNOWN_TARGETS, NoBuildToolsFound
+from .fix_build import FixerLimitReached, FixerOscillation
+from .session.metrics import metrics_phase
 from .resolver import (
     auto_resolver,
     native_resolvers,
@@ -42,6 +46,23 @@ def display_explain_commands(commands):
         logging.info("  %s (to install %s)", command, ", ".join(map(str, reqs)))
 
 
+def report_unresolved_requirements(session, resolver, requirements, path=None):
+    """Explain what was tried to resolve requirements that could not be met.
+
+    Args:
+      path: Optional path to write the explanations to, as JSON
+    """
+    from .resolver.explanation import explain_resolution, format_explanations
+
+    explanations = [
+        explain_resolution(session, resolver, req) for req in requirements]
+    for line in format_explanations(explanations).splitlines():
+        logging.info("%s", line)
+    if path:
+        with open(path, "w") as f:
+            json.dump([e.json() for e in explanations], f, indent=2)
+
+
 def get_necessary_declared_requirements(resolver, requirements, stages):
     missing = []
     for stage, req in requirements:
@@ -66,7 +87,15 @@ def install_necessary_declared_requirements(
         get_necessary_declared_requirements(resolver, declared_reqs, stages)
     )
 
+    if relevant and not explain:
+        from .buildsystem import SetupPy
+
+        # Python dependencies are installed into the project virtualenv
+        for buildsystem in buildsystems:
+            if isinstance(buildsystem, SetupPy):
+                buildsystem.setup_virtualenv(session)
     install_missing_reqs(session, resolver, relevant, explain=explain)
+    return relevant
 
 
 # Types of dependencies:
@@ -74,6 +103,7 @@ def install_necessary_declared_requirements(
 # - build: necessary to build the package
 # - test: necessary to run the tests
 # - dev: necessary for development (e.g. linters, yacc)
+# - optional: used if present (e.g. R's Suggests)
 
 STAGE_MAP = {
     "dist": [],
@@ -82,6 +112,14 @@ STAGE_MAP = {
     "test": ["test", "build", "core"],
     "build": ["build", "core"],
     "clean": [],
+    "lint": ["build", "core"],
+    "coverage": ["test", "build", "core"],
+    "update-lockfile": [],
+    "vendor": [],
+    "envfile": [],
+    "containerfile": [],
+    "audit": [],
+    "outdated": [],
 }
 
 
@@ -100,11 +138,54 @@ def main():  # noqa: C901
         "--directory", "-d", type=str, help="Directory for project.", default="."
     )
     parser.add_argument("--schroot", type=str, help="schroot to run in.")
+    parser.add_argument(
+        "--create-chroot", action="store_true",
+        help="Create the schroot chroot if it does not exist.")
+    parser.add_argument(
+        "--update-chroot", action="store_true",
+        help="Update the packages in the schroot chroot before starting.")
+    parser.add_argument(
+        "--bind-mount", type=str, action="append", default=[],
+        metavar="SOURCE[:TARGET][:ro]",
+        help="Bind mount a host directory into the schroot session. "
+             "May be specified multiple times.")
+    parser.add_argument(
+        "--unshare", type=str, metavar="ROOT",
+        help="Root directory to run in, using user namespaces.")
+    parser.add_argument(
+        "--proot", type=str, metavar="ROOT",
+        help="Root directory to run in, using proot.")
+    parser.add_argument(
+        "--bootstrap", type=str, metavar="SUITE",
+        help="Run in a fresh Debian root filesystem created with mmdebstrap, "
+             "using user namespaces.")
+    parser.add_argument(
+        "--image", type=str, metavar="TARBALL",
+        help="Start from a session image created with --export-image, "
+             "using user namespaces.")
+    parser.add_argument(
+        "--work-dir", type=str, metavar="DIR",
+        help="Directory for temporary projects and session roots "
+             "(default: $OGNIBUILD_WORK_DIR or the system temporary directory).")
+    parser.add_argument(
+        "--work-dir-size", type=str, metavar="SIZE",
+        help="Mount a tmpfs of at most SIZE (e.g. 20G) on the work directory "
+             "for the duration of the run. Requires --work-dir and the "
+             "privileges to mount file systems.")
+    parser.add_argument(
+        "--export-image", type=str, metavar="TARBALL",
+        help="Export the session as an image once the declared requirements "
+             "are installed.")
     parser.add_argument(
         "--resolve",
-        choices=["apt", "native", "auto"],
+        choices=["apt", "apk", "dnf", "pkg", "native", "nix",
+                 "nix-shell", "conan", "vcpkg", "auto"],
         default="auto",
-        help="What to do about missing dependencies",
+        help="What to do about missing dependencies. nix installs into the "
+             "user's nix profile; nix-shell runs commands in a nix-shell "
+             "and writes the packages to .ognibuild/shell.nix. conan "
+             "installs C/C++ libraries from ConanCenter; vcpkg "
+             "installs them with vcpkg.",
     )
     parser.add_argument(
         '--apt', help=argparse.SUPPRESS,
@@ -117,19 +198,202 @@ def main():  # noqa: C901
         action="store_true",
         help="Explain what needs to be done rather than making changes",
     )
+    parser.add_argument(
+        "--build-dir", type=str, metavar="DIR",
+        help="Build in DIR (relative to the project) rather than in the "
+             "source tree, for build systems that support it.")
+    parser.add_argument(
+        "--jobs", "-j", type=int, metavar="N",
+        help="Number of jobs to run in parallel (default: number of CPUs).")
+    parser.add_argument(
+        "--target", type=str, metavar="ARCH", choices=sorted(KNOWN_TARGETS),
+        help="Cross-build for ARCH (a Debian architecture name, e.g. armhf).")
+    parser.add_argument(
+        "--extra-args", type=str, action="append", default=[],
+        metavar="BUILDSYSTEM=ARGS",
+        help="Additional arguments for the command that configures (or "
+             "builds) a particular build system, e.g. "
+             "autoconf='--without-docs'. Can be specified multiple times.")
+    parser.add_argument(
+        "--meson-cross-file", type=str, action="append", default=[],
+        metavar="PATH",
+        help="Meson cross file to use (relative to the project); by default "
+             "one is generated when --target is specified.")
+    parser.add_argument(
+        "--meson-native-file", type=str, action="append", default=[],
+        metavar="PATH",
+        help="Meson native file to use (relative to the project).")
+    parser.add_argument(
+        "--meson-option", type=str, action="append", default=[],
+        metavar="NAME=VALUE",
+        help="Set a Meson project option, e.g. docs=false; shorthand for "
+             "--extra-args meson=-Ddocs=false. Can be specified multiple "
+             "times.")
+    parser.add_argument(
+        "--r-check-args", type=str, metavar="ARGS",
+        help="Arguments for 'R CMD check' (default: --no-manual).")
+    parser.add_argument(
+        "--r-full-check", action="store_true",
+        help="Always run 'R CMD check' to test R packages, rather than just "
+             "the testthat suite.")
+    parser.add_argument(
+        "--resolution-report", type=str, metavar="PATH",
+        help="Write an explanation of what was tried to resolve missing "
+             "requirements to PATH, as JSON.")
     parser.add_argument(
         "--ignore-declared-dependencies",
         "--optimistic",
         action="store_true",
         help="Ignore declared dependencies, follow build errors only",
     )
+    parser.add_argument(
+        "--memory-limit", type=int, metavar="MB",
+        help="Maximum amount of memory commands may use, in megabytes "
+        "(enforced with cgroups v2)")
+    parser.add_argument(
+        "--command-wrapper", type=str, action="append", default=[],
+        metavar="WRAPPER",
+        help="Wrap commands, e.g. eatmydata, nice[=N], ionice[=CLASS[:LEVEL]], "
+             "timeout=SECONDS or proot[=ARGS]. May be specified multiple times.")
+    parser.add_argument(
+        "--metrics", type=str, metavar="PATH",
+        help="Write timing and resource usage of commands to PATH, as JSON.")
+    parser.add_argument(
+        "--log-timestamps", action="store_true",
+        help="Prefix command output with the time it was produced.")
+    parser.add_argument(
+        "--log-tags", action="store_true",
+        help="Prefix command output with the stream it came from.")
+    parser.add_argument(
+        "--log-file", type=str, metavar="PATH",
+        help="Write a copy of command output to PATH.")
+    parser.add_argument(
+        "--snap", action="store_true",
+        help="Install missing binaries as snaps if they can not be found "
+             "otherwise. Snaps are confined, so may not have access to "
+             "the project directory.")
+    parser.add_argument(
+        "--snap-classic", action="store_true",
+        help="With --snap, also install snaps that require classic "
+             "confinement.")
+    parser.add_argument(
+        "--compiler-cache", action="store_true",
+        help="Use shared ccache/sccache directories on the host.")
+    parser.add_argument(
+        "--maven-artifacts", type=str, metavar="PATH",
+        help="Provision a local Maven repository with the artifacts from a "
+             "Gradle lockfile or 'mvn dependency:list' report, for offline "
+             "builds.")
+    parser.add_argument(
+        "--create-scaffolding",
+        action="store_true",
+        help="Create minimal build files if no build system is found",
+    )
+    parser.add_argument(
+        "--state-dir", type=str, metavar="DIR",
+        help="Directory to keep state between runs in "
+             "(default: .ognibuild in the project directory).")
+    parser.add_argument(
+        "--from-scratch", action="store_true",
+        help="Ignore and discard the state recorded by earlier runs.")
+    parser.add_argument(
+        "--system-python", action="store_true",
+        help="Build Python projects with the session's Python rather than "
+             "in a virtualenv.")
+    parser.add_argument(
+        "--workspace", type=str, metavar="NAME",
+        help="Only act on this member of a workspace (monorepo).")
+    parser.add_argument(
+        "--buildsystem", type=str, metavar="NAMES",
+        help="Comma-separated names of the only build systems to use, in "
+             "order of preference (default: $OGNIBUILD_BUILDSYSTEM).")
+    parser.add_argument(
+        "--buildsystem-priority", type=str, metavar="NAMES",
+        help="Comma-separated names of build systems to prefer over "
+             "others (default: $OGNIBUILD_BUILDSYSTEM_PRIORITY).")
     parser.add_argument("--verbose", action="store_true", help="Be verbose")
     subparsers = parser.add_subparsers(dest="subcommand")
     subparsers.add_parser("dist")
-    subparsers.add_parser("build")
+    build_parser = subparsers.add_parser("build")
+    build_parser.add_argument(
+        "--profile", choices=BUILD_PROFILES,
+        help="Build profile to use (default: the build system's default).")
+    build_parser.add_argument(
+        "--abi-baseline", type=str, metavar="PATH",
+        help="Compare the symbols exported by the shared libraries that "
+             "were built against this baseline; it is created if it does "
+             "not exist yet.")
+    build_parser.add_argument(
+        "--update-abi-baseline", action="store_true",
+        help="Replace the ABI baseline with the symbols from this build.")
     subparsers.add_parser("clean")
     subparsers.add_parser("test")
+    subparsers.add_parser("lint")
+    subparsers.add_parser("update-lockfile")
+    subparsers.add_parser("vendor")
+    coverage_parser = subparsers.add_parser("coverage")
+    coverage_parser.add_argument(
+        "--format", choices=["lcov", "cobertura"], default=None,
+        help="Export the coverage data in this format.")
+    coverage_parser.add_argument(
+        "--output", "-o", type=str,
+        help="File to export coverage data to (default: stdout).")
     subparsers.add_parser("info")
+    envfile_parser = subparsers.add_parser("envfile")
+    envfile_parser.add_argument(
+        "--format", choices=["shell.nix", "flake.nix", "guix"], default="shell.nix",
+        help="Format of environment file to generate.")
+    envfile_parser.add_argument(
+        "--output", "-o", type=str, help="File to write to (default: stdout).")
+    containerfile_parser = subparsers.add_parser("containerfile")
+    containerfile_parser.add_argument(
+        "--base-image", type=str, help="Base image to start from.")
+    containerfile_parser.add_argument(
+        "--output", "-o", type=str, help="File to write to (default: stdout).")
+    audit_parser = subparsers.add_parser(
+        "audit", help="Check dependencies for known vulnerabilities.")
+    audit_parser.add_argument(
+        "--json", action="store_true", help="Output findings as JSON.")
+    outdated_parser = subparsers.add_parser(
+        "outdated", help="Report declared dependencies that could be upgraded.")
+    outdated_parser.add_argument(
+        "--json", action="store_true", help="Output the report as JSON.")
+    environment_parser = subparsers.add_parser(
+        "environment", help="Record the build environment.")
+    environment_parser.add_argument(
+        "--output", "-o", type=str, help="File to write to (default: stdout).")
+    compare_parser = subparsers.add_parser(
+        "compare-environments",
+        help="Compare two build environments recorded with 'environment'.")
+    compare_parser.add_argument("old", type=str)
+    compare_parser.add_argument("new", type=str)
+    compare_parser.add_argument(
+        "--json", action="store_true", help="Output the differences as JSON.")
+    cleanup_parser = subparsers.add_parser(
+        "cleanup-orphans",
+        help="Clean up sessions and directories left behind by crashed runs.")
+    cleanup_parser.add_argument(
+        "--dry-run", action="store_true",
+        help="Only report what would be cleaned up.")
+    chain_parser = subparsers.add_parser(
+        "chain",
+        help="Build and install projects that depend on each other, in order.")
+    chain_parser.add_argument(
+        "projects", nargs="*", metavar="DIRECTORY",
+        help="Project directories; each depends on the previous one.")
+    chain_parser.add_argument(
+        "--chain-file", type=str, metavar="PATH",
+        help="JSON file listing the projects and their dependencies.")
+    chain_parser.add_argument(
+        "--prefix", type=str,
+        help="Prefix inside the session to install the projects into.")
+    chain_parser.add_argument(
+        "--layers", type=str, metavar="DIR",
+        help="Export a session image to DIR after each project.")
+    detect_parser = subparsers.add_parser(
+        "detect", help="Show which build systems are detected, and why.")
+    detect_parser.add_argument(
+        "--json", action="store_true", help="Output JSON.")
     exec_parser = subparsers.add_parser("exec")
     exec_parser.add_argument('subargv', nargs=argparse.REMAINDER, help='Command to run.')
     install_parser = subparsers.add_parser("install")
@@ -147,14 +411,139 @@ def main():  # noqa: C901
         logging.basicConfig(level=logging.DEBUG, format="%(message)s")
     else:
         logging.basicConfig(level=logging.INFO, format="%(message)s")
+    from .buildsystem import parse_extra_args
+
+    try:
+        args.extra_args = parse_extra_args(args.extra_args, args.meson_option)
+    except ValueError as e:
+        parser.error(str(e))
+    args.buildsystem = (
+        args.buildsystem.split(",") if args.buildsystem else None)
+    args.buildsystem_priority = (
+        args.buildsystem_priority.split(",")
+        if args.buildsystem_priority else None)
+    if args.subcommand == "detect":
+        from .buildsystem import format_probe_results, probe_buildsystems
+
+        results = probe_buildsystems(
+            args.directory, override=args.buildsystem,
+            priority=args.buildsystem_priority)
+        if args.json:
+            json.dump([r.json() for r in results], sys.stdout, indent=2)
+            sys.stdout.write("\n")
+        else:
+            sys.stdout.write(format_probe_results(results))
+        return 0 if any(r.selected for r in results) else 1
+    if args.subcommand == "compare-environments":
+        from .buildenv import (
+            BuildEnvironment,
+            compare_build_environments,
+            format_environment_differences,
+        )
+
+        differences = compare_build_environments(
+            BuildEnvironment.load(args.old), BuildEnvironment.load(args.new))
+        if args.json:
+            json.dump([d.json() for d in differences], sys.stdout, indent=2)
+            sys.stdout.write("\n")
+        else:
+            sys.stdout.write(format_environment_differences(differences))
+        return 1 if differences else 0
+    if args.subcommand == "cleanup-orphans":
+        from .session.cleanup import cleanup_orphans
+
+        found = cleanup_orphans(
+            unshare_roots=[args.unshare] if args.unshare else [],
+            work_dirs=[args.work_dir] if args.work_dir else None,
+            dry_run=args.dry_run)
+        for description in found:
+            logging.info("%s %s", "Found" if args.dry_run else "Cleaned up", description)
+        return 0
+    if args.work_dir_size:
+        if not args.work_dir:
+            parser.error("--work-dir-size requires --work-dir")
+        from .session import mount_size_limited_tmpfs, unmount_tmpfs
+
+        mount_size_limited_tmpfs(args.work_dir, args.work_dir_size)
+        try:
+            return _open_session_and_run(parser, args)
+        finally:
+            unmount_tmpfs(args.work_dir)
+    return _open_session_and_run(parser, args)
+
+
+def _open_session_and_run(parser, args):  # noqa: C901
     if args.schroot:
         from .session.schroot import SchrootSession
+        from .session.unshare import BindMount
+
+        try:
+            bind_mounts = [BindMount.parse(text) for text in args.bind_mount]
+        except ValueError as e:
+            parser.error(str(e))
+        session = SchrootSession(
+            args.schroot, create_chroot=args.create_chroot,
+            update_chroot=args.update_chroot, bind_mounts=bind_mounts)
+    elif args.unshare:
+        from .session.unshare import UnshareSession
+
+        session = UnshareSession(args.unshare)
+    elif args.proot:
+        from .session.proot import ProotSession
 
-        session = SchrootSession(args.schroot)
+        session = ProotSession(args.proot)
+    elif args.image:
+        from .session.unshare import UnshareSession
+
+        session = UnshareSession.from_image(args.image, work_dir=args.work_dir)
+    elif args.bootstrap:
+        from .session.unshare import UnshareSession
+
+        session = UnshareSession.bootstrap(
+            args.bootstrap, work_dir=args.work_dir)
     else:
         from .session.plain import PlainSession
 
         session = PlainSession()
+    if args.work_dir:
+        session.set_work_dir(args.work_dir)
+    if args.memory_limit:
+        from .session import ResourceLimits
+
+        session.set_resource_limits(
+            ResourceLimits(memory=args.memory_limit * 1024 * 1024))
+    if args.command_wrapper:
+        from .session.wrappers import parse_wrapper
+
+        for text in args.command_wrapper:
+            try:
+                session.add_command_wrapper(parse_wrapper(text))
+            except ValueError as e:
+                parser.error(str(e))
+    if args.metrics:
+        from .session.metrics import MetricsCollector
+
+        session.set_metrics_collector(MetricsCollector())
+    log_file = None
+    if args.log_timestamps or args.log_tags or args.log_file:
+        from .session.output import OutputForwarder
+
+        if args.log_file:
+            log_file = open(args.log_file, "ab")
+        session.set_output_forwarder(OutputForwarder(
+            timestamps=args.log_timestamps, tags=args.log_tags, sink=log_file))
+    try:
+        return _run_in_session(args, session)
+    finally:
+        if log_file is not None:
+            log_file.close()
+        if args.metrics:
+            logging.info("%s", session.metrics.format_report().rstrip("\n"))
+            with open(args.metrics, "w") as f:
+                json.dump(session.metrics.json(), f, indent=2)
+
+
+def _run_in_session(args, session):  # noqa: C901
     with session:
         logging.info("Preparing directory %s", args.directory)
         external_dir, internal_dir = session.setup_from_directory(args.directory)
@@ -164,56 +553,239 @@ def main():  # noqa: C901
         if not session.is_temporary and args.subcommand == 'info':
             args.explain = True
 
+        if args.subcommand == 'containerfile':
+            args.explain = True
+
         if args.resolve == "apt":
             from .resolver.apt import AptResolver
-            resolver = AptResolver.from_session(session)
+            resolver = AptResolver.from_session(session, host_arch=args.target)
+        elif args.resolve == "apk":
+            from .resolver.apk import ApkResolver
+            resolver = ApkResolver.from_session(session)
+        elif args.resolve == "dnf":
+            from .resolver.dnf import DnfResolver
+            resolver = DnfResolver.from_session(session)
+        elif args.resolve == "pkg":
+            from .resolver.freebsd import FreeBSDPkgResolver
+            resolver = FreeBSDPkgResolver.from_session(session)
         elif args.resolve == "native":
             resolver = native_resolvers(session, user_local=args.user)
+        elif args.resolve in ("nix", "nix-shell"):
+            from .resolver.nix import NixResolver
+            resolver = NixResolver(
+                session, user_local=args.user,
+                shell_nix=(os.path.join(".ognibuild", "shell.nix")
+                           if args.resolve == "nix-shell" else None))
+        elif args.resolve == "conan":
+            from .resolver.conan import ConanResolver
+            resolver = ConanResolver(session, user_local=args.user)
+        elif args.resolve == "vcpkg":
+            from .resolver.vcpkg import VcpkgResolver
+            resolver = VcpkgResolver(session, user_local=args.user)
         elif args.resolve == "auto":
-            resolver = auto_resolver(session, explain=args.explain)
+            resolver = auto_resolver(
+                session, explain=args.explain, host_arch=args.target)
+        if args.snap:
+            from .resolver import StackedResolver
+            from .resolver.snap import SnapResolver
+            resolver = StackedResolver([
+                resolver, SnapResolver(session, allow_classic=args.snap_classic)])
         logging.info("Using requirement resolver: %s", resolver)
-        fixers = determine_fixers(session, resolver, explain=args.explain)
+        if args.compiler_cache:
+            from .compiler_cache import enable_compiler_caches
+
+            enable_compiler_caches(
+                session, None if args.explain else resolver)
+        if args.maven_artifacts:
+            from .maven import provision_maven_repository, read_artifacts
+
+            provision_maven_repository(
+                session, read_artifacts(args.maven_artifacts))
+        if args.subcommand == "environment":
+            from .buildenv import capture_build_environment
+
+            environment = capture_build_environment(session)
+            if args.output:
+                environment.save(args.output)
+            else:
+                json.dump(environment.json(), sys.stdout, indent=2, sort_keys=True)
+                sys.stdout.write("\n")
+            return 0
+        from .state import (
+            ProjectState,
+            RecordingFixer,
+            default_state_dir,
+            detect_buildsystems_with_state,
+        )
+
+        state_dir = args.state_dir or default_state_dir(args.directory)
+        if args.from_scratch:
+            state = ProjectState(state_dir)
+            state.clear()
+        else:
+            state = ProjectState.load(state_dir)
+        fixers = [
+            RecordingFixer(fixer, state)
+            for fixer in determine_fixers(session, resolver, explain=args.explain)]
         try:
             if args.subcommand == "exec":
                 from .fix_build import run_with_build_fixers
                 run_with_build_fixers(session, args.subargv, fixers)
                 return 0
-            bss = list(detect_buildsystems(args.directory))
+            if args.subcommand == "chain":
+                from .chain import (
+                    DEFAULT_CHAIN_PREFIX, ChainCycle, linear_chain,
+                    read_chain_file, run_chain)
+
+                if args.chain_file:
+                    projects = read_chain_file(args.chain_file)
+                else:
+                    projects = linear_chain(args.projects)
+                if not projects:
+                    logging.fatal("No projects specified.")
+                    return 1
+
+                def prepare(project, bss):
+                    if args.ignore_declared_dependencies:
+                        return
+                    install_necessary_declared_requirements(
+                        session, resolver, fixers, bss, STAGE_MAP["install"],
+                        explain=args.explain)
+
+                if args.prefix:
+                    prefix = args.prefix
+                elif session.is_temporary:
+                    prefix = DEFAULT_CHAIN_PREFIX
+                else:
+                    prefix = os.path.abspath(
+                        os.path.join(state_dir, "chain-prefix"))
+                try:
+                    run_chain(
+                        session, projects, resolver, fixers, prefix=prefix,
+                        prepare=prepare, layer_directory=args.layers)
+                except (ChainCycle, ValueError) as e:
+                    logging.fatal("Invalid chain: %s", e)
+                    return 1
+                logging.info("Projects are installed in %s", prefix)
+                return 0
+            bss = detect_buildsystems_with_state(
+                args.directory, state, override=args.buildsystem,
+                priority=args.buildsystem_priority)
+            if not bss and args.create_scaffolding:
+                from .scaffold import create_scaffolding
+
+                created = create_scaffolding(
+                    external_dir,
+                    os.path.basename(os.path.abspath(args.directory)))
+                if created:
+                    logging.info("Created %s", ", ".join(created))
+                    bss = detect_buildsystems_with_state(
+                        external_dir, state, override=args.buildsystem,
+                        priority=args.buildsystem_priority)
             logging.info("Detected buildsystems: %s", ", ".join(map(str, bss)))
+            from .buildsystem import BuildConfig, Target, default_jobs
+
+            config = BuildConfig(
+                build_dir=args.build_dir, jobs=args.jobs or default_jobs(),
+                target=Target.from_arch(args.target) if args.target else None,
+                extra_args=args.extra_args)
+            for bs in bss:
+                bs.build_config = config
+            from .buildsystem import Meson, MesonOptions
+
+            for bs in bss:
+                if isinstance(bs, Meson):
+                    bs.setup_options = MesonOptions(
+                        cross_files=args.meson_cross_file,
+                        native_files=args.meson_native_file)
+            from .buildsystem import R
+
+            for bs in bss:
+                if isinstance(bs, R):
+                    bs.full_check = args.r_full_check
+                    if args.r_check_args is not None:
+                        bs.check_args = shlex.split(args.r_check_args)
+            if args.system_python or args.subcommand == "install":
+                from .buildsystem import SetupPy
+
+                # Installing into the virtualenv would be pointless
+                for bs in bss:
+                    if isinstance(bs, SetupPy):
+                        bs.use_virtualenv = False
+            if args.workspace:
+                for bs in bss:
+                    try:
+                        bs.select_workspace(args.workspace)
+                    except NotImplementedError:
+                        logging.warning(
+                            "%s does not support workspaces", bs)
+                    except ValueError as e:
+                        logging.fatal("%s", e)
+                        return 1
             if not args.ignore_declared_dependencies:
                 stages = STAGE_MAP[args.subcommand]
                 if stages:
                     logging.info("Checking that declared requirements are present")
                     try:
-                        install_necessary_declared_requirements(
-                            session, resolver, fixers, bss, stages, explain=args.explain
-                        )
+                        with metrics_phase(session, "dependencies"):
+                            state.record_dependencies(
+                                install_necessary_declared_requirements(
+                                    session, resolver, fixers, bss, stages,
+                                    explain=args.explain))
                     except UnsatisfiedRequirements as e:
                         logging.info('Unable to install declared dependencies:')
-                        for req in e.requirements:
-                            logging.info(' * %s', req)
+                        report_unresolved_requirements(
+                            session, resolver, e.requirements,
+                            args.resolution_report)
                         return 1
                     except ExplainInstall as e:
                         display_explain_commands(e.commands)
                         return 1
+            if args.export_image:
+                session.export_image(args.export_image)
             if args.subcommand == "dist":
                 from .dist import run_dist, DistNoTarball
 
+                dist_events = []
                 try:
-                    run_dist(
+                    filename = run_dist(
                         session=session,
                         buildsystems=bss,
                         resolver=resolver,
                         fixers=fixers,
                         target_directory=".",
+                        event_cb=dist_events.append,
                     )
                 except DistNoTarball:
                     logging.fatal('No tarball created.')
+                    for event in dist_events:
+                        logging.info('Considered %s', event)
                     return 1
+                if filename and os.path.exists(filename):
+                    state.record_artifact(filename)
             if args.subcommand == "build":
                 from .build import run_build
 
-                run_build(session, buildsystems=bss, resolver=resolver, fixers=fixers)
+                run_build(
+                    session, buildsystems=bss, resolver=resolver, fixers=fixers,
+                    profile=args.profile)
+                if args.abi_baseline:
+                    from .abi import (
+                        AbiSnapshot, capture_abi, compare_abi, format_abi_changes)
+
+                    snapshot = capture_abi(session, external_dir)
+                    if args.update_abi_baseline or not os.path.exists(
+                            args.abi_baseline):
+                        logging.info(
+                            "Saving ABI of %d libraries to %s",
+                            len(snapshot.libraries), args.abi_baseline)
+                        snapshot.save(args.abi_baseline)
+                    else:
+                        changes = compare_abi(
+                            AbiSnapshot.load(args.abi_baseline), snapshot)
+                        sys.stdout.write(format_abi_changes(changes))
+                        if any(change.is_break for change in changes):
+                            return 1
             if args.subcommand == "clean":
                 from .clean import run_clean
 
@@ -233,17 +805,127 @@ def main():  # noqa: C901
                 from .test import run_test
 
                 run_test(session, buildsystems=bss, resolver=resolver, fixers=fixers)
+            if args.subcommand == "lint":
+                from .lint import run_lint
+
+                try:
+                    run_lint(
+                        session, buildsystems=bss, resolver=resolver,
+                        fixers=fixers)
+                except NotImplementedError:
+                    logging.info("No linters known for this project.")
+                    return 1
+            if args.subcommand in ("update-lockfile", "vendor"):
+                from .lockfile import run_update_lockfile, run_vendor
+
+                action = {
+                    "update-lockfile": run_update_lockfile,
+                    "vendor": run_vendor,
+                }[args.subcommand]
+                try:
+                    action(
+                        session, buildsystems=bss, resolver=resolver,
+                        fixers=fixers)
+                except NotImplementedError:
+                    logging.info(
+                        "%s is not supported for this project.", args.subcommand)
+                    return 1
+            if args.subcommand == "coverage":
+                from .coverage import export_coverage, run_coverage
+
+                try:
+                    report = run_coverage(
+                        session, buildsystems=bss, resolver=resolver,
+                        fixers=fixers)
+                except NotImplementedError:
+                    logging.info("Unable to collect coverage for this project.")
+                    return 1
+                if args.format is None:
+                    sys.stdout.write(str(report))
+                elif args.output:
+                    with open(args.output, "w") as f:
+                        export_coverage(report, f, args.format)
+                else:
+                    export_coverage(report, sys.stdout, args.format)
             if args.subcommand == "info":
                 from .info import run_info
 
                 run_info(session, buildsystems=bss, fixers=fixers)
+            if args.subcommand == "envfile":
+                from .envfile import declared_requirements, FORMATS
+
+                reqs = declared_requirements(
+                    session, bss, ["core", "build", "test"], fixers=fixers)
+                contents = FORMATS[args.format](reqs)
+            if args.subcommand == "containerfile":
+                from .containerfile import containerfile_for_project
+
+                contents = containerfile_for_project(
+                    session, resolver, bss, fixers=fixers,
+                    base_image=args.base_image)
+            if args.subcommand == "audit":
+                from .audit import run_audit, format_audit_report
+
+                findings = run_audit(
+                    session, resolver, external_dir, explain=args.explain)
+                if args.json:
+                    json.dump([f.json() for f in findings], sys.stdout, indent=2)
+                else:
+                    sys.stdout.write(format_audit_report(findings))
+                return 1 if findings else 0
+            if args.subcommand == "outdated":
+                from .outdated import (
+                    run_outdated, format_outdated_report, RegistryVersionSource,
+                    AptVersionSource)
+
+                sources = [RegistryVersionSource()]
+                if args.resolve == "apt":
+                    sources.append(AptVersionSource(resolver))
+                outdated = run_outdated(session, bss, sources, fixers=fixers)
+                if args.json:
+                    json.dump([o.json() for o in outdated], sys.stdout, indent=2)
+                else:
+                    sys.stdout.write(format_outdated_report(outdated))
+                return 0
+            if args.subcommand in ("envfile", "containerfile"):
+                if args.output:
+                    with open(args.output, "w") as f:
+                        f.write(contents)
+                else:
+                    sys.stdout.write(contents)
         except ExplainInstall as e:
             display_explain_commands(e.commands)
-        except (UnidentifiedError, DetailedFailure):
+        except UnsatisfiedRequirements as e:
+            logging.info("Unable to install requirements:")
+            report_unresolved_requirements(
+                session, resolver, e.requirements, args.resolution_report)
+            return 1
+        except DetailedFailure as e:
+            reqs = problem_to_upstream_requirement(e.error)
+            if reqs is not None:
+                logging.info("Unable to install missing requirements:")
+                report_unresolved_requirements(
+                    session, resolver, reqs if isinstance(reqs, list) else [reqs],
+                    args.resolution_report)
+            return 1
+        except UnidentifiedError:
+            return 1
+        except (FixerLimitReached, FixerOscillation) as e:
+            logging.fatal("%s", e)
             return 1
         except NoBuildToolsFound:
             logging.info("No build tools found.")
             return 1
+        finally:
+            if not args.explain:
+                # Don't let a failure to save the state hide the actual
+                # error, if any
+                try:
+                    state.save()
+                except OSError as e:
+                    logging.warning(
+                        "Unable to save project state to %s: %s",
+                        state.path, e)
         return 0
 
 
@@ -0,0 +1,200 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Track the symbols exported by shared libraries.
+
+After a build, the exported symbols of the shared libraries that were
+built are recorded and compared against a baseline from an earlier build,
+so that ABI breaks (removed symbols or libraries) are noticed before e.g.
+a Debian symbols file has to be updated.
+"""
+
+import json
+import logging
+import os
+import re
+import subprocess
+from typing import Dict, List, Optional
+
+from .session import Session
+
+
+# Symbol types (as printed by nm) of symbols that are part of the ABI
+EXPORTED_SYMBOL_TYPES = set("BDGRSTVWiu")
+
+_SHARED_LIBRARY_RE = re.compile(r".*\.so(\.[0-9]+)*$")
+
+
+class AbiSnapshot(object):
+    """The exported symbols of a set of shared libraries.
+
+    Attributes:
+      libraries: Dictionary mapping library names (SONAME, if set) to
+        sorted lists of exported symbols
+    """
+
+    def __init__(self, libraries: Optional[Dict[str, List[str]]] = None):
+        self.libraries = dict(libraries or {})
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, sorted(self.libraries))
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.libraries == other.libraries
+
+    def json(self):
+        return {"libraries": self.libraries}
+
+    @classmethod
+    def from_json(cls, data):
+        return cls(data.get("libraries"))
+
+    @classmethod
+    def load(cls, path: str) -> "AbiSnapshot":
+        with open(path, "r") as f:
+            return cls.from_json(json.load(f))
+
+    def save(self, path: str) -> None:
+        with open(path, "w") as f:
+            json.dump(self.json(), f, indent=2, sort_keys=True)
+
+
+def find_shared_libraries(directory: str) -> List[str]:
+    """Find the shared libraries in a directory tree.
+
+    Returns: paths relative to directory
+    """
+    ret = []
+    for root, dirs, files in os.walk(directory):
+        dirs[:] = [d for d in dirs if not d.startswith(".")]
+        for name in files:
+            path = os.path.join(root, name)
+            if not _SHARED_LIBRARY_RE.match(name) or os.path.islink(path):
+                continue
+            with open(path, "rb") as f:
+                if f.read(4) != b"\x7fELF":
+                    continue
+            ret.append(os.path.relpath(path, directory))
+    return sorted(ret)
+
+
+def _soname(session: Session, path: str) -> Optional[str]:
+    try:
+        output = session.check_output(["objdump", "-p", path])
+    except (FileNotFoundError, subprocess.CalledProcessError):
+        return None
+    for line in output.decode("utf-8", "replace").splitlines():
+        parts = line.split()
+        if len(parts) == 2 and parts[0] == "SONAME":
+            return parts[1]
+    return None
+
+
+def exported_symbols(session: Session, path: str) -> List[str]:
+    """List the symbols exported by a shared library, using nm."""
+    output = session.check_output(
+        ["nm", "--dynamic", "--defined-only", "--with-symbol-versions",
+         "--format=posix", path])
+    ret = set()
+    for line in output.decode("utf-8", "replace").splitlines():
+        parts = line.split()
+        if len(parts) < 2 or parts[1] not in EXPORTED_SYMBOL_TYPES:
+            continue
+        ret.add(parts[0])
+    return sorted(ret)
+
+
+def capture_abi(session: Session, directory: str) -> AbiSnapshot:
+    """Record the exported symbols of the shared libraries in a project.
+
+    Args:
+      session: Session to run nm in
+      directory: Path of the project, outside of the session
+    """
+    libraries = {}
+    for path in find_shared_libraries(directory):
+        name = _soname(session, path) or os.path.basename(path)
+        libraries[name] = exported_symbols(session, path)
+        logging.debug(
+            "Found %d exported symbols in %s", len(libraries[name]), path)
+    return AbiSnapshot(libraries)
+
+
+class AbiChange(object):
+    """A change in the exported symbols of a library.
+
+    Attributes:
+      kind: "removed-library", "added-library", "removed-symbol" or
+        "added-symbol"
+      library: Name of the library
+      symbol: Name of the symbol, or None for library changes
+    """
+
+    def __init__(self, kind: str, library: str, symbol: Optional[str] = None):
+        self.kind = kind
+        self.library = library
+        self.symbol = symbol
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__, self.kind, self.library, self.symbol)
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    @property
+    def is_break(self) -> bool:
+        """Whether this change breaks users of the library."""
+        return self.kind.startswith("removed-")
+
+    def json(self):
+        return dict(self.__dict__)
+
+
+def compare_abi(old: AbiSnapshot, new: AbiSnapshot) -> List[AbiChange]:
+    """Find the changes between two ABI snapshots."""
+    ret = []
+    for library in sorted(set(old.libraries) | set(new.libraries)):
+        if library not in new.libraries:
+            ret.append(AbiChange("removed-library", library))
+            continue
+        if library not in old.libraries:
+            ret.append(AbiChange("added-library", library))
+            continue
+        old_symbols = set(old.libraries[library])
+        new_symbols = set(new.libraries[library])
+        for symbol in sorted(old_symbols - new_symbols):
+            ret.append(AbiChange("removed-symbol", library, symbol))
+        for symbol in sorted(new_symbols - old_symbols):
+            ret.append(AbiChange("added-symbol", library, symbol))
+    return ret
+
+
+def format_abi_changes(changes: List[AbiChange]) -> str:
+    if not changes:
+        return "No ABI changes.\n"
+    lines = []
+    for change in changes:
+        sign = "-" if change.is_break else "+"
+        if change.symbol is None:
+            lines.append("%s %s" % (sign, change.library))
+        else:
+            lines.append("%s %s: %s" % (sign, change.library, change.symbol))
+    breaks = len([c for c in changes if c.is_break])
+    if breaks:
+        lines.append("%d incompatible ABI change(s)." % breaks)
+    return "\n".join(lines) + "\n"
@@ -0,0 +1,315 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Run ecosystem vulnerability scanners and merge their findings.
+
+Scanners are installed on demand, and run inside the session. Most of them
+exit with a non-zero code when vulnerabilities are found, so their exit code
+is ignored in favour of their JSON output.
+"""
+
+import json
+import logging
+import os
+import subprocess
+from typing import List, Optional
+
+from .buildlog import install_missing_reqs
+from .requirements import (
+    BinaryRequirement,
+    PythonPackageRequirement,
+    RubyGemRequirement,
+)
+
+
+class AuditFailed(Exception):
+    """A vulnerability scanner failed to run."""
+
+    def __init__(self, auditor, reason):
+        self.auditor = auditor
+        self.reason = reason
+
+    def __str__(self):
+        return "%s failed: %s" % (self.auditor, self.reason)
+
+
+class Finding(object):
+    """A known vulnerability in a dependency."""
+
+    def __init__(
+        self,
+        tool: str,
+        package: str,
+        advisory: str,
+        version: Optional[str] = None,
+        title: Optional[str] = None,
+        severity: Optional[str] = None,
+        fixed_versions: Optional[List[str]] = None,
+        url: Optional[str] = None,
+    ):
+        self.tool = tool
+        self.package = package
+        self.advisory = advisory
+        self.version = version
+        self.title = title
+        self.severity = severity
+        self.fixed_versions = fixed_versions or []
+        self.url = url
+
+    def __repr__(self):
+        return "%s(%r, %r, %r, version=%r)" % (
+            type(self).__name__, self.tool, self.package, self.advisory,
+            self.version)
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    def json(self):
+        return dict(self.__dict__)
+
+
+class Auditor(object):
+    """A vulnerability scanner for a particular ecosystem."""
+
+    name: str
+
+    def __str__(self):
+        return self.name
+
+    @classmethod
+    def probe(cls, path: str) -> Optional["Auditor"]:
+        raise NotImplementedError(cls.probe)
+
+    def requirements(self):
+        """Requirements for running the scanner."""
+        return []
+
+    def argv(self) -> List[str]:
+        raise NotImplementedError(self.argv)
+
+    def parse(self, output: bytes) -> List[Finding]:
+        raise NotImplementedError(self.parse)
+
+    def run(self, session) -> List[Finding]:
+        argv = self.argv()
+        logging.info("Running %s", " ".join(argv))
+        p = session.Popen(argv, stdout=subprocess.PIPE, stderr=subprocess.PIPE)
+        stdout, stderr = p.communicate()
+        try:
+            return self.parse(stdout)
+        except ValueError:
+            raise AuditFailed(
+                self, stderr.decode(errors="replace").strip()
+                or "exited with code %d" % p.returncode)
+
+
+class CargoAuditor(Auditor):
+
+    name = "cargo-audit"
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "Cargo.lock")):
+            return cls()
+        return None
+
+    def requirements(self):
+        return [BinaryRequirement("cargo-audit")]
+
+    def argv(self):
+        return ["cargo", "audit", "--json"]
+
+    def parse(self, output):
+        ret = []
+        data = json.loads(output)
+        for entry in data.get("vulnerabilities", {}).get("list", []):
+            advisory = entry["advisory"]
+            ret.append(Finding(
+                self.name, entry["package"]["name"], advisory["id"],
+                version=entry["package"].get("version"),
+                title=advisory.get("title"),
+                severity=advisory.get("severity"),
+                fixed_versions=entry.get("versions", {}).get("patched", []),
+                url=advisory.get("url")))
+        return ret
+
+
+class NpmAuditor(Auditor):
+
+    name = "npm-audit"
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "package-lock.json")):
+            return cls()
+        return None
+
+    def requirements(self):
+        return [BinaryRequirement("npm")]
+
+    def argv(self):
+        return ["npm", "audit", "--json"]
+
+    def parse(self, output):
+        ret = []
+        data = json.loads(output)
+        for name, vuln in sorted(data.get("vulnerabilities", {}).items()):
+            for via in vuln.get("via", []):
+                # Strings refer to vulnerabilities in other packages, which
+                # are reported separately.
+                if not isinstance(via, dict):
+                    continue
+                ret.append(Finding(
+                    self.name, name, str(via.get("source", via.get("url"))),
+                    version=via.get("range"),
+                    title=via.get("title"),
+                    severity=via.get("severity"),
+                    url=via.get("url")))
+        return ret
+
+
+class PipAuditor(Auditor):
+
+    name = "pip-audit"
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "requirements.txt")):
+            return cls(["-r", "requirements.txt"])
+        for name in ["pyproject.toml", "setup.py"]:
+            if os.path.exists(os.path.join(path, name)):
+                return cls(["."])
+        return None
+
+    def __init__(self, args):
+        self.args = args
+
+    def requirements(self):
+        return [PythonPackageRequirement("pip-audit")]
+
+    def argv(self):
+        return ["pip-audit", "--format", "json"] + self.args
+
+    def parse(self, output):
+        ret = []
+        data = json.loads(output)
+        if isinstance(data, dict):
+            data = data.get("dependencies", [])
+        for dep in data:
+            for vuln in dep.get("vulns", []):
+                ret.append(Finding(
+                    self.name, dep["name"], vuln["id"],
+                    version=dep.get("version"),
+                    title=vuln.get("description"),
+                    fixed_versions=vuln.get("fix_versions", [])))
+        return ret
+
+
+class BundlerAuditor(Auditor):
+
+    name = "bundler-audit"
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "Gemfile.lock")):
+            return cls()
+        return None
+
+    def requirements(self):
+        return [RubyGemRequirement("bundler-audit", None)]
+
+    def argv(self):
+        return ["bundle-audit", "check", "--update", "--format", "json"]
+
+    def parse(self, output):
+        ret = []
+        data = json.loads(output)
+        for result in data.get("results", []):
+            if result.get("type") != "unpatched_gem":
+                continue
+            advisory = result["advisory"]
+            if advisory.get("cve"):
+                advisory_id = "CVE-%s" % advisory["cve"]
+            elif advisory.get("ghsa"):
+                advisory_id = "GHSA-%s" % advisory["ghsa"]
+            else:
+                advisory_id = advisory["id"]
+            ret.append(Finding(
+                self.name, result["gem"]["name"], advisory_id,
+                version=result["gem"].get("version"),
+                title=advisory.get("title"),
+                severity=advisory.get("criticality"),
+                fixed_versions=advisory.get("patched_versions", []),
+                url=advisory.get("url")))
+        return ret
+
+
+AUDITOR_CLSES = [
+    CargoAuditor,
+    NpmAuditor,
+    PipAuditor,
+    BundlerAuditor,
+]
+
+
+def detect_auditors(path: str) -> List[Auditor]:
+    ret = []
+    for cls in AUDITOR_CLSES:
+        auditor = cls.probe(path)
+        if auditor is not None:
+            ret.append(auditor)
+    return ret
+
+
+def run_audit(session, resolver, path: str = ".", explain=False) -> List[Finding]:
+    """Run all applicable vulnerability scanners for the project in path.
+
+    Returns: list of findings, from all scanners
+    """
+    auditors = detect_auditors(path)
+    if not auditors:
+        logging.info("No vulnerability scanners apply to this project.")
+        return []
+    reqs = []
+    for auditor in auditors:
+        reqs.extend(auditor.requirements())
+    install_missing_reqs(session, resolver, reqs, explain=explain)
+    findings = []
+    for auditor in auditors:
+        findings.extend(auditor.run(session))
+    return findings
+
+
+def format_audit_report(findings: List[Finding]) -> str:
+    """Format findings as a human-readable report."""
+    if not findings:
+        return "No known vulnerabilities found.\n"
+    lines = []
+    for finding in findings:
+        package = finding.package
+        if finding.version:
+            package += " " + finding.version
+        line = "%s: %s" % (package, finding.advisory)
+        if finding.severity:
+            line += " (%s)" % finding.severity
+        lines.append(line)
+        if finding.title:
+            lines.append("  %s" % finding.title.splitlines()[0])
+        if finding.fixed_versions:
+            lines.append("  Fixed in: %s" % ", ".join(finding.fixed_versions))
+        lines.append("  Reported by: %s" % finding.tool)
+    return "\n".join(lines) + "\n"
@@ -16,15 +16,17 @@
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
 from .buildsystem import NoBuildToolsFound
+from .session.metrics import metrics_phase
 
 
-def run_build(session, buildsystems, resolver, fixers):
+def run_build(session, buildsystems, resolver, fixers, profile=None):
     # Some things want to write to the user's home directory,
     # e.g. pip caches in ~/.cache
     session.create_home()
 
     for buildsystem in buildsystems:
-        buildsystem.build(session, resolver, fixers)
+        with metrics_phase(session, "build"):
+            buildsystem.build(session, resolver, fixers, profile=profile)
         return
 
     raise NoBuildToolsFound()
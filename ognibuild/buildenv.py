@@ -0,0 +1,258 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Record build environments and compare them.
+
+This helps answering why a project builds in one place but not in
+another: record the environment in both places, and compare the records.
+"""
+
+import json
+import logging
+import subprocess
+from typing import Dict, List, Optional
+
+from .session import Session, which
+
+
+# Tools whose versions are recorded, with the arguments to print the version
+TOOLCHAINS = {
+    "cc": ["--version"],
+    "c++": ["--version"],
+    "ld": ["--version"],
+    "make": ["--version"],
+    "cmake": ["--version"],
+    "python3": ["--version"],
+    "perl": ["-e", "print $^V"],
+    "ruby": ["--version"],
+    "rustc": ["--version"],
+    "cargo": ["--version"],
+    "go": ["version"],
+    "node": ["--version"],
+    "java": ["-version"],
+    "ghc": ["--numeric-version"],
+}
+
+# Environment variables that are recorded; the environment can contain
+# credentials, so only variables known to affect builds are included.
+RECORDED_ENVIRONMENT_VARIABLES = [
+    "PATH", "LANG", "LANGUAGE", "LC_ALL", "LC_CTYPE", "LC_COLLATE",
+    "LC_MESSAGES", "TZ", "SHELL", "HOME", "USER", "TMPDIR", "SOURCE_DATE_EPOCH",
+    "CC", "CXX", "CPP", "FC", "LD", "AR", "RANLIB", "STRIP",
+    "CFLAGS", "CXXFLAGS", "CPPFLAGS", "FFLAGS", "LDFLAGS", "LDLIBS",
+    "CPATH", "C_INCLUDE_PATH", "CPLUS_INCLUDE_PATH", "LIBRARY_PATH",
+    "LD_LIBRARY_PATH", "LD_PRELOAD", "MAKEFLAGS", "MAKELEVEL",
+    "PKG_CONFIG", "PKG_CONFIG_PATH", "PKG_CONFIG_LIBDIR", "PKG_CONFIG_SYSROOT_DIR",
+    "CMAKE_PREFIX_PATH", "CMAKE_GENERATOR", "ACLOCAL_PATH",
+    "PYTHONPATH", "PYTHONHOME", "VIRTUAL_ENV", "PERL5LIB", "PERL_MM_OPT",
+    "PERL_MB_OPT", "RUBYLIB", "GEM_HOME", "GEM_PATH", "GOPATH", "GOROOT",
+    "GOFLAGS", "GO111MODULE", "CARGO_HOME", "RUSTUP_HOME", "RUSTFLAGS",
+    "NODE_PATH", "NODE_ENV", "JAVA_HOME", "CLASSPATH", "MAVEN_OPTS",
+    "GRADLE_OPTS", "R_LIBS", "R_LIBS_USER", "OCAMLPATH",
+]
+
+# Prefixes of environment variables that are recorded
+RECORDED_ENVIRONMENT_PREFIXES = ["DEB_", "DH_", "DPKG_"]
+
+# Environment variables that differ between runs without mattering
+IGNORED_ENVIRONMENT_VARIABLES = [
+    "_", "OLDPWD", "PWD", "SHLVL", "HOSTNAME", "TERM", "SSH_AUTH_SOCK",
+    "SSH_CLIENT", "SSH_CONNECTION", "SSH_TTY", "XDG_SESSION_ID",
+    "INVOCATION_ID", "JOURNAL_STREAM", "DISPLAY", "WINDOWID",
+]
+
+
+class BuildEnvironment(object):
+    """A record of the environment that builds run in.
+
+    Attributes:
+      packages: Dictionary mapping installed (Debian) packages to versions
+      environment: Environment variables
+      toolchains: Dictionary mapping tool names to their version output
+    """
+
+    def __init__(
+        self, packages: Optional[Dict[str, str]] = None,
+        environment: Optional[Dict[str, str]] = None,
+        toolchains: Optional[Dict[str, str]] = None
+    ):
+        self.packages = dict(packages or {})
+        self.environment = dict(environment or {})
+        self.toolchains = dict(toolchains or {})
+
+    def __repr__(self):
+        return "%s(%d packages, %d variables, %r)" % (
+            type(self).__name__, len(self.packages), len(self.environment),
+            sorted(self.toolchains))
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    def json(self):
+        return {
+            "packages": self.packages,
+            "environment": self.environment,
+            "toolchains": self.toolchains,
+        }
+
+    @classmethod
+    def from_json(cls, data):
+        return cls(
+            packages=data.get("packages"),
+            environment=data.get("environment"),
+            toolchains=data.get("toolchains"))
+
+    @classmethod
+    def load(cls, path: str) -> "BuildEnvironment":
+        with open(path, "r") as f:
+            return cls.from_json(json.load(f))
+
+    def save(self, path: str) -> None:
+        with open(path, "w") as f:
+            json.dump(self.json(), f, indent=2, sort_keys=True)
+
+
+def _installed_packages(session: Session) -> Dict[str, str]:
+    try:
+        output = session.check_output(
+            ["dpkg-query", "-W",
+             "-f", "${db:Status-Abbrev}\t${Package}\t${Version}\n"],
+            cwd="/")
+    except (FileNotFoundError, subprocess.CalledProcessError):
+        logging.debug("Unable to list installed packages in %r", session)
+        return {}
+    ret = {}
+    for line in output.decode("utf-8", "replace").splitlines():
+        status, package, version = line.split("\t")
+        if status.startswith("ii"):
+            ret[package] = version
+    return ret
+
+
+def _is_recorded_variable(name: str) -> bool:
+    return name in RECORDED_ENVIRONMENT_VARIABLES or any(
+        name.startswith(prefix) for prefix in RECORDED_ENVIRONMENT_PREFIXES)
+
+
+def _environment(session: Session) -> Dict[str, str]:
+    output = session.check_output(["env", "-0"], cwd="/")
+    ret = {}
+    for entry in output.decode("utf-8", "replace").split("\0"):
+        if "=" in entry:
+            key, value = entry.split("=", 1)
+            if _is_recorded_variable(key):
+                ret[key] = value
+    return ret
+
+
+def _toolchain_version(session: Session, name: str, args: List[str]) -> Optional[str]:
+    if which(session, name) is None:
+        return None
+    p = session.Popen(
+        [name] + args, cwd="/", stdout=subprocess.PIPE,
+        stderr=subprocess.STDOUT)
+    output = p.communicate()[0].decode("utf-8", "replace").strip()
+    if p.returncode != 0 or not output:
+        return None
+    return output.splitlines()[0]
+
+
+def capture_build_environment(session: Session) -> BuildEnvironment:
+    """Record the build environment in a session."""
+    toolchains = {}
+    for name, args in TOOLCHAINS.items():
+        version = _toolchain_version(session, name, args)
+        if version is not None:
+            toolchains[name] = version
+    return BuildEnvironment(
+        packages=_installed_packages(session),
+        environment=_environment(session),
+        toolchains=toolchains)
+
+
+class EnvironmentDifference(object):
+    """A difference between two build environments.
+
+    Attributes:
+      kind: "package", "environment" or "toolchain"
+      name: Name of the package, variable or tool
+      old: Value in the first environment, or None if absent
+      new: Value in the second environment, or None if absent
+    """
+
+    def __init__(self, kind: str, name: str, old: Optional[str], new: Optional[str]):
+        self.kind = kind
+        self.name = name
+        self.old = old
+        self.new = new
+
+    def __repr__(self):
+        return "%s(%r, %r, %r, %r)" % (
+            type(self).__name__, self.kind, self.name, self.old, self.new)
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    def json(self):
+        return dict(self.__dict__)
+
+
+def _compare_dicts(kind, old, new, ignore=()):
+    for name in sorted(set(old) | set(new)):
+        if name in ignore:
+            continue
+        if old.get(name) != new.get(name):
+            yield EnvironmentDifference(kind, name, old.get(name), new.get(name))
+
+
+def compare_build_environments(
+    old: BuildEnvironment, new: BuildEnvironment,
+    ignore_environment: Optional[List[str]] = None
+) -> List[EnvironmentDifference]:
+    """Find the differences between two build environments.
+
+    Args:
+      old: First environment
+      new: Second environment
+      ignore_environment: Environment variables to ignore (defaults to
+        IGNORED_ENVIRONMENT_VARIABLES)
+    Returns: differences, with toolchains first since they are the most
+        likely to matter
+    """
+    if ignore_environment is None:
+        ignore_environment = IGNORED_ENVIRONMENT_VARIABLES
+    ret = []
+    ret.extend(_compare_dicts("toolchain", old.toolchains, new.toolchains))
+    ret.extend(_compare_dicts("package", old.packages, new.packages))
+    ret.extend(_compare_dicts(
+        "environment", old.environment, new.environment, ignore_environment))
+    return ret
+
+
+def format_environment_differences(differences: List[EnvironmentDifference]) -> str:
+    if not differences:
+        return "The build environments are identical.\n"
+    lines = []
+    for diff in differences:
+        if diff.old is None:
+            lines.append("+ %s %s: %s" % (diff.kind, diff.name, diff.new))
+        elif diff.new is None:
+            lines.append("- %s %s: %s" % (diff.kind, diff.name, diff.old))
+        else:
+            lines.append("~ %s %s: %s -> %s" % (
+                diff.kind, diff.name, diff.old, diff.new))
+    return "\n".join(lines) + "\n"
@@ -69,6 +69,15 @@ from buildlog_consultant.common import (
 )
 
 from .fix_build import BuildFixer
+from .problems import (
+    GhcVersionMismatch,
+    HiddenHaskellPackage,
+    MissingDartPackage,
+    MissingHaskellModule,
+    MissingHexPackages,
+    MissingOcamlFindlibPackage,
+    MissingQtModules,
+)
 from .requirements import (
     BinaryRequirement,
     PathRequirement,
@@ -78,8 +87,10 @@ from .requirements import (
     ValaPackageRequirement,
     RubyGemRequirement,
     GoPackageRequirement,
+    GoRequirement,
     DhAddonRequirement,
     PhpClassRequirement,
+    PhpExtensionRequirement,
     RPackageRequirement,
     NodePackageRequirement,
     LibraryRequirement,
@@ -88,6 +99,7 @@ from .requirements import (
     SprocketsFileRequirement,
     JavaClassRequirement,
     CMakefileRequirement,
+    HaskellModuleRequirement,
     HaskellPackageRequirement,
     MavenArtifactRequirement,
     BoostComponentRequirement,
@@ -103,6 +115,7 @@ from .requirements import (
     CertificateAuthorityRequirement,
     NodeModuleRequirement,
     QTRequirement,
+    QtModuleRequirement,
     X11Requirement,
     LibtoolRequirement,
     VagueDependencyRequirement,
@@ -111,6 +124,9 @@ from .requirements import (
     LatexPackageRequirement,
     CargoCrateRequirement,
     StaticLibraryRequirement,
+    OcamlFindlibRequirement,
+    DartPackageRequirement,
+    HexPackageRequirement,
 )
 from .resolver import UnsatisfiedRequirements
 
@@ -168,6 +184,13 @@ def problem_to_upstream_requirement(problem):  # noqa: C901
         return [CMakefileRequirement(filename, problem.version) for filename in problem.filenames]
     elif isinstance(problem, MissingHaskellDependencies):
         return [HaskellPackageRequirement.from_string(dep) for dep in problem.deps]
+    elif isinstance(problem, MissingHaskellModule):
+        return HaskellModuleRequirement(problem.module)
+    elif isinstance(problem, (HiddenHaskellPackage, GhcVersionMismatch)):
+        # Not something that can be installed; a hidden package is already
+        # installed but needs to be declared as a dependency, and system
+        # packages only provide a single GHC version.
+        return None
     elif isinstance(problem, MissingMavenArtifacts):
         return [
             MavenArtifactRequirement.from_str(artifact)
@@ -223,6 +246,12 @@ def problem_to_upstream_requirement(problem):  # noqa: C901
             module=problem.module, filename=problem.filename, inc=problem.inc
         )
     elif isinstance(problem, MissingPerlFile):
+        if (problem.filename.endswith(".pm")
+                and not problem.filename.startswith("/")):
+            # e.g. "Can't locate Foo/Bar.pm in @INC"
+            return PerlModuleRequirement(
+                module=problem.filename[:-3].replace("/", "::"),
+                filename=problem.filename, inc=problem.inc)
         return PerlFileRequirement(filename=problem.filename)
     elif isinstance(problem, MissingAutoconfMacro):
         return AutoconfMacroRequirement(problem.macro)
@@ -238,9 +267,34 @@ def problem_to_upstream_requirement(problem):  # noqa: C901
             python_version=problem.python_version,
             minimum_version=problem.minimum_version,
         )
+    elif isinstance(problem, MissingOcamlFindlibPackage):
+        return OcamlFindlibRequirement(problem.package)
+    elif isinstance(problem, MissingDartPackage):
+        return DartPackageRequirement(problem.package)
+    elif isinstance(problem, MissingHexPackages):
+        return [HexPackageRequirement(package) for package in problem.packages]
+    elif isinstance(problem, MissingQtModules):
+        return [QtModuleRequirement(module) for module in problem.modules]
     elif problem.kind == 'unsatisfied-apt-dependencies':
         from .resolver.apt import AptRequirement
         return AptRequirement(problem.relations)
+    # The problems below are matched by kind, since not all versions of
+    # buildlog-consultant define them.
+    elif problem.kind == 'missing-php-extension':
+        return PhpExtensionRequirement(problem.extension)
+    elif problem.kind == 'missing-go.sum-entry':
+        version = problem.version
+        if version and version.startswith('v'):
+            version = version[1:]
+        return GoPackageRequirement(problem.package, version)
+    elif problem.kind == 'missing-go-runtime':
+        return GoRequirement()
+    elif problem.kind == 'missing-go.mod-file':
+        # Not something that can be installed; the project either predates
+        # Go modules or is being built from the wrong directory.
+        return None
+    elif problem.kind == 'missing-fortran-compiler':
+        return BinaryRequirement("gfortran")
     else:
         return None
 
@@ -307,16 +361,32 @@ class ExplainInstallFixer(BuildFixer):
         raise ExplainInstall(explanations)
 
 
+def requirements_met(session, reqs):
+    """Check which requirements are met.
+
+    The checks run concurrently if the session is thread-safe.
+
+    Returns: list of booleans, one per requirement; requirements that can
+        not be checked are considered unmet
+    """
+    from concurrent.futures import ThreadPoolExecutor
+    from .session import DEFAULT_PARALLELISM
+
+    def check(req):
+        return bool(req.try_met(session))
+
+    if not session.thread_safe:
+        return [check(req) for req in reqs]
+
+    with ThreadPoolExecutor(max_workers=DEFAULT_PARALLELISM) as executor:
+        return list(executor.map(check, reqs))
+
+
 def install_missing_reqs(session, resolver, reqs, explain=False):
     if not reqs:
         return
-    missing = []
-    for req in reqs:
-        try:
-            if not req.met(session):
-                missing.append(req)
-        except NotImplementedError:
-            missing.append(req)
+    missing = [req for (req, met) in zip(reqs, requirements_met(session, reqs))
+               if not met]
     if missing:
         if explain:
             commands = resolver.explain(missing)
@@ -21,18 +21,22 @@ import logging
 import os
 import re
 import shlex
-from typing import Optional, Tuple
+from typing import Dict, List, Optional, Tuple
 import warnings
 
 from . import shebang_binary, UnidentifiedError
-from .dist_catcher import DistCatcher
+from .dist_catcher import DistCatcher, DistNoTarball
 from .outputs import (
     BinaryOutput,
     PythonPackageOutput,
     RPackageOutput,
 )
 from .requirements import (
+    BazelModuleRequirement,
     BinaryRequirement,
+    CHeaderRequirement,
+    LibraryRequirement,
+    PkgConfigRequirement,
     PythonPackageRequirement,
     PerlModuleRequirement,
     NodePackageRequirement,
@@ -43,6 +47,10 @@ from .requirements import (
     MavenArtifactRequirement,
     GoRequirement,
     GoPackageRequirement,
+    HaskellPackageRequirement,
+    NuGetPackageRequirement,
+    NimblePackageRequirement,
+    CondaPackageRequirement,
 )
 from .fix_build import run_with_build_fixers
 from .session import which
@@ -59,6 +67,244 @@ class NoBuildToolsFound(Exception):
     """No supported build tools were found."""
 
 
+def run_for_each(session, buildsystems, phase, action):
+    """Run an action for all build systems that support it.
+
+    Build systems for which action raises NotImplementedError are skipped.
+
+    Raises:
+      NoBuildToolsFound: if there are no build systems
+      NotImplementedError: if none of the build systems support the action
+    """
+    from .session.metrics import metrics_phase
+
+    if not buildsystems:
+        raise NoBuildToolsFound()
+
+    done = False
+    for buildsystem in buildsystems:
+        with metrics_phase(session, phase):
+            try:
+                action(buildsystem)
+            except NotImplementedError:
+                logging.info("%s is not supported for %s", phase, buildsystem)
+                continue
+        done = True
+
+    if not done:
+        raise NotImplementedError(phase)
+
+
+# Build profiles that can be requested from BuildSystem.build
+BUILD_PROFILES = ["debug", "release"]
+
+# Compiler flags for build systems that don't have their own profiles
+PROFILE_FLAGS = {
+    "debug": "-g -O0",
+    "release": "-O2 -DNDEBUG",
+}
+
+
+def profile_variables(profile: Optional[str]) -> Dict[str, str]:
+    """Compiler flag variables for a build profile.
+
+    Returns: dictionary with CFLAGS and CXXFLAGS, or an empty dictionary
+        if no profile was requested
+    """
+    if profile is None:
+        return {}
+    return {
+        "CFLAGS": PROFILE_FLAGS[profile], "CXXFLAGS": PROFILE_FLAGS[profile]}
+
+
+def default_jobs() -> int:
+    """Number of jobs to run in parallel by default: the number of CPUs."""
+    try:
+        return len(os.sched_getaffinity(0))
+    except AttributeError:
+        return os.cpu_count() or 1
+
+
+# Debian architectures, with their GNU triplets, Rust target triples and
+# Meson CPU family/CPU/endianness
+KNOWN_TARGETS = {
+    "amd64": ("x86_64-linux-gnu", "x86_64-unknown-linux-gnu",
+              ("x86_64", "x86_64", "little")),
+    "i386": ("i686-linux-gnu", "i686-unknown-linux-gnu",
+             ("x86", "i686", "little")),
+    "arm64": ("aarch64-linux-gnu", "aarch64-unknown-linux-gnu",
+              ("aarch64", "aarch64", "little")),
+    "armhf": ("arm-linux-gnueabihf", "armv7-unknown-linux-gnueabihf",
+              ("arm", "armv7", "little")),
+    "armel": ("arm-linux-gnueabi", "armv5te-unknown-linux-gnueabi",
+              ("arm", "armv5te", "little")),
+    "ppc64el": ("powerpc64le-linux-gnu", "powerpc64le-unknown-linux-gnu",
+                ("ppc64", "ppc64le", "little")),
+    "s390x": ("s390x-linux-gnu", "s390x-unknown-linux-gnu",
+              ("s390x", "s390x", "big")),
+    "riscv64": ("riscv64-linux-gnu", "riscv64gc-unknown-linux-gnu",
+                ("riscv64", "riscv64", "little")),
+    "mips64el": ("mips64el-linux-gnuabi64", "mips64el-unknown-linux-gnuabi64",
+                 ("mips64", "mips64", "little")),
+}
+
+
+class Target(object):
+    """A platform to cross-build for.
+
+    Attributes:
+      arch: Debian architecture name, e.g. "armhf"
+      triple: GNU triplet, e.g. "arm-linux-gnueabihf"
+    """
+
+    def __init__(self, arch: str, triple: str):
+        self.arch = arch
+        self.triple = triple
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.arch, self.triple)
+
+    def __str__(self):
+        return self.arch
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    @classmethod
+    def from_arch(cls, arch: str) -> "Target":
+        """Create a target from a Debian architecture name.
+
+        Raises:
+          KeyError: if the architecture is not known
+        """
+        return cls(arch, KNOWN_TARGETS[arch][0])
+
+    @classmethod
+    def from_debian_arch(cls, arch: str) -> "Target":
+        """Create a target from a Debian architecture name.
+
+        Architectures that are not known are looked up with
+        dpkg-architecture.
+        """
+        import subprocess
+
+        try:
+            return cls.from_arch(arch)
+        except KeyError:
+            triple = subprocess.check_output(
+                ["dpkg-architecture", "-a%s" % arch, "-qDEB_HOST_GNU_TYPE"],
+                stderr=subprocess.DEVNULL).decode().strip()
+            return cls(arch, triple)
+
+    @property
+    def rust_triple(self) -> str:
+        try:
+            return KNOWN_TARGETS[self.arch][1]
+        except KeyError:
+            # Close enough for most triplets
+            cpu, rest = self.triple.split("-", 1)
+            return "%s-unknown-%s" % (cpu, rest)
+
+    def tool(self, name: str) -> str:
+        """Name of the cross tool, e.g. tool("gcc") -> "arm-linux-gnueabihf-gcc"."""
+        return "%s-%s" % (self.triple, name)
+
+    def meson_cross_file(self) -> str:
+        """Contents of a Meson cross file for this target."""
+        try:
+            cpu_family, cpu, endian = KNOWN_TARGETS[self.arch][2]
+        except KeyError:
+            cpu_family = cpu = self.triple.split("-")[0]
+            endian = "little"
+        return """\
+[binaries]
+c = '%(cc)s'
+cpp = '%(cxx)s'
+ar = '%(ar)s'
+strip = '%(strip)s'
+pkg-config = '%(pkg_config)s'
+
+[host_machine]
+system = 'linux'
+cpu_family = '%(cpu_family)s'
+cpu = '%(cpu)s'
+endian = '%(endian)s'
+""" % {
+            "cc": self.tool("gcc"), "cxx": self.tool("g++"),
+            "ar": self.tool("ar"), "strip": self.tool("strip"),
+            "pkg_config": self.tool("pkg-config"),
+            "cpu_family": cpu_family, "cpu": cpu, "endian": endian}
+
+
+class BuildConfig(object):
+    """Configuration shared by the actions on a build system.
+
+    Attributes:
+      build_dir: Directory to build in, relative to the project or absolute
+        (inside the session); None to use the build system's default. Build
+        systems that can not build out of tree ignore it.
+      jobs: Number of jobs to run in parallel; None to use the build
+        system's default
+      target: Target to cross-build for, or None to build for the host
+      extra_args: Dictionary mapping build system names to additional
+        arguments for the command that configures (or, if there is no
+        separate configure step, builds) the project
+    """
+
+    def __init__(
+        self, build_dir: Optional[str] = None, jobs: Optional[int] = None,
+        target: Optional[Target] = None,
+        extra_args: Optional[Dict[str, List[str]]] = None
+    ):
+        self.build_dir = build_dir
+        self.jobs = jobs
+        self.target = target
+        self.extra_args = dict(extra_args or {})
+
+    def __repr__(self):
+        return "%s(build_dir=%r, jobs=%r, target=%r, extra_args=%r)" % (
+            type(self).__name__, self.build_dir, self.jobs, self.target,
+            self.extra_args)
+
+    def extra_args_for(self, name: str) -> List[str]:
+        return list(self.extra_args.get(name, []))
+
+    def jobs_args(self) -> List[str]:
+        """Arguments for make-like tools that set the number of jobs."""
+        if self.jobs is None:
+            return []
+        return ["-j%d" % self.jobs]
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+
+def parse_extra_args(extra_args, meson_options=None):
+    """Parse --extra-args and --meson-option values.
+
+    Args:
+      extra_args: List of BUILDSYSTEM=ARGS strings
+      meson_options: List of NAME=VALUE strings
+    Returns: dictionary mapping build system names to lists of arguments
+    Raises:
+      ValueError: if one of the values is malformed
+    """
+    ret = {}
+    for text in extra_args:
+        name, sep, value = text.partition("=")
+        if not sep or not name:
+            raise ValueError(
+                "invalid --extra-args %r; expected BUILDSYSTEM=ARGS" % text)
+        ret.setdefault(name, []).extend(shlex.split(value))
+    for text in meson_options or []:
+        name, sep, value = text.partition("=")
+        if not sep or not name:
+            raise ValueError(
+                "invalid --meson-option %r; expected NAME=VALUE" % text)
+        ret.setdefault("meson", []).append("-D%s" % text)
+    return ret
+
+
 class InstallTarget(object):
 
     # Whether to prefer user-specific installation
@@ -69,11 +315,35 @@ class InstallTarget(object):
     # TODO(jelmer): Add information about target directory, layout, etc.
 
 
+# Directory (relative to the project) that coverage data is written to
+COVERAGE_DIR = os.path.join(".ognibuild", "coverage")
+
+
+def _coverage_path(session, name):
+    """Path for a coverage file, creating the coverage directory."""
+    os.makedirs(session.external_path(COVERAGE_DIR), exist_ok=True)
+    return os.path.join(COVERAGE_DIR, name)
+
+
+def _lcov_capture(session, fixers, directory, output):
+    """Collect the gcov data in a directory into an lcov tracefile."""
+    run_with_build_fixers(
+        session, ["lcov", "--capture", "--directory", directory,
+                  "--output-file", output], fixers)
+    return output
+
+
 class BuildSystem(object):
     """A particular buildsystem."""
 
     name: str
 
+    build_config: BuildConfig
+
+    def __init__(self, path):
+        self.path = path
+        self.build_config = BuildConfig()
+
     def __str__(self):
         return self.name
 
@@ -85,12 +355,45 @@ class BuildSystem(object):
     def test(self, session, resolver, fixers):
         raise NotImplementedError(self.test)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
+        """Build the project.
+
+        Args:
+          profile: Optional build profile ("debug" or "release"); build
+            systems without such a concept ignore it
+        """
         raise NotImplementedError(self.build)
 
     def clean(self, session, resolver, fixers):
         raise NotImplementedError(self.clean)
 
+    def lint(self, session, resolver, fixers):
+        """Run the linters or static analysis tools for the project."""
+        raise NotImplementedError(self.lint)
+
+    def coverage(self, session, resolver, fixers) -> str:
+        """Run the testsuite, collecting code coverage.
+
+        Returns: path of the resulting lcov tracefile, relative to the
+          project
+        """
+        raise NotImplementedError(self.coverage)
+
+    def targets(self, session) -> List[str]:
+        """List the targets that can be built."""
+        raise NotImplementedError(self.targets)
+
+    def update_lockfile(self, session, resolver, fixers):
+        """Refresh the lockfile of the project."""
+        raise NotImplementedError(self.update_lockfile)
+
+    def vendor(self, session, resolver, fixers):
+        """Vendor the dependencies of the project into the source tree.
+
+        This allows building the project without network access later.
+        """
+        raise NotImplementedError(self.vendor)
+
     def install(self, session, resolver, fixers, install_target):
         raise NotImplementedError(self.install)
 
@@ -100,11 +403,50 @@ class BuildSystem(object):
     def get_declared_outputs(self, session, fixers=None):
         raise NotImplementedError(self.get_declared_outputs)
 
+    def select_workspace(self, name: str) -> None:
+        """Only act on a single member of a workspace (monorepo).
+
+        Raises:
+          ValueError: if there is no such member
+        """
+        raise NotImplementedError(self.select_workspace)
+
+    def project_version(self, session) -> Optional[str]:
+        """Determine the version of the project.
+
+        Returns: version string, or None if it could not be determined
+        """
+        return version_from_git_describe(session)
+
     @classmethod
     def probe(cls, path):
         return None
 
 
+def version_from_git_describe(session) -> Optional[str]:
+    """Determine the project version from the most recent git tag."""
+    import subprocess
+
+    if not session.exists(".git"):
+        return None
+    try:
+        p = session.Popen(
+            ["git", "describe", "--tags"],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.DEVNULL,
+        )
+    except FileNotFoundError:
+        return None
+    output, _ = p.communicate()
+    if p.returncode != 0:
+        return None
+    version = output.decode().strip()
+    m = re.fullmatch(r"(?:v|version-|release-)?([0-9].*)", version)
+    if m:
+        return m.group(1)
+    return version or None
+
+
 def xmlparse_simplify_namespaces(path, namespaces):
     import xml.etree.ElementTree as ET
 
@@ -126,7 +468,7 @@ class Pear(BuildSystem):
         ]
 
     def __init__(self, path):
-        self.path = path
+        super(Pear, self).__init__(path)
 
     def dist(self, session, resolver, fixers, target_directory: str, quiet=False):
         with DistCatcher([session.external_path(".")]) as dc:
@@ -136,7 +478,7 @@ class Pear(BuildSystem):
     def test(self, session, resolver, fixers):
         run_with_build_fixers(session, [guaranteed_which(session, resolver, "pear"), "run-tests"], fixers)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
         run_with_build_fixers(session, [guaranteed_which(session, resolver, "pear"), "build", self.path], fixers)
 
     def clean(self, session, resolver, fixers):
@@ -279,13 +621,26 @@ with open(%(output_path)s, 'w') as f:
 """
 
 
+# Names of Python dependency groups (extras, Poetry/PDM groups or Hatch
+# environments) whose dependencies are needed to run the tests; the
+# dependencies in other groups are only needed for development.
+PYTHON_TEST_GROUPS = ["dev", "test", "tests", "testing", "hatch-test"]
+
+
+def python_dependency_group_stage(name):
+    return "test" if name.lower() in PYTHON_TEST_GROUPS else "dev"
+
+
 class SetupPy(BuildSystem):
 
     name = "setup.py"
     DEFAULT_PYTHON = "python3"
 
     def __init__(self, path):
-        self.path = path
+        super(SetupPy, self).__init__(path)
+        # Whether to build in a virtualenv, rather than the session's Python
+        self.use_virtualenv = True
+        self._virtualenv_path: Optional[str] = None
         if os.path.exists(os.path.join(self.path, "setup.py")):
             self.has_setup_py = True
         else:
@@ -323,6 +678,32 @@ class SetupPy(BuildSystem):
             return read_configuration(p)
         raise FileNotFoundError(p)
 
+    def project_version(self, session):
+        if self.pyproject:
+            version = self.pyproject.get("project", {}).get("version")
+            if version:
+                return version
+            version = (
+                self.pyproject.get("tool", {}).get("poetry", {}).get("version"))
+            if version:
+                return version
+        if self.config:
+            version = self.config.get("metadata", {}).get("version")
+            if version:
+                return str(version)
+        return super(SetupPy, self).project_version(session)
+
+    def setup_virtualenv(self, session):
+        """Create and activate the project virtualenv, if enabled."""
+        from .virtualenv import activate_virtualenv, create_virtualenv_directory
+
+        if not self.use_virtualenv:
+            return
+        if self._virtualenv_path is None:
+            self._virtualenv_path = create_virtualenv_directory(session)
+        activate_virtualenv(
+            session, self._virtualenv_path, python=self._determine_interpreter())
+
     def _extract_setup(self, session=None, fixers=None):
         if not self.has_setup_py:
             return None
@@ -390,6 +771,7 @@ class SetupPy(BuildSystem):
         return "%s(%r)" % (type(self).__name__, self.path)
 
     def test(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
         if os.path.exists(os.path.join(self.path, "tox.ini")):
             run_with_build_fixers(session, ["tox"], fixers)
         elif self.pyproject:
@@ -401,19 +783,21 @@ class SetupPy(BuildSystem):
             # a 'test' subcommand and some packages fall back to distutils
             # if setuptools is not available.
             setuptools_req = PythonPackageRequirement("setuptools")
-            if not setuptools_req.met(session):
+            if not setuptools_req.try_met(session):
                 resolver.install([setuptools_req])
             self._run_setup(session, resolver, ["test"], fixers)
         else:
             raise NotImplementedError
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup_virtualenv(session)
         if self.has_setup_py:
             self._run_setup(session, resolver, ["build"], fixers)
         else:
             raise NotImplementedError
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        self.setup_virtualenv(session)
         # TODO(jelmer): Look at self.build_backend
         if self.has_setup_py:
             preargs = []
@@ -422,7 +806,7 @@ class SetupPy(BuildSystem):
             # Preemptively install setuptools since some packages fail in
             # some way without it.
             setuptools_req = PythonPackageRequirement("setuptools")
-            if not setuptools_req.met(session):
+            if not setuptools_req.try_met(session):
                 resolver.install([setuptools_req])
             with DistCatcher([session.external_path("dist")]) as dc:
                 self._run_setup(session, resolver, preargs + ["sdist"], fixers)
@@ -438,12 +822,33 @@ class SetupPy(BuildSystem):
         raise AssertionError("no setup.py or pyproject.toml")
 
     def clean(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
         if self.has_setup_py:
             self._run_setup(session, resolver, ["clean"], fixers)
         else:
             raise NotImplementedError
 
+    def lint(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
+        uses_ruff = (
+            "ruff" in (self.pyproject or {}).get("tool", {})
+            or os.path.exists(os.path.join(self.path, "ruff.toml"))
+            or os.path.exists(os.path.join(self.path, ".ruff.toml")))
+        if uses_ruff:
+            run_with_build_fixers(session, ["ruff", "check", "."], fixers)
+        else:
+            run_with_build_fixers(session, ["flake8"], fixers)
+
+    def coverage(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
+        output = _coverage_path(session, "python.info")
+        run_with_build_fixers(
+            session, ["coverage", "run", "-m", "pytest"], fixers)
+        run_with_build_fixers(session, ["coverage", "lcov", "-o", output], fixers)
+        return output
+
     def install(self, session, resolver, fixers, install_target):
+        self.setup_virtualenv(session)
         if self.has_setup_py:
             extra_args = []
             if install_target.user:
@@ -452,7 +857,20 @@ class SetupPy(BuildSystem):
                 extra_args.append("--prefix=%s" % install_target.prefix)
             self._run_setup(session, resolver, ["install"] + extra_args, fixers)
         else:
-            raise NotImplementedError
+            self._pep517_install(session, fixers, install_target)
+
+    def _pep517_install(self, session, fixers, install_target):
+        """Install the project with pip, using its PEP 517 build backend.
+
+        Tools like poetry, hatch and pdm only install into their own
+        virtualenvs, so pip is used to honour the install target.
+        """
+        argv = ["python3", "-m", "pip", "install", "."]
+        if install_target.user:
+            argv.append("--user")
+        if install_target.prefix:
+            argv.append("--prefix=%s" % install_target.prefix)
+        run_with_build_fixers(session, argv, fixers)
 
     def _determine_interpreter(self):
         interpreter = None
@@ -514,6 +932,9 @@ class SetupPy(BuildSystem):
                     yield "build", PythonPackageRequirement.from_requirement_str(
                         require
                     )
+            if not self.has_setup_py:
+                # With a setup.py, setuptools reports these already
+                yield from self._pep621_dependencies()
         if self.config:
             options = self.config.get("options", {})
             for require in options.get("setup_requires", []):
@@ -521,6 +942,22 @@ class SetupPy(BuildSystem):
             for require in options.get("install_requires", []):
                 yield "core", PythonPackageRequirement.from_requirement_str(require)
 
+    def _pep621_dependencies(self):
+        project = self.pyproject.get("project", {})
+        for require in project.get("dependencies", []):
+            yield "core", PythonPackageRequirement.from_requirement_str(require)
+        for name, requires in project.get("optional-dependencies", {}).items():
+            stage = python_dependency_group_stage(name)
+            for require in requires:
+                yield stage, PythonPackageRequirement.from_requirement_str(require)
+        # PEP 735 dependency groups
+        for name, requires in self.pyproject.get("dependency-groups", {}).items():
+            stage = python_dependency_group_stage(name)
+            for require in requires:
+                if isinstance(require, str):
+                    yield stage, PythonPackageRequirement.from_requirement_str(
+                        require)
+
     def get_declared_outputs(self, session, fixers=None):
         distribution = self._extract_setup(session, fixers)
         all_packages = set()
@@ -557,16 +994,397 @@ class SetupPy(BuildSystem):
             logging.debug("Found setup.py, assuming python project.")
             return cls(path)
         if os.path.exists(os.path.join(path, "pyproject.toml")):
+            if (Poetry.exists(path) or Hatch.exists(path) or Pdm.exists(path)
+                    or Maturin.exists(path)):
+                return None
             logging.debug("Found pyproject.toml, assuming python project.")
             return cls(path)
 
 
+def _poetry_constraint_to_specs(constraint):
+    """Convert a Poetry version constraint to PEP 440 specifiers.
+
+    See https://python-poetry.org/docs/dependency-specification/
+    """
+    def bump(version, index):
+        parts = [int(p) if p.isdigit() else 0 for p in version.split(".")]
+        index = min(index, len(parts) - 1)
+        return ".".join(
+            [str(p) for p in parts[:index]] + [str(parts[index] + 1)])
+
+    alternatives = re.split(r"\|\|?", constraint)
+    if len(alternatives) > 1:
+        return _poetry_union_specs(
+            [_poetry_constraint_to_specs(alt) for alt in alternatives])
+
+    specs = []
+    for part in re.split(r",|\s+(?=[<>=!^~])", constraint):
+        part = part.strip()
+        if part in ("", "*"):
+            continue
+        if part.startswith("^"):
+            version = part[1:]
+            parts = version.split(".")
+            # The first non-zero component may not change
+            index = next(
+                (i for i, p in enumerate(parts) if p != "0"), len(parts) - 1)
+            specs.extend([(">=", version), ("<", bump(version, index))])
+        elif part.startswith("~="):
+            specs.append(("~=", part[2:].strip()))
+        elif part.startswith("~"):
+            version = part[1:]
+            index = 0 if len(version.split(".")) == 1 else 1
+            specs.extend([(">=", version), ("<", bump(version, index))])
+        else:
+            m = re.fullmatch(r"(==|!=|>=|<=|>|<)?\s*(.*)", part)
+            specs.append((m.group(1) or "==", m.group(2)))
+    return specs
+
+
+def _poetry_union_specs(alternatives):
+    """Approximate a union of specifier lists with a single list.
+
+    Specifier lists can only express intersections, so this returns the
+    loosest bounds that cover all alternatives. Exclusions are dropped.
+    """
+    from .outdated import version_key
+
+    if any(not specs for specs in alternatives):
+        return []
+    specs = []
+    for ops in [(">=", ">", "==", "~="), ("<", "<=", "==")]:
+        bounds = []
+        for alternative in alternatives:
+            matching = [spec for spec in alternative if spec[0] in ops]
+            if not matching:
+                # One of the alternatives is unbounded on this side
+                break
+            bounds.append(matching[0])
+        else:
+            op, version = (min if ops[0] == ">=" else max)(
+                bounds, key=lambda spec: version_key(spec[1]))
+            if op in ("==", "~="):
+                op = ops[0] if ops[0] == ">=" else "<="
+            specs.append((op, version))
+    return specs
+
+
+def _poetry_requirements(dependencies):
+    for name, spec in dependencies.items():
+        if name == "python":
+            continue
+        if isinstance(spec, list):
+            # Multiple constraints, for different environments
+            spec = spec[0]
+        if isinstance(spec, dict):
+            if spec.get("optional"):
+                continue
+            if "path" in spec or "git" in spec or "url" in spec:
+                yield PythonPackageRequirement(name)
+                continue
+            spec = spec.get("version", "*")
+        yield PythonPackageRequirement(
+            name, specs=_poetry_constraint_to_specs(spec))
+
+
+class Poetry(SetupPy):
+    """Python project managed with Poetry."""
+
+    name = "poetry"
+
+    def __init__(self, path):
+        super(Poetry, self).__init__(path)
+        self.poetry = self.pyproject["tool"]["poetry"]
+
+    def _poetry(self, session, resolver):
+        return guaranteed_which(session, resolver, "poetry")
+
+    def test(self, session, resolver, fixers):
+        poetry = self._poetry(session, resolver)
+        run_with_build_fixers(
+            session, [poetry, "install", "--no-root"], fixers)
+        run_with_build_fixers(session, [poetry, "run", "pytest"], fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        run_with_build_fixers(
+            session, [self._poetry(session, resolver), "build"], fixers)
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        argv = [self._poetry(session, resolver), "build", "--format", "sdist"]
+        if quiet:
+            argv.append("--quiet")
+        with DistCatcher([session.external_path("dist")]) as dc:
+            run_with_build_fixers(session, argv, fixers)
+        return dc.copy_single(target_directory)
+
+    def clean(self, session, resolver, fixers):
+        raise NotImplementedError
+
+    def get_declared_dependencies(self, session, fixers=None):
+        yield from super(Poetry, self).get_declared_dependencies(session, fixers)
+        for req in _poetry_requirements(self.poetry.get("dependencies", {})):
+            yield "core", req
+        # Before Poetry 1.2, there were only development dependencies
+        for req in _poetry_requirements(self.poetry.get("dev-dependencies", {})):
+            yield "test", req
+        for name, group in self.poetry.get("group", {}).items():
+            stage = python_dependency_group_stage(name)
+            for req in _poetry_requirements(group.get("dependencies", {})):
+                yield stage, req
+
+    @classmethod
+    def exists(cls, path):
+        if not os.path.exists(os.path.join(path, "pyproject.toml")):
+            return False
+        import toml
+
+        with open(os.path.join(path, "pyproject.toml"), "r") as f:
+            pyproject = toml.load(f)
+        return "poetry" in pyproject.get("tool", {})
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "setup.py")):
+            return None
+        if cls.exists(path):
+            logging.debug("Found [tool.poetry] in pyproject.toml, assuming poetry project.")
+            return cls(path)
+
+
+def _pyproject_build_backend(path):
+    """Return the build backend and tool tables from pyproject.toml."""
+    import toml
+
+    p = os.path.join(path, "pyproject.toml")
+    if not os.path.exists(p):
+        return None, {}
+    with open(p, "r") as f:
+        pyproject = toml.load(f)
+    return (
+        pyproject.get("build-system", {}).get("build-backend"),
+        pyproject.get("tool", {}))
+
+
+class Hatch(SetupPy):
+    """Python project built with Hatch (hatchling)."""
+
+    name = "hatch"
+
+    def _hatch(self, session, resolver):
+        return guaranteed_which(session, resolver, "hatch")
+
+    def test(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
+        run_with_build_fixers(
+            session, [self._hatch(session, resolver), "test"], fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup_virtualenv(session)
+        run_with_build_fixers(
+            session, [self._hatch(session, resolver), "build"], fixers)
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        self.setup_virtualenv(session)
+        argv = [self._hatch(session, resolver), "build", "--target", "sdist"]
+        with DistCatcher([session.external_path("dist")]) as dc:
+            run_with_build_fixers(session, argv, fixers)
+        return dc.copy_single(target_directory)
+
+    def clean(self, session, resolver, fixers):
+        run_with_build_fixers(
+            session, [self._hatch(session, resolver), "clean"], fixers)
+
+    def get_declared_dependencies(self, session, fixers=None):
+        yield from super(Hatch, self).get_declared_dependencies(session, fixers)
+        envs = self.pyproject.get("tool", {}).get("hatch", {}).get("envs", {})
+        for name, env in envs.items():
+            stage = python_dependency_group_stage(
+                "test" if name == "default" else name)
+            for key in ["dependencies", "extra-dependencies"]:
+                for require in env.get(key, []):
+                    yield stage, PythonPackageRequirement.from_requirement_str(
+                        require)
+
+    @classmethod
+    def exists(cls, path):
+        backend, tool = _pyproject_build_backend(path)
+        return backend == "hatchling.build" or (backend is None and "hatch" in tool)
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "setup.py")):
+            return None
+        if cls.exists(path):
+            logging.debug("Found hatchling build backend, assuming hatch project.")
+            return cls(path)
+
+
+class Pdm(SetupPy):
+    """Python project managed with PDM."""
+
+    name = "pdm"
+
+    def _pdm(self, session, resolver):
+        return guaranteed_which(session, resolver, "pdm")
+
+    def _pdm_scripts(self):
+        return self.pyproject.get("tool", {}).get("pdm", {}).get("scripts", {})
+
+    def test(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
+        pdm = self._pdm(session, resolver)
+        if "test" in self._pdm_scripts():
+            argv = [pdm, "run", "test"]
+        else:
+            argv = [pdm, "run", "pytest"]
+        run_with_build_fixers(session, argv, fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup_virtualenv(session)
+        run_with_build_fixers(
+            session, [self._pdm(session, resolver), "build"], fixers)
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        self.setup_virtualenv(session)
+        argv = [self._pdm(session, resolver), "build", "--no-wheel"]
+        if quiet:
+            argv.append("--quiet")
+        with DistCatcher([session.external_path("dist")]) as dc:
+            run_with_build_fixers(session, argv, fixers)
+        return dc.copy_single(target_directory)
+
+    def clean(self, session, resolver, fixers):
+        raise NotImplementedError
+
+    def get_declared_dependencies(self, session, fixers=None):
+        yield from super(Pdm, self).get_declared_dependencies(session, fixers)
+        pdm = self.pyproject.get("tool", {}).get("pdm", {})
+        for name, requires in pdm.get("dev-dependencies", {}).items():
+            stage = python_dependency_group_stage(name)
+            for require in requires:
+                # Skip editable and path requirements, e.g. "-e file:///..."
+                if require.startswith("-e "):
+                    continue
+                yield stage, PythonPackageRequirement.from_requirement_str(
+                    require)
+
+    @classmethod
+    def exists(cls, path):
+        backend, tool = _pyproject_build_backend(path)
+        return backend in ("pdm.backend", "pdm.pep517.api") or (
+            backend is None and "pdm" in tool)
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "setup.py")):
+            return None
+        if cls.exists(path):
+            logging.debug("Found pdm build backend, assuming pdm project.")
+            return cls(path)
+
+
+class Maturin(SetupPy):
+    """Mixed Rust/Python project built with maturin (e.g. using PyO3)."""
+
+    name = "maturin"
+
+    def _maturin(self, session, resolver):
+        return guaranteed_which(session, resolver, "maturin")
+
+    def _cargo_manifest(self):
+        maturin = self.pyproject.get("tool", {}).get("maturin", {})
+        return os.path.join(self.path, maturin.get("manifest-path", "Cargo.toml"))
+
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup_virtualenv(session)
+        run_with_build_fixers(
+            session, [self._maturin(session, resolver), "build"]
+            + (["--release"] if profile == "release" else []), fixers)
+
+    def test(self, session, resolver, fixers):
+        self.setup_virtualenv(session)
+        # Build the extension and install it into the virtualenv, so that
+        # the Python tests can import it.
+        run_with_build_fixers(
+            session, [self._maturin(session, resolver), "develop"], fixers)
+        run_with_build_fixers(session, ["python3", "-m", "pytest"], fixers)
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        self.setup_virtualenv(session)
+        argv = [self._maturin(session, resolver), "sdist", "--out", "dist"]
+        with DistCatcher([session.external_path("dist")]) as dc:
+            run_with_build_fixers(session, argv, fixers)
+        return dc.copy_single(target_directory)
+
+    def clean(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["cargo", "clean"], fixers)
+
+    def get_declared_dependencies(self, session, fixers=None):
+        yield from super(Maturin, self).get_declared_dependencies(session, fixers)
+        manifest = self._cargo_manifest()
+        if os.path.exists(manifest):
+            yield from Cargo(manifest).get_declared_dependencies(session, fixers)
+        yield "build", BinaryRequirement("cargo")
+
+    @classmethod
+    def exists(cls, path):
+        backend, tool = _pyproject_build_backend(path)
+        return backend == "maturin" or "maturin" in tool
+
+    @classmethod
+    def probe(cls, path):
+        if cls.exists(path):
+            logging.debug("Found maturin build backend, assuming maturin project.")
+            return cls(path)
+
+
+def _starlark_calls(text, function):
+    """Find the calls to a function in a Starlark file.
+
+    Only keyword arguments with literal values are returned; this is
+    enough for the declarations in MODULE.bazel and WORKSPACE files.
+
+    Returns: iterator over dictionaries with keyword arguments
+    """
+    text = re.sub(r"#[^\n]*", "", text)
+    for m in re.finditer(r"\b%s\s*\(" % re.escape(function), text):
+        depth = 0
+        for i in range(m.end() - 1, len(text)):
+            if text[i] in "([{":
+                depth += 1
+            elif text[i] in ")]}":
+                depth -= 1
+                if depth == 0:
+                    break
+        body = text[m.end():i]
+        kwargs = {}
+        for km in re.finditer(
+                r"\b(\w+)\s*=\s*(\"[^\"]*\"|'[^']*'|True|False|\[[^\]]*\])",
+                body):
+            value = km.group(2)
+            if value in ("True", "False"):
+                kwargs[km.group(1)] = value == "True"
+            elif value.startswith("["):
+                kwargs[km.group(1)] = [
+                    v[1:-1] for v in re.findall(r"\"[^\"]*\"|'[^']*'", value)]
+            else:
+                kwargs[km.group(1)] = value[1:-1]
+        yield kwargs
+
+
+# Repository rules in WORKSPACE files that fetch external code
+BAZEL_REPOSITORY_RULES = [
+    "http_archive", "git_repository", "new_git_repository", "http_jar",
+    "http_file", "go_repository",
+]
+
+
 class Bazel(BuildSystem):
 
     name = "bazel"
 
     def __init__(self, path):
-        self.path = path
+        super(Bazel, self).__init__(path)
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
@@ -575,6 +1393,9 @@ class Bazel(BuildSystem):
     def exists(cls, path):
         if not os.path.exists(os.path.join(path, "BUILD")):
             return False
+        # Please uses BUILD files too
+        if Please.exists(path):
+            return False
         return True
 
     @classmethod
@@ -583,19 +1404,108 @@ class Bazel(BuildSystem):
             logging.debug("Found BUILD, assuming bazel package.")
             return cls(path)
 
-    def build(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["bazel", "build", "//..."], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        args = ["bazel", "build"]
+        if self.build_config.jobs is not None:
+            args.append("--jobs=%d" % self.build_config.jobs)
+        if profile is not None:
+            args.append("--compilation_mode=%s" % (
+                "opt" if profile == "release" else "dbg"))
+        run_with_build_fixers(session, args + ["//..."], fixers)
 
     def test(self, session, resolver, fixers):
         run_with_build_fixers(session, ["bazel", "test", "//..."], fixers)
 
+    def _read(self, *names):
+        for name in names:
+            try:
+                with open(os.path.join(self.path, name), "r") as f:
+                    return f.read()
+            except FileNotFoundError:
+                pass
+        return None
+
+    def get_declared_dependencies(self, session, fixers=None):
+        seen = set()
+        module = self._read("MODULE.bazel")
+        if module is not None:
+            for kwargs in _starlark_calls(module, "bazel_dep"):
+                if "name" not in kwargs:
+                    continue
+                seen.add(kwargs["name"])
+                # Development dependencies are only used by the module's
+                # own tests
+                yield (
+                    "test" if kwargs.get("dev_dependency") else "build",
+                    BazelModuleRequirement(kwargs["name"], kwargs.get("version")))
+        workspace = self._read("WORKSPACE", "WORKSPACE.bazel")
+        if workspace is not None:
+            for rule in BAZEL_REPOSITORY_RULES:
+                for kwargs in _starlark_calls(workspace, rule):
+                    if "name" in kwargs and kwargs["name"] not in seen:
+                        seen.add(kwargs["name"])
+                        yield "build", BazelModuleRequirement(kwargs["name"])
+            for kwargs in _starlark_calls(workspace, "maven_install"):
+                for artifact in kwargs.get("artifacts", []):
+                    yield "build", MavenArtifactRequirement.from_str(artifact)
+
+
+class Please(BuildSystem):
+    """The Please build system (https://please.build/)."""
+
+    name = "please"
+
+    def __init__(self, path, executable="plz"):
+        super(Please, self).__init__(path)
+        self.executable = executable
+
+    def __repr__(self):
+        return "%s(%r, executable=%r)" % (
+            type(self).__name__, self.path, self.executable)
+
+    @classmethod
+    def exists(cls, path):
+        return os.path.exists(os.path.join(path, ".plzconfig"))
+
+    @classmethod
+    def probe(cls, path):
+        if cls.exists(path):
+            logging.debug("Found .plzconfig, assuming please package.")
+            # Projects can ship a wrapper that downloads the right version
+            if os.path.exists(os.path.join(path, "pleasew")):
+                return cls(path, "./pleasew")
+            return cls(path)
+
+    def _run(self, session, resolver, fixers, args):
+        if self.executable.startswith("./"):
+            argv = [self.executable]
+            # pleasew is a bash script
+            if not os.access(
+                    os.path.join(self.path, self.executable), os.X_OK):
+                argv.insert(0, "bash")
+        else:
+            argv = [guaranteed_which(session, resolver, self.executable)]
+        run_with_build_fixers(session, argv + args, fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        args = ["build"]
+        if profile is not None:
+            args.extend(["-c", "opt" if profile == "release" else "dbg"])
+        self._run(session, resolver, fixers, args + ["//..."])
+
+    def test(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, ["test", "//..."])
+
+    def clean(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, ["clean"])
+
 
 class Octave(BuildSystem):
 
     name = "octave"
 
     def __init__(self, path):
-        self.path = path
+        super(Octave, self).__init__(path)
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
@@ -643,7 +1553,7 @@ class Gradle(BuildSystem):
     name = "gradle"
 
     def __init__(self, path, executable="gradle"):
-        self.path = path
+        super(Gradle, self).__init__(path)
         self.executable = executable
 
     def __repr__(self):
@@ -670,7 +1580,7 @@ class Gradle(BuildSystem):
     def setup(self, session, resolver):
         if not self.executable.startswith("./"):
             binary_req = BinaryRequirement(self.executable)
-            if not binary_req.met(session):
+            if not binary_req.try_met(session):
                 resolver.install([binary_req])
 
     def _run(self, session, resolver, task, args, fixers):
@@ -682,6 +1592,10 @@ class Gradle(BuildSystem):
             argv.append("sh")
         argv.extend([self.executable, task])
         argv.extend(args)
+        from .maven import offline_requested
+
+        if offline_requested(session):
+            argv.append("--offline")
         try:
             run_with_build_fixers(session, argv, fixers)
         except UnidentifiedError as e:
@@ -699,8 +1613,10 @@ class Gradle(BuildSystem):
     def clean(self, session, resolver, fixers):
         self._run(session, resolver, "clean", [], fixers)
 
-    def build(self, session, resolver, fixers):
-        self._run(session, resolver, "build", [], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        self._run(
+            session, resolver, "build", self.build_config.extra_args_for(self.name),
+            fixers)
 
     def test(self, session, resolver, fixers):
         self._run(session, resolver, "test", [], fixers)
@@ -722,13 +1638,23 @@ class R(BuildSystem):
 
     name = "R"
 
-    def __init__(self, path):
-        self.path = path
+    # Building the PDF manual requires a full LaTeX installation
+    DEFAULT_CHECK_ARGS = ["--no-manual"]
+
+    def __init__(self, path, check_args=None, full_check=False):
+        super(R, self).__init__(path)
+        # Additional arguments for "R CMD check"
+        self.check_args = (
+            list(self.DEFAULT_CHECK_ARGS) if check_args is None
+            else list(check_args))
+        # Whether to always run "R CMD check", rather than just the
+        # testthat suite if there is one
+        self.full_check = full_check
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
         pass
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
@@ -745,8 +1671,19 @@ class R(BuildSystem):
         run_with_build_fixers(session, [r_path, "CMD", "INSTALL", "."] + extra_args, fixers)
 
     def test(self, session, resolver, fixers):
+        if not self.full_check and os.path.isdir(
+                os.path.join(self.path, "tests", "testthat")):
+            # Much faster than a full check, since the package does not
+            # have to be built and installed first
+            rscript_path = guaranteed_which(session, resolver, "Rscript")
+            run_with_build_fixers(
+                session, [rscript_path, "-e",
+                          "testthat::test_local(stop_on_failure = TRUE)"],
+                fixers)
+            return
         r_path = guaranteed_which(session, resolver, "R")
-        run_with_build_fixers(session, [r_path, "CMD", "check", "."], fixers)
+        run_with_build_fixers(
+            session, [r_path, "CMD", "check", "."] + self.check_args, fixers)
 
     @classmethod
     def probe(cls, path):
@@ -767,19 +1704,37 @@ class R(BuildSystem):
             return [s.strip() for s in t.split(",") if s.strip()]
 
         description = self._read_description()
+        # Packages that are explicitly installed from Bioconductor
+        bioc_packages = set()
+        if "Remotes" in description:
+            for s in parse_list(description["Remotes"]):
+                if s.startswith("bioc::"):
+                    bioc_packages.add(s[len("bioc::"):].split("/")[-1])
+
+        def parse_req(s):
+            req = RPackageRequirement.from_str(s)
+            if req.package in bioc_packages:
+                req.repository = "bioconductor"
+            return req
+
         if "Suggests" in description:
+            # Suggested packages are used by the tests, examples and
+            # vignettes, which "R CMD check" all runs.
             for s in parse_list(description["Suggests"]):
-                yield "build", RPackageRequirement.from_str(s)
+                yield "test", parse_req(s)
+        if "VignetteBuilder" in description:
+            # Vignettes are built as part of "R CMD build"
+            for s in parse_list(description["VignetteBuilder"]):
+                yield "build", parse_req(s)
         if "Depends" in description:
             for s in parse_list(description["Depends"]):
-                yield "build", RPackageRequirement.from_str(s)
+                yield "build", parse_req(s)
         if "Imports" in description:
             for s in parse_list(description["Imports"]):
-                yield "build", RPackageRequirement.from_str(s)
+                yield "build", parse_req(s)
         if "LinkingTo" in description:
             for s in parse_list(description["LinkingTo"]):
-                yield "build", RPackageRequirement.from_str(s)
-        # TODO(jelmer): Suggests
+                yield "build", parse_req(s)
 
     def get_declared_outputs(self, session, fixers=None):
         description = self._read_description()
@@ -787,34 +1742,95 @@ class R(BuildSystem):
             yield RPackageOutput(description["Package"])
 
 
+class MesonOptions(object):
+    """Options for setting up a Meson build directory.
+
+    Attributes:
+      cross_files: Cross files to pass to "meson setup"; if there are none
+        and a target is set, a cross file is generated
+      native_files: Native files to pass to "meson setup"
+    """
+
+    def __init__(self, cross_files=None, native_files=None):
+        self.cross_files = list(cross_files or [])
+        self.native_files = list(native_files or [])
+
+    def __repr__(self):
+        return "%s(cross_files=%r, native_files=%r)" % (
+            type(self).__name__, self.cross_files, self.native_files)
+
+
 class Meson(BuildSystem):
 
     name = "meson"
 
-    def __init__(self, path):
-        self.path = path
+    def __init__(self, path, setup_options=None):
+        super(Meson, self).__init__(path)
+        self.setup_options = setup_options or MesonOptions()
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
 
-    def _setup(self, session, fixers):
-        if not session.exists("build"):
-            session.mkdir("build")
-        run_with_build_fixers(session, ["meson", "setup", "build"], fixers)
+    @property
+    def builddir(self):
+        return self.build_config.build_dir or "build"
+
+    def _cross_file(self, session, target):
+        # Meson reads the cross file again when it regenerates the build, so
+        # keep it with the build rather than in the source tree.
+        path = os.path.join(self.builddir, "meson-cross-%s.ini" % target.arch)
+        with open(session.external_path(path), "w") as f:
+            f.write(target.meson_cross_file())
+        return path
+
+    def _setup(self, session, fixers, options=None):
+        # Extra arguments (e.g. -D project options) also apply to existing
+        # build directories
+        options = (options or []) + self.build_config.extra_args_for(self.name)
+        if not session.exists(self.builddir):
+            session.mkdir(self.builddir)
+        if session.exists(os.path.join(self.builddir, "meson-private")):
+            if options:
+                # "meson setup" would ignore the options without --reconfigure
+                run_with_build_fixers(
+                    session,
+                    ["meson", "setup", "--reconfigure", self.builddir] + options,
+                    fixers)
+                return
+        else:
+            # Cross and native files can only be set when the build is first
+            # set up
+            for path in self.setup_options.native_files:
+                options.extend(["--native-file", path])
+            if self.setup_options.cross_files:
+                for path in self.setup_options.cross_files:
+                    options.extend(["--cross-file", path])
+            elif self.build_config.target is not None:
+                options.extend([
+                    "--cross-file",
+                    self._cross_file(session, self.build_config.target)])
+        run_with_build_fixers(
+            session, ["meson", "setup", self.builddir] + options, fixers)
 
     def clean(self, session, resolver, fixers):
         self._setup(session, fixers)
-        run_with_build_fixers(session, ["ninja", "-C", "build", "clean"], fixers)
+        run_with_build_fixers(session, ["ninja", "-C", self.builddir, "clean"], fixers)
 
-    def build(self, session, resolver, fixers):
-        self._setup(session, fixers)
-        run_with_build_fixers(session, ["ninja", "-C", "build"], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        options = []
+        if profile is not None:
+            options.append("--buildtype=%s" % profile)
+        self._setup(session, fixers, options)
+        run_with_build_fixers(
+            session, ["ninja", "-C", self.builddir] + self.build_config.jobs_args(),
+            fixers)
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
         self._setup(session, fixers)
-        with DistCatcher([session.external_path("build/meson-dist")]) as dc:
+        with DistCatcher([session.external_path(
+            os.path.join(self.builddir, "meson-dist"))]) as dc:
             try:
-                run_with_build_fixers(session, ["ninja", "-C", "build", "dist"], fixers)
+                run_with_build_fixers(session, ["ninja", "-C", self.builddir, "dist"], fixers)
             except UnidentifiedError as e:
                 if "ninja: error: unknown target 'dist', did you mean 'dino'?" in e.lines:
                     raise NotImplementedError
@@ -823,11 +1839,31 @@ class Meson(BuildSystem):
 
     def test(self, session, resolver, fixers):
         self._setup(session, fixers)
-        run_with_build_fixers(session, ["ninja", "-C", "build", "test"], fixers)
+        run_with_build_fixers(
+            session, ["ninja", "-C", self.builddir, "test"]
+            + self.build_config.jobs_args(), fixers)
+
+    def coverage(self, session, resolver, fixers):
+        self._setup(session, fixers, ["-Db_coverage=true"])
+        run_with_build_fixers(
+            session, ["ninja", "-C", self.builddir, "test"]
+            + self.build_config.jobs_args(), fixers)
+        return _lcov_capture(
+            session, fixers, self.builddir, _coverage_path(session, "meson.info"))
 
     def install(self, session, resolver, fixers, install_target):
         self._setup(session, fixers)
-        run_with_build_fixers(session, ["ninja", "-C", "build", "install"], fixers)
+        run_with_build_fixers(session, ["ninja", "-C", self.builddir, "install"], fixers)
+
+    def project_version(self, session):
+        with open(self.path, "r") as f:
+            contents = f.read()
+        m = re.search(
+            r"\bproject\s*\((?:[^()]|\([^()]*\))*?\bversion\s*:\s*'([^']+)'",
+            contents)
+        if m:
+            return m.group(1)
+        return super(Meson, self).project_version(session)
 
     @classmethod
     def probe(cls, path):
@@ -836,65 +1872,308 @@ class Meson(BuildSystem):
             return Meson(os.path.join(path, "meson.build"))
 
 
+def _node_workspace_patterns(package, directory):
+    """Find the workspace patterns for a node project.
+
+    These are listed in the "workspaces" field in package.json (npm and
+    yarn) or in pnpm-workspace.yaml (pnpm).
+    """
+    workspaces = package.get("workspaces")
+    if isinstance(workspaces, dict):
+        # Older yarn style, with nohoist settings
+        workspaces = workspaces.get("packages")
+    if workspaces:
+        return list(workspaces)
+    pnpm_workspace = os.path.join(directory, "pnpm-workspace.yaml")
+    if os.path.exists(pnpm_workspace):
+        import ruamel.yaml
+
+        with open(pnpm_workspace, "r") as f:
+            data = ruamel.yaml.load(f, ruamel.yaml.SafeLoader) or {}
+        return list(data.get("packages") or [])
+    return []
+
+
+def _find_node_workspace_members(package, directory):
+    """Find the members of a node workspace.
+
+    Returns: list of (relative path, package.json contents) tuples
+    """
+    import glob
+    import json
+
+    included = []
+    excluded = set()
+    for pattern in _node_workspace_patterns(package, directory):
+        if pattern.startswith("!"):
+            excluded.update(
+                os.path.relpath(p, directory) for p in
+                glob.glob(os.path.join(directory, pattern[1:])))
+            continue
+        for p in sorted(glob.glob(os.path.join(directory, pattern))):
+            relpath = os.path.relpath(p, directory)
+            if relpath not in included:
+                included.append(relpath)
+    ret = []
+    for relpath in included:
+        if relpath in excluded:
+            continue
+        try:
+            with open(os.path.join(directory, relpath, "package.json"), "r") as f:
+                ret.append((relpath, json.load(f)))
+        except FileNotFoundError:
+            continue
+    return ret
+
+
+def detect_node_package_manager(package, directory):
+    """Determine which package manager a node project uses.
+
+    The "packageManager" field in package.json (as used by corepack) takes
+    precedence; otherwise the lockfile that is present decides.
+
+    Returns: tuple with package manager name ("npm", "yarn" or "pnpm") and
+        the packageManager specification (e.g. "yarn@3.6.1"), if any
+    """
+    spec = package.get("packageManager")
+    if spec:
+        name = spec.split("@", 1)[0]
+        if name in ("npm", "yarn", "pnpm"):
+            return name, spec
+        logging.warning("Unknown package manager %r in package.json", spec)
+    if os.path.exists(os.path.join(directory, "pnpm-lock.yaml")):
+        return "pnpm", None
+    if os.path.exists(os.path.join(directory, "yarn.lock")):
+        return "yarn", None
+    return "npm", None
+
+
+def _is_yarn_berry(directory, spec=None):
+    """Check whether a project uses yarn 2 or later ("berry").
+
+    Args:
+      directory: Project directory
+      spec: packageManager specification from package.json, if any
+    """
+    m = re.match(r"yarn@(\d+)", spec or "")
+    if m:
+        return int(m.group(1)) >= 2
+    if os.path.exists(os.path.join(directory, ".yarnrc.yml")):
+        return True
+    try:
+        with open(os.path.join(directory, "yarn.lock"), "r") as f:
+            # Lockfiles written by yarn 1 do not have a metadata section
+            return any(line.startswith("__metadata:") for line in f)
+    except FileNotFoundError:
+        return False
+
+
 class Npm(BuildSystem):
 
     name = "npm"
 
+    ESLINT_CONFIG_FILES = [
+        ".eslintrc", ".eslintrc.js", ".eslintrc.cjs", ".eslintrc.json",
+        ".eslintrc.yaml", ".eslintrc.yml", "eslint.config.js",
+        "eslint.config.mjs", "eslint.config.cjs",
+    ]
+
     def __init__(self, path):
         import json
 
-        self.path = path
+        super(Npm, self).__init__(path)
 
         with open(path, "r") as f:
             self.package = json.load(f)
 
+        directory = os.path.dirname(path)
+        self.workspaces = _find_node_workspace_members(self.package, directory)
+        self.selected_workspace = None
+        self.package_manager, self.package_manager_spec = (
+            detect_node_package_manager(self.package, directory))
+        self.yarn_berry = self.package_manager == "yarn" and _is_yarn_berry(
+            directory, self.package_manager_spec)
+        # Versions other than the one shipped by the distribution are
+        # provided by corepack
+        self.use_corepack = (
+            self.package_manager_spec is not None or self.yarn_berry)
+        self._command = None
+
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
 
+    def select_workspace(self, name):
+        """Only act on a single workspace member.
+
+        Args:
+          name: Package name or relative path of the member
+        """
+        for relpath, package in self.workspaces:
+            if name in (relpath, package.get("name")):
+                self.selected_workspace = (relpath, package)
+                return
+        raise ValueError("no workspace member %r in %s" % (name, self.path))
+
+    def _members(self):
+        """Determine the packages to act on.
+
+        Returns: list of (relative path, package.json contents) tuples
+        """
+        if self.selected_workspace is not None:
+            return [self.selected_workspace]
+        return [(".", self.package)] + self.workspaces
+
     def get_declared_dependencies(self, session, fixers=None):
-        if "dependencies" in self.package:
-            for name, unused_version in self.package["dependencies"].items():
+        # Workspace members can depend on each other; those dependencies are
+        # satisfied by the workspace itself.
+        local = set(package.get("name") for (relpath, package) in self.workspaces)
+        for relpath, package in self._members():
+            for name, unused_version in package.get("dependencies", {}).items():
+                if name in local:
+                    continue
                 # TODO(jelmer): Look at version
                 yield "core", NodePackageRequirement(name)
-        if "devDependencies" in self.package:
-            for name, unused_version in self.package["devDependencies"].items():
+            for name, unused_version in package.get("devDependencies", {}).items():
+                if name in local:
+                    continue
                 # TODO(jelmer): Look at version
                 yield "build", NodePackageRequirement(name)
 
-    def setup(self, session, resolver):
-        binary_req = BinaryRequirement("npm")
-        if not binary_req.met(session):
-            resolver.install([binary_req])
+    def setup(self, session, resolver, fixers):
+        if self._command is not None:
+            return
+        if self.use_corepack:
+            binary_req = BinaryRequirement("corepack")
+            if not binary_req.try_met(session):
+                resolver.install([binary_req])
+            if self.package_manager_spec:
+                run_with_build_fixers(
+                    session,
+                    ["corepack", "prepare", self.package_manager_spec,
+                     "--activate"], fixers)
+            self._command = ["corepack", self.package_manager]
+        elif self.package_manager == "yarn" and which(session, "yarnpkg"):
+            # Debian ships yarn 1 as yarnpkg
+            self._command = ["yarnpkg"]
+        else:
+            self._command = [
+                guaranteed_which(session, resolver, self.package_manager)]
+        logging.info(
+            "Using node package manager: %s", " ".join(self._command))
+
+    def _install_dependencies(self, session, resolver, fixers):
+        """Install the dependencies of a yarn 2+ project, unless present.
+
+        With yarn's Plug'n'Play there is no node_modules directory that
+        missing packages could be added to, so the package manager has to
+        install them. For other package managers, missing packages are
+        installed by the resolver when the build fails.
+        """
+        self.setup(session, resolver, fixers)
+        if not self.yarn_berry:
+            return
+        directory = os.path.dirname(self.path)
+        if any(os.path.exists(os.path.join(directory, name))
+               for name in ["node_modules", ".pnp.cjs", ".pnp.js"]):
+            return
+        run_with_build_fixers(session, self._command + ["install"], fixers)
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
-        self.setup(session, resolver)
-        with DistCatcher([session.external_path(".")]) as dc:
-            run_with_build_fixers(session, ["npm", "pack"], fixers)
-        return dc.copy_single(target_directory)
-
-    def test(self, session, resolver, fixers):
-        self.setup(session, resolver)
-        test_script = self.package["scripts"].get("test")
-        if test_script:
-            run_with_build_fixers(session, shlex.split(test_script), fixers)
+        self._install_dependencies(session, resolver, fixers)
+        if self.selected_workspace is not None:
+            members = [self.selected_workspace]
+        elif self.package.get("private") and self.workspaces:
+            # The root of a monorepo is not published; its members are
+            members = [
+                (relpath, package) for (relpath, package) in self.workspaces
+                if not package.get("private")]
         else:
-            raise NotImplementedError
-
-    def build(self, session, resolver, fixers):
-        self.setup(session, resolver)
-        build_script = self.package["scripts"].get("build")
-        if build_script:
-            run_with_build_fixers(session, shlex.split(build_script), fixers)
+            members = [(".", self.package)]
+        filenames = []
+        for relpath, package in members:
+            with DistCatcher([session.external_path(relpath)]) as dc:
+                run_with_build_fixers(
+                    session, self._command + ["pack"], fixers,
+                    cwd=None if relpath == "." else relpath)
+            filenames.append(dc.copy_single(target_directory))
+        if not filenames:
+            raise DistNoTarball()
+        if len(filenames) > 1:
+            logging.info("Created tarballs for workspace: %s", ", ".join(filenames))
+        return filenames[0]
+
+    def _run_script(self, session, fixers, name):
+        if self.selected_workspace is not None:
+            members = [self.selected_workspace]
+        elif self.package.get("scripts", {}).get(name):
+            # A script in the root package takes care of the members itself
+            members = [(".", self.package)]
         else:
+            members = self.workspaces
+        ran = False
+        for relpath, package in members:
+            script = package.get("scripts", {}).get(name)
+            if not script:
+                continue
+            if self.package_manager == "npm":
+                argv = shlex.split(script)
+            else:
+                # Dependencies may not be in node_modules (e.g. with yarn's
+                # Plug'n'Play), so let the package manager run the script
+                argv = self._command + ["run", name]
+            run_with_build_fixers(
+                session, argv, fixers,
+                cwd=None if relpath == "." else relpath)
+            ran = True
+        if not ran:
             raise NotImplementedError
 
+    def test(self, session, resolver, fixers):
+        self._install_dependencies(session, resolver, fixers)
+        self._run_script(session, fixers, "test")
+
+    def build(self, session, resolver, fixers, profile=None):
+        self._install_dependencies(session, resolver, fixers)
+        self._run_script(session, fixers, "build")
+
     def clean(self, session, resolver, fixers):
-        self.setup(session, resolver)
-        clean_script = self.package["scripts"].get("clean")
-        if clean_script:
-            run_with_build_fixers(session, shlex.split(clean_script), fixers)
+        self.setup(session, resolver, fixers)
+        self._run_script(session, fixers, "clean")
+
+    def lint(self, session, resolver, fixers):
+        self._install_dependencies(session, resolver, fixers)
+        try:
+            self._run_script(session, fixers, "lint")
+        except NotImplementedError:
+            if not any(
+                    os.path.exists(
+                        os.path.join(os.path.dirname(self.path), name))
+                    for name in self.ESLINT_CONFIG_FILES):
+                raise
+            run_with_build_fixers(session, ["eslint", "."], fixers)
+
+    def update_lockfile(self, session, resolver, fixers):
+        self.setup(session, resolver, fixers)
+        if self.package_manager == "npm":
+            args = ["install", "--package-lock-only"]
+        elif self.package_manager == "pnpm":
+            args = ["install", "--lockfile-only"]
+        elif self.package_manager == "yarn" and self.use_corepack:
+            args = ["install", "--mode=update-lockfile"]
         else:
+            # yarn 1 can not update the lockfile without installing
+            args = ["install", "--ignore-scripts"]
+        run_with_build_fixers(session, self._command + args, fixers)
+
+    def coverage(self, session, resolver, fixers):
+        self._install_dependencies(session, resolver, fixers)
+        if not self.package.get("scripts", {}).get("test"):
             raise NotImplementedError
+        report_dir = os.path.dirname(_coverage_path(session, "lcov.info"))
+        run_with_build_fixers(
+            session, ["nyc", "--reporter=lcovonly", "--report-dir", report_dir]
+            + self._command + ["test"], fixers)
+        return os.path.join(report_dir, "lcov.info")
 
     @classmethod
     def probe(cls, path):
@@ -903,27 +2182,133 @@ class Npm(BuildSystem):
             return cls(os.path.join(path, "package.json"))
 
 
+def _waf_info(path):
+    """Inspect a waf script.
+
+    Returns: tuple with waf version (or None), the Python interpreter named
+        in the shebang (or None) and whether the embedded waflib archive is
+        present
+    """
+    version = None
+    interpreter = None
+    has_archive = False
+    with open(path, "rb") as f:
+        first = f.readline()
+        if first.startswith(b"#!"):
+            m = re.search(rb"\b(python[0-9.]*)\b", first)
+            if m:
+                interpreter = m.group(1).decode()
+        for line in f:
+            if version is None:
+                m = re.match(rb"VERSION\s*=\s*[\"']([0-9.]+)[\"']", line)
+                if m:
+                    version = m.group(1).decode()
+            # The archive follows this marker, as a comment
+            if line.startswith(b"#==>"):
+                has_archive = True
+                break
+    return version, interpreter, has_archive
+
+
 class Waf(BuildSystem):
 
     name = "waf"
 
     def __init__(self, path):
-        self.path = path
+        super(Waf, self).__init__(path)
+        self.version, interpreter, self.has_archive = _waf_info(path)
+        self.python = self._python_for(self.version, interpreter)
+        self._command = None
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    @staticmethod
+    def _python_for(version, interpreter):
+        if interpreter in ("python2", "python3"):
+            return interpreter
+        if interpreter is not None and interpreter.startswith("python2"):
+            return "python2"
+        # Python 3 is supported since waf 1.6
+        if version is not None:
+            try:
+                if tuple(int(x) for x in version.split(".")[:2]) < (1, 6):
+                    return "python2"
+            except ValueError:
+                pass
+        return "python3"
+
+    def _has_waflib(self):
+        directory = os.path.dirname(self.path)
+        if os.path.isdir(os.path.join(directory, "waflib")):
+            return True
+        # Unpacked by an earlier run of the waf script
+        for entry in os.scandir(directory):
+            if (entry.name.startswith(".waf-") or entry.name.startswith(".waf3-")) and (
+                    os.path.isdir(os.path.join(entry.path, "waflib"))):
+                return True
+        return False
 
     def setup(self, session, resolver, fixers):
-        binary_req = BinaryRequirement("python3")
-        if not binary_req.met(session):
+        if self._command is not None:
+            return
+        binary_req = BinaryRequirement(self.python)
+        if not binary_req.try_met(session):
             resolver.install([binary_req])
+        if not self._has_waflib():
+            if not self.has_archive:
+                # The embedded waflib archive is commonly stripped from
+                # trees that come from Debian, since it is not source code.
+                logging.warning(
+                    "waflib archive has been stripped from %s", self.path)
+            # Unpack waflib, so that problems while doing so are reported
+            # separately from problems with the build
+            run_with_build_fixers(
+                session, [self.python, "./waf", "--help"], fixers)
+        self._command = [self.python, "./waf"]
 
-    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+    def _run(self, session, resolver, fixers, command, args=None):
         self.setup(session, resolver, fixers)
+        try:
+            run_with_build_fixers(
+                session, self._command + [command] + (args or []), fixers)
+        except UnidentifiedError as e:
+            if any(re.match(
+                    r"No function '?%s'? defined in .*" % re.escape(command),
+                    line) for line in e.lines):
+                raise NotImplementedError
+            raise
+
+    def _configure(self, session, resolver, fixers, prefix=None):
+        args = []
+        if prefix is not None:
+            args.append("--prefix=%s" % prefix)
+        if self.build_config.build_dir is not None:
+            # Later commands find the build directory through the lock file
+            args.append("--out=%s" % self.build_config.build_dir)
+        args.extend(self.build_config.extra_args_for(self.name))
+        self._run(session, resolver, fixers, "configure", args)
+
+    def build(self, session, resolver, fixers, profile=None):
+        self._configure(session, resolver, fixers)
+        self._run(session, resolver, fixers, "build", self.build_config.jobs_args())
+
+    def clean(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, "distclean")
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
         with DistCatcher.default(session.external_path(".")) as dc:
-            run_with_build_fixers(session, ["./waf", "dist"], fixers)
+            self._run(session, resolver, fixers, "dist")
         return dc.copy_single(target_directory)
 
     def test(self, session, resolver, fixers):
-        self.setup(session, resolver, fixers)
-        run_with_build_fixers(session, ["./waf", "test"], fixers)
+        self._configure(session, resolver, fixers)
+        self._run(session, resolver, fixers, "test")
+
+    def install(self, session, resolver, fixers, install_target):
+        self._configure(
+            session, resolver, fixers, prefix=install_target.prefix)
+        self._run(session, resolver, fixers, "install")
 
     @classmethod
     def probe(cls, path):
@@ -937,7 +2322,7 @@ class Gem(BuildSystem):
     name = "gem"
 
     def __init__(self, path):
-        self.path = path
+        super(Gem, self).__init__(path)
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
         gemfiles = [
@@ -965,7 +2350,7 @@ class DistZilla(BuildSystem):
     name = "dist-zilla"
 
     def __init__(self, path):
-        self.path = path
+        super(DistZilla, self).__init__(path)
         self.dist_inkt_class = None
         with open(self.path, "rb") as f:
             for line in f:
@@ -1007,7 +2392,7 @@ class DistZilla(BuildSystem):
         self.setup(resolver)
         run_with_build_fixers(session, [guaranteed_which(session, resolver, "dzil"), "test"], fixers)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
         self.setup(resolver)
         run_with_build_fixers(session, [guaranteed_which(session, resolver, "dzil"), "build"], fixers)
 
@@ -1032,7 +2417,7 @@ class RunTests(BuildSystem):
     name = "runtests"
 
     def __init__(self, path):
-        self.path = path
+        super(RunTests, self).__init__(path)
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
@@ -1086,17 +2471,45 @@ class CMake(BuildSystem):
     name = "cmake"
 
     def __init__(self, path):
-        self.path = path
-        self.builddir = 'build'
+        super(CMake, self).__init__(path)
+
+    @property
+    def builddir(self):
+        return self.build_config.build_dir or 'build'
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
 
-    def setup(self, session, resolver, fixers):
+    def _target_args(self):
+        target = self.build_config.target
+        if target is None:
+            return []
+        return [
+            "-DCMAKE_SYSTEM_NAME=Linux",
+            "-DCMAKE_SYSTEM_PROCESSOR=%s" % target.triple.split("-")[0],
+            "-DCMAKE_C_COMPILER=%s" % target.tool("gcc"),
+            "-DCMAKE_CXX_COMPILER=%s" % target.tool("g++"),
+        ]
+
+    def _toolchain_args(self, session):
+        # Set by e.g. the vcpkg and conan resolvers; older versions of CMake
+        # do not read it from the environment.
+        args = []
+        for name in ["CMAKE_TOOLCHAIN_FILE", "VCPKG_CHAINLOAD_TOOLCHAIN_FILE"]:
+            value = session.getenv(name)
+            if value is not None:
+                args.append("-D%s=%s" % (name, value))
+        return args
+
+    def setup(self, session, resolver, fixers, args=None):
         if not session.exists(self.builddir):
             session.mkdir(self.builddir)
         try:
-            run_with_build_fixers(session, ["cmake", '.', '-B%s' % self.builddir], fixers)
+            run_with_build_fixers(
+                session, ["cmake", '.', '-B%s' % self.builddir]
+                + self._target_args() + self._toolchain_args(session)
+                + (args or [])
+                + self.build_config.extra_args_for(self.name), fixers)
         except Exception:
             session.rmtree(self.builddir)
             raise
@@ -1107,9 +2520,15 @@ class CMake(BuildSystem):
             return cls(path)
         return None
 
-    def build(self, session, resolver, fixers):
-        self.setup(session, resolver, fixers)
-        run_with_build_fixers(session, ["cmake", "--build", self.builddir], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        args = []
+        if profile is not None:
+            args.append("-DCMAKE_BUILD_TYPE=%s" % profile.capitalize())
+        self.setup(session, resolver, fixers, args)
+        args = ["cmake", "--build", self.builddir]
+        if self.build_config.jobs is not None:
+            args.extend(["--parallel", str(self.build_config.jobs)])
+        run_with_build_fixers(session, args, fixers)
 
     def install(self, session, resolver, fixers, install_target):
         self.setup(session, resolver, fixers)
@@ -1120,17 +2539,45 @@ class CMake(BuildSystem):
         run_with_build_fixers(session, ["cmake", "--build %s" % self.builddir, ".", "--target", "clean"], fixers)
 
 
+# Targets that are commonly provided by Makefiles
+STANDARD_MAKE_TARGETS = [
+    "all", "check", "test", "install", "dist", "clean", "distclean"]
+
+
+def parse_make_database(lines) -> List[str]:
+    """Find the explicit targets in the output of "make -pRrq :"."""
+    targets = []
+    not_a_target = False
+    in_files = False
+    for line in lines:
+        if line.startswith("# Files"):
+            in_files = True
+            continue
+        if line.startswith("# Finished Make data base"):
+            break
+        if not in_files:
+            continue
+        if line.startswith("# Not a target:"):
+            not_a_target = True
+            continue
+        if line.startswith(("#", "\t")) or not line.strip():
+            continue
+        m = re.match(r"^([^\s:=#%$][^:=#%$]*?):(?!=)", line)
+        if m and not not_a_target:
+            for target in m.group(1).split():
+                # Special targets, e.g. .PHONY
+                if not target.startswith(".") and target not in targets:
+                    targets.append(target)
+        not_a_target = False
+    return targets
+
+
 class Make(BuildSystem):
 
     def __init__(self, path):
-        self.path = path
+        super(Make, self).__init__(path)
         if os.path.exists(os.path.join(path, 'Makefile.PL')):
             self.name = 'makefile.pl'
-        elif os.path.exists(os.path.join(path, 'Makefile.am')):
-            self.name = 'automake'
-        elif any([os.path.exists(os.path.join(path, n))
-                 for n in ['configure.ac', 'configure.in', 'autogen.sh']]):
-            self.name = 'autoconf'
         else:
             self.name = "make"
 
@@ -1146,29 +2593,12 @@ class Make(BuildSystem):
         if session.exists("Makefile.PL") and not makefile_exists():
             run_with_build_fixers(session, ["perl", "Makefile.PL"], fixers)
 
-        if not makefile_exists() and not session.exists("configure"):
-            if session.exists("autogen.sh"):
-                if shebang_binary(os.path.join(self.path, "autogen.sh")) is None:
-                    run_with_build_fixers(session, ["/bin/sh", "./autogen.sh"], fixers)
-                try:
-                    run_with_build_fixers(session, ["./autogen.sh"], fixers)
-                except UnidentifiedError as e:
-                    if (
-                        "Gnulib not yet bootstrapped; "
-                        "run ./bootstrap instead." in e.lines
-                    ):
-                        run_with_build_fixers(session, ["./bootstrap"], fixers)
-                        run_with_build_fixers(session, ["./autogen.sh"], fixers)
-                    else:
-                        raise
-
-            elif session.exists("configure.ac") or session.exists("configure.in"):
-                run_with_build_fixers(session, ["autoreconf", "-i"], fixers)
-
         if not makefile_exists() and session.exists("configure"):
             extra_args = []
             if prefix is not None:
                 extra_args.append('--prefix=%s' % prefix)
+            if self.build_config.target is not None:
+                extra_args.append("--host=%s" % self.build_config.target.triple)
             run_with_build_fixers(session, ["./configure"] + extra_args, fixers)
 
         if not makefile_exists() and any(
@@ -1176,15 +2606,22 @@ class Make(BuildSystem):
         ):
             run_with_build_fixers(session, ["qmake"], fixers)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
         self.setup(session, resolver, fixers)
-        self._run_make(session, ["all"], fixers)
+        # Makefiles often add their own flags to CFLAGS, so the profile
+        # only provides defaults through the environment
+        self._run_make(
+            session, ["all"] + self._build_args(), fixers,
+            env=profile_variables(profile))
+
+    def _build_args(self):
+        return self.build_config.extra_args_for(self.name)
 
     def clean(self, session, resolver, fixers):
         self.setup(session, resolver, fixers)
         self._run_make(session, ["clean"], fixers)
 
-    def _run_make(self, session, args, fixers, prefix=None):
+    def _run_make(self, session, args, fixers, prefix=None, env=None):
         def _wants_configure(line):
             if line.startswith("Run ./configure"):
                 return True
@@ -1199,31 +2636,67 @@ class Make(BuildSystem):
                     line):
                 return True
             return False
-        if session.exists('build'):
-            cwd = 'build'
-        else:
-            cwd = None
+        cwd = self._make_dir(session)
         try:
-            run_with_build_fixers(session, ["make"] + args, fixers, cwd=cwd)
+            run_with_build_fixers(
+                session, ["make"] + self.build_config.jobs_args() + args, fixers,
+                cwd=cwd, env=env)
         except UnidentifiedError as e:
             if len(e.lines) < 5 and any([_wants_configure(line) for line in e.lines]):
                 extra_args = []
                 if prefix is not None:
                     extra_args.append("--prefix=%s" % prefix)
                 run_with_build_fixers(session, ["./configure"] + extra_args, fixers)
-                run_with_build_fixers(session, ["make"] + args, fixers)
+                run_with_build_fixers(
+                    session, ["make"] + self.build_config.jobs_args() + args, fixers,
+                    env=env)
             elif (
                 "Reconfigure the source tree "
                 "(via './config' or 'perl Configure'), please."
             ) in e.lines:
                 run_with_build_fixers(session, ["./config"], fixers)
-                run_with_build_fixers(session, ["make"] + args, fixers)
+                run_with_build_fixers(
+                    session, ["make"] + self.build_config.jobs_args() + args, fixers,
+                    env=env)
             else:
                 raise
 
+    def _make_dir(self, session):
+        """Directory to run make in, if not the current directory."""
+        if session.exists('build'):
+            return 'build'
+        return None
+
+    def targets(self, session):
+        import subprocess
+
+        try:
+            # Print the database while building nothing: -q with the
+            # no-op ":" target, and without the built-in rules and variables
+            # (-R -r), which would otherwise be listed as well
+            p = session.Popen(
+                ["make", "-pRrq", ":"], cwd=self._make_dir(session),
+                stdout=subprocess.PIPE, stderr=subprocess.DEVNULL)
+        except FileNotFoundError:
+            raise NotImplementedError(self.targets)
+        output, _ = p.communicate()
+        return parse_make_database(
+            output.decode("utf-8", "replace").splitlines())
+
     def test(self, session, resolver, fixers):
         self.setup(session, resolver, fixers)
-        self._run_make(session, ["check"], fixers)
+        try:
+            targets = self.targets(session)
+        except NotImplementedError:
+            targets = []
+        if "check" in targets or not targets:
+            # Without a list of targets, guess
+            target = "check"
+        elif "test" in targets:
+            target = "test"
+        else:
+            raise NotImplementedError
+        self._run_make(session, [target], fixers)
 
     def install(self, session, resolver, fixers, install_target):
         self.setup(session, resolver, fixers, prefix=install_target.prefix)
@@ -1299,9 +2772,6 @@ class Make(BuildSystem):
                     "GNUmakefile",
                     "makefile",
                     "Makefile.PL",
-                    "autogen.sh",
-                    "configure.ac",
-                    "configure.in",
                 ]
             ]
         ):
@@ -1312,6 +2782,226 @@ class Make(BuildSystem):
                 return cls(path)
 
 
+AUTOCONF_CHECK_PROG_RE = re.compile(
+    r"AC_(?:CHECK|PATH)_PROGS?\(\s*\[?\w+\]?\s*,\s*\[?([^\],)]+)\]?")
+AUTOCONF_CHECK_HEADERS_RE = re.compile(
+    r"AC_CHECK_HEADERS?\(\s*\[?([^\],)]+)\]?")
+AUTOCONF_CHECK_LIB_RE = re.compile(
+    r"AC_(?:CHECK|SEARCH)_LIBS?\(\s*\[?([^\],)]+)\]?\s*,\s*\[?([^\],)]+)\]?")
+PKG_CHECK_MODULES_RE = re.compile(
+    r"PKG_CHECK_MODULES\(\s*\[?\w+\]?\s*,\s*\[?([^\],)]+)\]?")
+
+
+def _pkg_config_requirements(text):
+    """Parse a pkg-config module list like "glib-2.0 >= 2.40 gio-2.0"."""
+    tokens = text.split()
+    while tokens:
+        module = tokens.pop(0)
+        minimum_version = None
+        if len(tokens) >= 2 and tokens[0] in (">=", "=", ">"):
+            minimum_version = tokens[1]
+            del tokens[:2]
+        if "$" not in module:
+            yield PkgConfigRequirement(module, minimum_version)
+
+
+def _autoconf_words(text):
+    # Skip shell variables and line continuations
+    return [word for word in text.split() if "$" not in word and word != "\\"]
+
+
+def _declared_deps_from_configure_ac(f):
+    """Extract dependencies from the AC_CHECK_* macros in configure.ac."""
+    text = f.read()
+    text = re.sub(r"(^|\s)dnl\b.*$", "", text, flags=re.MULTILINE)
+    text = re.sub(r"^\s*#.*$", "", text, flags=re.MULTILINE)
+    for m in AUTOCONF_CHECK_PROG_RE.finditer(text):
+        for name in _autoconf_words(m.group(1)):
+            yield "build", BinaryRequirement(name)
+    for m in AUTOCONF_CHECK_HEADERS_RE.finditer(text):
+        for name in _autoconf_words(m.group(1)):
+            yield "build", CHeaderRequirement(name)
+    for m in AUTOCONF_CHECK_LIB_RE.finditer(text):
+        if m.group(0).startswith("AC_SEARCH_LIBS"):
+            names = _autoconf_words(m.group(2))
+        else:
+            names = _autoconf_words(m.group(1))
+        for name in names:
+            yield "build", LibraryRequirement(name)
+    for m in PKG_CHECK_MODULES_RE.finditer(text):
+        for req in _pkg_config_requirements(m.group(1)):
+            yield "build", req
+
+
+class Autotools(Make):
+    """Projects using autoconf, and optionally automake.
+
+    Args:
+      path: Path to the project
+      configure_args: Additional arguments to pass to ./configure
+    """
+
+    def __init__(self, path, configure_args=None):
+        # Make.__init__ inspects the Makefile, which may not exist yet
+        BuildSystem.__init__(self, path)
+        self.configure_args = list(configure_args or [])
+        if os.path.exists(os.path.join(path, "Makefile.am")):
+            self.name = "automake"
+        else:
+            self.name = "autoconf"
+
+    def __repr__(self):
+        return "%s(%r, configure_args=%r)" % (
+            type(self).__name__, self.path, self.configure_args)
+
+    def bootstrap(self, session, fixers):
+        """Generate the configure script."""
+        if session.exists("autogen.sh"):
+            if shebang_binary(os.path.join(self.path, "autogen.sh")) is None:
+                argv = ["/bin/sh", "./autogen.sh"]
+            else:
+                argv = ["./autogen.sh"]
+            try:
+                run_with_build_fixers(session, argv, fixers)
+            except UnidentifiedError as e:
+                if (
+                    "Gnulib not yet bootstrapped; "
+                    "run ./bootstrap instead." in e.lines
+                ):
+                    run_with_build_fixers(session, ["./bootstrap"], fixers)
+                    run_with_build_fixers(session, argv, fixers)
+                else:
+                    raise
+        else:
+            run_with_build_fixers(session, ["autoreconf", "-fi"], fixers)
+
+    def _build_args(self):
+        # Extra arguments are passed to configure instead
+        return []
+
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup(session, resolver, fixers)
+        # CFLAGS and CXXFLAGS are reserved for the user in automake, so
+        # they can be overridden without reconfiguring
+        self._run_make(
+            session, ["all"] + [
+                "%s=%s" % item
+                for item in sorted(profile_variables(profile).items())],
+            fixers)
+
+    def _make_dir(self, session):
+        return self.build_config.build_dir
+
+    def _configure_script(self, session):
+        build_dir = self.build_config.build_dir
+        if build_dir is None:
+            return "./configure"
+        if os.path.isabs(build_dir):
+            srcdir = session.check_output(["pwd"]).decode().strip()
+        else:
+            srcdir = os.path.relpath(".", build_dir)
+        return os.path.join(srcdir, "configure")
+
+    def configure(self, session, fixers, prefix=None, extra_args=None):
+        args = []
+        if prefix is not None:
+            args.append("--prefix=%s" % prefix)
+        if self.build_config.target is not None:
+            args.append("--host=%s" % self.build_config.target.triple)
+        args.extend(self.configure_args)
+        args.extend(extra_args or [])
+        args.extend(self.build_config.extra_args_for(self.name))
+        if self.build_config.build_dir is not None and not session.exists(
+                self.build_config.build_dir):
+            session.mkdir(self.build_config.build_dir)
+        # With a build directory, this is a VPATH build
+        run_with_build_fixers(
+            session, [self._configure_script(session)] + args, fixers,
+            cwd=self.build_config.build_dir)
+
+    def setup(self, session, resolver, fixers, prefix=None):
+        build_dir = self.build_config.build_dir or "."
+        if any([session.exists(os.path.join(build_dir, p))
+                for p in ["Makefile", "GNUmakefile", "makefile"]]):
+            return
+        if not session.exists("configure"):
+            self.bootstrap(session, fixers)
+        self.configure(session, fixers, prefix=prefix)
+
+    def _run_make(self, session, args, fixers, prefix=None, env=None):
+        try:
+            run_with_build_fixers(
+                session, ["make"] + self.build_config.jobs_args() + args, fixers,
+                cwd=self.build_config.build_dir, env=env)
+        except UnidentifiedError as e:
+            if len(e.lines) < 5 and any(
+                    [line.startswith("Run ./configure")
+                     or line == "Please run ./configure first"
+                     for line in e.lines]):
+                self.configure(session, fixers, prefix=prefix)
+                run_with_build_fixers(
+                    session, ["make"] + self.build_config.jobs_args() + args, fixers,
+                    cwd=self.build_config.build_dir, env=env)
+            else:
+                raise
+
+    def coverage(self, session, resolver, fixers):
+        if not session.exists("configure"):
+            self.bootstrap(session, fixers)
+        # Always reconfigure, since an existing build lacks instrumentation
+        self.configure(session, fixers, extra_args=[
+            "CFLAGS=--coverage -O0 -g", "CXXFLAGS=--coverage -O0 -g",
+            "LDFLAGS=--coverage"])
+        self._run_make(session, ["check"], fixers)
+        return _lcov_capture(
+            session, fixers, self.build_config.build_dir or ".",
+            _coverage_path(session, "autotools.info"))
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        self.setup(session, resolver, fixers)
+        with DistCatcher.default(
+                session.external_path(self.build_config.build_dir or ".")) as dc:
+            try:
+                self._run_make(session, ["distcheck"], fixers)
+            except UnidentifiedError as e:
+                if not any("No rule to make target 'distcheck'" in line
+                           for line in e.lines):
+                    raise
+                self._run_make(session, ["dist"], fixers)
+        return dc.copy_single(target_directory)
+
+    def get_declared_dependencies(self, session, fixers=None):
+        for name in ["configure.ac", "configure.in"]:
+            try:
+                with open(os.path.join(self.path, name), "r") as f:
+                    yield from _declared_deps_from_configure_ac(f)
+            except FileNotFoundError:
+                continue
+            return
+        raise NotImplementedError
+
+    @classmethod
+    def probe(cls, path):
+        if any(
+            [
+                os.path.exists(os.path.join(path, p))
+                for p in ["autogen.sh", "configure.ac", "configure.in"]
+            ]
+        ):
+            return cls(path)
+        return None
+
+
+# Configuration that makes cargo use the sources written by "cargo vendor"
+CARGO_VENDOR_CONFIG = """\
+[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"""
+
+
 class Cargo(BuildSystem):
 
     name = "cargo"
@@ -1322,7 +3012,7 @@ class Cargo(BuildSystem):
     def __init__(self, path):
         from toml.decoder import load
 
-        self.path = path
+        super(Cargo, self).__init__(path)
 
         with open(path, "r") as f:
             self.cargo = load(f)
@@ -1339,14 +3029,90 @@ class Cargo(BuildSystem):
                     version=details.get("version"),
                 )
 
+    def project_version(self, session):
+        version = self.cargo.get("package", {}).get("version")
+        # Workspace members can inherit the version, as {"workspace": true}
+        if isinstance(version, str):
+            return version
+        return super(Cargo, self).project_version(session)
+
+    def _target_dir_args(self):
+        if self.build_config.build_dir is None:
+            return []
+        return ["--target-dir", self.build_config.build_dir]
+
+    def _jobs_args(self):
+        if self.build_config.jobs is None:
+            return []
+        return ["--jobs", str(self.build_config.jobs)]
+
+    def _target_args(self):
+        if self.build_config.target is None:
+            return []
+        return ["--target", self.build_config.target.rust_triple]
+
+    def _env(self):
+        target = self.build_config.target
+        if target is None:
+            return None
+        # cargo uses the host linker otherwise
+        return {
+            "CARGO_TARGET_%s_LINKER" % target.rust_triple.upper().replace("-", "_"):
+            target.tool("gcc")}
+
     def test(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["cargo", "test"], fixers)
+        run_with_build_fixers(
+            session, ["cargo", "test"] + self._target_dir_args()
+            + self._jobs_args() + self._target_args()
+            + self.build_config.extra_args_for(self.name), fixers, env=self._env())
 
     def clean(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["cargo", "clean"], fixers)
-
-    def build(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["cargo", "build"], fixers)
+        run_with_build_fixers(
+            session, ["cargo", "clean"] + self._target_dir_args(), fixers)
+
+    def lint(self, session, resolver, fixers):
+        # cargo reports "no such command" rather than failing to find a
+        # binary, so install clippy up front
+        clippy_req = BinaryRequirement("cargo-clippy")
+        if not clippy_req.try_met(session):
+            resolver.install([clippy_req])
+        run_with_build_fixers(
+            session, ["cargo", "clippy"] + self._target_dir_args()
+            + self._jobs_args() + self._target_args(), fixers, env=self._env())
+
+    def update_lockfile(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["cargo", "update"], fixers)
+
+    def vendor(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["cargo", "vendor"], fixers)
+        config_dir = session.external_path(".cargo")
+        if any(os.path.exists(os.path.join(config_dir, name))
+               for name in ["config", "config.toml"]):
+            logging.warning(
+                "Not overwriting existing cargo configuration; "
+                "add the vendored-sources source from 'cargo vendor' manually.")
+            return
+        os.makedirs(config_dir, exist_ok=True)
+        with open(os.path.join(config_dir, "config.toml"), "w") as f:
+            f.write(CARGO_VENDOR_CONFIG)
+
+    def coverage(self, session, resolver, fixers):
+        llvm_cov_req = BinaryRequirement("cargo-llvm-cov")
+        if not llvm_cov_req.try_met(session):
+            resolver.install([llvm_cov_req])
+        output = _coverage_path(session, "cargo.info")
+        run_with_build_fixers(
+            session, ["cargo", "llvm-cov", "--lcov", "--output-path", output]
+            + self._target_dir_args() + self._jobs_args(), fixers)
+        return output
+
+    def build(self, session, resolver, fixers, profile=None):
+        run_with_build_fixers(
+            session, ["cargo", "build"] + self._target_dir_args()
+            + self._jobs_args() + self._target_args()
+            + (["--release"] if profile == "release" else [])
+            + self.build_config.extra_args_for(self.name), fixers,
+            env=self._env())
 
     @classmethod
     def probe(cls, path):
@@ -1386,7 +3152,7 @@ class Golang(BuildSystem):
     name = "golang"
 
     def __init__(self, path):
-        self.path = path
+        super(Golang, self).__init__(path)
 
     def __repr__(self):
         return "%s()" % (type(self).__name__)
@@ -1394,34 +3160,74 @@ class Golang(BuildSystem):
     def test(self, session, resolver, fixers):
         run_with_build_fixers(session, ["go", "test", "./..."], fixers)
 
-    def build(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["go", "build"], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        run_with_build_fixers(
+            session, ["go", "build"] + self.build_config.extra_args_for(self.name),
+            fixers)
 
     def install(self, session, resolver, fixers):
         run_with_build_fixers(session, ["go", "install"], fixers)
 
+    def lint(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["golangci-lint", "run"], fixers)
+
+    def update_lockfile(self, session, resolver, fixers):
+        # go.sum is the closest thing to a lockfile
+        run_with_build_fixers(session, ["go", "mod", "tidy"], fixers)
+
+    def vendor(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["go", "mod", "vendor"], fixers)
+
     def clean(self, session, resolver, fixers):
         session.check_call(["go", "clean"])
 
     def get_declared_dependencies(self, session, fixers=None):
         go_mod_path = os.path.join(self.path, "go.mod")
-        if os.path.exists(go_mod_path):
-            with open(go_mod_path, "r") as f:
-                for parts in _parse_go_mod(f):
-                    if parts[0] == "go":
-                        yield "build", GoRequirement(parts[1])
-                    elif parts[0] == "require":
-                        yield "build", GoPackageRequirement(
-                            parts[1], parts[2].lstrip("v") if len(parts) > 2 else None
-                        )
-                    elif parts[0] == "exclude":
-                        pass  # TODO(jelmer): Create conflicts?
-                    elif parts[0] == "replace":
-                        pass  # TODO(jelmer): do.. something?
-                    elif parts[0] == "module":
-                        pass
-                    else:
-                        logging.warning("Unknown directive %s in go.mod", parts[0])
+        if not os.path.exists(go_mod_path):
+            return
+        with open(go_mod_path, "r") as f:
+            directives = list(_parse_go_mod(f))
+        # Replacements apply to all requirements, regardless of where in
+        # go.mod they appear
+        replacements = {}
+        for parts in directives:
+            if parts[0] != "replace" or "=>" not in parts:
+                continue
+            i = parts.index("=>")
+            old, new = parts[1:i], parts[i + 1:]
+            if not old or not new:
+                logging.warning("Invalid replace directive in go.mod: %r", parts)
+                continue
+            replacements[tuple(old)] = new
+        for parts in directives:
+            if parts[0] == "go":
+                yield "build", GoRequirement(parts[1])
+            elif parts[0] == "toolchain":
+                # The suggested toolchain, not a minimum; "go" above is what
+                # is actually required.
+                logging.debug("go.mod suggests toolchain %s", parts[1])
+            elif parts[0] == "require":
+                module = parts[1]
+                version = parts[2] if len(parts) > 2 else None
+                new = replacements.get((module, version)) or replacements.get(
+                    (module, ))
+                if new is not None:
+                    if new[0].startswith(("./", "../", "/")):
+                        # Replaced by a directory in the source tree
+                        continue
+                    module = new[0]
+                    if len(new) > 1:
+                        version = new[1]
+                # Go uses minimal version selection, so this is the
+                # minimum version
+                yield "build", GoPackageRequirement(
+                    module, version.lstrip("v") if version else None)
+            elif parts[0] == "exclude":
+                pass  # TODO(jelmer): Create conflicts?
+            elif parts[0] in ("replace", "module", "retract", "godebug"):
+                pass
+            else:
+                logging.warning("Unknown directive %s in go.mod", parts[0])
 
     @classmethod
     def probe(cls, path):
@@ -1438,12 +3244,420 @@ class Golang(BuildSystem):
                         return Golang(path)
 
 
+def _dotnet_solution_projects(path):
+    """List the project files referenced by a .sln file."""
+    ret = []
+    with open(path, "r", encoding="utf-8-sig") as f:
+        for line in f:
+            m = re.match(
+                r'Project\("\{[^}]+\}"\)\s*=\s*"[^"]*",\s*"([^"]+)"', line)
+            if not m:
+                continue
+            relpath = m.group(1).replace("\\", "/")
+            if os.path.splitext(relpath)[1] in Dotnet.PROJECT_EXTENSIONS:
+                ret.append(os.path.join(os.path.dirname(path), relpath))
+    return ret
+
+
+def _nuget_package_references(path):
+    """Find the PackageReference entries in an MSBuild project file."""
+    import xml.etree.ElementTree as ET
+
+    try:
+        root = ET.parse(path).getroot()
+    except (ET.ParseError, FileNotFoundError) as e:
+        logging.warning("Unable to parse %s: %s", path, e)
+        return
+    # Old-style project files use the MSBuild namespace
+    for el in root.iter():
+        if el.tag.rsplit("}", 1)[-1] != "PackageReference":
+            continue
+        package = el.get("Include") or el.get("Update")
+        if not package:
+            continue
+        version = el.get("Version")
+        if version is None:
+            for child in el:
+                if child.tag.rsplit("}", 1)[-1] == "Version":
+                    version = child.text
+        yield NuGetPackageRequirement(package, version)
+
+
+class Dotnet(BuildSystem):
+    """.NET projects, built with the dotnet CLI (MSBuild)."""
+
+    name = "dotnet"
+
+    PROJECT_EXTENSIONS = [".csproj", ".fsproj", ".vbproj"]
+
+    def __init__(self, path, project=None):
+        super(Dotnet, self).__init__(path)
+        # Solution or project file to act on; None lets dotnet pick
+        self.project = project
+
+    def __repr__(self):
+        return "%s(%r, project=%r)" % (
+            type(self).__name__, self.path, self.project)
+
+    def _run(self, session, resolver, fixers, command, args=None):
+        dotnet = guaranteed_which(session, resolver, "dotnet")
+        argv = [dotnet, command]
+        if self.project is not None:
+            argv.append(self.project)
+        run_with_build_fixers(session, argv + (args or []), fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        args = []
+        if profile is not None:
+            args.extend(["-c", profile.capitalize()])
+        args.extend(self.build_config.extra_args_for(self.name))
+        self._run(session, resolver, fixers, "build", args)
+
+    def test(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, "test")
+
+    def clean(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, "clean")
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        import shutil
+        import tempfile
+
+        # NuGet packages are zip files with a different extension, so
+        # DistCatcher would not pick them up.
+        with tempfile.TemporaryDirectory(
+                dir=session.external_path("."), prefix=".ognibuild-pack-") as td:
+            internal_td = os.path.basename(td)
+            self._run(session, resolver, fixers, "pack", ["-o", internal_td])
+            filenames = sorted(
+                entry.name for entry in os.scandir(td)
+                if entry.name.endswith(".nupkg")
+                and not entry.name.endswith(".symbols.nupkg"))
+            if not filenames:
+                raise DistNoTarball()
+            for filename in filenames:
+                shutil.copy(os.path.join(td, filename), target_directory)
+        if len(filenames) > 1:
+            logging.info("Created NuGet packages: %s", ", ".join(filenames))
+        return filenames[0]
+
+    def _project_files(self):
+        if self.project is None:
+            names = sorted(os.listdir(self.path))
+            solutions = [n for n in names if n.endswith(".sln")]
+            if solutions:
+                return _dotnet_solution_projects(
+                    os.path.join(self.path, solutions[0]))
+            return [
+                os.path.join(self.path, n) for n in names
+                if os.path.splitext(n)[1] in self.PROJECT_EXTENSIONS]
+        path = os.path.join(self.path, self.project)
+        if path.endswith(".sln"):
+            return _dotnet_solution_projects(path)
+        return [path]
+
+    def get_declared_dependencies(self, session, fixers=None):
+        seen = set()
+        for path in self._project_files():
+            for req in _nuget_package_references(path):
+                if (req.package, req.version) in seen:
+                    continue
+                seen.add((req.package, req.version))
+                yield "build", req
+
+    @classmethod
+    def probe(cls, path):
+        names = sorted(os.listdir(path))
+        solutions = [n for n in names if n.endswith(".sln")]
+        projects = [
+            n for n in names if os.path.splitext(n)[1] in cls.PROJECT_EXTENSIONS]
+        if not solutions and not projects:
+            return None
+        logging.debug("Found %s, assuming .NET project.", (solutions + projects)[0])
+        # dotnet refuses to guess if there are several candidates, so pick one
+        if len(solutions) > 1:
+            return cls(path, solutions[0])
+        if not solutions and len(projects) > 1:
+            return cls(path, projects[0])
+        return cls(path)
+
+
+def _nimble_requires(f):
+    """Find the requirements listed in a .nimble file."""
+    contents = f.read()
+    for m in re.finditer(
+            r"^\s*requires\b\s*\(?((?:\s*\"[^\"]*\"\s*,?)+)", contents,
+            re.MULTILINE):
+        for req in re.findall(r'"([^"]*)"', m.group(1)):
+            for part in req.split(","):
+                if part.strip():
+                    yield NimblePackageRequirement.from_str(part)
+
+
+class Nimble(BuildSystem):
+
+    name = "nimble"
+
+    def __init__(self, path):
+        super(Nimble, self).__init__(path)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    def _run(self, session, resolver, fixers, args):
+        nimble = guaranteed_which(session, resolver, "nimble")
+        run_with_build_fixers(session, [nimble] + args, fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        self._run(session, resolver, fixers, ["build", "-y"])
+
+    def test(self, session, resolver, fixers):
+        self._run(session, resolver, fixers, ["test", "-y"])
+
+    def install(self, session, resolver, fixers, install_target):
+        args = []
+        if install_target.prefix:
+            # Binaries are linked into the bin directory of the nimble
+            # directory, so this puts them in $prefix/bin
+            args.append("--nimbleDir:%s" % install_target.prefix)
+        self._run(session, resolver, fixers, args + ["install", "-y"])
+
+    def get_declared_dependencies(self, session, fixers=None):
+        with open(self.path, "r") as f:
+            for req in _nimble_requires(f):
+                if req.package == "nim":
+                    yield "build", BinaryRequirement("nim")
+                else:
+                    yield "core", req
+
+    def project_version(self, session):
+        with open(self.path, "r") as f:
+            m = re.search(
+                r'^\s*version\s*=\s*"([^"]+)"', f.read(), re.MULTILINE)
+        if m:
+            return m.group(1)
+        return super(Nimble, self).project_version(session)
+
+    @classmethod
+    def probe(cls, path):
+        for entry in sorted(os.scandir(path), key=lambda e: e.name):
+            if entry.name.endswith(".nimble") and entry.is_file():
+                logging.debug("Found %s, assuming nimble package.", entry.name)
+                return cls(entry.path)
+
+
+def _conda_selector_namespace():
+    """Variables for evaluating conda recipe selectors on this machine."""
+    import platform
+    import sys
+
+    machine = platform.machine()
+    ns = {
+        "linux": sys.platform.startswith("linux"),
+        "osx": sys.platform == "darwin",
+        "win": sys.platform == "win32",
+        "x86_64": machine in ("x86_64", "AMD64"),
+        "aarch64": machine == "aarch64",
+        "arm64": machine == "arm64" or (
+            machine == "aarch64" and sys.platform == "darwin"),
+        "ppc64le": machine == "ppc64le",
+        "py": int("%d%d" % sys.version_info[:2]),
+        "py3k": sys.version_info[0] == 3,
+        "py2k": sys.version_info[0] == 2,
+    }
+    ns["unix"] = not ns["win"]
+    ns["x86"] = ns["x86_64"]
+    for name in ["linux", "osx", "win"]:
+        ns[name + "64"] = ns[name] and ns["x86_64"]
+    return ns
+
+
+_CONDA_SELECTOR_COMPARISONS = {
+    "Eq": lambda a, b: a == b,
+    "NotEq": lambda a, b: a != b,
+    "Lt": lambda a, b: a < b,
+    "LtE": lambda a, b: a <= b,
+    "Gt": lambda a, b: a > b,
+    "GtE": lambda a, b: a >= b,
+}
+
+
+def _eval_conda_selector(node, namespace):
+    """Evaluate a parsed conda selector.
+
+    Only names, boolean operators, comparisons and int or str constants
+    are supported; recipes come from untrusted trees, so selectors are
+    never passed to eval().
+
+    Raises:
+      ValueError: if the selector uses anything else
+    """
+    import ast
+
+    if isinstance(node, ast.Expression):
+        return _eval_conda_selector(node.body, namespace)
+    if isinstance(node, ast.Name):
+        try:
+            return namespace[node.id]
+        except KeyError:
+            raise ValueError(node.id)
+    if isinstance(node, ast.BoolOp):
+        values = [_eval_conda_selector(v, namespace) for v in node.values]
+        if isinstance(node.op, ast.And):
+            return all(values)
+        return any(values)
+    if isinstance(node, ast.UnaryOp) and isinstance(node.op, ast.Not):
+        return not _eval_conda_selector(node.operand, namespace)
+    if isinstance(node, ast.Compare):
+        left = _eval_conda_selector(node.left, namespace)
+        for op, comparator in zip(node.ops, node.comparators):
+            right = _eval_conda_selector(comparator, namespace)
+            try:
+                compare = _CONDA_SELECTOR_COMPARISONS[type(op).__name__]
+                if not compare(left, right):
+                    return False
+            except (KeyError, TypeError):
+                raise ValueError(op)
+            left = right
+        return True
+    # Python < 3.8 has separate nodes for numbers and strings
+    if type(node).__name__ == "Num":
+        value = node.n
+    elif type(node).__name__ == "Str":
+        value = node.s
+    elif isinstance(node, ast.Constant):
+        value = node.value
+    else:
+        raise ValueError(node)
+    if not isinstance(value, (int, str)):
+        raise ValueError(value)
+    return value
+
+
+def _apply_conda_selectors(text, namespace):
+    """Drop the lines of a conda recipe whose selector does not match.
+
+    Selectors are comments at the end of a line, e.g. "  - pywin32  # [win]".
+    Lines with selectors that can not be evaluated are kept.
+    """
+    import ast
+
+    lines = []
+    for line in text.splitlines(True):
+        m = re.search(r"\s*#\s*\[(.*)\]\s*$", line)
+        if m:
+            try:
+                selected = _eval_conda_selector(
+                    ast.parse(m.group(1).strip(), mode="eval"), namespace)
+            except (SyntaxError, ValueError):
+                selected = True
+            if not selected:
+                continue
+        lines.append(line)
+    return "".join(lines)
+
+
+def _load_conda_recipe(path):
+    """Load a conda recipe, ignoring the Jinja2 templating.
+
+    Statements are dropped and expressions replaced by a placeholder; this
+    is good enough to find the requirements. Selectors are evaluated for
+    the current machine.
+    """
+    import ruamel.yaml
+
+    with open(path, "r") as f:
+        text = f.read()
+    text = _apply_conda_selectors(text, _conda_selector_namespace())
+    text = re.sub(r"\{%.*?%\}", "", text, flags=re.DOTALL)
+    text = re.sub(r"\{\{.*?\}\}", "JINJA", text)
+    return ruamel.yaml.load(text, ruamel.yaml.SafeLoader) or {}
+
+
+class CondaBuild(BuildSystem):
+    """conda-build recipes."""
+
+    name = "conda"
+
+    # Recipe sections, and the stages that they are needed in
+    REQUIREMENT_SECTIONS = [
+        ("build", "build"),
+        ("host", "build"),
+        ("run", "core"),
+    ]
+
+    def __init__(self, path):
+        # Path to the recipe directory
+        super(CondaBuild, self).__init__(path)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    def _recipe(self, session):
+        return os.path.relpath(self.path, session.external_path("."))
+
+    def _run(self, session, resolver, fixers, args):
+        conda = guaranteed_which(session, resolver, "conda")
+        run_with_build_fixers(session, [conda, "build"] + args, fixers)
+
+    def _packages(self, session, resolver):
+        """Find the paths of the packages that the recipe builds."""
+        conda = guaranteed_which(session, resolver, "conda")
+        output = session.check_output(
+            [conda, "build", "--output", self._recipe(session)])
+        return [line for line in output.decode().splitlines()
+                if line.strip()]
+
+    def build(self, session, resolver, fixers, profile=None):
+        self._run(
+            session, resolver, fixers, ["--no-test", self._recipe(session)])
+
+    def test(self, session, resolver, fixers):
+        # Run the tests from the recipe against the built packages, rather
+        # than building them again
+        packages = self._packages(session, resolver)
+        if not all(session.exists(package) for package in packages):
+            self.build(session, resolver, fixers)
+        for package in packages:
+            self._run(session, resolver, fixers, ["--test", package])
+
+    def get_declared_dependencies(self, session, fixers=None):
+        recipe = _load_conda_recipe(os.path.join(self.path, "meta.yaml"))
+        requirements = recipe.get("requirements") or {}
+        sections = [
+            (requirements.get(name), stage)
+            for (name, stage) in self.REQUIREMENT_SECTIONS]
+        sections.append(((recipe.get("test") or {}).get("requires"), "test"))
+        for entries, stage in sections:
+            for entry in entries or []:
+                # e.g. {{ compiler('c') }}
+                if not isinstance(entry, str) or entry.startswith("JINJA"):
+                    continue
+                yield stage, CondaPackageRequirement.from_str(entry)
+
+    def project_version(self, session):
+        recipe = _load_conda_recipe(os.path.join(self.path, "meta.yaml"))
+        version = (recipe.get("package") or {}).get("version")
+        if isinstance(version, str) and "JINJA" not in version:
+            return version
+        return super(CondaBuild, self).project_version(session)
+
+    @classmethod
+    def probe(cls, path):
+        for subpath in [".", "recipe", "conda.recipe"]:
+            if os.path.exists(os.path.join(path, subpath, "meta.yaml")):
+                logging.debug(
+                    "Found %s, assuming conda recipe.",
+                    os.path.normpath(os.path.join(subpath, "meta.yaml")))
+                return cls(os.path.normpath(os.path.join(path, subpath)))
+
+
 class Maven(BuildSystem):
 
     name = "maven"
 
     def __init__(self, path):
-        self.path = path
+        super(Maven, self).__init__(path)
 
     @classmethod
     def probe(cls, path):
@@ -1460,8 +3674,10 @@ class Maven(BuildSystem):
     def install(self, session, resolver, fixers, install_target):
         run_with_build_fixers(session, ["mvn", "install"], fixers)
 
-    def build(self, session, resolver, fixers):
-        run_with_build_fixers(session, ["mvn", "compile"], fixers)
+    def build(self, session, resolver, fixers, profile=None):
+        run_with_build_fixers(
+            session, ["mvn", "compile"] + self.build_config.extra_args_for(self.name),
+            fixers)
 
     def dist(self, session, resolver, fixers, target_directory, quiet=False):
         # TODO(jelmer): 'mvn generate-sources' creates a jar in target/.
@@ -1495,7 +3711,7 @@ class Cabal(BuildSystem):
     name = "cabal"
 
     def __init__(self, path):
-        self.path = path
+        super(Cabal, self).__init__(path)
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
@@ -1534,16 +3750,170 @@ class Cabal(BuildSystem):
             return cls(os.path.join(path, "Setup.hs"))
 
 
+def _declared_deps_from_cabal_file(f):
+    """Extract the build-depends fields from a .cabal file."""
+    package = None
+    values = []
+    current = None
+    for line in f:
+        line = line.split("--", 1)[0].rstrip()
+        if not line.strip():
+            continue
+        indent = len(line) - len(line.lstrip())
+        if current is not None and indent > current[0]:
+            current[1].append(line.strip())
+            continue
+        current = None
+        m = re.match(r"\s*([A-Za-z-]+)\s*:(.*)", line)
+        if not m:
+            continue
+        name = m.group(1).lower()
+        if name == "name" and indent == 0:
+            package = m.group(2).strip()
+        elif name == "build-depends":
+            current = (indent, [m.group(2).strip()])
+            values.append(current[1])
+    for value in values:
+        for dep in " ".join(value).split(","):
+            if not dep.strip():
+                continue
+            req = HaskellPackageRequirement.from_string(dep)
+            if req.package != package:
+                yield "build", req
+
+
+class Stack(BuildSystem):
+    """Haskell projects built with stack.
+
+    Dependencies are resolved against the snapshot configured in stack.yaml,
+    so only dependencies outside the snapshot (extra-deps) are pinned.
+    """
+
+    name = "stack"
+
+    def __init__(self, path):
+        super(Stack, self).__init__(path)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    def setup(self, session, resolver):
+        binary_req = BinaryRequirement("stack")
+        if not binary_req.try_met(session):
+            resolver.install([binary_req])
+
+    def _run(self, session, resolver, args, fixers):
+        self.setup(session, resolver)
+        run_with_build_fixers(session, ["stack"] + args, fixers)
+
+    def build(self, session, resolver, fixers, profile=None):
+        args = ["build"]
+        if self.build_config.jobs is not None:
+            args.append("--jobs=%d" % self.build_config.jobs)
+        self._run(session, resolver, args, fixers)
+
+    def test(self, session, resolver, fixers):
+        self._run(session, resolver, ["test"], fixers)
+
+    def clean(self, session, resolver, fixers):
+        self._run(session, resolver, ["clean"], fixers)
+
+    def install(self, session, resolver, fixers, install_target):
+        args = ["install"]
+        if install_target.prefix:
+            args.append(
+                "--local-bin-path=%s" % os.path.join(install_target.prefix, "bin"))
+        self._run(session, resolver, args, fixers)
+
+    def dist(self, session, resolver, fixers, target_directory, quiet=False):
+        with DistCatcher([session.external_path("dist")]) as dc:
+            self._run(session, resolver, ["sdist", "--tar-dir=dist"], fixers)
+        return dc.copy_single(target_directory)
+
+    def _load_yaml(self, name):
+        import ruamel.yaml
+
+        with open(os.path.join(self.path, name), "r") as f:
+            return ruamel.yaml.load(f, ruamel.yaml.SafeLoader)
+
+    def get_declared_dependencies(self, session, fixers=None):
+        config = self._load_yaml("stack.yaml") or {}
+        snapshot = config.get("snapshot") or config.get("resolver")
+        if isinstance(snapshot, str):
+            # Compiler-only snapshots, e.g. "ghc-9.2.7"
+            m = re.fullmatch(r"ghc-([0-9.]+)", snapshot)
+            if m:
+                yield "build", HaskellPackageRequirement(
+                    "ghc", specs=["==" + m.group(1)])
+        for dep in config.get("extra-deps") or []:
+            if not isinstance(dep, str):
+                # git or archive dependencies
+                continue
+            m = re.fullmatch(r"(.*)-([0-9.]+)(@.*)?", dep)
+            if m:
+                yield "build", HaskellPackageRequirement(
+                    m.group(1), specs=["==" + m.group(2)])
+        for package in config.get("packages") or ["."]:
+            yield from self._package_dependencies(package)
+
+    def _package_dependencies(self, package):
+        path = os.path.join(self.path, package)
+        if os.path.exists(os.path.join(path, "package.yaml")):
+            # hpack
+            data = self._load_yaml(os.path.join(package, "package.yaml")) or {}
+            sections = [data]
+            for key in ["library", "internal-libraries", "executables",
+                        "tests", "benchmarks"]:
+                section = data.get(key) or {}
+                if key == "library":
+                    sections.append(section)
+                else:
+                    sections.extend(section.values())
+            for section in sections:
+                deps = section.get("dependencies") or []
+                if isinstance(deps, str):
+                    deps = [deps]
+                for dep in deps:
+                    if not isinstance(dep, str):
+                        continue
+                    req = HaskellPackageRequirement.from_string(dep)
+                    if req.package != data.get("name"):
+                        yield "build", req
+            return
+        try:
+            entries = os.listdir(path)
+        except FileNotFoundError:
+            return
+        for name in entries:
+            if name.endswith(".cabal"):
+                with open(os.path.join(path, name), "r") as f:
+                    yield from _declared_deps_from_cabal_file(f)
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "stack.yaml")):
+            logging.debug("Found stack.yaml, assuming stack project.")
+            return cls(path)
+        return None
+
+
 class Composer(BuildSystem):
 
     name = "composer"
 
     def __init__(self, path):
-        self.path = path
+        super(Composer, self).__init__(path)
 
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.path)
 
+    def update_lockfile(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["composer", "update", "--lock"], fixers)
+
+    def vendor(self, session, resolver, fixers):
+        run_with_build_fixers(
+            session, ["composer", "install", "--no-scripts"], fixers)
+
     @classmethod
     def probe(cls, path):
         if os.path.exists(os.path.join(path, "composer.json")):
@@ -1551,12 +3921,52 @@ class Composer(BuildSystem):
             return cls(path)
 
 
+class Bundler(BuildSystem):
+    """Ruby projects with a Gemfile."""
+
+    name = "bundler"
+
+    def __init__(self, path):
+        super(Bundler, self).__init__(path)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    def setup(self, session, resolver):
+        binary_req = BinaryRequirement("bundle")
+        if not binary_req.try_met(session):
+            resolver.install([binary_req])
+
+    def build(self, session, resolver, fixers, profile=None):
+        self.setup(session, resolver)
+        run_with_build_fixers(session, ["bundle", "install"], fixers)
+
+    def test(self, session, resolver, fixers):
+        self.setup(session, resolver)
+        if not os.path.exists(os.path.join(self.path, "Rakefile")):
+            raise NotImplementedError
+        run_with_build_fixers(session, ["bundle", "exec", "rake", "test"], fixers)
+
+    def update_lockfile(self, session, resolver, fixers):
+        run_with_build_fixers(session, ["bundle", "lock"], fixers)
+
+    def vendor(self, session, resolver, fixers):
+        # Stores the gems in vendor/cache
+        run_with_build_fixers(session, ["bundle", "cache", "--all"], fixers)
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "Gemfile")):
+            logging.debug("Found Gemfile, assuming bundler project.")
+            return cls(path)
+
+
 class PerlBuildTiny(BuildSystem):
 
     name = "perl-build-tiny"
 
     def __init__(self, path):
-        self.path = path
+        super(PerlBuildTiny, self).__init__(path)
         self.minilla = os.path.exists(os.path.join(self.path, "minil.toml"))
 
     def __repr__(self):
@@ -1572,7 +3982,7 @@ class PerlBuildTiny(BuildSystem):
         else:
             run_with_build_fixers(session, ["./Build", "test"], fixers)
 
-    def build(self, session, resolver, fixers):
+    def build(self, session, resolver, fixers, profile=None):
         self.setup(session, fixers)
         run_with_build_fixers(session, ["./Build", "build"], fixers)
 
@@ -1637,25 +4047,36 @@ class PerlBuildTiny(BuildSystem):
 
 BUILDSYSTEM_CLSES = [
     Pear,
+    Poetry,
+    Hatch,
+    Pdm,
+    Maturin,
     SetupPy,
     Npm,
     Waf,
     Meson,
     Cargo,
+    Stack,
     Cabal,
     Gradle,
     Maven,
+    Dotnet,
+    Nimble,
+    CondaBuild,
     DistZilla,
     Gem,
     PerlBuildTiny,
     Golang,
     R,
     Octave,
+    Please,
     Bazel,
     CMake,
+    Autotools,
     # Make is intentionally at the end of the list.
     Make,
     Composer,
+    Bundler,
     RunTests,
 ]
 
@@ -1674,11 +4095,126 @@ def scan_buildsystems(path):
     return ret
 
 
-def detect_buildsystems(path):
+# Environment variables with comma-separated build system names, to
+# restrict detection to (in order) or to probe first respectively.
+BUILDSYSTEM_OVERRIDE_ENV = "OGNIBUILD_BUILDSYSTEM"
+BUILDSYSTEM_PRIORITY_ENV = "OGNIBUILD_BUILDSYSTEM_PRIORITY"
+
+
+def buildsystem_names_from_env(name: str) -> Optional[List[str]]:
+    value = os.environ.get(name)
+    if not value:
+        return None
+    return [n.strip() for n in value.split(",") if n.strip()]
+
+
+def _name_index(bs, names: List[str]) -> Optional[int]:
+    """Find a build system in a list of names.
+
+    Both the build system name (e.g. "automake") and the lowercased class
+    name (e.g. "autotools") match.
+    """
+    for i, name in enumerate(names):
+        if name in (bs.name, type(bs).__name__.lower()):
+            return i
+    return None
+
+
+class ProbeResult(object):
+    """Result of probing a directory for a build system.
+
+    Attributes:
+      cls: The build system class that was probed
+      buildsystem: The build system that was found, or None
+      selected: Whether the build system is used
+      reason: Why the build system is (not) used
+    """
+
+    def __init__(self, cls, buildsystem, selected: bool, reason: str):
+        self.cls = cls
+        self.buildsystem = buildsystem
+        self.selected = selected
+        self.reason = reason
+
+    def __repr__(self):
+        return "%s(%s, %r, %r, %r)" % (
+            type(self).__name__, self.cls.__name__, self.buildsystem,
+            self.selected, self.reason)
+
+    def json(self):
+        return {
+            "class": self.cls.__name__,
+            "buildsystem": (
+                str(self.buildsystem) if self.buildsystem is not None else None),
+            "selected": self.selected,
+            "reason": self.reason,
+        }
+
+
+def probe_buildsystems(
+    path: str, override: Optional[List[str]] = None,
+    priority: Optional[List[str]] = None
+) -> List[ProbeResult]:
+    """Probe a directory for all known build systems.
+
+    Args:
+      path: Path to the project
+      override: Names of the only build systems to use, in order of
+        preference; defaults to $OGNIBUILD_BUILDSYSTEM
+      priority: Names of build systems to prefer over the others, in
+        order; defaults to $OGNIBUILD_BUILDSYSTEM_PRIORITY
+    Returns: probe results for every build system class, with the
+      selected build systems first, in order of preference
+    """
+    if override is None:
+        override = buildsystem_names_from_env(BUILDSYSTEM_OVERRIDE_ENV)
+    if priority is None:
+        priority = buildsystem_names_from_env(BUILDSYSTEM_PRIORITY_ENV)
+    order = override if override is not None else (priority or [])
+    results = []
     for bs_cls in BUILDSYSTEM_CLSES:
         bs = bs_cls.probe(path)
-        if bs is not None:
-            yield bs
+        if bs is None:
+            results.append(ProbeResult(bs_cls, None, False, "not detected"))
+        elif _name_index(bs, order) is not None:
+            results.append(ProbeResult(
+                bs_cls, bs, True, "detected; selected by %s" % (
+                    "override" if override is not None else "priority")))
+        elif override is not None:
+            results.append(ProbeResult(
+                bs_cls, bs, False, "detected; not in override"))
+        else:
+            results.append(ProbeResult(bs_cls, bs, True, "detected"))
+
+    def sort_key(result):
+        if result.buildsystem is None:
+            return len(order)
+        index = _name_index(result.buildsystem, order)
+        return len(order) if index is None else index
+
+    # Sorting is stable, so other build systems keep their default order
+    results.sort(key=sort_key)
+    if override is not None and not any(r.selected for r in results):
+        logging.warning(
+            "None of the build systems %s were detected", ", ".join(override))
+    return results
+
+
+def format_probe_results(results: List[ProbeResult]) -> str:
+    lines = []
+    for result in results:
+        lines.append("%s %-15s %s%s" % (
+            "*" if result.selected else " ", result.cls.__name__,
+            result.reason,
+            " (%s)" % result.buildsystem
+            if result.buildsystem is not None else ""))
+    return "\n".join(lines) + "\n"
+
+
+def detect_buildsystems(path, override=None, priority=None):
+    for result in probe_buildsystems(path, override=override, priority=priority):
+        if result.selected:
+            yield result.buildsystem
 
 
 def get_buildsystem(path: str) -> Tuple[str, BuildSystem]:
@@ -0,0 +1,255 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Build chains of source projects that depend on each other.
+
+Each project is built and installed into a prefix that is shared by the
+whole chain, and which is added to the search paths for the projects that
+come after it. This makes it possible to bootstrap e.g. a library, its
+language bindings and an application using them, none of which are
+packaged yet.
+"""
+
+import json
+import logging
+import os
+from typing import Callable, Dict, List, Optional
+
+from .buildsystem import NoBuildToolsFound, detect_buildsystems
+from .session import Session
+
+
+# Prefix inside the session that projects in a chain are installed into
+DEFAULT_CHAIN_PREFIX = "/opt/ognibuild-chain"
+
+
+class ChainCycle(Exception):
+    """The dependencies between the projects in a chain form a cycle."""
+
+    def __init__(self, projects: List[str]):
+        self.projects = projects
+
+    def __str__(self):
+        return "Dependency cycle between projects: %s" % ", ".join(self.projects)
+
+
+class ChainedProject(object):
+    """A source project in a build chain.
+
+    Attributes:
+      name: Name of the project
+      directory: Path of the project on the host
+      depends: Names of the projects in the chain that this project needs
+    """
+
+    def __init__(self, name: str, directory: str, depends: Optional[List[str]] = None):
+        self.name = name
+        self.directory = directory
+        self.depends = list(depends or [])
+
+    def __repr__(self):
+        return "%s(%r, %r, depends=%r)" % (
+            type(self).__name__, self.name, self.directory, self.depends)
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+
+def read_chain_file(path: str) -> List[ChainedProject]:
+    """Read a chain description.
+
+    The file contains JSON, e.g.::
+
+      {"projects": [
+        {"name": "lib", "directory": "libfoo"},
+        {"name": "app", "directory": "foo-app", "depends": ["lib"]}]}
+
+    Relative directories are relative to the chain file.
+    """
+    with open(path, "r") as f:
+        data = json.load(f)
+    base = os.path.dirname(os.path.abspath(path))
+    return [
+        ChainedProject(
+            entry["name"], os.path.join(base, entry["directory"]),
+            entry.get("depends"))
+        for entry in data["projects"]]
+
+
+def linear_chain(directories: List[str]) -> List[ChainedProject]:
+    """Create a chain in which each project depends on the previous one."""
+    ret: List[ChainedProject] = []
+    for directory in directories:
+        name = os.path.basename(os.path.abspath(directory))
+        ret.append(ChainedProject(
+            name, directory, [ret[-1].name] if ret else []))
+    return ret
+
+
+def order_chain(projects: List[ChainedProject]) -> List[ChainedProject]:
+    """Order projects so that each comes after the projects it depends on.
+
+    Projects that do not depend on each other keep their original order.
+
+    Raises:
+      ValueError: if a project depends on a project that is not in the chain
+      ChainCycle: if the dependencies form a cycle
+    """
+    by_name = {project.name: project for project in projects}
+    for project in projects:
+        for dep in project.depends:
+            if dep not in by_name:
+                raise ValueError(
+                    "%s depends on unknown project %s" % (project.name, dep))
+    ret: List[ChainedProject] = []
+    done = set()
+    remaining = list(projects)
+    while remaining:
+        for project in remaining:
+            if all(dep in done for dep in project.depends):
+                break
+        else:
+            raise ChainCycle([project.name for project in remaining])
+        remaining.remove(project)
+        done.add(project.name)
+        ret.append(project)
+    return ret
+
+
+# Search path variables that chain_environment extends
+CHAIN_PATH_VARIABLES = [
+    "PATH", "PKG_CONFIG_PATH", "CMAKE_PREFIX_PATH", "CPATH", "LIBRARY_PATH",
+    "LD_LIBRARY_PATH", "PERL5LIB", "PYTHONPATH"]
+
+
+def base_environment(session: Session) -> Dict[str, str]:
+    """Retrieve the search path variables of a session before chaining.
+
+    Returns: dictionary mapping variable names to their (possibly empty) values
+    """
+    env = {}
+    for variable in CHAIN_PATH_VARIABLES:
+        value = session.getenv(variable)
+        if value is None:
+            value = session.check_output(
+                ["sh", "-c", "echo ${%s}" % variable],
+                cwd="/").decode().strip()
+        env[variable] = value
+    return env
+
+
+def chain_environment(
+    session: Session, prefix: str, base_env: Dict[str, str]
+) -> Dict[str, str]:
+    """Environment variables that make a prefix visible to builds.
+
+    Args:
+      session: Session the prefix lives in
+      prefix: Path of the prefix inside the session
+      base_env: Values of the search path variables without the prefix,
+        as returned by base_environment; the prefix is prepended to these
+    """
+    paths = {
+        "PATH": ["%s/bin" % prefix],
+        "PKG_CONFIG_PATH": [
+            "%s/lib/pkgconfig" % prefix, "%s/share/pkgconfig" % prefix],
+        "CMAKE_PREFIX_PATH": [prefix],
+        "CPATH": ["%s/include" % prefix],
+        "LIBRARY_PATH": ["%s/lib" % prefix],
+        "LD_LIBRARY_PATH": ["%s/lib" % prefix],
+        "PERL5LIB": ["%s/share/perl5" % prefix],
+    }
+    # Python packages end up in a directory that includes the Python version
+    site_packages = []
+    lib = session.external_path(os.path.join(prefix, "lib"))
+    if os.path.isdir(lib):
+        for entry in sorted(os.scandir(lib), key=lambda e: e.name):
+            if entry.name.startswith("python"):
+                for name in ["site-packages", "dist-packages"]:
+                    if os.path.isdir(os.path.join(entry.path, name)):
+                        site_packages.append(
+                            os.path.join(prefix, "lib", entry.name, name))
+    if site_packages:
+        paths["PYTHONPATH"] = site_packages
+    env = {}
+    for variable, entries in paths.items():
+        base = base_env.get(variable)
+        if base:
+            entries = entries + [
+                entry for entry in base.split(":") if entry not in entries]
+        env[variable] = ":".join(entries)
+    return env
+
+
+def run_chain(
+    session: Session,
+    projects: List[ChainedProject],
+    resolver,
+    fixers,
+    prefix: str = DEFAULT_CHAIN_PREFIX,
+    prepare: Optional[Callable[[ChainedProject, list], None]] = None,
+    layer_directory: Optional[str] = None,
+) -> List[str]:
+    """Build and install a chain of projects in a session.
+
+    Args:
+      session: Session to build in; shared by all projects
+      projects: Projects in the chain, in any order
+      resolver: Resolver for missing requirements
+      fixers: Build fixers
+      prefix: Absolute path of the prefix inside the session to install
+        projects into
+      prepare: Optional callback, called with each project and its build
+        systems before building (e.g. to install declared dependencies)
+      layer_directory: If set, export a session image after each project
+        into this directory, so that later sessions can start from the
+        state after any project in the chain (see --image)
+    Returns: names of the projects that were built, in order
+    """
+    from .build import run_build
+    from .install import run_install
+
+    ordered = order_chain(projects)
+    logging.info(
+        "Building chain: %s", " -> ".join(project.name for project in ordered))
+    base_env = base_environment(session)
+    os.makedirs(session.external_path(prefix), exist_ok=True)
+    built = []
+    for project in ordered:
+        logging.info("Building %s from %s", project.name, project.directory)
+        external_dir, internal_dir = session.setup_from_directory(
+            project.directory)
+        session.chdir(internal_dir)
+        bss = list(detect_buildsystems(external_dir))
+        if not bss:
+            logging.info("No build tools found for %s.", project.name)
+            raise NoBuildToolsFound()
+        if prepare is not None:
+            prepare(project, bss)
+        run_build(session, buildsystems=bss, resolver=resolver, fixers=fixers)
+        run_install(
+            session, buildsystems=bss, resolver=resolver, fixers=fixers,
+            prefix=prefix)
+        for key, value in chain_environment(session, prefix, base_env).items():
+            session.setenv(key, value)
+        built.append(project.name)
+        if layer_directory is not None:
+            os.makedirs(layer_directory, exist_ok=True)
+            layer = os.path.join(layer_directory, "%s.tar.gz" % project.name)
+            logging.info("Exporting layer for %s to %s", project.name, layer)
+            session.export_image(layer)
+    return built
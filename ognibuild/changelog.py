@@ -0,0 +1,240 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Locate and update changelog/NEWS files for new releases."""
+
+from datetime import date as _date
+import logging
+import os
+import re
+from typing import List, Optional
+
+
+class NoChangelogFound(Exception):
+    """No changelog file in a supported format was found."""
+
+    def __init__(self, path):
+        self.path = path
+
+    def __str__(self):
+        return "No supported changelog file found in %s" % self.path
+
+
+class ChangelogFile(object):
+    """A changelog file of a particular format."""
+
+    name: str
+
+    # Candidate file names, in order of preference
+    filenames: List[str] = []
+
+    def __init__(self, path):
+        self.path = path
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    @classmethod
+    def probe(cls, directory) -> Optional["ChangelogFile"]:
+        for filename in cls.filenames:
+            path = os.path.join(directory, filename)
+            if os.path.exists(path) and cls.matches(path):
+                return cls(path)
+        return None
+
+    @classmethod
+    def matches(cls, path) -> bool:
+        return True
+
+    def add_release(
+        self, version: str, changes: List[str], date: Optional[_date] = None
+    ) -> None:
+        """Add an entry for a new release.
+
+        Args:
+          version: Version of the new release
+          changes: List of changes, one line each
+          date: Release date (defaults to today)
+        """
+        raise NotImplementedError(self.add_release)
+
+
+class KeepAChangelogFile(ChangelogFile):
+    """Changelog in the format described at https://keepachangelog.com/.
+
+    Any existing "Unreleased" section becomes the new release; a new empty
+    "Unreleased" section is added above it.
+    """
+
+    name = "keep-a-changelog"
+    filenames = ["CHANGELOG.md", "CHANGELOG", "ChangeLog.md"]
+
+    RELEASE_RE = re.compile(r"^## \[?([^\]\s]+)\]?", re.MULTILINE)
+
+    @classmethod
+    def matches(cls, path):
+        with open(path, "r") as f:
+            contents = f.read()
+        return (
+            "keepachangelog.com" in contents
+            or re.search(r"^## \[", contents, re.MULTILINE) is not None
+        )
+
+    def add_release(self, version, changes, date=None):
+        if date is None:
+            date = _date.today()
+        with open(self.path, "r") as f:
+            contents = f.read()
+        heading = "## [%s] - %s\n" % (version, date.isoformat())
+        entries = "".join(["- %s\n" % change for change in changes])
+        m = re.search(r"^## \[Unreleased\][^\n]*\n", contents, re.MULTILINE | re.I)
+        if m:
+            body_start = m.end()
+            next_m = self.RELEASE_RE.search(contents, body_start)
+            body_end = next_m.start() if next_m else len(contents)
+            body = contents[body_start:body_end].strip("\n")
+            if entries:
+                body = (body + "\n" + entries) if body else ("\n" + entries)
+            new = "## [Unreleased]\n\n" + heading + body.rstrip("\n") + "\n\n"
+            contents = contents[: m.start()] + new + contents[body_end:]
+        else:
+            new = heading + "\n" + entries + "\n"
+            m = self.RELEASE_RE.search(contents)
+            if m:
+                contents = contents[: m.start()] + new + contents[m.start():]
+            else:
+                contents = contents.rstrip("\n") + "\n\n" + new
+        # Keep the link reference list at the bottom up to date, if present
+        m = re.search(
+            r"^\[Unreleased\]: (.*)/compare/(v?)(\S+?)\.\.\.HEAD$",
+            contents,
+            re.MULTILINE | re.I,
+        )
+        if m:
+            base, prefix, previous = m.group(1), m.group(2), m.group(3)
+            contents = (
+                contents[: m.start()]
+                + "[Unreleased]: %s/compare/%s%s...HEAD\n" % (base, prefix, version)
+                + "[%s]: %s/compare/%s%s...%s%s"
+                % (version, base, prefix, previous, prefix, version)
+                + contents[m.end():]
+            )
+        with open(self.path, "w") as f:
+            f.write(contents.rstrip("\n") + "\n")
+
+
+class NewsFile(ChangelogFile):
+    """Free-form NEWS file, with the newest release at the top.
+
+    New entries look like:
+
+        1.2.0 (2021-06-01)
+
+         * Change one
+    """
+
+    name = "news"
+    filenames = ["NEWS", "NEWS.md", "NEWS.rst", "NEWS.txt"]
+
+    def add_release(self, version, changes, date=None):
+        if date is None:
+            date = _date.today()
+        with open(self.path, "r") as f:
+            contents = f.read()
+        heading = "%s (%s)" % (version, date.isoformat())
+        if self.path.endswith(".md"):
+            heading = "## " + heading + "\n"
+        elif self.path.endswith(".rst"):
+            heading = heading + "\n" + "-" * len(heading) + "\n"
+        else:
+            heading = heading + "\n"
+        entries = "".join([" * %s\n" % change for change in changes])
+        new = heading + "\n" + entries
+        with open(self.path, "w") as f:
+            f.write(new + ("\n" + contents if contents.strip() else ""))
+
+
+class DebianChangelogFile(ChangelogFile):
+    """debian/changelog, for upstream trees that also ship packaging.
+
+    The new entry targets UNRELEASED. If the version has no Debian revision
+    and the package is not native, "-1" is appended.
+    """
+
+    name = "debian"
+    filenames = ["debian/changelog"]
+
+    def add_release(self, version, changes, date=None):
+        from debian.changelog import Version, format_date, get_maintainer
+        from debmutate.changelog import ChangelogEditor
+
+        if date is None:
+            timestamp = None
+        else:
+            import time
+
+            timestamp = time.mktime(date.timetuple())
+        maintainer = get_maintainer()
+        with ChangelogEditor(self.path) as editor:
+            previous = editor[0]
+            new_version = Version(version)
+            if (
+                not new_version.debian_revision
+                and previous.version.debian_revision
+            ):
+                new_version.debian_revision = "1"
+            editor.changelog.new_block(
+                package=previous.package,
+                version=new_version,
+                distributions="UNRELEASED",
+                urgency="medium",
+                changes=[""] + ["  * %s" % change for change in changes] + [""],
+                author="%s <%s>" % maintainer,
+                date=format_date(timestamp),
+            )
+
+
+CHANGELOG_CLSES = [
+    KeepAChangelogFile,
+    DebianChangelogFile,
+    NewsFile,
+]
+
+
+def find_changelog(directory: str) -> ChangelogFile:
+    """Find the changelog file for the project in directory."""
+    for cls in CHANGELOG_CLSES:
+        changelog = cls.probe(directory)
+        if changelog is not None:
+            return changelog
+    raise NoChangelogFound(directory)
+
+
+def update_changelog(
+    directory: str,
+    version: str,
+    changes: List[str],
+    date: Optional[_date] = None,
+) -> str:
+    """Add a release entry to the changelog of the project in directory.
+
+    Returns: path of the updated changelog file
+    """
+    changelog = find_changelog(directory)
+    logging.info("Adding entry for %s to %s", version, changelog.path)
+    changelog.add_release(version, changes, date=date)
+    return changelog.path
@@ -0,0 +1,121 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Share compiler caches (ccache, sccache) between builds.
+
+The cache directories live on the host, so that they survive the sessions
+that use them.
+"""
+
+import logging
+import os
+from typing import Dict, List, Optional
+
+from .requirements import BinaryRequirement
+from .session import Session, default_cache_dir, which
+
+
+class CompilerCache(object):
+
+    name: str
+
+    # Directory inside the session to use for the cache
+    target: str
+
+    def env(self, session: Session, cache_dir: str) -> Dict[str, str]:
+        """Environment variables that make builds use this cache."""
+        raise NotImplementedError(self.env)
+
+
+class Ccache(CompilerCache):
+
+    name = "ccache"
+    target = "/var/cache/ognibuild/ccache"
+
+    def env(self, session, cache_dir):
+        path = session.check_output(
+            ["sh", "-c", "echo $PATH"], cwd="/").decode().strip()
+        return {
+            "CCACHE_DIR": cache_dir,
+            # Picked up by autotools and plain make, via the cc/gcc symlinks
+            "PATH": "/usr/lib/ccache:" + path,
+            "CMAKE_C_COMPILER_LAUNCHER": "ccache",
+            "CMAKE_CXX_COMPILER_LAUNCHER": "ccache",
+        }
+
+
+class Sccache(CompilerCache):
+
+    name = "sccache"
+    target = "/var/cache/ognibuild/sccache"
+
+    def env(self, session, cache_dir):
+        return {
+            "SCCACHE_DIR": cache_dir,
+            "RUSTC_WRAPPER": "sccache",
+        }
+
+
+COMPILER_CACHES = [Ccache(), Sccache()]
+
+
+def enable_compiler_caches(
+    session: Session,
+    resolver=None,
+    cache_dir: Optional[str] = None,
+    names: Optional[List[str]] = None,
+) -> List[str]:
+    """Make the session use shared compiler caches.
+
+    This should be called before anything is built in the session.
+
+    Args:
+      session: Session to enable the caches in
+      resolver: Resolver to install missing cache tools with; if not
+        specified, caches that are not installed are skipped
+      cache_dir: Host directory to store caches in
+      names: Names of the caches to enable (default: all)
+    Returns: names of the caches that were enabled
+    """
+    if cache_dir is None:
+        cache_dir = default_cache_dir()
+    enabled = []
+    for cache in COMPILER_CACHES:
+        if names is not None and cache.name not in names:
+            continue
+        if which(session, cache.name) is None:
+            if resolver is None:
+                logging.debug("%s is not installed, not enabling it.", cache.name)
+                continue
+            try:
+                resolver.install([BinaryRequirement(cache.name)])
+            except Exception as e:
+                logging.warning("Unable to install %s: %s", cache.name, e)
+                continue
+        source = os.path.join(cache_dir, cache.name)
+        os.makedirs(source, exist_ok=True)
+        target = session.share_host_directory(source, cache.target)
+        if target is None:
+            logging.warning(
+                "Unable to share %s with %r; not enabling %s.",
+                source, session, cache.name)
+            continue
+        for key, value in cache.env(session, target).items():
+            session.setenv(key, value)
+        logging.info("Using %s in %s", cache.name, source)
+        enabled.append(cache.name)
+    return enabled
@@ -0,0 +1,208 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Generate a Containerfile (Dockerfile) for building a project."""
+
+import json
+import logging
+import shlex
+from typing import List, Optional
+
+from .requirements import BinaryRequirement
+
+
+DEFAULT_BASE_IMAGE = "debian:sid"
+
+
+# Build commands per build system; anything not listed here falls back to
+# running "ogni build" inside the container.
+BUILD_COMMANDS = {
+    "setup.py": [["python3", "setup.py", "build"]],
+    "cargo": [["cargo", "build"]],
+    "golang": [["go", "build"]],
+    "meson": [["meson", "setup", "build"], ["ninja", "-C", "build"]],
+    "cmake": [["cmake", ".", "-Bbuild"], ["cmake", "--build", "build"]],
+    "maven": [["mvn", "compile"]],
+    "gradle": [["gradle", "build"]],
+    "npm": [["npm", "run", "build"]],
+    "make": [["make"]],
+    "autoconf": [["autoreconf", "-fi"], ["./configure"], ["make"]],
+    "automake": [["autoreconf", "-fi"], ["./configure"], ["make"]],
+    "makefile.pl": [["perl", "Makefile.PL"], ["make"]],
+    "perl-build-tiny": [["perl", "Build.PL"], ["./Build", "build"]],
+    "bazel": [["bazel", "build", "//..."]],
+    "stack": [["stack", "build"]],
+    "poetry": [["poetry", "build"]],
+    "hatch": [["hatch", "build"]],
+    "pdm": [["pdm", "build"]],
+    "maturin": [["maturin", "build"]],
+    "dotnet": [["dotnet", "build"]],
+    "nimble": [["nimble", "build", "-y"]],
+    "please": [["plz", "build", "//..."]],
+}
+
+# Requirements that the build tools for each build system need.
+BASE_REQUIREMENTS = {
+    "setup.py": [BinaryRequirement("python3")],
+    "cargo": [BinaryRequirement("cargo")],
+    "golang": [BinaryRequirement("go")],
+    "meson": [BinaryRequirement("meson"), BinaryRequirement("ninja")],
+    "cmake": [BinaryRequirement("cmake"), BinaryRequirement("make")],
+    "maven": [BinaryRequirement("mvn")],
+    "gradle": [BinaryRequirement("gradle")],
+    "npm": [BinaryRequirement("npm")],
+    "make": [BinaryRequirement("make")],
+    "autoconf": [BinaryRequirement("autoreconf"), BinaryRequirement("make")],
+    "automake": [
+        BinaryRequirement("autoreconf"),
+        BinaryRequirement("automake"),
+        BinaryRequirement("make"),
+    ],
+    "makefile.pl": [BinaryRequirement("perl"), BinaryRequirement("make")],
+    "perl-build-tiny": [BinaryRequirement("perl")],
+    "bazel": [BinaryRequirement("bazel")],
+    "stack": [BinaryRequirement("stack")],
+    "poetry": [BinaryRequirement("poetry")],
+    "hatch": [BinaryRequirement("hatch")],
+    "pdm": [BinaryRequirement("pdm")],
+    "maturin": [BinaryRequirement("maturin"), BinaryRequirement("cargo")],
+    "dotnet": [BinaryRequirement("dotnet")],
+    "nimble": [BinaryRequirement("nimble")],
+    "please": [BinaryRequirement("plz")],
+    "conda": [BinaryRequirement("conda"), BinaryRequirement("conda-build")],
+}
+
+
+def _wrapper_script(buildsystem) -> Optional[str]:
+    """Find the wrapper script that a build system runs its tool through.
+
+    Some projects ship a script (e.g. ./gradlew or ./pleasew) that fetches
+    the right version of the build tool and runs it.
+    """
+    executable = getattr(buildsystem, "executable", None)
+    if executable is not None and executable.startswith("./"):
+        return executable
+    return None
+
+
+def build_commands(buildsystem) -> List[List[str]]:
+    """Find the commands that build a project in a container."""
+    commands = BUILD_COMMANDS[buildsystem.name]
+    wrapper = _wrapper_script(buildsystem)
+    if wrapper is not None:
+        commands = [[wrapper] + argv[1:] for argv in commands]
+    return commands
+
+
+def base_requirements(buildsystem) -> List[BinaryRequirement]:
+    """Find the requirements of the tools that build a project."""
+    reqs = list(BASE_REQUIREMENTS.get(buildsystem.name, []))
+    if _wrapper_script(buildsystem) is not None:
+        # The wrapper script provides the build tool itself
+        tools = set(argv[0] for argv in BUILD_COMMANDS[buildsystem.name])
+        reqs = [req for req in reqs if req.binary_name not in tools]
+    return reqs
+
+
+def _shell_command(command):
+    if isinstance(command, list):
+        return shlex.join(command)
+    return command
+
+
+# Package manager commands that prompt for confirmation unless told not to;
+# a container build has no terminal to answer on.
+NONINTERACTIVE_COMMANDS = {
+    ("apt", "satisfy"): "-y",
+    ("apt", "install"): "-y",
+    ("apt-get", "install"): "-y",
+    ("dnf", "install"): "-y",
+}
+
+
+def _noninteractive(command):
+    if not isinstance(command, list):
+        return command
+    offset = 1 if command[:1] == ["sudo"] else 0
+    flag = NONINTERACTIVE_COMMANDS.get(tuple(command[offset:offset + 2]))
+    if flag is None or flag in command:
+        return command
+    return command[:offset + 2] + [flag] + command[offset + 2:]
+
+
+def install_commands(resolver, requirements) -> List[str]:
+    """Determine the shell commands needed to install requirements."""
+    ret = []
+    for explanation in resolver.explain(requirements):
+        if isinstance(explanation, tuple):
+            command = explanation[0]
+        else:
+            command = explanation
+        if isinstance(command, list) and command and isinstance(command[0], list):
+            commands = command
+        else:
+            commands = [command]
+        for c in commands:
+            line = _shell_command(_noninteractive(c))
+            if line not in ret:
+                ret.append(line)
+    return ret
+
+
+def generate_containerfile(
+    buildsystem,
+    install_commands: List[str],
+    base_image: Optional[str] = None,
+    workdir: str = "/src",
+) -> str:
+    if base_image is None:
+        base_image = DEFAULT_BASE_IMAGE
+    lines = ["# Generated by ognibuild", "FROM %s" % base_image]
+    if base_image.startswith("debian:") or base_image.startswith("ubuntu:"):
+        lines.append("RUN apt-get update")
+    for command in install_commands:
+        if command.startswith("sudo "):
+            command = command[len("sudo "):]
+        lines.append("RUN %s" % command)
+    lines.append("COPY . %s" % workdir)
+    lines.append("WORKDIR %s" % workdir)
+    try:
+        commands = build_commands(buildsystem)
+    except KeyError:
+        logging.warning(
+            "No known build commands for %s, using ogni", buildsystem.name)
+        commands = [["ogni", "build"]]
+    for argv in commands:
+        lines.append("RUN %s" % json.dumps(argv))
+    return "\n".join(lines) + "\n"
+
+
+def containerfile_for_project(
+    session, resolver, buildsystems, fixers=None, base_image=None
+) -> str:
+    from .envfile import declared_requirements
+
+    if not buildsystems:
+        from .buildsystem import NoBuildToolsFound
+
+        raise NoBuildToolsFound()
+    buildsystem = buildsystems[0]
+    reqs = base_requirements(buildsystem)
+    reqs.extend(
+        declared_requirements(session, buildsystems, ["core", "build"], fixers=fixers))
+    return generate_containerfile(
+        buildsystem, install_commands(resolver, reqs), base_image=base_image)
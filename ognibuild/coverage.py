@@ -0,0 +1,199 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Collect code coverage while running a project's testsuite.
+
+Each build system produces an lcov tracefile, using whatever tool is
+common for it (cargo-llvm-cov, coverage.py, nyc or gcov with lcov). The
+tracefile is then parsed into a CoverageReport, which can be summarized
+or exported as lcov or Cobertura XML.
+"""
+
+import logging
+import time
+from typing import Dict, Optional
+
+from .buildsystem import NoBuildToolsFound
+from .session.metrics import metrics_phase
+
+
+EXPORT_FORMATS = ["lcov", "cobertura"]
+
+
+class CoverageReport(object):
+    """Line coverage of a project.
+
+    Attributes:
+      files: Dictionary mapping file names to dictionaries mapping line
+        numbers to hit counts
+    """
+
+    def __init__(self, files: Optional[Dict[str, Dict[int, int]]] = None):
+        self.files = dict(files or {})
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, sorted(self.files))
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.files == other.files
+
+    @property
+    def lines_total(self) -> int:
+        return sum(len(lines) for lines in self.files.values())
+
+    @property
+    def lines_covered(self) -> int:
+        return sum(
+            len([hits for hits in lines.values() if hits])
+            for lines in self.files.values())
+
+    @property
+    def percent(self) -> Optional[float]:
+        if not self.lines_total:
+            return None
+        return 100.0 * self.lines_covered / self.lines_total
+
+    def json(self):
+        return {
+            "lines_total": self.lines_total,
+            "lines_covered": self.lines_covered,
+            "percent": self.percent,
+            "files": {
+                filename: {
+                    "lines_total": len(lines),
+                    "lines_covered": len([h for h in lines.values() if h]),
+                }
+                for filename, lines in sorted(self.files.items())},
+        }
+
+    def __str__(self):
+        lines = []
+        width = max([len(filename) for filename in self.files] + [5])
+        for filename, file_lines in sorted(self.files.items()):
+            covered = len([h for h in file_lines.values() if h])
+            lines.append("%-*s %5d/%-5d %s" % (
+                width, filename, covered, len(file_lines),
+                _format_percent(covered, len(file_lines))))
+        lines.append("%-*s %5d/%-5d %s" % (
+            width, "TOTAL", self.lines_covered, self.lines_total,
+            _format_percent(self.lines_covered, self.lines_total)))
+        return "\n".join(lines) + "\n"
+
+
+def _format_percent(covered, total):
+    if not total:
+        return "-"
+    return "%.1f%%" % (100.0 * covered / total)
+
+
+def parse_lcov(f) -> CoverageReport:
+    """Parse an lcov tracefile."""
+    report = CoverageReport()
+    lines: Optional[Dict[int, int]] = None
+    for line in f:
+        line = line.strip()
+        if line.startswith("SF:"):
+            lines = report.files.setdefault(line[3:], {})
+        elif line.startswith("DA:"):
+            if lines is None:
+                logging.warning("Ignoring line data outside of a file record")
+                continue
+            fields = line[3:].split(",")
+            lineno, hits = int(fields[0]), int(fields[1])
+            lines[lineno] = lines.get(lineno, 0) + hits
+        elif line == "end_of_record":
+            lines = None
+    return report
+
+
+def write_lcov(report: CoverageReport, f) -> None:
+    for filename, lines in sorted(report.files.items()):
+        f.write("SF:%s\n" % filename)
+        for lineno, hits in sorted(lines.items()):
+            f.write("DA:%d,%d\n" % (lineno, hits))
+        f.write("LH:%d\n" % len([h for h in lines.values() if h]))
+        f.write("LF:%d\n" % len(lines))
+        f.write("end_of_record\n")
+
+
+def write_cobertura(report: CoverageReport, f) -> None:
+    """Write a report as Cobertura XML."""
+    import xml.etree.ElementTree as ET
+
+    def line_rate(covered, total):
+        return "%.4f" % (float(covered) / total if total else 1.0)
+
+    root = ET.Element("coverage", {
+        "line-rate": line_rate(report.lines_covered, report.lines_total),
+        "lines-covered": str(report.lines_covered),
+        "lines-valid": str(report.lines_total),
+        "branch-rate": "0",
+        "version": "ognibuild",
+        "timestamp": str(int(time.time())),
+    })
+    ET.SubElement(root, "sources")
+    package = ET.SubElement(ET.SubElement(root, "packages"), "package", {
+        "name": "",
+        "line-rate": line_rate(report.lines_covered, report.lines_total),
+        "branch-rate": "0",
+    })
+    classes = ET.SubElement(package, "classes")
+    for filename, lines in sorted(report.files.items()):
+        covered = len([h for h in lines.values() if h])
+        klass = ET.SubElement(classes, "class", {
+            "name": filename,
+            "filename": filename,
+            "line-rate": line_rate(covered, len(lines)),
+            "branch-rate": "0",
+        })
+        ET.SubElement(klass, "methods")
+        lines_el = ET.SubElement(klass, "lines")
+        for lineno, hits in sorted(lines.items()):
+            ET.SubElement(lines_el, "line", {
+                "number": str(lineno), "hits": str(hits)})
+    f.write(ET.tostring(root, encoding="unicode"))
+    f.write("\n")
+
+
+def export_coverage(report: CoverageReport, f, format: str) -> None:
+    if format == "lcov":
+        write_lcov(report, f)
+    elif format == "cobertura":
+        write_cobertura(report, f)
+    else:
+        raise ValueError("unknown coverage format %r" % format)
+
+
+def run_coverage(session, buildsystems, resolver, fixers) -> CoverageReport:
+    # Some things want to write to the user's home directory,
+    # e.g. pip caches in ~/.cache
+    session.create_home()
+
+    if not buildsystems:
+        raise NoBuildToolsFound()
+
+    for buildsystem in buildsystems:
+        with metrics_phase(session, "coverage"):
+            try:
+                path = buildsystem.coverage(session, resolver, fixers)
+            except NotImplementedError:
+                logging.info("Unable to collect coverage for %s", buildsystem)
+                continue
+        with open(session.external_path(path), "r") as f:
+            return parse_lcov(f)
+
+    raise NotImplementedError(run_coverage)
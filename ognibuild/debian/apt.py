@@ -17,6 +17,8 @@
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
 import logging
+import re
+import time
 from typing import List, Optional
 
 import os
@@ -31,20 +33,84 @@ from .file_search import (
     get_apt_contents_file_searcher,
     GENERATED_FILE_SEARCHER,
     get_packages_for_paths,
+    get_packages_for_binary,
 )
 
 
+# Number of times to retry apt if it fails for transient reasons
+DEFAULT_APT_RETRIES = 5
+
+# Delay before the first retry, in seconds; doubled for each further retry
+DEFAULT_APT_RETRY_DELAY = 5.0
+
+
+APT_LOCK_FAILURES = [
+    re.compile(r"E: Could not get lock (.*)"),
+    re.compile(r"E: Unable to acquire the dpkg frontend lock (.*)"),
+    re.compile(r"E: Unable to lock (?:directory|the administration directory) (.*)"),
+]
+
+APT_TRANSIENT_FAILURES = [
+    re.compile(r"(?:W|E): Failed to fetch .*(?:Temporary failure resolving|"
+               r"Connection timed out|Connection failed|Could not connect|"
+               r"Hash Sum mismatch|50[234] |Connection reset by peer).*"),
+    re.compile(r"(?:W|E): Some index files failed to download.*"),
+    re.compile(r"E: Unable to fetch some archives.*"),
+]
+
+
+def find_transient_apt_failure(lines: List[str]) -> Optional[str]:
+    """Check whether apt failed for a reason that might go away on retry.
+
+    Returns: "lock" for lock contention, "network" for mirror failures,
+        None otherwise
+    """
+    for line in lines:
+        line = line.rstrip("\n")
+        if any(r.match(line) for r in APT_LOCK_FAILURES):
+            return "lock"
+    for line in lines:
+        line = line.rstrip("\n")
+        if any(r.match(line) for r in APT_TRANSIENT_FAILURES):
+            return "network"
+    return None
+
+
 def run_apt(
-    session: Session, args: List[str], prefix: Optional[List[str]] = None
+    session: Session, args: List[str], prefix: Optional[List[str]] = None,
+    retries: int = DEFAULT_APT_RETRIES,
+    retry_delay: float = DEFAULT_APT_RETRY_DELAY,
 ) -> None:
-    """Run apt."""
+    """Run apt.
+
+    If apt fails because of lock contention (e.g. with another session on the
+    same host) or a transient mirror failure, it is retried up to retries
+    times.
+    """
     if prefix is None:
         prefix = []
-    args = prefix = ["apt", "-y"] + args
-    logging.info('apt: running %r', args)
-    retcode, lines = run_with_tee(session, args, cwd="/", user="root")
-    if retcode == 0:
-        return
+    update = args == ["update"]
+    args = prefix + ["apt", "-y"] + args
+    attempt = 0
+    while True:
+        logging.info('apt: running %r', args)
+        retcode, lines = run_with_tee(session, args, cwd="/", user="root")
+        if retcode == 0:
+            return
+        kind = find_transient_apt_failure(lines)
+        if kind is None or attempt >= retries:
+            break
+        delay = retry_delay * (2 ** attempt)
+        attempt += 1
+        logging.warning(
+            "apt failed (%s), retrying in %d seconds (attempt %d of %d)",
+            "lock contention" if kind == "lock" else "transient network failure",
+            delay, attempt, retries)
+        time.sleep(delay)
+        if kind == "network" and not update:
+            # The package lists may refer to files no longer on the mirror
+            run_with_tee(
+                session, prefix + ["apt", "-y", "update"], cwd="/", user="root")
     match, error = find_apt_get_failure(lines)
     if error is not None:
         raise DetailedFailure(retcode, args, error)
@@ -62,6 +128,8 @@ class AptManager(object):
         self.session = session
         self._apt_cache = None
         self._searchers = None
+        # If set, file searches are recorded here (see AptResolver.trace)
+        self.search_log: Optional[List[dict]] = None
         if prefix is None:
             prefix = []
         self.prefix = prefix
@@ -98,9 +166,21 @@ class AptManager(object):
 
     def get_packages_for_paths(self, paths, regex=False, case_insensitive=False):
         logging.debug("Searching for packages containing %r", paths)
-        return get_packages_for_paths(
+        ret = get_packages_for_paths(
             paths, self.searchers(), regex=regex, case_insensitive=case_insensitive
         )
+        if self.search_log is not None:
+            self.search_log.append(
+                {"query": list(paths), "regex": regex, "packages": ret})
+        return ret
+
+    def get_packages_for_binary(self, name):
+        logging.debug("Searching for packages providing binary %r", name)
+        ret = get_packages_for_binary(name, self.searchers())
+        if self.search_log is not None:
+            self.search_log.append(
+                {"query": ["binary %s" % name], "regex": False, "packages": ret})
+        return ret
 
     def missing(self, packages):
         root = getattr(self.session, "location", "/")
@@ -124,6 +204,19 @@ class AptManager(object):
         if packages:
             run_apt(self.session, ["install"] + packages, prefix=self.prefix)
 
+    def add_architecture(self, arch: str) -> None:
+        """Make packages for a foreign architecture installable."""
+        native = self.session.check_output(
+            ["dpkg", "--print-architecture"]).decode().strip()
+        foreign = self.session.check_output(
+            ["dpkg", "--print-foreign-architectures"]).decode().split()
+        if arch == native or arch in foreign:
+            return
+        logging.info("Adding foreign architecture %s", arch)
+        self.session.check_call(
+            ["dpkg", "--add-architecture", arch], cwd="/", user="root")
+        run_apt(self.session, ["update"], prefix=self.prefix)
+
     def satisfy(self, deps: List[str]) -> None:
         run_apt(self.session, ["satisfy"] + deps, prefix=self.prefix)
 
@@ -23,6 +23,7 @@ __all__ = [
     "UnidentifiedDebianBuildError",
 ]
 
+import contextlib
 from datetime import datetime
 from debmutate.changelog import ChangelogEditor
 import logging
@@ -31,6 +32,7 @@ import re
 import shlex
 import subprocess
 import sys
+import tempfile
 
 from debian.changelog import Changelog
 from debmutate.changelog import get_maintainer
@@ -170,6 +172,29 @@ def get_latest_changelog_entry(local_tree, subpath=""):
         return cl[0]
 
 
+def deb_build_options(current, jobs=None, profile=None):
+    """Adjust a DEB_BUILD_OPTIONS value.
+
+    Args:
+      current: Existing value of DEB_BUILD_OPTIONS
+      jobs: Optional number of jobs to run in parallel
+      profile: Optional build profile ("debug" or "release")
+    Returns: new value for DEB_BUILD_OPTIONS
+    """
+    options = current.split()
+    if jobs is not None:
+        options = [
+            option for option in options if not option.startswith("parallel=")]
+        options.append("parallel=%d" % jobs)
+    if profile == "debug":
+        options.extend(
+            [option for option in ["noopt", "nostrip"] if option not in options])
+    elif profile == "release":
+        options = [
+            option for option in options if option not in ("noopt", "nostrip")]
+    return " ".join(options)
+
+
 def build(
     local_tree,
     outf,
@@ -179,9 +204,32 @@ def build(
     subpath="",
     source_date_epoch=None,
     extra_repositories=None,
+    forwarder=None,
+    jobs=None,
+    host_arch=None,
+    profile=None,
 ):
+    """Build a package, writing the build log to outf.
+
+    Args:
+      forwarder: Optional OutputForwarder to also forward the build log to
+        as it is written
+      jobs: Optional number of jobs to run in parallel, passed on to
+        dpkg-buildpackage through DEB_BUILD_OPTIONS
+      host_arch: Optional Debian architecture to cross-build for; for
+        Meson projects built outside of sbuild, a Meson cross file is
+        generated and passed on to debhelper
+      profile: Optional build profile ("debug" or "release"); debug
+        builds are done without optimization and stripping
+    """
     for repo in extra_repositories or []:
         build_command += " --extra-repository=" + shlex.quote(repo)
+    if host_arch is not None:
+        if shlex.split(build_command)[0] == "sbuild":
+            build_command += " --host=" + shlex.quote(host_arch)
+        else:
+            # dpkg-buildpackage and debuild
+            build_command += " -a" + shlex.quote(host_arch)
     args = [
         sys.executable,
         "-m",
@@ -199,12 +247,52 @@ def build(
         env["DISTRIBUTION"] = distribution
     if source_date_epoch is not None:
         env["SOURCE_DATE_EPOCH"] = "%d" % source_date_epoch
+    if jobs is not None or profile is not None:
+        env["DEB_BUILD_OPTIONS"] = deb_build_options(
+            env.get("DEB_BUILD_OPTIONS", ""), jobs=jobs, profile=profile)
     logging.info("Building debian packages, running %r.", build_command)
-    try:
-        subprocess.check_call(
-            args, cwd=local_tree.abspath(subpath), stdout=outf, stderr=outf, env=env
-        )
-    except subprocess.CalledProcessError:
+    with contextlib.ExitStack() as es:
+        if (host_arch is not None
+                and shlex.split(build_command)[0] != "sbuild"
+                and os.path.exists(local_tree.abspath(
+                    os.path.join(subpath, "meson.build")))):
+            # Inside sbuild, debhelper generates a cross file itself
+            td = es.enter_context(tempfile.TemporaryDirectory())
+            env["DH_MESON_CROSS_FILE"] = write_meson_cross_file(td, host_arch)
+        _run_builder(args, local_tree.abspath(subpath), outf, env, forwarder)
+
+
+def write_meson_cross_file(directory, host_arch):
+    """Write a Meson cross file for a Debian architecture.
+
+    Returns: path to the cross file
+    """
+    from ..buildsystem import Target
+
+    path = os.path.join(directory, "meson-cross-%s.ini" % host_arch)
+    with open(path, "w") as f:
+        f.write(Target.from_debian_arch(host_arch).meson_cross_file())
+    return path
+
+
+def _run_builder(args, cwd, outf, env, forwarder=None):
+    if forwarder is None:
+        try:
+            subprocess.check_call(
+                args, cwd=cwd, stdout=outf, stderr=outf, env=env)
+        except subprocess.CalledProcessError:
+            raise BuildFailedError()
+        return
+    p = subprocess.Popen(
+        args, cwd=cwd, stdout=subprocess.PIPE,
+        stderr=subprocess.STDOUT, env=env)
+    with p.stdout:
+        for line in iter(p.stdout.readline, b""):
+            # The build log itself is kept as is, since it is parsed later
+            outf.write(line.decode("utf-8", "surrogateescape"))
+            outf.flush()
+            forwarder.forward("output", line)
+    if p.wait() != 0:
         raise BuildFailedError()
 
 
@@ -215,7 +303,11 @@ def build_once(
     build_command,
     subpath="",
     source_date_epoch=None,
-    extra_repositories=None
+    extra_repositories=None,
+    forwarder=None,
+    jobs=None,
+    host_arch=None,
+    profile=None,
 ):
     build_log_path = os.path.join(output_directory, "build.log")
     logging.debug("Writing build log to %s", build_log_path)
@@ -230,6 +322,10 @@ def build_once(
                 subpath=subpath,
                 source_date_epoch=source_date_epoch,
                 extra_repositories=extra_repositories,
+                forwarder=forwarder,
+                jobs=jobs,
+                host_arch=host_arch,
+                profile=profile,
             )
     except BuildFailedError as e:
         with open(build_log_path, "rb") as f:
@@ -270,7 +366,11 @@ def attempt_build(
     subpath="",
     source_date_epoch=None,
     run_gbp_dch=False,
-    extra_repositories=None
+    extra_repositories=None,
+    forwarder=None,
+    jobs=None,
+    host_arch=None,
+    profile=None,
 ):
     """Attempt a build, with a custom distribution set.
 
@@ -299,4 +399,8 @@ def attempt_build(
         subpath,
         source_date_epoch=source_date_epoch,
         extra_repositories=extra_repositories,
+        forwarder=forwarder,
+        jobs=jobs,
+        host_arch=host_arch,
+        profile=profile,
     )
@@ -15,10 +15,12 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
-"""Tie breaking by build deps."""
+"""Tie breaking by build deps, and exporting resolved build deps."""
 
 
 import logging
+import os
+from typing import List, Tuple
 
 
 class BuildDependencyTieBreaker(object):
@@ -72,6 +74,87 @@ class BuildDependencyTieBreaker(object):
         return top[0]
 
 
+class BuildDependsPlan(object):
+    """Debian dependencies that were added while fixing a build.
+
+    This allows exporting the dependencies that were necessary as a
+    minimal change to debian/control, e.g. to submit upstream.
+    """
+
+    def __init__(self):
+        self.entries: List[Tuple[Tuple[str, ...], object]] = []
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.entries)
+
+    def add(self, phase, requirement) -> None:
+        """Record that a dependency was added.
+
+        Args:
+          phase: Phase the dependency was needed for, e.g. ("build", )
+          requirement: The AptRequirement that was added
+        """
+        self.entries.append((tuple(phase), requirement))
+
+    def build_depends(self) -> str:
+        """Combine the dependencies for the build phase into a relation string.
+
+        Duplicate relations are merged, keeping the strictest version
+        constraint.
+        """
+        from debian.deb822 import PkgRelation
+        from debmutate.control import ensure_relation
+
+        ret = ""
+        for phase, requirement in self.entries:
+            if phase[0] != "build":
+                continue
+            for rel in requirement.relations:
+                ret = ensure_relation(ret, PkgRelation.str([rel]))
+        return ret
+
+    def apply(self, control_path: str) -> bool:
+        """Add the build dependencies to a debian/control file.
+
+        Relations that are already satisfied by the existing Build-Depends
+        are left alone.
+
+        Returns: whether the file was changed
+        """
+        from debian.deb822 import PkgRelation
+        from debmutate.control import ControlEditor, ensure_relation
+
+        with ControlEditor(path=control_path) as updater:
+            for rel in PkgRelation.parse_relations(self.build_depends()):
+                updater.source["Build-Depends"] = ensure_relation(
+                    updater.source.get("Build-Depends", ""),
+                    PkgRelation.str([rel]))
+        return updater.changed
+
+    def patch(self, control_text: str, path: str = "debian/control") -> str:
+        """Generate a unified diff that adds the build dependencies.
+
+        Args:
+          control_text: Contents of the debian/control file to patch
+          path: Path to use in the diff headers
+        Returns: the diff, or an empty string if nothing needs to change
+        """
+        import difflib
+        import tempfile
+
+        with tempfile.TemporaryDirectory() as td:
+            control_path = os.path.join(td, "control")
+            with open(control_path, "w") as f:
+                f.write(control_text)
+            if not self.apply(control_path):
+                return ""
+            with open(control_path, "r") as f:
+                new_text = f.read()
+        return "".join(difflib.unified_diff(
+            control_text.splitlines(True), new_text.splitlines(True),
+            "a/" + path, "b/" + path))
+
+
 if __name__ == "__main__":
     import argparse
     from ..resolver.apt import AptRequirement
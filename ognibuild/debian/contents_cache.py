@@ -0,0 +1,296 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""User cache for apt Contents files.
+
+Contents files are large, so rather than downloading them in full every
+time, the cached copy is brought up to date using the incremental diffs
+("pdiffs") that archives publish alongside them. If that is not possible,
+the full file is downloaded, from the by-hash location if the archive
+supports it.
+"""
+
+import gzip
+import hashlib
+import logging
+import os
+import re
+from typing import Dict, Iterable, Iterator, List, Optional, Tuple
+
+from debian.deb822 import Deb822
+
+
+# Size of the chunks that files are hashed in
+CHUNK_SIZE = 1024 * 1024
+
+
+class ContentsReleaseInfo(object):
+    """What the Release file says about a Contents file.
+
+    Attributes:
+      sha256: SHA256 of the uncompressed file
+      by_hash: Dictionary mapping compression extensions (e.g. ".gz") to
+        by-hash URLs, if the archive supports by-hash
+    """
+
+    def __init__(self, sha256: Optional[str], by_hash: Dict[str, str]):
+        self.sha256 = sha256
+        self.by_hash = by_hash
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.sha256, self.by_hash)
+
+
+class PatchFailed(Exception):
+    """Applying a pdiff failed."""
+
+
+_ED_COMMAND_RE = re.compile(rb"^(\d+)(?:,(\d+))?([acd])$")
+
+
+def _parse_ed_patch(patch: bytes) -> List[Tuple[int, int, bytes, List[bytes]]]:
+    commands = []
+    patch_lines = patch.splitlines(True)
+    i = 0
+    while i < len(patch_lines):
+        command = patch_lines[i].rstrip(b"\n")
+        i += 1
+        m = _ED_COMMAND_RE.match(command)
+        if not m:
+            raise PatchFailed("invalid ed command %r" % command)
+        start = int(m.group(1))
+        end = int(m.group(2)) if m.group(2) else start
+        action = m.group(3)
+        new = []
+        if action in (b"a", b"c"):
+            while True:
+                if i >= len(patch_lines):
+                    raise PatchFailed("unterminated ed insertion")
+                line = patch_lines[i]
+                i += 1
+                if line.rstrip(b"\n") == b".":
+                    break
+                new.append(line)
+        commands.append((start, end, action, new))
+    return commands
+
+
+def apply_ed_patch(lines: Iterable[bytes], patch: bytes) -> Iterator[bytes]:
+    """Apply an ed script, as produced by diff --ed, to a stream of lines.
+
+    Commands in diff --ed output are ordered from the end of the file to the
+    start, so that line numbers remain valid while applying them. That also
+    means all line numbers refer to the original file, so the commands can
+    be applied in reverse in a single pass, without holding the whole file
+    in memory.
+    """
+    it = iter(lines)
+    # Number of lines of the original file that have been consumed
+    consumed = 0
+
+    def take(lineno):
+        try:
+            return next(it)
+        except StopIteration:
+            raise PatchFailed("line %d out of range" % lineno)
+
+    for start, end, action, new in reversed(_parse_ed_patch(patch)):
+        if action == b"a":
+            first, last = start + 1, start
+        else:
+            first, last = start, end
+        if first <= consumed:
+            raise PatchFailed("ed commands are not in reverse order")
+        while consumed < first - 1:
+            consumed += 1
+            yield take(consumed)
+        while consumed < last:
+            consumed += 1
+            take(consumed)
+        yield from new
+    yield from it
+
+
+def _sha256(f) -> str:
+    h = hashlib.sha256()
+    for chunk in iter(lambda: f.read(CHUNK_SIZE), b""):
+        h.update(chunk)
+    return h.hexdigest()
+
+
+def _parse_hash_list(text: str) -> List[List[str]]:
+    return [line.split() for line in text.strip().splitlines() if line.strip()]
+
+
+class ContentsCache(object):
+    """Cache of Contents files, in the user's cache directory.
+
+    Args:
+      path: Directory to store the cache in
+      load_url: Function to download a URL; returns a file-like object with
+        the decompressed contents, or raises FileNotFoundError
+    """
+
+    def __init__(self, path: Optional[str] = None, load_url=None):
+        if path is None:
+            from ..session import default_cache_dir
+
+            path = os.path.join(default_cache_dir(), "contents")
+        if load_url is None:
+            from .file_search import load_direct_url
+
+            load_url = load_direct_url
+        self.path = path
+        self._load_url = load_url
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    def _cache_path(self, url: str) -> str:
+        import apt_pkg
+
+        return os.path.join(self.path, apt_pkg.uri_to_filename(url) + ".gz")
+
+    def _cached_sha256(self, url: str) -> Optional[str]:
+        try:
+            with gzip.open(self._cache_path(url), "rb") as f:
+                return _sha256(f)
+        except FileNotFoundError:
+            return None
+
+    def _write_new(self, url: str, lines: Iterable[bytes]) -> Tuple[str, str]:
+        """Write lines to a new cache file, next to the existing one.
+
+        Returns: tuple with path of the new file and its SHA256
+        """
+        os.makedirs(self.path, exist_ok=True)
+        path = self._cache_path(url) + ".new"
+        h = hashlib.sha256()
+        try:
+            with gzip.open(path, "wb", compresslevel=1) as f:
+                for line in lines:
+                    h.update(line)
+                    f.write(line)
+        except BaseException:
+            os.unlink(path)
+            raise
+        return path, h.hexdigest()
+
+    def _update_with_pdiffs(
+        self, url: str, cached_hash: str, current: str
+    ) -> bool:
+        """Bring a cached file up to date using pdiffs.
+
+        Returns: whether the cached file was updated
+        """
+        try:
+            index = Deb822(self._load_url(url + ".diff/Index").read())
+        except FileNotFoundError:
+            logging.debug("No pdiffs available for %s", url)
+            return False
+        history = _parse_hash_list(index.get("SHA256-History", ""))
+        patch_names = [entry[2] for entry in history]
+        for i, entry in enumerate(history):
+            if entry[0] == cached_hash:
+                break
+        else:
+            logging.debug("Cached copy of %s is too old for pdiffs", url)
+            return False
+        if index.get("X-Patch-Precedence") == "merged":
+            # Each patch takes the file straight to the current version
+            to_apply = [patch_names[i]]
+        else:
+            to_apply = patch_names[i:]
+        patch_hashes = {
+            entry[2]: entry[0]
+            for entry in _parse_hash_list(index.get("SHA256-Patches", ""))}
+        patches = []
+        for name in to_apply:
+            try:
+                patch = self._load_url(url + ".diff/" + name).read()
+            except FileNotFoundError:
+                logging.debug("pdiff %s for %s went missing", name, url)
+                return False
+            if name in patch_hashes and (
+                    hashlib.sha256(patch).hexdigest() != patch_hashes[name]):
+                logging.warning("pdiff %s for %s has unexpected hash", name, url)
+                return False
+            patches.append(patch)
+        with gzip.open(self._cache_path(url), "rb") as f:
+            lines: Iterable[bytes] = f
+            for patch in patches:
+                lines = apply_ed_patch(lines, patch)
+            try:
+                path, new_hash = self._write_new(url, lines)
+            except PatchFailed as e:
+                logging.warning("Unable to apply pdiffs to %s: %s", url, e)
+                return False
+        if new_hash != current:
+            logging.warning("Applying pdiffs to %s gave unexpected result", url)
+            os.unlink(path)
+            return False
+        os.rename(path, self._cache_path(url))
+        logging.info("Updated %s using %d pdiffs", url, len(to_apply))
+        return True
+
+    def _download(self, url: str, info: Optional[ContentsReleaseInfo]):
+        if info is not None:
+            for ext, by_hash_url in info.by_hash.items():
+                from .file_search import _unwrap
+
+                try:
+                    f = self._load_url_exact(by_hash_url)
+                except FileNotFoundError:
+                    continue
+                return _unwrap(f, ext)
+        return self._load_url(url)
+
+    def _load_url_exact(self, url):
+        from urllib.error import HTTPError
+        from urllib.request import urlopen, Request
+        from .. import USER_AGENT
+
+        try:
+            return urlopen(Request(url, headers={"User-Agent": USER_AGENT}))
+        except HTTPError as e:
+            if e.code == 404:
+                raise FileNotFoundError(url)
+            raise
+
+    def load(self, url: str, info: Optional[ContentsReleaseInfo] = None):
+        """Load a Contents file, updating the cache if necessary.
+
+        Returns: file-like object with the uncompressed contents
+        """
+        if info is not None and info.sha256:
+            cached_hash = self._cached_sha256(url)
+            if cached_hash == info.sha256:
+                logging.debug("Using cached %s", url)
+                return gzip.open(self._cache_path(url), "rb")
+            if cached_hash is not None and self._update_with_pdiffs(
+                    url, cached_hash, info.sha256):
+                return gzip.open(self._cache_path(url), "rb")
+        logging.info("Downloading %s", url)
+        path, new_hash = self._write_new(url, self._download(url, info))
+        if info is not None and info.sha256 and new_hash != info.sha256:
+            logging.warning("Downloaded %s does not match the Release file", url)
+            # Use it this once, but don't keep it around
+            f = gzip.open(path, "rb")
+            os.unlink(path)
+            return f
+        os.rename(path, self._cache_path(url))
+        return gzip.open(self._cache_path(url), "rb")
@@ -0,0 +1,113 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Support for DEP-14 repository layouts.
+
+See https://dep-team.pages.debian.net/deps/dep14/ for the specification.
+
+Packaging branches either contain the full upstream source ("merge mode")
+or just the debian/ directory ("overlay mode"). In the latter case, the
+upstream sources have to be combined with the packaging before building.
+"""
+
+import logging
+import os
+from typing import List
+
+from breezy.errors import NotBranchError
+
+from ..vcs import export_vcs_tree
+
+
+DEFAULT_VENDOR = "debian"
+
+
+def packaging_branch_names(vendor: str = DEFAULT_VENDOR) -> List[str]:
+    """Candidate packaging branch names, in order of preference."""
+    return [
+        "%s/latest" % vendor,
+        "%s/unstable" % vendor,
+        "%s/sid" % vendor,
+        "%s/master" % vendor,
+        "%s/main" % vendor,
+    ]
+
+
+UPSTREAM_BRANCH_NAMES = ["upstream/latest", "upstream"]
+
+
+def _find_branch(controldir, names):
+    try:
+        available = controldir.branch_names()
+    except (NotImplementedError, NotBranchError):
+        return None
+    for name in names:
+        if name in available:
+            logging.info("Using DEP-14 branch %s", name)
+            return controldir.open_branch(name=name)
+    return None
+
+
+def find_packaging_branch(controldir, vendor: str = DEFAULT_VENDOR):
+    """Find the DEP-14 packaging branch in a control directory, if any."""
+    return _find_branch(controldir, packaging_branch_names(vendor))
+
+
+def find_upstream_branch(controldir):
+    """Find the DEP-14 upstream branch in a control directory, if any."""
+    return _find_branch(controldir, UPSTREAM_BRANCH_NAMES)
+
+
+def is_packaging_tree(tree, subpath: str = "") -> bool:
+    return tree.has_filename(os.path.join(subpath, "debian", "changelog"))
+
+
+def is_overlay_tree(tree, subpath: str = "") -> bool:
+    """Check whether a packaging tree only contains the debian/ directory."""
+    with tree.lock_read():
+        names = set([
+            os.path.basename(entry[0])
+            for entry in tree.list_files(from_dir=subpath, recursive=False)
+        ])
+    names.discard(".gitignore")
+    return names == set(["debian"])
+
+
+def add_upstream_sources(tree, upstream_tree, subpath: str = "") -> None:
+    """Add the upstream sources to an overlay mode packaging checkout.
+
+    The upstream files are added as unversioned files, so that they are
+    available for building but are not committed along with fixes to the
+    packaging. Files that already exist in the checkout are left alone.
+
+    Args:
+      tree: Working tree with the packaging
+      upstream_tree: Tree with the upstream sources
+      subpath: Path of the package in tree
+    """
+    import shutil
+    import tempfile
+
+    target = tree.abspath(subpath)
+    with tempfile.TemporaryDirectory() as td:
+        export_directory = os.path.join(td, "upstream")
+        export_vcs_tree(upstream_tree, export_directory)
+        for name in os.listdir(export_directory):
+            if name == "debian" or os.path.lexists(os.path.join(target, name)):
+                continue
+            shutil.move(
+                os.path.join(export_directory, name), os.path.join(target, name))
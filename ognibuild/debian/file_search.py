@@ -20,6 +20,7 @@ import apt_pkg
 from datetime import datetime
 from debian.deb822 import Release
 import os
+import posixpath
 import re
 import subprocess
 from typing import Iterator, List
@@ -28,6 +29,39 @@ import logging
 
 from .. import USER_AGENT
 from ..session import Session
+from .contents_cache import ContentsCache, ContentsReleaseInfo
+
+
+# Directories that binaries on the default PATH are installed into
+BIN_PATHS = ["/usr/bin", "/bin", "/usr/sbin", "/sbin", "/usr/games"]
+
+# Regexes matching directories with binaries that are put on the PATH by
+# update-alternatives rather than shipped in BIN_PATHS by the package itself,
+# e.g. /usr/lib/jvm/java-17-openjdk-amd64/bin/jar or /usr/lib/llvm-15/bin/clang
+ALTERNATIVES_BIN_PATHS = [
+    "/usr/lib/jvm/[^/]+/bin",
+    "/usr/lib/[^/]+/bin",
+    "/usr/lib/[^/]+/[^/]+/bin",
+]
+
+# Generic command names that are provided through update-alternatives (with
+# the link created by a maintainer script rather than shipped in the
+# package), and the binaries that can provide them, in order of preference.
+BINARY_ALTERNATIVES = {
+    "cc": ["gcc", "clang"],
+    "c++": ["g++", "clang++"],
+    "c89": ["c89-gcc"],
+    "c99": ["c99-gcc"],
+    "awk": ["gawk", "mawk"],
+    "yacc": ["bison.yacc", "byacc"],
+    "lex": ["flex"],
+    "python": ["python3"],
+    "pager": ["less"],
+    "editor": ["vim.basic", "nano"],
+    "vi": ["vim.basic", "vim.tiny"],
+    "nc": ["nc.openbsd", "nc.traditional"],
+    "rename": ["file-rename"],
+}
 
 
 class FileSearcher(object):
@@ -36,6 +70,37 @@ class FileSearcher(object):
     ) -> Iterator[str]:
         raise NotImplementedError(self.search_files)
 
+    def _search_bin_paths(self, name: str) -> Iterator[str]:
+        for dirname in BIN_PATHS:
+            yield from self.search_files(posixpath.join(dirname, name))
+
+    def search_binary(self, name: str) -> Iterator[str]:
+        """Find the packages that provide a binary with a specific name.
+
+        Alternatives are only considered if none of the packages ship the
+        binary in one of the BIN_PATHS.
+        """
+        found = False
+        for pkg in self._search_bin_paths(name):
+            found = True
+            yield pkg
+        if found:
+            return
+        for alternative in BINARY_ALTERNATIVES.get(name, []):
+            for pkg in self._search_bin_paths(alternative):
+                found = True
+                yield pkg
+            if found:
+                return
+        seen = set()
+        for dirname in ALTERNATIVES_BIN_PATHS:
+            for pkg in self.search_files(
+                    posixpath.join(dirname, re.escape(name)) + "$", regex=True):
+                # The directory patterns overlap
+                if pkg not in seen:
+                    seen.add(pkg)
+                    yield pkg
+
 
 class ContentsFileNotFound(Exception):
     """The contents file was not found."""
@@ -47,7 +112,13 @@ def read_contents_file(f):
         yield path, rest
 
 
-def contents_urls_from_sources_entry(source, arches, load_url):
+def contents_urls_from_sources_entry(source, arches, load_url, release_info=None):
+    """Find the URLs of the Contents files for a sources.list entry.
+
+    Args:
+      release_info: Optional dictionary that will be populated with
+        ContentsReleaseInfo objects for each of the URLs
+    """
     if source.invalid or source.disabled:
         return
     if source.type == "deb-src":
@@ -90,17 +161,32 @@ def contents_urls_from_sources_entry(source, arches, load_url):
         for arch in arches:
             contents_files.add("Contents-%s" % (arch,))
 
+    by_hash = release.get("Acquire-By-Hash") == "yes"
+    sha256sums = {
+        entry["name"]: entry["sha256"] for entry in release.get("SHA256Sum", [])}
+
     for fn in contents_files:
         if fn in existing_names:
             url = "%s/%s/%s" % (dists_url, name, fn)
+            if release_info is not None:
+                by_hash_urls = {}
+                if by_hash:
+                    for ext in [".xz", ".gz"]:
+                        if fn + ext in sha256sums:
+                            by_hash_urls[ext] = "%s/%s/by-hash/SHA256/%s" % (
+                                dists_url, posixpath.join(name, posixpath.dirname(fn)),
+                                sha256sums[fn + ext])
+                release_info[url] = ContentsReleaseInfo(
+                    sha256sums.get(fn), by_hash_urls)
             yield url
 
 
-def contents_urls_from_sourceslist(sl, arch, load_url):
+def contents_urls_from_sourceslist(sl, arch, load_url, release_info=None):
     # TODO(jelmer): Verify signatures, etc.
     arches = [arch, "all"]
     for source in sl.list:
-        yield from contents_urls_from_sources_entry(source, arches, load_url)
+        yield from contents_urls_from_sources_entry(
+            source, arches, load_url, release_info)
 
 
 def _unwrap(f, ext):
@@ -110,9 +196,8 @@ def _unwrap(f, ext):
         return gzip.GzipFile(fileobj=f)
     elif ext == ".xz":
         import lzma
-        from io import BytesIO
 
-        f = BytesIO(lzma.decompress(f.read()))
+        return lzma.LZMAFile(f)
     else:
         return f
 
@@ -135,12 +220,14 @@ def load_direct_url(url):
     return _unwrap(response, ext)
 
 
-def load_url_with_cache(url, cache_dirs):
+def load_url_with_cache(url, cache_dirs, contents_cache=None, info=None):
     for cache_dir in cache_dirs:
         try:
             return load_apt_cache_file(url, cache_dir)
         except FileNotFoundError:
             pass
+    if contents_cache is not None:
+        return contents_cache.load(url, info)
     return load_direct_url(url)
 
 
@@ -252,10 +339,12 @@ class RemoteContentsFileSearcher(FileSearcher):
         def load_url(url):
             return load_url_with_cache(url, cache_dirs)
 
+        release_info = {}
         urls = list(
-            contents_urls_from_sourceslist(sl, get_build_architecture(), load_url)
+            contents_urls_from_sourceslist(
+                sl, get_build_architecture(), load_url, release_info)
         )
-        self._load_urls(urls, cache_dirs, load_url)
+        self._load_urls(urls, cache_dirs, release_info)
 
     def load_from_session(self, session):
         # TODO(jelmer): what about sources.list.d?
@@ -276,15 +365,19 @@ class RemoteContentsFileSearcher(FileSearcher):
         def load_url(url):
             return load_url_with_cache(url, cache_dirs)
 
+        release_info = {}
         urls = list(
-            contents_urls_from_sourceslist(sl, get_build_architecture(), load_url)
+            contents_urls_from_sourceslist(
+                sl, get_build_architecture(), load_url, release_info)
         )
-        self._load_urls(urls, cache_dirs, load_url)
+        self._load_urls(urls, cache_dirs, release_info)
 
-    def _load_urls(self, urls, cache_dirs, load_url):
+    def _load_urls(self, urls, cache_dirs, release_info):
+        contents_cache = ContentsCache()
         for url in urls:
             try:
-                f = load_url(url)
+                f = load_url_with_cache(
+                    url, cache_dirs, contents_cache, release_info.get(url))
                 self.load_file(f, url)
             # TODO(jelmer): Handle ConnectionReset; 
             except ContentsFileNotFound:
@@ -318,7 +411,7 @@ class RemoteContentsFileSearcher(FileSearcher):
 
     def load_file(self, f, url):
         start_time = datetime.now()
-        for path, rest in read_contents_file(f.readlines()):
+        for path, rest in read_contents_file(f):
             self[path] = rest
         logging.debug("Read %s in %s", url, datetime.now() - start_time)
 
@@ -389,6 +482,18 @@ def get_packages_for_paths(
     return candidates
 
 
+def get_packages_for_binary(
+    name: str, searchers: List[FileSearcher]
+) -> List[str]:
+    """Find the packages that provide a binary, in any of the searchers."""
+    candidates: List[str] = list()
+    for searcher in searchers:
+        for pkg in searcher.search_binary(name):
+            if pkg not in candidates:
+                candidates.append(pkg)
+    return candidates
+
+
 def main(argv):
     import argparse
     from ..session.plain import PlainSession
@@ -396,6 +501,9 @@ def main(argv):
     parser = argparse.ArgumentParser()
     parser.add_argument("path", help="Path to search for.", type=str, nargs="*")
     parser.add_argument("--regex", "-x", help="Search for regex.", action="store_true")
+    parser.add_argument(
+        "--binary", action="store_true",
+        help="Search for binaries with these names rather than paths.")
     parser.add_argument("--debug", action="store_true")
     args = parser.parse_args()
 
@@ -408,7 +516,15 @@ def main(argv):
     main_searcher.load_local()
     searchers = [main_searcher, GENERATED_FILE_SEARCHER]
 
-    packages = get_packages_for_paths(args.path, searchers=searchers, regex=args.regex)
+    if args.binary:
+        packages = []
+        for name in args.path:
+            packages.extend(
+                pkg for pkg in get_packages_for_binary(name, searchers)
+                if pkg not in packages)
+    else:
+        packages = get_packages_for_paths(
+            args.path, searchers=searchers, regex=args.regex)
     for package in packages:
         print(package)
 
@@ -120,7 +120,15 @@ from .build import (
     UnidentifiedDebianBuildError,
     )
 from ..buildlog import problem_to_upstream_requirement
-from ..fix_build import BuildFixer, resolve_error
+from ..fix_build import (
+    BuildFixer,
+    FixAttempt,
+    FixerLimitReached,
+    FixerOscillation,
+    check_recurring_problem,
+    find_fixer_for_error,
+    resolve_error,  # noqa: F401
+)
 from ..resolver.apt import (
     AptRequirement,
 )
@@ -139,13 +147,15 @@ class CircularDependency(Exception):
 
 class DebianPackagingContext(object):
     def __init__(
-        self, tree, subpath, committer, update_changelog, commit_reporter=None
+        self, tree, subpath, committer, update_changelog, commit_reporter=None,
+        dependency_plan=None
     ):
         self.tree = tree
         self.subpath = subpath
         self.committer = committer
         self.update_changelog = update_changelog
         self.commit_reporter = commit_reporter
+        self.dependency_plan = dependency_plan
 
     def abspath(self, *parts):
         return self.tree.abspath(os.path.join(self.subpath, *parts))
@@ -211,12 +221,15 @@ class PackageDependencyFixer(BuildFixer):
 
 def add_dependency(context, phase, requirement: AptRequirement):
     if phase[0] == "autopkgtest":
-        return add_test_dependency(context, phase[1], requirement)
+        changed = add_test_dependency(context, phase[1], requirement)
     elif phase[0] == "build":
-        return add_build_dependency(context, requirement)
+        changed = add_build_dependency(context, requirement)
     else:
         logging.warning("Unknown phase %r", phase)
         return False
+    if changed and context.dependency_plan is not None:
+        context.dependency_plan.add(phase, requirement)
+    return changed
 
 
 def add_build_dependency(context, requirement: AptRequirement):
@@ -515,10 +528,14 @@ def apt_fixers(apt, packaging_context) -> List[BuildFixer]:
     ]
 
 
-def default_fixers(local_tree, subpath, apt, committer=None, update_changelog=None):
+def default_fixers(
+    local_tree, subpath, apt, committer=None, update_changelog=None,
+    dependency_plan=None
+):
     packaging_context = DebianPackagingContext(
         local_tree, subpath, committer, update_changelog,
-        commit_reporter=NullCommitReporter()
+        commit_reporter=NullCommitReporter(),
+        dependency_plan=dependency_plan,
     )
     return versioned_package_fixers(apt.session, packaging_context, apt) + apt_fixers(
         apt, packaging_context
@@ -539,13 +556,18 @@ def build_incrementally(
     source_date_epoch=None,
     update_changelog=True,
     extra_repositories=None,
-    fixers=None
+    fixers=None,
+    dependency_plan=None,
+    forwarder=None,
+    jobs=None,
+    host_arch=None,
+    profile=None,
 ):
-    fixed_errors = []
+    history: List[FixAttempt] = []
     if fixers is None:
         fixers = default_fixers(
             local_tree, subpath, apt, committer=committer,
-            update_changelog=update_changelog)
+            update_changelog=update_changelog, dependency_plan=dependency_plan)
     logging.info("Using fixers: %r", fixers)
     while True:
         try:
@@ -560,6 +582,10 @@ def build_incrementally(
                 source_date_epoch=source_date_epoch,
                 run_gbp_dch=(update_changelog is False),
                 extra_repositories=extra_repositories,
+                forwarder=forwarder,
+                jobs=jobs,
+                host_arch=host_arch,
+                profile=profile,
             )
         except UnidentifiedDebianBuildError:
             logging.warning("Build failed with unidentified error. Giving up.")
@@ -568,15 +594,16 @@ def build_incrementally(
             if e.phase is None:
                 logging.info("No relevant context, not making any changes.")
                 raise
-            if (e.error, e.phase) in fixed_errors:
+            if check_recurring_problem(history, e.phase, e.error):
                 logging.warning("Error was still not fixed on second try. Giving up.")
                 raise
-            if max_iterations is not None and len(fixed_errors) > max_iterations:
+            if max_iterations is not None and len(history) > max_iterations:
                 logging.warning("Last fix did not address the issue. Giving up.")
                 raise
             reset_tree(local_tree, subpath=subpath)
             try:
-                if not resolve_error(e.error, e.phase, fixers):
+                fixer = find_fixer_for_error(e.error, e.phase, fixers)
+                if fixer is None:
                     logging.warning("Failed to resolve error %r. Giving up.", e.error)
                     raise
             except GeneratedFile:
@@ -592,7 +619,7 @@ def build_incrementally(
                     e.error,
                 )
                 raise e
-            fixed_errors.append((e.error, e.phase))
+            history.append(FixAttempt(e.phase, e.error, fixer))
             if os.path.exists(os.path.join(output_directory, "build.log")):
                 i = 1
                 while os.path.exists(
@@ -606,6 +633,7 @@ def build_incrementally(
 
 def main(argv=None):
     import argparse
+    from ..buildsystem import BUILD_PROFILES
 
     parser = argparse.ArgumentParser("ognibuild.debian.fix_build")
     parser.add_argument(
@@ -646,6 +674,28 @@ def main(argv=None):
         default=None,
     )
     parser.add_argument("--schroot", type=str, help="chroot to use.")
+    parser.add_argument(
+        "--create-chroot", action="store_true",
+        help="Create the chroot if it does not exist.")
+    parser.add_argument(
+        "--vendor", type=str, default="debian",
+        help="Vendor to use when looking for DEP-14 packaging branches.")
+    parser.add_argument(
+        "--export-build-depends", type=str, metavar="PATH",
+        help="Write the build dependencies that had to be added as a patch "
+             "against debian/control.")
+    parser.add_argument(
+        "--host-arch", type=str, metavar="ARCH",
+        help="Debian architecture to cross-build for.")
+    parser.add_argument(
+        "--jobs", "-j", type=int, metavar="N",
+        help="Number of jobs to run in parallel (default: number of CPUs).")
+    parser.add_argument(
+        "--profile", choices=BUILD_PROFILES,
+        help="Build profile to use (default: the package's default).")
+    parser.add_argument(
+        "--log-timestamps", action="store_true",
+        help="Show the build log live, with the time each line was produced.")
     parser.add_argument("--verbose", action="store_true", help="Be verbose")
 
     args = parser.parse_args()
@@ -671,8 +721,37 @@ def main(argv=None):
             output_directory = args.output_directory
 
         tree = WorkingTree.open(".")
+        from .dep14 import (
+            add_upstream_sources,
+            find_packaging_branch,
+            find_upstream_branch,
+            is_overlay_tree,
+            is_packaging_tree,
+        )
+
+        controldir = tree.controldir
+        packaging_branch = None
+        if not is_packaging_tree(tree):
+            packaging_branch = find_packaging_branch(controldir, args.vendor)
+        if packaging_branch is not None:
+            # Work in a separate checkout of the DEP-14 packaging branch, and
+            # push any fixes back to it once the package builds.
+            td = es.enter_context(tempfile.TemporaryDirectory())
+            tree = packaging_branch.controldir.sprout(
+                td, source_branch=packaging_branch
+            ).open_workingtree()
+        if is_overlay_tree(tree):
+            upstream_branch = find_upstream_branch(controldir)
+            if upstream_branch is None:
+                logging.warning(
+                    "Packaging branch only contains debian/, but no DEP-14 "
+                    "upstream branch was found")
+            else:
+                logging.info("Merging upstream sources into overlay packaging.")
+                add_upstream_sources(tree, upstream_branch.basis_tree())
         if args.schroot:
-            session = SchrootSession(args.schroot)
+            session = SchrootSession(
+                args.schroot, create_chroot=args.create_chroot)
         else:
             session = PlainSession()
 
@@ -680,6 +759,21 @@ def main(argv=None):
 
         apt = AptManager(session)
 
+        from .build_deps import BuildDependsPlan
+
+        dependency_plan = BuildDependsPlan()
+        with open(tree.abspath("debian/control"), "r") as f:
+            orig_control = f.read()
+
+        from ..buildsystem import default_jobs
+
+        forwarder = None
+        if args.log_timestamps:
+            from ..session.output import OutputForwarder
+
+            forwarder = OutputForwarder(timestamps=True)
+            session.set_output_forwarder(forwarder)
+
         try:
             (changes_filenames, cl_entry) = build_incrementally(
                 tree,
@@ -692,6 +786,11 @@ def main(argv=None):
                 committer=args.committer,
                 update_changelog=args.update_changelog,
                 max_iterations=args.max_iterations,
+                dependency_plan=dependency_plan,
+                forwarder=forwarder,
+                jobs=args.jobs or default_jobs(),
+                host_arch=args.host_arch,
+                profile=args.profile,
             )
         except DetailedDebianBuildFailure as e:
             if e.phase is None:
@@ -711,11 +810,25 @@ def main(argv=None):
                 phase = "%s (%s)" % (e.phase[0], e.phase[1])
             logging.fatal("Error during %s: %s", phase, e.description)
             return 1
+        except (FixerLimitReached, FixerOscillation) as e:
+            logging.fatal("%s", e)
+            return 1
 
         logging.info(
             'Built %s - changes file at %r.',
             cl_entry.version, changes_filenames)
 
+        if packaging_branch is not None:
+            logging.info("Pushing fixes to %s", packaging_branch.name)
+            tree.branch.push(packaging_branch)
+
+        if args.export_build_depends:
+            with open(args.export_build_depends, "w") as f:
+                f.write(dependency_plan.patch(orig_control))
+            logging.info(
+                "Wrote build dependency changes to %s",
+                args.export_build_depends)
+
 
 if __name__ == "__main__":
     sys.exit(main(sys.argv))
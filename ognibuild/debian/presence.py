@@ -0,0 +1,158 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Report availability of dependencies across multiple releases.
+
+This makes it possible to see e.g. that a package depends on something that
+only exists in experimental, before attempting a build.
+"""
+
+from typing import Dict, List, Tuple
+
+from debian.debian_support import version_compare
+
+from ..resolver.apt import AptRequirement
+
+
+SATISFIED = "satisfied"
+# A package with the right name exists, but its version is not acceptable
+OUTDATED = "outdated"
+MISSING = "missing"
+
+
+class PackageVersionSource(object):
+    """Source of information about package versions in releases."""
+
+    def get_versions(
+        self, packages: List[str], releases: List[str]
+    ) -> Dict[Tuple[str, str], List[str]]:
+        """Look up the versions of packages in releases.
+
+        Returns: dictionary mapping (package, release) to list of versions
+        """
+        raise NotImplementedError(self.get_versions)
+
+
+class UDDVersionSource(PackageVersionSource):
+    """Look up package versions in the Ultimate Debian Database."""
+
+    def __init__(self, udd=None):
+        if udd is None:
+            from .udd import UDD
+
+            udd = UDD()
+            udd.connect()
+        self.udd = udd
+
+    def get_versions(self, packages, releases):
+        return self.udd.get_package_versions(packages, releases)
+
+
+_VERSION_OPS = {
+    ">=": lambda c: c >= 0,
+    "<=": lambda c: c <= 0,
+    ">>": lambda c: c > 0,
+    "<<": lambda c: c < 0,
+    "=": lambda c: c == 0,
+}
+
+
+def _entry_status(entry, versions):
+    if not versions:
+        return MISSING
+    if not entry.get("version"):
+        return SATISFIED
+    op, required = entry["version"]
+    if any(_VERSION_OPS[op](version_compare(v, required)) for v in versions):
+        return SATISFIED
+    return OUTDATED
+
+
+def requirement_status(
+    req: AptRequirement, release: str, versions: Dict[Tuple[str, str], List[str]]
+) -> str:
+    """Determine whether a requirement can be satisfied in a release."""
+    ret = SATISFIED
+    for rel in req.relations:
+        statuses = [
+            _entry_status(entry, versions.get((entry["name"], release), []))
+            for entry in rel
+        ]
+        if SATISFIED in statuses:
+            continue
+        elif OUTDATED in statuses:
+            ret = OUTDATED
+        else:
+            return MISSING
+    return ret
+
+
+def presence_report(
+    requirements: List[AptRequirement],
+    releases: List[str],
+    source: PackageVersionSource,
+) -> Dict[AptRequirement, Dict[str, str]]:
+    """Report the availability of requirements in a set of releases.
+
+    All package versions are retrieved from source in a single call.
+
+    Returns: dictionary mapping requirements to a dictionary mapping release
+        names to one of SATISFIED, OUTDATED or MISSING
+    """
+    packages = set()
+    for req in requirements:
+        packages.update(req.package_names())
+    if packages:
+        versions = source.get_versions(sorted(packages), releases)
+    else:
+        versions = {}
+    return {
+        req: {release: requirement_status(req, release, versions)
+              for release in releases}
+        for req in requirements
+    }
+
+
+def unavailable_requirements(report, releases):
+    """Find requirements that can not be satisfied in all releases.
+
+    Yields: tuples with requirement and list of releases that satisfy it
+    """
+    for req, statuses in report.items():
+        available = [r for r in releases if statuses[r] == SATISFIED]
+        if len(available) < len(releases):
+            yield req, available
+
+
+def format_presence_report(report, releases) -> str:
+    """Format a presence report as a table."""
+    names = {req: req.pkg_relation_str() for req in report}
+    width = max([len(n) for n in names.values()] + [len("requirement")])
+    widths = [max(len(r), len(SATISFIED)) for r in releases]
+    lines = [
+        " ".join(
+            ["requirement".ljust(width)]
+            + [r.ljust(w) for (r, w) in zip(releases, widths)]).rstrip()
+    ]
+    for req, statuses in report.items():
+        lines.append(
+            " ".join(
+                [names[req].ljust(width)]
+                + [statuses[r].ljust(w) for (r, w) in zip(releases, widths)]
+            ).rstrip()
+        )
+    return "\n".join(lines) + "\n"
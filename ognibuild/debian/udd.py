@@ -40,6 +40,22 @@ class UDD(object):
         )
         return cursor.fetchone()[0]
 
+    def get_package_versions(self, packages, releases):
+        """Retrieve the versions of binary packages in a set of releases.
+
+        Returns: dictionary mapping (package, release) to list of versions
+        """
+        cursor = self._conn.cursor()
+        cursor.execute(
+            "SELECT DISTINCT package, release, version FROM packages "
+            "WHERE package IN %s AND release IN %s",
+            (tuple(packages), tuple(releases)),
+        )
+        ret = {}
+        for package, release, version in cursor.fetchall():
+            ret.setdefault((package, release), []).append(version)
+        return ret
+
 
 def popcon_tie_breaker(candidates):
     # TODO(jelmer): Pick package based on what appears most commonly in
@@ -39,14 +39,22 @@ from buildlog_consultant.common import (
 
 
 from . import DetailedFailure, UnidentifiedError
-from .dist_catcher import DistNoTarball
+from .dist_catcher import DistNoTarball, dist_events
 from .buildsystem import NoBuildToolsFound
 from .resolver import auto_resolver
 from .session import Session
+from .session.metrics import metrics_phase
 from .session.schroot import SchrootSession
 
 
-def run_dist(session, buildsystems, resolver, fixers, target_directory, quiet=False):
+def run_dist(session, buildsystems, resolver, fixers, target_directory,
+             quiet=False, event_cb=None):
+    """Create a dist tarball using the first of a list of build systems.
+
+    Args:
+      event_cb: Optional callback that is called with a DistCatcherEvent
+        for each file that is considered as the tarball
+    """
     # Some things want to write to the user's home directory,
     # e.g. pip caches in ~/.cache
     session.create_home()
@@ -54,9 +62,13 @@ def run_dist(session, buildsystems, resolver, fixers, target_directory, quiet=Fa
     logging.info('Using dependency resolver: %s', resolver)
 
     for buildsystem in buildsystems:
-        filename = buildsystem.dist(
-            session, resolver, fixers, target_directory, quiet=quiet
-        )
+        version = buildsystem.project_version(session)
+        if version:
+            logging.info('Creating dist for %s version %s', buildsystem, version)
+        with metrics_phase(session, "dist"), dist_events(event_cb):
+            filename = buildsystem.dist(
+                session, resolver, fixers, target_directory, quiet=quiet
+            )
         return filename
 
     raise NoBuildToolsFound()
@@ -69,6 +81,7 @@ def create_dist(
     include_controldir: bool = True,
     subdir: Optional[str] = None,
     cleanup: bool = False,
+    create_scaffolding: bool = False,
 ) -> Optional[str]:
     from .buildsystem import detect_buildsystems
     from .buildlog import InstallFixer
@@ -92,6 +105,15 @@ def create_dist(
 
     # TODO(jelmer): use scan_buildsystems to also look in subdirectories
     buildsystems = list(detect_buildsystems(export_directory))
+    if not buildsystems and create_scaffolding:
+        from .scaffold import create_scaffolding as _create_scaffolding
+
+        # The export directory is named after subdir, not the project
+        basedir = getattr(tree, "basedir", None)
+        if _create_scaffolding(
+                export_directory,
+                os.path.basename(basedir) if basedir else None):
+            buildsystems = list(detect_buildsystems(export_directory))
     resolver = auto_resolver(session)
     fixers: List[BuildFixer] = [UnexpandedAutoconfMacroFixer(session, resolver)]
 
@@ -114,6 +136,7 @@ def create_dist_schroot(
     include_controldir: bool = True,
     subdir: Optional[str] = None,
     cleanup: bool = False,
+    create_scaffolding: bool = False,
 ) -> Optional[str]:
     with SchrootSession(chroot) as session:
         if packaging_tree is not None:
@@ -127,6 +150,7 @@ def create_dist_schroot(
             include_controldir=include_controldir,
             subdir=subdir,
             cleanup=cleanup,
+            create_scaffolding=create_scaffolding,
         )
 
 
@@ -15,10 +15,13 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
+from contextlib import contextmanager
+import fnmatch
 import os
 import logging
 import shutil
 import time
+from typing import Callable, List, Optional
 
 
 class DistNoTarball(Exception):
@@ -44,17 +47,89 @@ def is_dist_file(fn):
     return False
 
 
+class DistCatcherEvent(object):
+    """Something that happened while looking for a tarball.
+
+    Attributes:
+      kind: One of "discovered", "copied" or "rejected"
+      path: Path of the file involved
+      reason: Why the file was rejected, for "rejected" events
+    """
+
+    DISCOVERED = "discovered"
+    COPIED = "copied"
+    REJECTED = "rejected"
+
+    def __init__(self, kind: str, path: str, reason: Optional[str] = None):
+        self.kind = kind
+        self.path = path
+        self.reason = reason
+
+    def __repr__(self):
+        return "%s(%r, %r, reason=%r)" % (
+            type(self).__name__, self.kind, self.path, self.reason)
+
+    def __str__(self):
+        if self.reason:
+            return "%s %s: %s" % (self.kind, self.path, self.reason)
+        return "%s %s" % (self.kind, self.path)
+
+
+# Callbacks registered with dist_events
+_event_callbacks: List[Callable[["DistCatcherEvent"], None]] = []
+
+
+@contextmanager
+def dist_events(event_cb: Optional[Callable[["DistCatcherEvent"], None]]):
+    """Pass events from all DistCatchers used within a block to a callback.
+
+    This allows callers of BuildSystem.dist to observe what happened, without
+    access to the DistCatcher the build system creates.
+    """
+    if event_cb is None:
+        yield
+        return
+    _event_callbacks.append(event_cb)
+    try:
+        yield
+    finally:
+        _event_callbacks.remove(event_cb)
+
+
 class DistCatcher(object):
-    def __init__(self, directories):
+    """Find tarballs created in a set of directories.
+
+    Args:
+      directories: Directories to look in, in order of preference
+      event_cb: Optional callback that is called with a DistCatcherEvent
+        for each file that is considered
+    """
+
+    def __init__(
+        self, directories,
+        event_cb: Optional[Callable[[DistCatcherEvent], None]] = None
+    ):
         self.directories = [os.path.abspath(d) for d in directories]
         self.files = []
         self.existing_files = None
         self.start_time = time.time()
+        self.event_cb = event_cb
+        self.events: List[DistCatcherEvent] = []
+
+    def _event(self, kind, path, reason=None):
+        event = DistCatcherEvent(kind, path, reason)
+        logging.debug("dist: %s", event)
+        self.events.append(event)
+        if self.event_cb is not None:
+            self.event_cb(event)
+        for event_cb in list(_event_callbacks):
+            event_cb(event)
 
     @classmethod
-    def default(cls, directory):
+    def default(cls, directory, event_cb=None):
         return cls(
-            [os.path.join(directory, "dist"), directory, os.path.join(directory, "..")]
+            [os.path.join(directory, "dist"), directory, os.path.join(directory, "..")],
+            event_cb=event_cb,
         )
 
     def __enter__(self):
@@ -76,7 +151,11 @@ class DistCatcher(object):
             if not os.path.isdir(directory):
                 continue
             for entry in os.scandir(directory):
-                if not entry.is_file() or not is_dist_file(entry.name):
+                if not is_dist_file(entry.name):
+                    continue
+                if not entry.is_file():
+                    self._event(
+                        DistCatcherEvent.REJECTED, entry.path, "not a regular file")
                     continue
                 old_entry = old_files.get(entry.name)
                 if not old_entry:
@@ -85,23 +164,49 @@ class DistCatcher(object):
                 if entry.stat().st_mtime > self.start_time:
                     possible_updated.append(entry)
                     continue
+                self._event(
+                    DistCatcherEvent.REJECTED, entry.path,
+                    "already present and not modified")
             if len(possible_new) == 1:
                 entry = possible_new[0]
                 logging.info("Found new tarball %s in %s.", entry.name, directory)
+                self._event(DistCatcherEvent.DISCOVERED, entry.path)
                 self.files.append(entry.path)
                 return entry.name
             elif len(possible_new) > 1:
                 logging.warning(
                     "Found multiple tarballs %r in %s.", possible_new, directory
                 )
+                for entry in possible_new:
+                    self._event(DistCatcherEvent.DISCOVERED, entry.path)
                 self.files.extend([entry.path for entry in possible_new])
                 return possible_new[0].name
 
             if len(possible_updated) == 1:
                 entry = possible_updated[0]
                 logging.info("Found updated tarball %s in %s.", entry.name, directory)
+                self._event(DistCatcherEvent.DISCOVERED, entry.path)
                 self.files.append(entry.path)
                 return entry.name
+            for entry in possible_updated:
+                self._event(
+                    DistCatcherEvent.REJECTED, entry.path,
+                    "one of multiple updated files")
+
+    def find_files_matching(self, pattern: str) -> List[str]:
+        """Find files in the watched directories whose name matches a glob.
+
+        Unlike find_files, this does not consider whether the files are new,
+        which is useful to diagnose why no tarball was found.
+        """
+        ret = []
+        for directory in self.directories:
+            if not os.path.isdir(directory):
+                continue
+            for entry in os.scandir(directory):
+                if fnmatch.fnmatch(entry.name, pattern):
+                    ret.append(entry.path)
+        return ret
 
     def __exit__(self, exc_type, exc_val, exc_tb):
         self.find_files()
@@ -113,6 +218,10 @@ class DistCatcher(object):
                 shutil.copy(path, target_dir)
             except shutil.SameFileError:
                 pass
+            self._event(DistCatcherEvent.COPIED, path)
             return os.path.basename(path)
         logging.info("No tarball created :(")
+        for event in self.events:
+            if event.kind == DistCatcherEvent.REJECTED:
+                logging.info("Ignored %s: %s", event.path, event.reason)
         raise DistNoTarball()
@@ -0,0 +1,263 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Generate Nix and Guix environment files from declared requirements."""
+
+import logging
+import re
+from typing import List, Optional
+
+from .requirements import (
+    Requirement,
+    BinaryRequirement,
+    PythonPackageRequirement,
+    PerlModuleRequirement,
+    NodePackageRequirement,
+    RPackageRequirement,
+    HaskellPackageRequirement,
+    PkgConfigRequirement,
+    GoRequirement,
+    JDKRequirement,
+    JRERequirement,
+    LibtoolRequirement,
+    NixPackageRequirement,
+)
+
+
+# Binaries whose nixpkgs attribute differs from the binary name.
+NIX_BINARY_MAP = {
+    "python3": "python3",
+    "perl": "perl",
+    "mvn": "maven",
+    "R": "R",
+    "cmake": "cmake",
+    "pkg-config": "pkg-config",
+    "aclocal": "automake",
+    "autoreconf": "autoconf",
+    "runhaskell": "ghc",
+    "npm": "nodejs",
+    "node": "nodejs",
+}
+
+
+# pkg-config modules and the nixpkgs attributes that provide them. Modules
+# that are not listed are assumed to share the name of their package, minus
+# any API version suffix (e.g. "libarchive" or "libsoup-2.4").
+NIX_PKG_CONFIG_MAP = {
+    "alsa": "alsa-lib",
+    "blkid": "util-linux",
+    "dbus-1": "dbus",
+    "egl": "libGL",
+    "expat": "expat",
+    "freetype2": "freetype",
+    "gdk-pixbuf-2.0": "gdk-pixbuf",
+    "gio-2.0": "glib",
+    "gio-unix-2.0": "glib",
+    "glib-2.0": "glib",
+    "gl": "libGL",
+    "gmodule-2.0": "glib",
+    "gobject-2.0": "glib",
+    "gobject-introspection-1.0": "gobject-introspection",
+    "gpg-error": "libgpg-error",
+    "gstreamer-1.0": "gst_all_1.gstreamer",
+    "gthread-2.0": "glib",
+    "gtk+-2.0": "gtk2",
+    "gtk+-3.0": "gtk3",
+    "gtk4": "gtk4",
+    "icu-i18n": "icu",
+    "icu-uc": "icu",
+    "json-c": "json_c",
+    "libcrypto": "openssl",
+    "libcurl": "curl",
+    "liblzma": "xz",
+    "libpcre": "pcre",
+    "libpcre2-8": "pcre2",
+    "libpq": "postgresql",
+    "libpulse": "libpulseaudio",
+    "libsoup-3.0": "libsoup_3",
+    "libssl": "openssl",
+    "libsystemd": "systemd",
+    "libudev": "systemd",
+    "libusb-1.0": "libusb1",
+    "libxml-2.0": "libxml2",
+    "libzstd": "zstd",
+    "mount": "util-linux",
+    "ncursesw": "ncurses",
+    "pangocairo": "pango",
+    "python3": "python3",
+    "sdl2": "SDL2",
+    "sqlite3": "sqlite",
+    "uuid": "libuuid",
+    "vulkan": "vulkan-loader",
+    "wayland-client": "wayland",
+    "wayland-server": "wayland",
+    "x11": "xorg.libX11",
+    "xcb": "xorg.libxcb",
+    "xext": "xorg.libXext",
+    "xkbcommon": "libxkbcommon",
+    "xrandr": "xorg.libXrandr",
+    "xrender": "xorg.libXrender",
+    "yaml-0.1": "libyaml",
+}
+
+
+def _pkg_config_attr(module):
+    try:
+        return NIX_PKG_CONFIG_MAP[module]
+    except KeyError:
+        return re.sub(r"-[0-9]+(\.[0-9]+)*$", "", module)
+
+
+def _perl_module_attr(module):
+    return module.replace("::", "")
+
+
+def resolve_requirement_nix(req: Requirement) -> Optional[str]:
+    """Find the nixpkgs attribute for a requirement, if known."""
+    if isinstance(req, NixPackageRequirement):
+        return req.attribute
+    elif isinstance(req, BinaryRequirement):
+        return NIX_BINARY_MAP.get(req.binary_name, req.binary_name)
+    elif isinstance(req, PythonPackageRequirement):
+        return "python3Packages.%s" % req.package.lower().replace("_", "-")
+    elif isinstance(req, PerlModuleRequirement):
+        return "perlPackages.%s" % _perl_module_attr(req.module)
+    elif isinstance(req, NodePackageRequirement):
+        return "nodePackages.%s" % req.package
+    elif isinstance(req, RPackageRequirement):
+        return "rPackages.%s" % req.package.replace(".", "_")
+    elif isinstance(req, HaskellPackageRequirement):
+        return "haskellPackages.%s" % req.package
+    elif isinstance(req, PkgConfigRequirement):
+        return _pkg_config_attr(req.module)
+    elif isinstance(req, GoRequirement):
+        return "go"
+    elif isinstance(req, (JDKRequirement, JRERequirement)):
+        return "jdk"
+    elif isinstance(req, LibtoolRequirement):
+        return "libtool"
+    return None
+
+
+def resolve_requirement_guix(req: Requirement) -> Optional[str]:
+    """Find the Guix package name for a requirement, if known."""
+    if isinstance(req, BinaryRequirement):
+        return NIX_BINARY_MAP.get(req.binary_name, req.binary_name).lower()
+    elif isinstance(req, PythonPackageRequirement):
+        return "python-%s" % req.package.lower().replace("_", "-")
+    elif isinstance(req, PerlModuleRequirement):
+        return "perl-%s" % req.module.lower().replace("::", "-")
+    elif isinstance(req, NodePackageRequirement):
+        return "node-%s" % req.package.lstrip("@").replace("/", "-")
+    elif isinstance(req, RPackageRequirement):
+        return "r-%s" % req.package.lower().replace(".", "")
+    elif isinstance(req, HaskellPackageRequirement):
+        return "ghc-%s" % req.package.lower()
+    elif isinstance(req, PkgConfigRequirement):
+        return req.module.lower()
+    elif isinstance(req, GoRequirement):
+        return "go"
+    elif isinstance(req, (JDKRequirement, JRERequirement)):
+        return "openjdk"
+    elif isinstance(req, LibtoolRequirement):
+        return "libtool"
+    return None
+
+
+def _resolve_all(requirements, resolve_fn):
+    names: List[str] = []
+    for req in requirements:
+        name = resolve_fn(req)
+        if name is None:
+            logging.warning("No known package for %r, skipping", req)
+            continue
+        if name not in names:
+            names.append(name)
+    return names
+
+
+def _nix_list(attrs, indent):
+    return "".join(["%s%s\n" % (" " * indent, attr) for attr in attrs])
+
+
+def generate_shell_nix(requirements: List[Requirement]) -> str:
+    attrs = _resolve_all(requirements, resolve_requirement_nix)
+    return (
+        "{ pkgs ? import <nixpkgs> {} }:\n"
+        "\n"
+        "pkgs.mkShell {\n"
+        "  buildInputs = with pkgs; [\n"
+        + _nix_list(attrs, 4)
+        + "  ];\n"
+        "}\n"
+    )
+
+
+def generate_flake_nix(requirements: List[Requirement], description=None) -> str:
+    attrs = _resolve_all(requirements, resolve_requirement_nix)
+    if description is None:
+        description = "Development environment generated by ognibuild"
+    return (
+        "{\n"
+        '  description = "%s";\n'
+        "\n"
+        '  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixpkgs-unstable";\n'
+        '  inputs.flake-utils.url = "github:numtide/flake-utils";\n'
+        "\n"
+        "  outputs = { self, nixpkgs, flake-utils }:\n"
+        "    flake-utils.lib.eachDefaultSystem (system:\n"
+        "      let pkgs = nixpkgs.legacyPackages.${system}; in {\n"
+        "        devShells.default = pkgs.mkShell {\n"
+        "          buildInputs = with pkgs; [\n"
+        % re.sub(r'(["\\$])', r"\\\1", description)
+        + _nix_list(attrs, 12)
+        + "          ];\n"
+        "        };\n"
+        "      });\n"
+        "}\n"
+    )
+
+
+def generate_guix_manifest(requirements: List[Requirement]) -> str:
+    names = _resolve_all(requirements, resolve_requirement_guix)
+    return (
+        "(specifications->manifest\n"
+        " '(" + "\n   ".join(['"%s"' % name for name in names]) + "))\n"
+    )
+
+
+FORMATS = {
+    "shell.nix": generate_shell_nix,
+    "flake.nix": generate_flake_nix,
+    "guix": generate_guix_manifest,
+}
+
+
+def declared_requirements(session, buildsystems, stages, fixers=None):
+    """Collect the declared requirements for the specified stages."""
+    ret = []
+    for buildsystem in buildsystems:
+        try:
+            deps = list(buildsystem.get_declared_dependencies(session, fixers))
+        except NotImplementedError:
+            logging.warning(
+                "Unable to determine declared dependencies from %r", buildsystem)
+            continue
+        for stage, req in deps:
+            if stage in stages:
+                ret.append(req)
+    return ret
@@ -26,7 +26,8 @@ from buildlog_consultant.common import (
 )
 
 from . import DetailedFailure, UnidentifiedError
-from .session import Session, run_with_tee
+from .problems import find_extra_problem
+from .session import Session, run_with_tee, output_lines
 
 
 # Number of attempts to fix a build before giving up.
@@ -37,6 +38,60 @@ class FixerLimitReached(Exception):
     """The maximum number of fixes has been reached."""
 
 
+class FixAttempt(object):
+    """A fix that was applied for a problem."""
+
+    def __init__(self, phase, problem: Problem, fixer: "BuildFixer"):
+        self.phase = phase
+        self.problem = problem
+        self.fixer = fixer
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__, self.phase, self.problem, self.fixer)
+
+
+class FixerOscillation(Exception):
+    """Fixes kept reintroducing problems that were fixed earlier.
+
+    Attributes:
+      cycle: The fixes that were applied since the problem was first fixed,
+        starting with the fix for the recurring problem
+    """
+
+    def __init__(self, cycle: List[FixAttempt]):
+        self.cycle = cycle
+
+    def __str__(self):
+        return "Fixes are going around in circles: %s -> %r" % (
+            " -> ".join(
+                ["%r (fixed by %s)" % (a.problem, a.fixer) for a in self.cycle]),
+            self.cycle[0].problem)
+
+
+def check_recurring_problem(history: List[FixAttempt], phase, problem) -> bool:
+    """Check whether a problem has been fixed before.
+
+    Returns: True if the problem persisted despite the most recent fix,
+        False if it has not been seen before
+    Raises:
+      FixerOscillation: if the problem was fixed before, but other fixes
+        brought it back
+    """
+    for i in range(len(history) - 1, -1, -1):
+        if history[i].problem == problem and history[i].phase == phase:
+            break
+    else:
+        return False
+    if i == len(history) - 1:
+        return True
+    cycle = history[i:]
+    logging.warning(
+        "Problem %r came back after %d other fixes; giving up.",
+        problem, len(cycle) - 1)
+    raise FixerOscillation(cycle)
+
+
 class BuildFixer(object):
     """Build fixer."""
 
@@ -52,20 +107,42 @@ class BuildFixer(object):
         return self._fix(problem, phase)
 
 
-def run_detecting_problems(session: Session, args: List[str], check_success=None, **kwargs):
+def run_detecting_problems(
+    session: Session, args: List[str], check_success=None,
+    separate_streams: bool = False, **kwargs
+):
+    """Run a command, and try to identify the problem if it fails.
+
+    Args:
+      separate_streams: Keep stdout and stderr apart; see run_with_tee.
+        Problems are then looked for in stderr first.
+    Returns: the output of the command, as returned by run_with_tee
+    """
     if check_success is None:
         def check_success(retcode, contents):
             return (retcode == 0)
     try:
-        retcode, contents = run_with_tee(session, args, **kwargs)
+        retcode, contents = run_with_tee(
+            session, args, separate_streams=separate_streams, **kwargs)
     except FileNotFoundError:
         error = MissingCommand(args[0])
         retcode = 1
     else:
         if check_success(retcode, contents):
             return contents
-        lines = "".join(contents).splitlines(False)
-        match, error = find_build_failure_description(lines)
+        if separate_streams:
+            lines = output_lines(contents)
+            # Errors are usually reported on stderr; looking there first
+            # avoids matching on noise in stdout.
+            match, error = find_build_failure_description(
+                output_lines(contents, "stderr"))
+            if error is None:
+                match, error = find_build_failure_description(lines)
+        else:
+            lines = "".join(contents).splitlines(False)
+            match, error = find_build_failure_description(lines)
+        if error is None:
+            error = find_extra_problem(lines)
         if error is None:
             if match:
                 logging.warning("Build failed with unidentified error:")
@@ -76,16 +153,23 @@ def run_detecting_problems(session: Session, args: List[str], check_success=None
     raise DetailedFailure(retcode, args, error)
 
 
-def iterate_with_build_fixers(fixers: List[BuildFixer], cb: Callable[[], Any], limit=DEFAULT_LIMIT):
+def iterate_with_build_fixers(
+    fixers: List[BuildFixer], cb: Callable[[], Any], limit=DEFAULT_LIMIT,
+    history: Optional[List[FixAttempt]] = None
+):
     """Call cb() until there are no more DetailedFailures we can fix.
 
     Args:
       fixers: List of fixers to use to resolve issues
       cb: Callable to run the build
       limit: Maximum number of fixing attempts before giving up
+      history: List to record the applied fixes in
+    Raises:
+      FixerOscillation: if fixes keep undoing each other
     """
     attempts = 0
-    fixed_errors = []
+    if history is None:
+        history = []
     while True:
         to_resolve = []
         try:
@@ -95,7 +179,7 @@ def iterate_with_build_fixers(fixers: List[BuildFixer], cb: Callable[[], Any], l
         while to_resolve:
             f = to_resolve.pop(-1)
             logging.info("Identified error: %r", f.error)
-            if f.error in fixed_errors:
+            if check_recurring_problem(history, None, f.error):
                 logging.warning(
                     "Failed to resolve error %r, it persisted. Giving up.", f.error
                 )
@@ -104,7 +188,7 @@ def iterate_with_build_fixers(fixers: List[BuildFixer], cb: Callable[[], Any], l
             if limit is not None and limit <= attempts:
                 raise FixerLimitReached(limit)
             try:
-                resolved = resolve_error(f.error, None, fixers=fixers)
+                fixer = find_fixer_for_error(f.error, None, fixers=fixers)
             except DetailedFailure as n:
                 logging.info("New error %r while resolving %r", n, f)
                 if n in to_resolve:
@@ -112,12 +196,12 @@ def iterate_with_build_fixers(fixers: List[BuildFixer], cb: Callable[[], Any], l
                 to_resolve.append(f)
                 to_resolve.append(n)
             else:
-                if not resolved:
+                if fixer is None:
                     logging.warning(
                         "Failed to find resolution for error %r. Giving up.", f.error
                     )
                     raise f
-                fixed_errors.append(f.error)
+                history.append(FixAttempt(None, f.error, fixer))
 
 
 def run_with_build_fixers(
@@ -132,17 +216,25 @@ def run_with_build_fixers(
     )
 
 
-def resolve_error(error, phase, fixers):
+def find_fixer_for_error(error, phase, fixers) -> Optional[BuildFixer]:
+    """Fix an error, using the first fixer that makes changes.
+
+    Returns: the fixer that made changes, or None if no fixer did
+    """
     relevant_fixers = []
     for fixer in fixers:
         if fixer.can_fix(error):
             relevant_fixers.append(fixer)
     if not relevant_fixers:
         logging.warning("No fixer found for %r", error)
-        return False
+        return None
     for fixer in relevant_fixers:
         logging.info("Attempting to use fixer %s to address %r", fixer, error)
         made_changes = fixer.fix(error, phase)
         if made_changes:
-            return True
-    return False
+            return fixer
+    return None
+
+
+def resolve_error(error, phase, fixers):
+    return find_fixer_for_error(error, phase, fixers) is not None
@@ -15,10 +15,15 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
+from .buildsystem import STANDARD_MAKE_TARGETS, Make
+
 
 def run_info(session, buildsystems, fixers=None):
     for buildsystem in buildsystems:
         print("%r:" % buildsystem)
+        version = buildsystem.project_version(session)
+        if version:
+            print("\tVersion: %s" % version)
         deps = {}
         try:
             for kind, dep in buildsystem.get_declared_dependencies(session, fixers=fixers):
@@ -43,3 +48,14 @@ def run_info(session, buildsystems, fixers=None):
             print("\tDeclared outputs:")
             for output in outputs:
                 print("\t\t%s" % output)
+        try:
+            targets = buildsystem.targets(session)
+        except NotImplementedError:
+            targets = []
+        if targets:
+            print("\tTargets:")
+            for target in targets:
+                print("\t\t%s" % target)
+        if isinstance(buildsystem, Make) and targets:
+            standard = [t for t in STANDARD_MAKE_TARGETS if t in targets]
+            print("\tStandard targets: %s" % (", ".join(standard) or "none"))
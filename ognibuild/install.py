@@ -16,6 +16,7 @@
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
 from .buildsystem import NoBuildToolsFound, InstallTarget
+from .session.metrics import metrics_phase
 from typing import Optional
 
 
@@ -29,7 +30,8 @@ def run_install(session, buildsystems, resolver, fixers, user: bool = False, pre
     install_target.prefix = prefix
 
     for buildsystem in buildsystems:
-        buildsystem.install(session, resolver, fixers, install_target)
+        with metrics_phase(session, "install"):
+            buildsystem.install(session, resolver, fixers, install_target)
         return
 
     raise NoBuildToolsFound()
@@ -0,0 +1,27 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from .buildsystem import run_for_each
+
+
+def run_lint(session, buildsystems, resolver, fixers):
+    # Linters that are missing are installed by the fixers, so they may
+    # end up in ~/.local or a cache in the user's home directory
+    session.create_home()
+    run_for_each(
+        session, buildsystems, "lint",
+        lambda bs: bs.lint(session, resolver, fixers))
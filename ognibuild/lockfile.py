@@ -0,0 +1,34 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Refresh lockfiles and vendor dependencies, e.g. before offline builds."""
+
+from .buildsystem import run_for_each
+
+
+def run_update_lockfile(session, buildsystems, resolver, fixers):
+    session.create_home()
+    run_for_each(
+        session, buildsystems, "update-lockfile",
+        lambda bs: bs.update_lockfile(session, resolver, fixers))
+
+
+def run_vendor(session, buildsystems, resolver, fixers):
+    session.create_home()
+    run_for_each(
+        session, buildsystems, "vendor",
+        lambda bs: bs.vendor(session, resolver, fixers))
@@ -0,0 +1,316 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Provision a local Maven repository for offline Java builds.
+
+The artifacts listed in a Gradle lockfile or a Maven dependency report are
+downloaded on the host, into a repository that is then shared with the
+session. Maven and Gradle in the session are pointed at that repository,
+so that builds work without network access.
+"""
+
+import hashlib
+import logging
+import os
+import re
+import time
+from typing import Dict, List, Optional, Set, Tuple
+from xml.etree import ElementTree
+
+from . import USER_AGENT
+from .requirements import MavenArtifactRequirement
+from .session import Session, default_cache_dir
+
+
+MAVEN_CENTRAL = "https://repo1.maven.org/maven2"
+
+# Directory inside the session to make the repository available in
+REPOSITORY_TARGET = "/var/cache/ognibuild/maven"
+
+# Checksum files that are published next to artifacts, strongest first
+CHECKSUM_ALGORITHMS = ["sha256", "sha1"]
+
+# Seconds after which cached SNAPSHOT artifacts are downloaded again;
+# release artifacts never change.
+SNAPSHOT_MAX_AGE = 24 * 60 * 60
+
+
+class ChecksumMismatch(Exception):
+    """A downloaded file does not match its published checksum."""
+
+    def __init__(self, url, expected, actual):
+        self.url = url
+        self.expected = expected
+        self.actual = actual
+
+    def __str__(self):
+        return "checksum mismatch for %s: expected %s, got %s" % (
+            self.url, self.expected, self.actual)
+
+
+def read_gradle_lockfile(f) -> List[MavenArtifactRequirement]:
+    """Read the artifacts from a gradle.lockfile."""
+    ret = []
+    for line in f:
+        line = line.strip()
+        if not line or line.startswith("#") or line.startswith("empty="):
+            continue
+        coordinates = line.split("=", 1)[0]
+        parts = coordinates.split(":")
+        if len(parts) != 3:
+            logging.warning("Ignoring invalid line in gradle lockfile: %r", line)
+            continue
+        ret.append(MavenArtifactRequirement(parts[0], parts[1], parts[2], "jar"))
+    return ret
+
+
+def read_maven_dependency_list(f) -> List[MavenArtifactRequirement]:
+    """Read the artifacts from the output of mvn dependency:list."""
+    ret = []
+    for line in f:
+        line = line.strip()
+        if line.startswith("[INFO]"):
+            line = line[len("[INFO]"):].strip()
+        # The module name is sometimes appended, e.g. "-- module foo"
+        line = line.split(" ", 1)[0]
+        parts = line.split(":")
+        if len(parts) == 6:
+            logging.debug("Skipping artifact with classifier: %s", line)
+            continue
+        if len(parts) != 5:
+            continue
+        (group_id, artifact_id, kind, version, scope) = parts
+        ret.append(MavenArtifactRequirement(group_id, artifact_id, version, kind))
+    return ret
+
+
+def read_artifacts(path: str) -> List[MavenArtifactRequirement]:
+    """Read artifacts from a Gradle lockfile or Maven dependency report."""
+    with open(path, "r") as f:
+        if path.endswith(".lockfile"):
+            return read_gradle_lockfile(f)
+        return read_maven_dependency_list(f)
+
+
+def artifact_path(req: MavenArtifactRequirement, extension: str) -> str:
+    """Path of an artifact file, relative to the root of a repository."""
+    return "%s/%s/%s/%s-%s.%s" % (
+        req.group_id.replace(".", "/"), req.artifact_id, req.version,
+        req.artifact_id, req.version, extension)
+
+
+def _fetch(url: str) -> bytes:
+    from urllib.error import HTTPError
+    from urllib.request import urlopen, Request
+
+    request = Request(url, headers={"User-Agent": USER_AGENT})
+    try:
+        response = urlopen(request)
+    except HTTPError as e:
+        if e.code == 404:
+            raise FileNotFoundError(url)
+        raise
+    with response:
+        return response.read()
+
+
+def _download(url: str, path: str) -> None:
+    """Download a file, verifying it against the published checksum."""
+    data = _fetch(url)
+    for algorithm in CHECKSUM_ALGORITHMS:
+        try:
+            published = _fetch("%s.%s" % (url, algorithm)).decode("ascii", "replace")
+        except FileNotFoundError:
+            continue
+        # Some checksum files also contain the filename
+        expected = (published.split() or [""])[0].lower()
+        actual = hashlib.new(algorithm, data).hexdigest()
+        if expected != actual:
+            raise ChecksumMismatch(url, expected, actual)
+        break
+    else:
+        logging.warning("No checksum published for %s; not verifying.", url)
+    os.makedirs(os.path.dirname(path), exist_ok=True)
+    with open(path + ".part", "wb") as f:
+        f.write(data)
+    os.rename(path + ".part", path)
+
+
+def _is_cached(req: MavenArtifactRequirement, path: str) -> bool:
+    if not os.path.exists(path):
+        return False
+    if req.version is not None and req.version.endswith("-SNAPSHOT"):
+        return time.time() - os.path.getmtime(path) < SNAPSHOT_MAX_AGE
+    return True
+
+
+def _strip_namespace(tag: str) -> str:
+    return tag.rsplit("}", 1)[-1]
+
+
+def _child_text(element, name: str) -> Optional[str]:
+    for child in element:
+        if _strip_namespace(child.tag) == name:
+            return (child.text or "").strip()
+    return None
+
+
+def _children(element, name: str):
+    return [child for child in element if _strip_namespace(child.tag) == name]
+
+
+def read_pom_references(f) -> List[MavenArtifactRequirement]:
+    """Read the POMs that a POM depends on to be usable.
+
+    These are its parent POM and the BOMs that it imports in
+    dependencyManagement.
+    """
+    root = ElementTree.parse(f).getroot()
+    properties: Dict[str, str] = {}
+    ret = []
+    for parent in _children(root, "parent"):
+        group_id = _child_text(parent, "groupId")
+        artifact_id = _child_text(parent, "artifactId")
+        version = _child_text(parent, "version")
+        if group_id and artifact_id and version:
+            ret.append(MavenArtifactRequirement(group_id, artifact_id, version, "pom"))
+            properties["project.parent.version"] = version
+            properties["project.version"] = version
+    version = _child_text(root, "version")
+    if version:
+        properties["project.version"] = version
+    for section in _children(root, "properties"):
+        for prop in section:
+            properties[_strip_namespace(prop.tag)] = (prop.text or "").strip()
+
+    def expand(text):
+        return re.sub(
+            r"\$\{([^}]+)\}", lambda m: properties.get(m.group(1), m.group(0)),
+            text)
+
+    for management in _children(root, "dependencyManagement"):
+        for dependencies in _children(management, "dependencies"):
+            for dependency in _children(dependencies, "dependency"):
+                if (_child_text(dependency, "scope") != "import"
+                        or _child_text(dependency, "type") != "pom"):
+                    continue
+                coordinates = [
+                    expand(_child_text(dependency, name) or "")
+                    for name in ["groupId", "artifactId", "version"]]
+                if not all(coordinates) or any("${" in c for c in coordinates):
+                    logging.debug(
+                        "Unable to resolve imported BOM %s", ":".join(coordinates))
+                    continue
+                ret.append(MavenArtifactRequirement(*coordinates, kind="pom"))
+    return ret
+
+
+def populate_repository(
+    repository: str, artifacts: List[MavenArtifactRequirement],
+    mirror: str = MAVEN_CENTRAL
+) -> List[MavenArtifactRequirement]:
+    """Download artifacts, and their POM files, into a repository.
+
+    Parent POMs and imported BOMs are downloaded as well. Artifacts that are
+    already present are not downloaded again, unless they are snapshots
+    older than SNAPSHOT_MAX_AGE.
+
+    Returns: artifacts that could not be downloaded
+    """
+    missing = []
+    todo = list(artifacts)
+    seen: Set[Tuple[str, str, Optional[str], Optional[str]]] = set()
+    while todo:
+        req = todo.pop(0)
+        key = (req.group_id, req.artifact_id, req.version, req.kind)
+        if key in seen:
+            continue
+        seen.add(key)
+        if req.version is None:
+            logging.warning("No version known for %s, skipping.", req)
+            missing.append(req)
+            continue
+        extensions = ["pom"]
+        if req.kind not in (None, "pom"):
+            extensions.append(req.kind)
+        for extension in extensions:
+            relpath = artifact_path(req, extension)
+            path = os.path.join(repository, relpath)
+            if not _is_cached(req, path):
+                logging.debug("Downloading %s", relpath)
+                try:
+                    _download("%s/%s" % (mirror.rstrip("/"), relpath), path)
+                except (ChecksumMismatch, OSError) as e:
+                    logging.warning("Unable to download %s: %s", relpath, e)
+                    missing.append(req)
+                    break
+            if extension == "pom":
+                try:
+                    with open(path, "rb") as f:
+                        todo.extend(read_pom_references(f))
+                except ElementTree.ParseError as e:
+                    logging.warning("Unable to parse %s: %s", relpath, e)
+    return missing
+
+
+def provision_maven_repository(
+    session: Session, artifacts: List[MavenArtifactRequirement],
+    cache_dir: Optional[str] = None, mirror: str = MAVEN_CENTRAL,
+    offline: bool = True
+) -> Optional[str]:
+    """Make a local Maven repository with the artifacts available in a session.
+
+    Args:
+      session: Session to provision
+      artifacts: Artifacts to download
+      cache_dir: Host directory to store the repository in
+      mirror: Maven repository to download from
+      offline: Make Maven run in offline mode
+    Returns: path of the repository in the session, or None if the session
+        does not support sharing directories
+    """
+    if cache_dir is None:
+        cache_dir = default_cache_dir()
+    repository = os.path.join(cache_dir, "maven-repository")
+    os.makedirs(repository, exist_ok=True)
+    missing = populate_repository(repository, artifacts, mirror=mirror)
+    if missing:
+        logging.warning(
+            "%d of %d artifacts could not be downloaded; offline builds may "
+            "fail.", len(missing), len(artifacts))
+    target = session.share_host_directory(repository, REPOSITORY_TARGET)
+    if target is None:
+        logging.warning(
+            "Unable to share %s with %r; not using local Maven repository.",
+            repository, session)
+        return None
+    # Gradle also honours maven.repo.local for mavenLocal()
+    session.setenv("MAVEN_OPTS", "-Dmaven.repo.local=%s" % target)
+    session.setenv("GRADLE_OPTS", "-Dmaven.repo.local=%s" % target)
+    if offline:
+        # Gradle has no environment variable for this; the Gradle build
+        # system passes --offline if MAVEN_ARGS does.
+        session.setenv("MAVEN_ARGS", "--offline")
+    logging.info(
+        "Using local Maven repository %s with %d artifacts",
+        repository, len(artifacts) - len(missing))
+    return target
+
+
+def offline_requested(session: Session) -> bool:
+    """Check whether provision_maven_repository requested offline builds."""
+    return "--offline" in (session.getenv("MAVEN_ARGS") or "").split()
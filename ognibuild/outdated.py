@@ -0,0 +1,268 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Report declared dependencies for which newer versions are available."""
+
+import json
+import logging
+import re
+from typing import List, Optional, Tuple
+
+from . import USER_AGENT, Requirement
+from .requirements import (
+    CargoCrateRequirement,
+    NodePackageRequirement,
+    PythonPackageRequirement,
+    RubyGemRequirement,
+)
+
+
+def declared_version(req: Requirement) -> Optional[str]:
+    """Find the version a requirement asks for, if any."""
+    specs = getattr(req, "specs", None)
+    if specs:
+        for op, version in specs:
+            if op in (">=", "==", "~=", "^", "~"):
+                return version
+    for attr in ["version", "minimum_version"]:
+        version = getattr(req, attr, None)
+        if isinstance(version, str) and version:
+            return version.lstrip("^~=>< ")
+    return None
+
+
+def _order(c: str) -> int:
+    # dpkg's ordering of non-digit characters: "~" sorts before anything,
+    # even the end of a part, and letters sort before other characters
+    if c == "~":
+        return -1
+    if c.isalpha():
+        return ord(c)
+    return ord(c) + 256
+
+
+def version_key(version: str) -> Tuple:
+    """Sort key for upstream version strings.
+
+    Versions are compared like Debian upstream versions, with pre-releases
+    (e.g. "1.0rc1" or "2.0-beta.2") sorting before the release.
+    """
+    version = re.sub(r"^[vV](?=\d)", "", version)
+    # Development releases come before alpha releases
+    version = re.sub(
+        r"(?<=\d)[-_.]?((?:alpha|beta|pre|preview|rc|dev)(?=[-_.]?\d|$)"
+        r"|(?:a|b|c)(?=\d))",
+        lambda m: ("~~" if m.group(1).lower() == "dev" else "~") + m.group(1),
+        version, flags=re.IGNORECASE)
+    parts = []
+    for m in re.finditer(r"(\D*)(\d*)", version):
+        if not m.group(0):
+            continue
+        parts.append((
+            tuple(_order(c) for c in m.group(1)) + (0,),
+            int(m.group(2)) if m.group(2) else 0))
+    # Versions that run out of parts compare like an empty part
+    parts.append(((0,), 0))
+    return tuple(parts)
+
+
+class OutdatedDependency(object):
+    """A declared dependency with a newer version available."""
+
+    def __init__(
+        self, requirement: Requirement, declared: str, latest: str,
+        source: str
+    ):
+        self.requirement = requirement
+        self.declared = declared
+        self.latest = latest
+        self.source = source
+
+    def __repr__(self):
+        return "%s(%r, %r, %r, %r)" % (
+            type(self).__name__, self.requirement, self.declared, self.latest,
+            self.source)
+
+    def json(self):
+        return {
+            "requirement": str(self.requirement),
+            "family": self.requirement.family,
+            "declared": self.declared,
+            "latest": self.latest,
+            "source": self.source,
+        }
+
+
+class VersionSource(object):
+    """Source for the latest available version of requirements."""
+
+    name: str
+
+    def __str__(self):
+        return self.name
+
+    def latest_version(self, req: Requirement) -> Optional[str]:
+        """Find the latest version of a requirement.
+
+        Raises:
+          NotImplementedError: if this source does not know about requirements
+            of this kind
+        Returns: latest version, or None if the requirement is not known
+        """
+        raise NotImplementedError(self.latest_version)
+
+
+class RegistryVersionSource(VersionSource):
+    """Look up latest versions in upstream package registries."""
+
+    name = "registry"
+
+    URLS = {
+        PythonPackageRequirement: "https://pypi.org/pypi/%s/json",
+        CargoCrateRequirement: "https://crates.io/api/v1/crates/%s",
+        NodePackageRequirement: "https://registry.npmjs.org/%s",
+        RubyGemRequirement: "https://rubygems.org/api/v1/gems/%s.json",
+    }
+
+    def _load_json(self, url):
+        from urllib.error import HTTPError
+        from urllib.request import urlopen, Request
+
+        request = Request(url, headers={"User-Agent": USER_AGENT})
+        try:
+            with urlopen(request) as f:
+                return json.load(f)
+        except HTTPError as e:
+            if e.code == 404:
+                return None
+            raise
+
+    def latest_version(self, req):
+        try:
+            url = self.URLS[type(req)]
+        except KeyError:
+            raise NotImplementedError(self.latest_version)
+        if isinstance(req, PythonPackageRequirement):
+            name = req.package
+        elif isinstance(req, CargoCrateRequirement):
+            name = req.crate
+        elif isinstance(req, NodePackageRequirement):
+            name = req.package
+        else:
+            name = req.gem
+        data = self._load_json(url % name)
+        if data is None:
+            return None
+        if isinstance(req, PythonPackageRequirement):
+            return data["info"]["version"]
+        elif isinstance(req, CargoCrateRequirement):
+            crate = data["crate"]
+            return crate.get("max_stable_version") or crate["max_version"]
+        elif isinstance(req, NodePackageRequirement):
+            return data.get("dist-tags", {}).get("latest")
+        else:
+            return data["version"]
+
+
+class AptVersionSource(VersionSource):
+    """Look up candidate versions of the apt packages for requirements.
+
+    The Debian revision and epoch are stripped, so that the result can be
+    compared with upstream versions.
+    """
+
+    name = "apt"
+
+    def __init__(self, resolver):
+        self.resolver = resolver
+
+    def latest_version(self, req):
+        from .resolver.apt import AptRequirement
+
+        if not isinstance(req, AptRequirement):
+            req = self.resolver.resolve(req)
+            if req is None:
+                return None
+        cache = self.resolver.apt.apt_cache
+        versions = []
+        for name in req.package_names():
+            try:
+                candidate = cache[name].candidate
+            except KeyError:
+                continue
+            if candidate is not None:
+                versions.append(candidate.version)
+        if not versions:
+            return None
+        from debian.debian_support import Version
+
+        return Version(max(versions, key=Version)).upstream_version
+
+
+def find_outdated(
+    requirements: List[Requirement], sources: List[VersionSource]
+) -> List[OutdatedDependency]:
+    """Find requirements for which newer versions are available.
+
+    Requirements without a declared version are skipped, since there is
+    nothing to upgrade.
+    """
+    ret = []
+    for req in requirements:
+        declared = declared_version(req)
+        if declared is None:
+            continue
+        for source in sources:
+            try:
+                latest = source.latest_version(req)
+            except NotImplementedError:
+                continue
+            except Exception as e:
+                logging.warning(
+                    "Unable to look up latest version of %s in %s: %s",
+                    req, source, e)
+                continue
+            if latest is None:
+                continue
+            if version_key(latest) > version_key(declared):
+                ret.append(OutdatedDependency(req, declared, latest, str(source)))
+    return ret
+
+
+def run_outdated(session, buildsystems, sources, fixers=None):
+    reqs = []
+    for buildsystem in buildsystems:
+        try:
+            for kind, req in buildsystem.get_declared_dependencies(
+                    session, fixers=fixers):
+                if req not in reqs:
+                    reqs.append(req)
+        except NotImplementedError:
+            logging.warning(
+                "Unable to determine declared dependencies for %s", buildsystem)
+    return find_outdated(reqs, sources)
+
+
+def format_outdated_report(outdated: List[OutdatedDependency]) -> str:
+    if not outdated:
+        return "All declared dependencies are up to date.\n"
+    lines = []
+    for entry in outdated:
+        lines.append("%s: %s -> %s (%s)" % (
+            entry.requirement, entry.declared, entry.latest,
+            entry.source))
+    return "\n".join(lines) + "\n"
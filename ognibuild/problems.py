@@ -0,0 +1,287 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Problems that buildlog-consultant does not detect.
+
+These are only looked for if buildlog-consultant was unable to identify
+the cause of a failure.
+"""
+
+import re
+from typing import List, Optional
+
+from buildlog_consultant import Problem
+
+
+class MissingHaskellModule(Problem):
+    """GHC could not find a module that was imported."""
+
+    kind = "missing-haskell-module"
+
+    def __init__(self, module: str):
+        self.module = module
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.module == other.module
+
+    def __str__(self):
+        return "Missing Haskell module: %s" % self.module
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.module)
+
+
+class HiddenHaskellPackage(Problem):
+    """A module was imported from a package that is not a declared dependency."""
+
+    kind = "hidden-haskell-package"
+
+    def __init__(self, package: str, module: Optional[str] = None):
+        self.package = package
+        self.module = module
+
+    def __eq__(self, other):
+        return (
+            isinstance(other, type(self))
+            and self.package == other.package
+            and self.module == other.module
+        )
+
+    def __str__(self):
+        return "Haskell package %s is hidden" % self.package
+
+    def __repr__(self):
+        return "%s(%r, module=%r)" % (
+            type(self).__name__, self.package, self.module)
+
+
+class GhcVersionMismatch(Problem):
+    """The project needs a different version of GHC."""
+
+    kind = "ghc-version-mismatch"
+
+    def __init__(self, expected: str, found: Optional[str] = None):
+        self.expected = expected
+        self.found = found
+
+    def __eq__(self, other):
+        return (
+            isinstance(other, type(self))
+            and self.expected == other.expected
+            and self.found == other.found
+        )
+
+    def __str__(self):
+        if self.found:
+            return "GHC %s required, found %s" % (self.expected, self.found)
+        return "GHC %s required" % self.expected
+
+    def __repr__(self):
+        return "%s(%r, found=%r)" % (
+            type(self).__name__, self.expected, self.found)
+
+
+class MissingOcamlFindlibPackage(Problem):
+    """ocamlfind or dune could not find an OCaml library."""
+
+    kind = "missing-ocaml-findlib-package"
+
+    def __init__(self, package: str):
+        self.package = package
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.package == other.package
+
+    def __str__(self):
+        return "Missing OCaml findlib package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+
+class MissingDartPackage(Problem):
+    """A Dart package that was imported could not be found."""
+
+    kind = "missing-dart-package"
+
+    def __init__(self, package: str):
+        self.package = package
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.package == other.package
+
+    def __str__(self):
+        return "Missing Dart package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+
+class MissingHexPackages(Problem):
+    """mix found dependencies that have not been fetched."""
+
+    kind = "missing-hex-packages"
+
+    def __init__(self, packages: List[str]):
+        self.packages = packages
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.packages == other.packages
+
+    def __str__(self):
+        return "Missing hex packages: %s" % ", ".join(self.packages)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.packages)
+
+
+class MissingQtModules(Problem):
+    """qmake could not find one or more Qt modules."""
+
+    kind = "missing-qt-modules"
+
+    def __init__(self, modules: List[str]):
+        self.modules = modules
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.modules == other.modules
+
+    def __str__(self):
+        return "Missing Qt modules: %s" % ", ".join(self.modules)
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.modules)
+
+
+_QUOTE = "[‘`']"
+_UNQUOTE = "[’']"
+
+
+def _ghc_missing_module(lines, i):
+    m = re.match(
+        r"\s*Could not (?:find|load) module %s([^’']+)%s" % (_QUOTE, _UNQUOTE),
+        lines[i])
+    if not m:
+        return None
+    module = m.group(1)
+    for line in lines[i + 1:i + 4]:
+        m = re.match(
+            r"\s*It is a member of the hidden package %s([^’']+)%s"
+            % (_QUOTE, _UNQUOTE), line)
+        if m:
+            # Strip the version, e.g. containers-0.6.5.1
+            package = re.sub(r"-[0-9][0-9.]*$", "", m.group(1))
+            return HiddenHaskellPackage(package, module)
+    return MissingHaskellModule(module)
+
+
+def _ghc_version_mismatch(lines, i):
+    # stack: No compiler found, expected minor version match with ghc-9.2.7
+    # (x86_64) (based on resolver setting in /build/stack.yaml).
+    m = re.match(
+        r".*No compiler found, expected (?:minor|exact) version match with "
+        r"ghc-([0-9.]+)", lines[i])
+    if m:
+        return GhcVersionMismatch(m.group(1))
+    # stack: Compiler version mismatched, found ghc-9.0.2 (x86_64), but
+    # expected minor version match with ghc-9.2.7 (x86_64)
+    m = re.match(
+        r".*Compiler version mismatched, found ghc-([0-9.]+).*, but expected "
+        r"(?:minor|exact) version match with ghc-([0-9.]+)", lines[i])
+    if m:
+        return GhcVersionMismatch(m.group(2), m.group(1))
+    return None
+
+
+def _ocaml_missing_findlib_package(lines, i):
+    # ocamlfind: Package `yojson' not found
+    m = re.match(
+        r"\s*ocamlfind: Package %s([^’']+)%s not found" % (_QUOTE, _UNQUOTE),
+        lines[i])
+    if m:
+        return MissingOcamlFindlibPackage(m.group(1))
+    # dune: Error: Library "yojson" not found.
+    m = re.match(r'\s*Error: Library "([^"]+)" not found\.', lines[i])
+    if m:
+        return MissingOcamlFindlibPackage(m.group(1))
+    return None
+
+
+def _dart_missing_package(lines, i):
+    # dart compile/run:
+    # Error: Couldn't resolve the package 'http' in 'package:http/http.dart'.
+    m = re.match(
+        r".*Error: Couldn't resolve the package '([^']+)' in 'package:",
+        lines[i])
+    if m:
+        return MissingDartPackage(m.group(1))
+    # dart analyze:
+    # error - Target of URI doesn't exist: 'package:http/http.dart'.
+    m = re.match(
+        r".*Target of URI doesn't exist: 'package:([^/']+)/", lines[i])
+    if m:
+        return MissingDartPackage(m.group(1))
+    return None
+
+
+def _mix_unchecked_dependencies(lines, i):
+    # Unchecked dependencies for environment dev:
+    # * jason (Hex package)
+    #   the dependency is not available, run "mix deps.get"
+    if not re.match(r"\s*Unchecked dependencies for environment ", lines[i]):
+        return None
+    packages = []
+    for j in range(i + 1, len(lines)):
+        m = re.match(r"\* (\S+) \(Hex package\)", lines[j])
+        if m:
+            packages.append(m.group(1))
+        elif not lines[j].startswith((" ", "*")):
+            break
+    if packages:
+        return MissingHexPackages(packages)
+    return None
+
+
+def _qmake_missing_qt_modules(lines, i):
+    # Project ERROR: Unknown module(s) in QT: svg xmlpatterns
+    m = re.match(r"\s*Project ERROR: Unknown module\(s\) in QT: (.*)", lines[i])
+    if m:
+        return MissingQtModules(m.group(1).split())
+    return None
+
+
+PROBLEM_MATCHERS = [
+    _ghc_missing_module,
+    _ghc_version_mismatch,
+    _ocaml_missing_findlib_package,
+    _dart_missing_package,
+    _mix_unchecked_dependencies,
+    _qmake_missing_qt_modules,
+]
+
+
+def find_extra_problem(lines: List[str]) -> Optional[Problem]:
+    """Look for problems that buildlog-consultant does not know about.
+
+    Returns: the first problem found, or None
+    """
+    for i in range(len(lines)):
+        for matcher in PROBLEM_MATCHERS:
+            problem = matcher(lines, i)
+            if problem is not None:
+                return problem
+    return None
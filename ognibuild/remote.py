@@ -0,0 +1,394 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Run ognibuild actions on a remote worker.
+
+The protocol is newline-delimited JSON over a pair of streams (e.g. the
+stdin/stdout of "ssh host python3 -m ognibuild.remote").
+
+The client sends one request per line:
+
+    {"id": 1, "project": <base64 encoded tarball>, "actions": ["build", "test"],
+     "schroot": null, "resolve": "auto"}
+
+The worker replies with any number of log events, followed by one result
+event per action and finally a "done" event:
+
+    {"id": 1, "event": "log", "line": "..."}
+    {"id": 1, "event": "result", "action": "build", "status": "success"}
+    {"id": 1, "event": "result", "action": "test", "status": "failure",
+     "error": {"kind": "detailed", "retcode": 2, "argv": [...], "problem": "..."}}
+    {"id": 1, "event": "done"}
+"""
+
+import base64
+import io
+import json
+import logging
+import os
+import subprocess
+import sys
+import tarfile
+import tempfile
+from typing import Callable, List, Optional
+
+from . import DetailedFailure, UnidentifiedError
+
+
+ACTIONS = ["dist", "build", "clean", "test", "install", "info"]
+
+
+class ProtocolError(Exception):
+    """The remote end sent something we could not understand."""
+
+
+def pack_project(directory: str) -> str:
+    f = io.BytesIO()
+    with tarfile.open(fileobj=f, mode="w:gz") as tf:
+        tf.add(directory, arcname=".")
+    return base64.b64encode(f.getvalue()).decode("ascii")
+
+
+def _check_members(tf, directory):
+    """Check the members of a tarball before extracting them.
+
+    This is a fallback for Python versions without extraction filters.
+    """
+    directory = os.path.realpath(directory)
+
+    def inside(path):
+        path = os.path.realpath(os.path.join(directory, path))
+        return path == directory or path.startswith(directory + os.sep)
+
+    for member in tf.getmembers():
+        if os.path.isabs(member.name) or not inside(member.name):
+            raise ProtocolError(
+                "refusing to extract project: %s is outside the project"
+                % member.name)
+        if member.issym():
+            target = os.path.join(
+                os.path.dirname(member.name), member.linkname)
+            if os.path.isabs(member.linkname) or not inside(target):
+                raise ProtocolError(
+                    "refusing to extract project: %s links outside the "
+                    "project" % member.name)
+        elif member.islnk():
+            if os.path.isabs(member.linkname) or not inside(member.linkname):
+                raise ProtocolError(
+                    "refusing to extract project: %s links outside the "
+                    "project" % member.name)
+        elif member.isdev():
+            raise ProtocolError(
+                "refusing to extract project: %s is a special file"
+                % member.name)
+        yield member
+
+
+def unpack_project(data: str, directory: str) -> None:
+    f = io.BytesIO(base64.b64decode(data))
+    with tarfile.open(fileobj=f, mode="r:gz") as tf:
+        if not hasattr(tarfile, "data_filter"):
+            members = list(_check_members(tf, directory))
+            tf.extractall(directory, members=members)
+            return
+        try:
+            # The "data" filter rejects absolute paths, paths and links
+            # pointing outside of directory and special files.
+            tf.extractall(directory, filter="data")
+        except tarfile.FilterError as e:
+            raise ProtocolError("refusing to extract project: %s" % e)
+
+
+class _EventWriter(object):
+
+    def __init__(self, outf, request_id):
+        self.outf = outf
+        self.request_id = request_id
+
+    def send(self, event, **kwargs):
+        kwargs["id"] = self.request_id
+        kwargs["event"] = event
+        self.outf.write(json.dumps(kwargs) + "\n")
+        self.outf.flush()
+
+
+class _LogBuffer(object):
+    """Stand-in for sys.stdout.buffer that forwards lines as log events."""
+
+    def __init__(self, writer):
+        self.writer = writer
+
+    def write(self, data):
+        if data:
+            self.writer.send("log", line=data.decode("utf-8", "surrogateescape"))
+
+    def flush(self):
+        pass
+
+
+class _LogStdout(object):
+
+    def __init__(self, writer):
+        self.buffer = _LogBuffer(writer)
+
+    def write(self, text):
+        if text:
+            self.buffer.writer.send("log", line=text)
+
+    def flush(self):
+        pass
+
+
+class _LogHandler(logging.Handler):
+
+    def __init__(self, writer):
+        super(_LogHandler, self).__init__()
+        self.writer = writer
+
+    def emit(self, record):
+        self.writer.send("log", line=self.format(record) + "\n")
+
+
+def error_to_json(e):
+    if isinstance(e, DetailedFailure):
+        return {
+            "kind": "detailed",
+            "retcode": e.retcode,
+            "argv": e.argv,
+            "problem": str(e.error),
+        }
+    elif isinstance(e, UnidentifiedError):
+        return {
+            "kind": "unidentified",
+            "retcode": e.retcode,
+            "argv": e.argv,
+            "lines": e.lines[-20:],
+        }
+    else:
+        return {"kind": type(e).__name__, "description": str(e)}
+
+
+def _run_action(action, session, buildsystems, resolver, fixers, target_directory):
+    if action == "dist":
+        from .dist import run_dist
+
+        return run_dist(
+            session=session, buildsystems=buildsystems, resolver=resolver,
+            fixers=fixers, target_directory=target_directory)
+    elif action == "build":
+        from .build import run_build
+
+        run_build(session, buildsystems=buildsystems, resolver=resolver, fixers=fixers)
+    elif action == "clean":
+        from .clean import run_clean
+
+        run_clean(session, buildsystems=buildsystems, resolver=resolver, fixers=fixers)
+    elif action == "test":
+        from .test import run_test
+
+        run_test(session, buildsystems=buildsystems, resolver=resolver, fixers=fixers)
+    elif action == "install":
+        from .install import run_install
+
+        run_install(
+            session, buildsystems=buildsystems, resolver=resolver, fixers=fixers)
+    elif action == "info":
+        from .info import run_info
+
+        run_info(session, buildsystems=buildsystems, fixers=fixers)
+    else:
+        raise ProtocolError("unknown action %r" % action)
+
+
+def handle_request(request, writer):
+    from .buildlog import InstallFixer
+    from .buildsystem import detect_buildsystems
+    from .resolver import auto_resolver, native_resolvers
+
+    if request.get("schroot"):
+        from .session.schroot import SchrootSession
+
+        session = SchrootSession(request["schroot"])
+    else:
+        from .session.plain import PlainSession
+
+        session = PlainSession()
+
+    with tempfile.TemporaryDirectory() as td, session:
+        project_dir = os.path.join(td, "project")
+        os.mkdir(project_dir)
+        unpack_project(request["project"], project_dir)
+        external_dir, internal_dir = session.setup_from_directory(project_dir)
+        session.chdir(internal_dir)
+        resolve = request.get("resolve", "auto")
+        if resolve == "apt":
+            from .resolver.apt import AptResolver
+
+            resolver = AptResolver.from_session(session)
+        elif resolve == "native":
+            resolver = native_resolvers(session, user_local=True)
+        else:
+            resolver = auto_resolver(session)
+        fixers = [InstallFixer(resolver)]
+        buildsystems = list(detect_buildsystems(external_dir))
+        for action in request.get("actions", []):
+            try:
+                ret = _run_action(
+                    action, session, buildsystems, resolver, fixers, td)
+            except Exception as e:
+                writer.send(
+                    "result", action=action, status="failure",
+                    error=error_to_json(e))
+            else:
+                result = {}
+                if action == "dist" and ret is not None:
+                    with open(os.path.join(td, ret), "rb") as f:
+                        result["filename"] = ret
+                        result["data"] = base64.b64encode(f.read()).decode("ascii")
+                writer.send("result", action=action, status="success", **result)
+
+
+def serve(inf, outf) -> None:
+    """Serve requests from inf until EOF."""
+    for line in inf:
+        if not line.strip():
+            continue
+        try:
+            request = json.loads(line)
+        except ValueError as e:
+            writer = _EventWriter(outf, None)
+            writer.send("error", description="invalid request: %s" % e)
+            writer.send("done")
+            continue
+        writer = _EventWriter(outf, request.get("id"))
+        handler = _LogHandler(writer)
+        root_logger = logging.getLogger()
+        root_logger.addHandler(handler)
+        old_stdout = sys.stdout
+        sys.stdout = _LogStdout(writer)  # type: ignore
+        try:
+            handle_request(request, writer)
+        except Exception as e:
+            writer.send("error", description=str(e))
+        finally:
+            sys.stdout = old_stdout
+            root_logger.removeHandler(handler)
+        writer.send("done")
+
+
+class RemoteWorker(object):
+    """Client side of the worker protocol."""
+
+    def __init__(self, argv: List[str]):
+        self.argv = argv
+        self._next_id = 1
+        self._p: Optional[subprocess.Popen] = None
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.argv)
+
+    def __enter__(self):
+        self._p = subprocess.Popen(
+            self.argv, stdin=subprocess.PIPE, stdout=subprocess.PIPE)
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self._p.stdin.close()
+        self._p.wait()
+        self._p = None
+        return False
+
+    def run(
+        self,
+        directory: str,
+        actions: List[str],
+        schroot: Optional[str] = None,
+        resolve: str = "auto",
+        log_cb: Optional[Callable[[str], None]] = None,
+    ):
+        """Run actions on the worker.
+
+        Args:
+          directory: Path to the project to build
+          actions: Names of the actions to run, in order
+          schroot: Name of schroot to use on the worker
+          resolve: Dependency resolver to use on the worker
+          log_cb: Callback for log lines, as they arrive
+        Returns: list with one result dictionary per action
+        """
+        for action in actions:
+            if action not in ACTIONS:
+                raise ValueError("unknown action %r" % action)
+        request_id = self._next_id
+        self._next_id += 1
+        request = {
+            "id": request_id,
+            "project": pack_project(directory),
+            "actions": actions,
+            "schroot": schroot,
+            "resolve": resolve,
+        }
+        self._p.stdin.write(json.dumps(request).encode("utf-8") + b"\n")
+        self._p.stdin.flush()
+        return self._read_results(request_id, log_cb)
+
+    def _read_results(self, request_id, log_cb=None):
+        results = []
+        error = None
+        for line in self._p.stdout:
+            event = json.loads(line)
+            if event.get("id") not in (request_id, None):
+                raise ProtocolError("unexpected response for %r" % event.get("id"))
+            if event["event"] == "log":
+                if log_cb is not None:
+                    log_cb(event["line"])
+            elif event["event"] == "result":
+                results.append(event)
+            elif event["event"] == "error":
+                # Keep reading until the "done" event, so that it isn't
+                # mistaken for the reply to the next request.
+                error = ProtocolError(event["description"])
+            elif event["event"] == "done":
+                if error is not None:
+                    raise error
+                return results
+            else:
+                raise ProtocolError("unknown event %r" % event["event"])
+        raise ProtocolError("worker exited unexpectedly")
+
+
+def main(argv=None):
+    import argparse
+
+    parser = argparse.ArgumentParser("ognibuild.remote")
+    parser.add_argument("--debug", action="store_true")
+    args = parser.parse_args(argv)
+
+    if args.debug:
+        logging.basicConfig(level=logging.DEBUG, format="%(message)s")
+    else:
+        logging.basicConfig(level=logging.INFO, format="%(message)s")
+
+    # Make sure nothing but protocol messages end up on stdout.
+    outf = os.fdopen(os.dup(sys.stdout.fileno()), "w")
+    os.dup2(sys.stderr.fileno(), sys.stdout.fileno())
+    serve(sys.stdin, outf)
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -18,12 +18,80 @@
 
 import posixpath
 import re
+import shlex
 import subprocess
-from typing import Optional, List, Set
+from typing import Dict, Optional, List, Set, Type
 
 from . import Requirement
 
 
+def any_path_exists(session, patterns: List[str]) -> bool:
+    """Check whether any of a set of paths exists in a session.
+
+    Args:
+      session: Session to check in
+      patterns: Shell glob patterns; literal parts must be quoted
+    """
+    script = 'for f in %s; do [ -e "$f" ] && exit 0; done; exit 1' % (
+        " ".join(patterns))
+    p = session.Popen(
+        ["sh", "-c", script],
+        stdout=subprocess.DEVNULL,
+        stderr=subprocess.DEVNULL,
+    )
+    p.communicate()
+    return p.returncode == 0
+
+
+# Used if the linker search path can not be determined
+DEFAULT_LIBRARY_DIRS = ["/usr/local/lib", "/lib", "/usr/lib", "/lib/*", "/usr/lib/*"]
+
+
+def parse_ld_search_dirs(text: str) -> List[str]:
+    """Parse the SEARCH_DIR entries from the output of "ld --verbose"."""
+    ret = []
+    for m in re.finditer(r'SEARCH_DIR\("=?([^"]+)"\)', text):
+        if m.group(1) not in ret:
+            ret.append(m.group(1))
+    return ret
+
+
+def library_search_dirs(session) -> List[str]:
+    """Find the directories the linker searches for libraries.
+
+    Returns: list of directories, possibly as shell glob patterns
+    """
+    try:
+        output = session.check_output(["ld", "--verbose"])
+    except (subprocess.CalledProcessError, FileNotFoundError):
+        return DEFAULT_LIBRARY_DIRS
+    dirs = parse_ld_search_dirs(output.decode(errors="replace"))
+    return [shlex.quote(d) for d in dirs] or DEFAULT_LIBRARY_DIRS
+
+
+def shared_library_cached(session, filename: str, versioned: bool = True) -> bool:
+    """Check whether the dynamic linker cache has a library.
+
+    Args:
+      session: Session to check in
+      filename: Library filename, e.g. "libz.so"
+      versioned: Whether versioned variants like "libz.so.1" also match.
+        Only the unversioned name is usable for linking, and is usually
+        only installed by development packages.
+    """
+    for ldconfig in ["ldconfig", "/sbin/ldconfig"]:
+        try:
+            output = session.check_output([ldconfig, "-p"])
+        except (subprocess.CalledProcessError, FileNotFoundError):
+            continue
+        pattern = re.escape(filename) + (r"(\.[0-9]+)* " if versioned else " ")
+        for line in output.decode(errors="replace").splitlines():
+            if re.match(pattern, line.strip()):
+                return True
+        return False
+    return False
+
+
 class PythonPackageRequirement(Requirement):
 
     package: str
@@ -38,6 +106,12 @@ class PythonPackageRequirement(Requirement):
             specs = []
         self.specs = specs
 
+    @classmethod
+    def from_json(cls, payload):
+        payload = dict(payload)
+        payload["specs"] = [tuple(spec) for spec in payload["specs"]]
+        return cls(**payload)
+
     def __repr__(self):
         return "%s(%r, python_version=%r, specs=%r)" % (
             type(self).__name__,
@@ -72,6 +146,12 @@ class PythonPackageRequirement(Requirement):
             cmd = "python3"
         else:
             raise NotImplementedError
+        from .virtualenv import active_virtualenv
+
+        virtualenv = active_virtualenv(session)
+        if virtualenv is not None and cmd == "python3":
+            # Check the packages installed in the virtualenv
+            cmd = posixpath.join(virtualenv, "bin", "python")
         text = self.package + ",".join(["".join(spec) for spec in self.specs])
         p = session.Popen(
             [cmd, "-c", "import pkg_resources; pkg_resources.require(%r)" % text],
@@ -85,6 +165,7 @@ class PythonPackageRequirement(Requirement):
 class LatexPackageRequirement(Requirement):
 
     def __init__(self, package: str):
+        super(LatexPackageRequirement, self).__init__("latex-package")
         self.package = package
 
     def __repr__(self):
@@ -99,6 +180,7 @@ class PhpPackageRequirement(Requirement):
         min_version: Optional[str] = None,
         max_version: Optional[str] = None,
     ):
+        super(PhpPackageRequirement, self).__init__("php-package")
         self.package = package
         self.channel = channel
         self.min_version = min_version
@@ -126,13 +208,38 @@ class BinaryRequirement(Requirement):
         return "%s(%r)" % (type(self).__name__, self.binary_name)
 
     def met(self, session):
-        p = session.Popen(
-            ["which", self.binary_name],
-            stdout=subprocess.DEVNULL,
-            stderr=subprocess.DEVNULL,
-        )
-        p.communicate()
-        return p.returncode == 0
+        if posixpath.isabs(self.binary_name):
+            p = session.Popen(
+                ["test", "-x", self.binary_name],
+                stdout=subprocess.DEVNULL,
+                stderr=subprocess.DEVNULL,
+            )
+            p.communicate()
+            return p.returncode == 0
+        from .session import which
+
+        # which uses the PATH of the session, so this also finds binaries
+        # in e.g. a virtualenv or in directories added with setenv
+        return which(session, self.binary_name) is not None
+
+
+class PhpExtensionRequirement(Requirement):
+
+    extension: str
+
+    def __init__(self, extension: str):
+        super(PhpExtensionRequirement, self).__init__("php-extension")
+        self.extension = extension
+
+    def met(self, session):
+        output = session.check_output(["php", "-m"], cwd="/")
+        # "php -m" lists extensions with their canonical capitalization,
+        # e.g. "SimpleXML", while error messages tend to use lowercase names.
+        return self.extension.lower() in [
+            line.strip().lower() for line in output.decode().splitlines()]
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.extension)
 
 
 class PerlModuleRequirement(Requirement):
@@ -166,6 +273,19 @@ class VagueDependencyRequirement(Requirement):
         self.minimum_version = minimum_version
 
     def expand(self):
+        lname = self.name.lower()
+        if "::" in self.name and " " not in self.name:
+            yield PerlModuleRequirement(self.name)
+            return
+        if lname in ("jdk", "java jdk", "java development kit", "openjdk"):
+            yield JDKRequirement()
+            return
+        if lname in ("jre", "java", "java runtime", "java runtime environment"):
+            yield JRERequirement()
+            return
+        if lname in ("go", "golang"):
+            yield GoRequirement(self.minimum_version)
+            return
         if " " not in self.name:
             yield BinaryRequirement(self.name)
             yield LibraryRequirement(self.name)
@@ -188,7 +308,7 @@ class VagueDependencyRequirement(Requirement):
 
     def met(self, session):
         for x in self.expand():
-            if x.met(session):
+            if x.try_met(session):
                 return True
         return False
 
@@ -268,6 +388,17 @@ class CargoCrateRequirement(Requirement):
         self.features = features
         self.version = version
 
+    def json(self):
+        ret = super(CargoCrateRequirement, self).json()
+        ret["payload"]["features"] = sorted(self.features)
+        return ret
+
+    @classmethod
+    def from_json(cls, payload):
+        payload = dict(payload)
+        payload["features"] = set(payload["features"])
+        return cls(**payload)
+
     def __repr__(self):
         return "%s(%r, features=%r, version=%r)" % (
             type(self).__name__,
@@ -358,11 +489,12 @@ class GoPackageRequirement(Requirement):
     def __init__(self, package: str, version: Optional[str] = None):
         super(GoPackageRequirement, self).__init__("go-package")
         self.package = package
+        # Minimum version, without the "v" prefix
         self.version = version
 
     def __str__(self):
         if self.version:
-            return "go package: %s (= %s)" % (self.package, self.version)
+            return "go package: %s (>= %s)" % (self.package, self.version)
         return "go package: %s" % self.package
 
 
@@ -375,7 +507,324 @@ class GoRequirement(Requirement):
         self.version = version
 
     def __str__(self):
-        return "go %s" % self.version
+        if self.version:
+            return "go %s" % self.version
+        return "go"
+
+
+class NuGetPackageRequirement(Requirement):
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(NuGetPackageRequirement, self).__init__("nuget-package")
+        self.package = package
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "nuget package: %s (%s)" % (self.package, self.version)
+        return "nuget package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.package, self.version)
+
+
+class NimblePackageRequirement(Requirement):
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(NimblePackageRequirement, self).__init__("nimble-package")
+        self.package = package
+        # Version constraint, e.g. ">= 1.0" or "#head"
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "nimble package: %s (%s)" % (self.package, self.version)
+        return "nimble package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.package, self.version)
+
+    @classmethod
+    def from_str(cls, text):
+        m = re.match(r"^\s*([^\s<>=#~^]+)\s*(.*?)\s*$", text)
+        if not m:
+            raise ValueError(text)
+        return cls(m.group(1), m.group(2) or None)
+
+
+class CondaPackageRequirement(Requirement):
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(CondaPackageRequirement, self).__init__("conda-package")
+        self.package = package
+        # Match specification, e.g. ">=1.2" or "1.21.*"
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "conda package: %s (%s)" % (self.package, self.version)
+        return "conda package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.package, self.version)
+
+    @classmethod
+    def from_str(cls, text):
+        parts = text.split(None, 1)
+        if not parts:
+            raise ValueError(text)
+        return cls(parts[0], parts[1].strip() if len(parts) > 1 else None)
+
+
+class BazelModuleRequirement(Requirement):
+    """An external Bazel module or repository."""
+
+    module: str
+    version: Optional[str]
+
+    def __init__(self, module: str, version: Optional[str] = None):
+        super(BazelModuleRequirement, self).__init__("bazel-module")
+        self.module = module
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "bazel module: %s (%s)" % (self.module, self.version)
+        return "bazel module: %s" % self.module
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.module, self.version)
+
+
+class OcamlFindlibRequirement(Requirement):
+    """An OCaml library, as known to findlib (e.g. "lwt.unix")."""
+
+    package: str
+
+    def __init__(self, package: str):
+        super(OcamlFindlibRequirement, self).__init__("ocaml-findlib")
+        self.package = package
+
+    def __str__(self):
+        return "OCaml findlib package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+    def met(self, session):
+        p = session.Popen(
+            ["ocamlfind", "query", self.package],
+            stdout=subprocess.DEVNULL,
+            stderr=subprocess.DEVNULL,
+        )
+        p.communicate()
+        return p.returncode == 0
+
+
+class HexPackageRequirement(Requirement):
+    """A package from hex.pm, the Elixir and Erlang package registry."""
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(HexPackageRequirement, self).__init__("hex-package")
+        self.package = package
+        # Version requirement, e.g. "~> 1.0"
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "hex package: %s (%s)" % (self.package, self.version)
+        return "hex package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.package, self.version)
+
+    def met(self, session):
+        # mix fetches dependencies into the deps directory of the project
+        if session.exists(posixpath.join("deps", self.package)):
+            return True
+        # Erlang libraries installed system-wide, e.g. by Debian packages
+        try:
+            p = session.Popen(
+                ["erl", "-noshell", "-eval",
+                 "case code:lib_dir(%s) of {error, _} -> halt(1); "
+                 "_ -> halt(0) end." % self.package],
+                stdout=subprocess.DEVNULL,
+                stderr=subprocess.DEVNULL,
+            )
+        except FileNotFoundError:
+            # Without Erlang, there are no system-wide Erlang libraries
+            return False
+        p.communicate()
+        return p.returncode == 0
+
+
+class DartPackageRequirement(Requirement):
+    """A package from pub.dev, for Dart and Flutter projects."""
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(DartPackageRequirement, self).__init__("dart-package")
+        self.package = package
+        # Version constraint, e.g. "^1.0.0"
+        self.version = version
+
+    def __str__(self):
+        if self.version:
+            return "dart package: %s (%s)" % (self.package, self.version)
+        return "dart package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.package, self.version)
+
+    def met(self, session):
+        import json
+
+        try:
+            output = session.check_output(["dart", "pub", "deps", "--json"])
+        except (FileNotFoundError, subprocess.CalledProcessError):
+            return False
+        try:
+            packages = json.loads(output).get("packages", [])
+        except ValueError:
+            return False
+        return any(p.get("name") == self.package for p in packages)
+
+
+class NixPackageRequirement(Requirement):
+    """A package from nixpkgs, identified by its attribute path."""
+
+    attribute: str
+
+    def __init__(self, attribute: str):
+        super(NixPackageRequirement, self).__init__("nix-package")
+        self.attribute = attribute
+
+    def __str__(self):
+        return "nix package: %s" % self.attribute
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.attribute)
+
+
+class ConanPackageRequirement(Requirement):
+    """A C/C++ package from ConanCenter."""
+
+    package: str
+    version: Optional[str]
+
+    def __init__(self, package: str, version: Optional[str] = None):
+        super(ConanPackageRequirement, self).__init__("conan-package")
+        self.package = package
+        self.version = version
+
+    @property
+    def reference(self) -> str:
+        """Conan reference, with a version range if no version is set."""
+        return "%s/%s" % (self.package, self.version or "[*]")
+
+    def __str__(self):
+        return "conan package: %s" % self.reference
+
+    def __repr__(self):
+        return "%s(%r, version=%r)" % (
+            type(self).__name__, self.package, self.version)
+
+
+class VcpkgPortRequirement(Requirement):
+    """A C/C++ library from the vcpkg ports tree."""
+
+    port: str
+
+    def __init__(self, port: str):
+        super(VcpkgPortRequirement, self).__init__("vcpkg-port")
+        self.port = port
+
+    def __str__(self):
+        return "vcpkg port: %s" % self.port
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.port)
+
+
+class ApkPackageRequirement(Requirement):
+    """A package from the Alpine Linux repositories."""
+
+    package: str
+
+    def __init__(self, package: str):
+        super(ApkPackageRequirement, self).__init__("apk-package")
+        self.package = package
+
+    def __str__(self):
+        return "apk package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+
+class RpmPackageRequirement(Requirement):
+    """A package from the repositories of an RPM-based distribution."""
+
+    package: str
+
+    def __init__(self, package: str):
+        super(RpmPackageRequirement, self).__init__("rpm-package")
+        self.package = package
+
+    def __str__(self):
+        return "rpm package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+
+class FreeBSDPackageRequirement(Requirement):
+    """A package from the FreeBSD package repositories."""
+
+    package: str
+
+    def __init__(self, package: str):
+        super(FreeBSDPackageRequirement, self).__init__("freebsd-package")
+        self.package = package
+
+    def __str__(self):
+        return "FreeBSD package: %s" % self.package
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.package)
+
+
+class SnapPackageRequirement(Requirement):
+    """A snap from the Snap Store."""
+
+    snap: str
+    classic: bool
+
+    def __init__(self, snap: str, classic: bool = False):
+        super(SnapPackageRequirement, self).__init__("snap")
+        self.snap = snap
+        self.classic = classic
+
+    def __str__(self):
+        return "snap: %s" % self.snap
+
+    def __repr__(self):
+        return "%s(%r, classic=%r)" % (
+            type(self).__name__, self.snap, self.classic)
 
 
 class DhAddonRequirement(Requirement):
@@ -400,19 +849,42 @@ class RPackageRequirement(Requirement):
 
     package: str
     minimum_version: Optional[str]
+    # "cran" or "bioconductor", if known
+    repository: Optional[str]
 
-    def __init__(self, package: str, minimum_version: Optional[str] = None):
+    def __init__(self, package: str, minimum_version: Optional[str] = None,
+                 repository: Optional[str] = None):
         super(RPackageRequirement, self).__init__("r-package")
         self.package = package
         self.minimum_version = minimum_version
+        self.repository = repository
 
     def __repr__(self):
+        if self.repository:
+            return "%s(%r, minimum_version=%r, repository=%r)" % (
+                type(self).__name__, self.package, self.minimum_version,
+                self.repository)
         return "%s(%r, minimum_version=%r)" % (
             type(self).__name__,
             self.package,
             self.minimum_version,
         )
 
+    def met(self, session):
+        script = "if (!requireNamespace('%s', quietly=TRUE)) quit(status=1)" % (
+            self.package)
+        if self.minimum_version:
+            script += (
+                "; if (packageVersion('%s') < '%s') quit(status=1)" % (
+                    self.package, self.minimum_version))
+        p = session.Popen(
+            ["Rscript", "-e", script],
+            stdout=subprocess.DEVNULL,
+            stderr=subprocess.DEVNULL,
+        )
+        p.communicate()
+        return p.returncode == 0
+
     def __str__(self):
         if self.minimum_version:
             return "R package: %s (>= %s)" % (self.package, self.minimum_version)
@@ -454,6 +926,20 @@ class OctavePackageRequirement(Requirement):
         else:
             return "Octave package: %s" % (self.package,)
 
+    def met(self, session):
+        script = "p = pkg('list', '%s'); failed = isempty(p);" % self.package
+        if self.minimum_version:
+            script += (
+                " failed = failed || ~compare_versions(p{1}.version, '%s', '>=');"
+                % self.minimum_version)
+        p = session.Popen(
+            ["octave-cli", "--no-gui", "--eval", script + " exit(failed)"],
+            stdout=subprocess.DEVNULL,
+            stderr=subprocess.DEVNULL,
+        )
+        p.communicate()
+        return p.returncode == 0
+
     @classmethod
     def from_str(cls, text):
         # TODO(jelmer): More complex parser
@@ -485,6 +971,14 @@ class StaticLibraryRequirement(Requirement):
         self.library = library
         self.filename = filename
 
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.library, self.filename)
+
+    def met(self, session):
+        return any_path_exists(session, [
+            posixpath.join(d, shlex.quote(self.filename))
+            for d in library_search_dirs(session)])
+
 
 class RubyFileRequirement(Requirement):
 
@@ -534,6 +1028,33 @@ class CMakefileRequirement(Requirement):
         self.filename = filename
         self.version = version
 
+    def __repr__(self):
+        return "%s(%r, version=%r)" % (
+            type(self).__name__, self.filename, self.version)
+
+    def search_dirs(self, prefixes: List[str]) -> List[str]:
+        """Directories (as shell globs) that CMake searches for the file."""
+        ret = ["/usr/share/cmake*/Modules"]
+        for prefix in prefixes:
+            ret.extend([
+                posixpath.join(prefix, "lib", "cmake", "*"),
+                posixpath.join(prefix, "lib", "*", "cmake", "*"),
+                posixpath.join(prefix, "share", "cmake", "*"),
+                posixpath.join(prefix, "share", "*", "cmake"),
+                posixpath.join(prefix, "cmake"),
+            ])
+        return ret
+
+    def met(self, session):
+        # TODO(jelmer): Check self.version
+        prefix_path = session.getenv("CMAKE_PREFIX_PATH")
+        prefixes = prefix_path.split(":") if prefix_path else []
+        prefixes.extend(["/usr/local", "/usr"])
+        prefixes = [shlex.quote(prefix) for prefix in prefixes if prefix]
+        return any_path_exists(session, [
+            posixpath.join(d, shlex.quote(self.filename))
+            for d in self.search_dirs(prefixes)])
+
 
 class HaskellPackageRequirement(Requirement):
 
@@ -546,8 +1067,39 @@ class HaskellPackageRequirement(Requirement):
 
     @classmethod
     def from_string(cls, text):
-        parts = text.split()
-        return cls(parts[0], specs=parts[1:])
+        """Parse a dependency like "text >= 1.2 && < 2" or "base ^>=4.14".
+
+        Unions ("||") can not be expressed as a list of specs, so they are
+        dropped.
+        """
+        m = re.match(r"\s*([^\s<>=^:(]+)(?::\S+)?\s*(.*)", text)
+        package, constraint = m.group(1), m.group(2).strip()
+        if not constraint or "||" in constraint:
+            return cls(package)
+        specs = []
+        for part in constraint.split("&&"):
+            part = re.sub(r"\s+", "", part).strip("()")
+            if part.startswith("^>="):
+                # PVP major bounds: ^>=1.2.3 means >=1.2.3 && <1.3
+                version = part[3:]
+                major = [int(p) for p in version.split(".")[:2]] + [0]
+                specs.extend(
+                    [">=" + version, "<%d.%d" % (major[0], major[1] + 1)])
+            elif part not in ("", "-any"):
+                specs.append(part)
+        return cls(package, specs=specs)
+
+
+class HaskellModuleRequirement(Requirement):
+
+    module: str
+
+    def __init__(self, module: str):
+        super(HaskellModuleRequirement, self).__init__("haskell-module")
+        self.module = module
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.module)
 
 
 class MavenArtifactRequirement(Requirement):
@@ -595,6 +1147,28 @@ class MavenArtifactRequirement(Requirement):
             raise ValueError("invalid number of parts to artifact %r" % parts)
         return cls(group_id, artifact_id, version, kind)
 
+    def repository_paths(self, repository: str) -> List[str]:
+        """Shell glob patterns for the artifact in a Maven repository."""
+        version = shlex.quote(self.version) if self.version else "*"
+        filename = "%s-%s%s" % (
+            shlex.quote(self.artifact_id), version,
+            shlex.quote("." + (self.kind or "jar")))
+        return [posixpath.join(
+            repository, shlex.quote(self.group_id.replace(".", "/")),
+            shlex.quote(self.artifact_id), version, filename)]
+
+    def met(self, session):
+        repositories = ['"$HOME"/.m2/repository', "/usr/share/maven-repo"]
+        # Set by e.g. provision_maven_repository
+        m = re.search(
+            r"-Dmaven\.repo\.local=(\S+)", session.getenv("MAVEN_OPTS") or "")
+        if m:
+            repositories.insert(0, shlex.quote(m.group(1)))
+        patterns = []
+        for repository in repositories:
+            patterns.extend(self.repository_paths(repository))
+        return any_path_exists(session, patterns)
+
 
 class GnomeCommonRequirement(Requirement):
     def __init__(self):
@@ -626,10 +1200,58 @@ class JRERequirement(Requirement):
         super(JRERequirement, self).__init__("jre")
 
 
+def qt_query(session, variable: str) -> Optional[str]:
+    """Look up a Qt installation variable, e.g. QT_INSTALL_LIBS.
+
+    Returns: the value, or None if no Qt installation was found
+    """
+    for cmd in [["qmake6", "-query"], ["qmake", "-query"],
+                ["qtpaths6", "--query"], ["qtpaths", "--query"]]:
+        try:
+            output = session.check_output(cmd + [variable])
+        except (subprocess.CalledProcessError, FileNotFoundError):
+            continue
+        value = output.decode().strip()
+        if value and value != "**Unknown**":
+            return value
+    return None
+
+
 class QTRequirement(Requirement):
     def __init__(self):
         super(QTRequirement, self).__init__("qt")
 
+    def met(self, session):
+        return qt_query(session, "QT_VERSION") is not None
+
+
+class QtModuleRequirement(Requirement):
+    """A Qt module, as listed in the QT variable of a qmake project."""
+
+    module: str
+
+    def __init__(self, module: str):
+        super(QtModuleRequirement, self).__init__("qt-module")
+        self.module = module
+
+    def __str__(self):
+        return "Qt module: %s" % self.module
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.module)
+
+    def met(self, session):
+        # Library names don't map mechanically to module names (e.g.
+        # "quickcontrols2" is libQt5QuickControls2), but every module
+        # ships a qmake module definition named after it.
+        archdata = qt_query(session, "QT_INSTALL_ARCHDATA")
+        if archdata is None:
+            return False
+        # Private modules, e.g. "core-private", use an underscore
+        name = "qt_lib_%s.pri" % self.module.replace("-", "_")
+        return any_path_exists(session, [posixpath.join(
+            shlex.quote(archdata), "mkspecs", "modules", shlex.quote(name))])
+
 
 class X11Requirement(Requirement):
     def __init__(self):
@@ -667,6 +1289,7 @@ class LibtoolRequirement(Requirement):
 
 class IntrospectionTypelibRequirement(Requirement):
     def __init__(self, library):
+        super(IntrospectionTypelibRequirement, self).__init__("introspection-typelib")
         self.library = library
 
 
@@ -716,3 +1339,125 @@ class BoostComponentRequirement(Requirement):
     def __init__(self, name):
         super(BoostComponentRequirement, self).__init__("boost-component")
         self.name = name
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.name)
+
+    def met(self, session):
+        # The runtime libraries (e.g. libboost_regex.so.1.83.0) are not
+        # enough to build against; look for the files from the development
+        # package.
+        basename = "libboost_%s" % self.name
+        if shared_library_cached(session, basename + ".so", versioned=False):
+            return True
+        patterns = []
+        for d in library_search_dirs(session):
+            patterns.append(posixpath.join(d, shlex.quote(basename + ".so")))
+            patterns.append(posixpath.join(d, shlex.quote(basename + ".a")))
+            patterns.append(posixpath.join(
+                d, "cmake", shlex.quote("boost_%s-" % self.name) + "*",
+                shlex.quote("boost_%s-config.cmake" % self.name)))
+        return any_path_exists(session, patterns)
+
+
+class UnknownRequirementFamily(Exception):
+    """No requirement class is registered for a family."""
+
+    def __init__(self, family):
+        self.family = family
+
+
+# Requirement classes by family, for requirement_from_json
+REQUIREMENT_FAMILIES: Dict[str, Type[Requirement]] = {
+    "python-package": PythonPackageRequirement,
+    "latex-package": LatexPackageRequirement,
+    "php-package": PhpPackageRequirement,
+    "php-extension": PhpExtensionRequirement,
+    "binary": BinaryRequirement,
+    "perl-module": PerlModuleRequirement,
+    "vague": VagueDependencyRequirement,
+    "npm-package": NodePackageRequirement,
+    "perl-predeclared": PerlPreDeclaredRequirement,
+    "npm-module": NodeModuleRequirement,
+    "cargo-crate": CargoCrateRequirement,
+    "pkg-config": PkgConfigRequirement,
+    "path": PathRequirement,
+    "c-header": CHeaderRequirement,
+    "javascript-runtime": JavaScriptRuntimeRequirement,
+    "vala": ValaPackageRequirement,
+    "gem": RubyGemRequirement,
+    "go-package": GoPackageRequirement,
+    "go": GoRequirement,
+    "nuget-package": NuGetPackageRequirement,
+    "nimble-package": NimblePackageRequirement,
+    "conda-package": CondaPackageRequirement,
+    "bazel-module": BazelModuleRequirement,
+    "ocaml-findlib": OcamlFindlibRequirement,
+    "hex-package": HexPackageRequirement,
+    "dart-package": DartPackageRequirement,
+    "nix-package": NixPackageRequirement,
+    "conan-package": ConanPackageRequirement,
+    "vcpkg-port": VcpkgPortRequirement,
+    "apk-package": ApkPackageRequirement,
+    "rpm-package": RpmPackageRequirement,
+    "freebsd-package": FreeBSDPackageRequirement,
+    "snap": SnapPackageRequirement,
+    "dh-addon": DhAddonRequirement,
+    "php-class": PhpClassRequirement,
+    "r-package": RPackageRequirement,
+    "octave-package": OctavePackageRequirement,
+    "lib": LibraryRequirement,
+    "static-lib": StaticLibraryRequirement,
+    "ruby-file": RubyFileRequirement,
+    "xml-entity": XmlEntityRequirement,
+    "sprockets-file": SprocketsFileRequirement,
+    "java-class": JavaClassRequirement,
+    "cmake-file": CMakefileRequirement,
+    "haskell-package": HaskellPackageRequirement,
+    "haskell-module": HaskellModuleRequirement,
+    "maven-artifact": MavenArtifactRequirement,
+    "gnome-common": GnomeCommonRequirement,
+    "jdk-file": JDKFileRequirement,
+    "jdk": JDKRequirement,
+    "jre": JRERequirement,
+    "qt": QTRequirement,
+    "qt-module": QtModuleRequirement,
+    "x11": X11Requirement,
+    "ca-cert": CertificateAuthorityRequirement,
+    "perl-file": PerlFileRequirement,
+    "autoconf-macro": AutoconfMacroRequirement,
+    "libtool": LibtoolRequirement,
+    "introspection-typelib": IntrospectionTypelibRequirement,
+    "python-module": PythonModuleRequirement,
+    "boost-component": BoostComponentRequirement,
+}
+
+# Families whose classes live in modules with optional dependencies
+_LAZY_REQUIREMENT_FAMILIES = {
+    "apt": ("ognibuild.resolver.apt", "AptRequirement"),
+}
+
+
+def register_requirement_family(family: str, cls: Type[Requirement]) -> None:
+    """Register a requirement class, so it can be deserialized."""
+    REQUIREMENT_FAMILIES[family] = cls
+
+
+def requirement_from_json(data) -> Requirement:
+    """Deserialize a requirement, as serialized by Requirement.json().
+
+    Raises:
+      UnknownRequirementFamily: if the family is not known
+    """
+    family = data["family"]
+    try:
+        cls = REQUIREMENT_FAMILIES[family]
+    except KeyError:
+        try:
+            modname, clsname = _LAZY_REQUIREMENT_FAMILIES[family]
+        except KeyError:
+            raise UnknownRequirementFamily(family)
+        import importlib
+
+        cls = getattr(importlib.import_module(modname), clsname)
+    return cls.from_json(data["payload"])
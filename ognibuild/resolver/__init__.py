@@ -17,9 +17,13 @@
 
 
 import logging
+import posixpath
+import shlex
 import subprocess
+from typing import List
 from .. import UnidentifiedError
 from ..fix_build import run_detecting_problems
+from ..session import get_home, prepend_path
 
 
 class UnsatisfiedRequirements(Exception):
@@ -37,10 +41,171 @@ class Resolver(object):
     def explain(self, requirements):
         raise NotImplementedError(self.explain)
 
+    def trace(self, requirement):
+        """Describe the steps taken to find a way to install a requirement.
+
+        Returns: list of ResolutionStep objects
+        """
+        from .explanation import ResolutionStep
+
+        try:
+            commands = list(self.explain([requirement]))
+        except NotImplementedError:
+            commands = []
+        if not commands:
+            return [ResolutionStep(
+                str(self), "unsupported",
+                "unable to install %s requirements" % requirement.family)]
+        ret = []
+        for command, reqs in commands:
+            if isinstance(command, list):
+                command = shlex.join(command)
+            ret.append(ResolutionStep(
+                str(self), "candidate", "would run: %s" % command,
+                {"command": command}))
+        return ret
+
     def env(self):
         return {}
 
 
+class PackageMappingResolver(Resolver):
+    """Base class for resolvers that map requirements onto packages.
+
+    Subclasses implement resolve(), which returns the package for a
+    requirement (or None), and _explain_command().
+    """
+
+    def _resolve_all(self, requirements):
+        resolved = []
+        missing = []
+        for requirement in requirements:
+            package = self.resolve(requirement)
+            if package is None:
+                missing.append(requirement)
+            else:
+                resolved.append((requirement, package))
+        return resolved, missing
+
+    def _explain_command(self, package) -> List[str]:
+        """Command that would install a single package."""
+        raise NotImplementedError(self._explain_command)
+
+    def explain(self, requirements):
+        resolved, unused_missing = self._resolve_all(requirements)
+        for requirement, package in resolved:
+            yield (self._explain_command(package), [requirement])
+
+
+class PackageManager(object):
+    """Search and install packages with a system package manager.
+
+    Subclasses set name and implement _provides() and install_command().
+    """
+
+    name: str
+
+    def __init__(self, session, prefix=None):
+        self.session = session
+        if prefix is None:
+            prefix = []
+        self.prefix = prefix
+        self._provides_cache = {}
+
+    def __repr__(self):
+        return "%s(%r, prefix=%r)" % (
+            type(self).__name__, self.session, self.prefix)
+
+    @classmethod
+    def from_session(cls, session):
+        from ..session import get_user
+
+        if get_user(session) != "root":
+            prefix = ["sudo"]
+        else:
+            prefix = []
+        return cls(session, prefix=prefix)
+
+    def provides(self, query: str) -> List[str]:
+        """Find the packages that provide something.
+
+        Args:
+          query: What to look for; a path, or something in the package
+            manager's own syntax (e.g. "pkgconfig(zlib)" for dnf)
+        Returns: list of package names, in order of preference
+        """
+        if query not in self._provides_cache:
+            self._provides_cache[query] = self._provides(query)
+        return self._provides_cache[query]
+
+    def _provides(self, query: str) -> List[str]:
+        raise NotImplementedError(self._provides)
+
+    def install_command(self, packages: List[str]) -> List[str]:
+        raise NotImplementedError(self.install_command)
+
+    def install(self, packages: List[str]) -> None:
+        args = self.install_command(packages)
+        logging.info("%s: running %r", self.name, args)
+        self.session.check_call(args, cwd="/", user="root")
+
+
+class PackageManagerResolver(Resolver):
+    """Base class for resolvers that install system packages.
+
+    Subclasses set manager_cls and implement resolve(), which returns a
+    requirement with the name of a package in its package attribute, or
+    None. All packages are installed with a single package manager run.
+    """
+
+    manager_cls: type
+
+    def __init__(self, manager: PackageManager):
+        self.manager = manager
+
+    def __str__(self):
+        return self.manager.name
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.manager)
+
+    @classmethod
+    def from_session(cls, session):
+        return cls(cls.manager_cls.from_session(session))
+
+    def install(self, requirements):
+        missing = []
+        for req in requirements:
+            if not req.try_met(self.manager.session):
+                missing.append(req)
+        if not missing:
+            return
+        still_missing = []
+        packages = []
+        for m in missing:
+            package_req = self.resolve(m)
+            if package_req is None:
+                still_missing.append(m)
+            elif package_req.package not in packages:
+                packages.append(package_req.package)
+        if packages:
+            self.manager.install(packages)
+        if still_missing:
+            raise UnsatisfiedRequirements(still_missing)
+
+    def explain(self, requirements):
+        resolved = []
+        for r in requirements:
+            package_req = self.resolve(r)
+            if package_req is not None:
+                resolved.append((r, package_req))
+        if resolved:
+            yield (
+                self.manager.install_command(
+                    sorted(set(p.package for (r, p) in resolved))),
+                [r for (r, p) in resolved])
+
+
 class CPANResolver(Resolver):
     def __init__(self, session, user_local=False, skip_tests=True):
         self.session = session
@@ -102,6 +267,81 @@ class CPANResolver(Resolver):
             raise UnsatisfiedRequirements(missing)
 
 
+class CpanmResolver(Resolver):
+    """Install Perl modules with cpanminus.
+
+    Only used if cpanm is available; otherwise the requirements are left
+    for e.g. CPANResolver.
+    """
+
+    def __init__(self, session, user_local=False, skip_tests=True):
+        self.session = session
+        self.user_local = user_local
+        self.skip_tests = skip_tests
+
+    def __str__(self):
+        return "cpanm"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _local_lib(self):
+        return posixpath.join(get_home(self.session), "perl5")
+
+    def _cmd(self, reqs, local_lib=None):
+        ret = ["cpanm"]
+        if self.skip_tests:
+            ret.append("--notest")
+        if local_lib is not None:
+            ret.extend(["--local-lib", local_lib])
+        ret.extend([req.module for req in reqs])
+        return ret
+
+    def _available(self):
+        from ..requirements import BinaryRequirement
+
+        return BinaryRequirement("cpanm").try_met(self.session)
+
+    def explain(self, requirements):
+        from ..requirements import PerlModuleRequirement
+
+        perlreqs = [
+            req for req in requirements
+            if isinstance(req, PerlModuleRequirement)]
+        if perlreqs and self._available():
+            local_lib = "~/perl5" if self.user_local else None
+            yield (self._cmd(perlreqs, local_lib), perlreqs)
+
+    def install(self, requirements):
+        from ..requirements import PerlModuleRequirement
+
+        missing = []
+        perlreqs = []
+        for requirement in requirements:
+            if isinstance(requirement, PerlModuleRequirement):
+                perlreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if perlreqs and not self._available():
+            missing.extend(perlreqs)
+            perlreqs = []
+        if perlreqs:
+            if self.user_local:
+                user = None
+                local_lib = self._local_lib()
+                prepend_path(
+                    self.session, posixpath.join(local_lib, "lib", "perl5"),
+                    "PERL5LIB")
+            else:
+                user = "root"
+                local_lib = None
+            cmd = self._cmd(perlreqs, local_lib)
+            logging.info("cpanm: running %r", cmd)
+            run_detecting_problems(self.session, cmd, user=user)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+
 class TlmgrResolver(Resolver):
     def __init__(self, session, repository: str, user_local=False):
         self.session = session
@@ -170,13 +410,17 @@ class CTANResolver(TlmgrResolver):
 
 
 class RResolver(Resolver):
+
+    # Name of the repository, as used in RPackageRequirement.repository
+    repository: str
+
     def __init__(self, session, repos, user_local=False):
         self.session = session
         self.repos = repos
         self.user_local = user_local
 
     def __str__(self):
-        return "cran"
+        return self.repository
 
     def __repr__(self):
         return "%s(%r, %r)" % (type(self).__name__, self.session, self.repos)
@@ -189,33 +433,38 @@ class RResolver(Resolver):
             "install.packages('%s', repos=%r)" % (req.package, self.repos),
         ]
 
-    def explain(self, requirements):
+    def _rreqs(self, requirements):
         from ..requirements import RPackageRequirement
 
         rreqs = []
+        missing = []
         for requirement in requirements:
-            if not isinstance(requirement, RPackageRequirement):
-                continue
-            rreqs.append(requirement)
-        if rreqs:
-            yield ([self._cmd(req) for req in rreqs])
+            if (isinstance(requirement, RPackageRequirement)
+                    and requirement.repository in (None, self.repository)):
+                rreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        return rreqs, missing
 
-    def install(self, requirements):
-        from ..requirements import RPackageRequirement
+    def explain(self, requirements):
+        rreqs, unused_missing = self._rreqs(requirements)
+        for req in rreqs:
+            yield (self._cmd(req), [req])
 
+    def install(self, requirements):
         if self.user_local:
             user = None
         else:
             user = "root"
 
-        missing = []
-        for requirement in requirements:
-            if not isinstance(requirement, RPackageRequirement):
-                missing.append(requirement)
-                continue
+        rreqs, missing = self._rreqs(requirements)
+        for requirement in rreqs:
             cmd = self._cmd(requirement)
             logging.info("RResolver(%r): running %r", self.repos, cmd)
             run_detecting_problems(self.session, cmd, user=user)
+            # R only warns if a package is not available in the repository
+            if requirement.try_met(self.session) is False:
+                missing.append(requirement)
         if missing:
             raise UnsatisfiedRequirements(missing)
 
@@ -232,22 +481,20 @@ class OctaveForgeResolver(Resolver):
         return "%s(%r)" % (type(self).__name__, self.session)
 
     def _cmd(self, req):
-        # TODO(jelmer: Handle self.user_local
-        return ["octave-cli", "--eval", "pkg install -forge %s" % req.package]
+        scope = "-local" if self.user_local else "-global"
+        return ["octave-cli", "--no-gui", "--eval",
+                "pkg install -forge %s %s" % (scope, req.package)]
 
     def explain(self, requirements):
         from ..requirements import OctavePackageRequirement
 
-        rreqs = []
         for requirement in requirements:
             if not isinstance(requirement, OctavePackageRequirement):
                 continue
-            rreqs.append(requirement)
-        if rreqs:
-            yield ([self._cmd(req) for req in rreqs])
+            yield (self._cmd(requirement), [requirement])
 
     def install(self, requirements):
-        from ..requirements import OctavePackageRequirement
+        from ..requirements import BinaryRequirement, OctavePackageRequirement
 
         if self.user_local:
             user = None
@@ -255,9 +502,19 @@ class OctaveForgeResolver(Resolver):
             user = "root"
 
         missing = []
+        octave_reqs = []
         for requirement in requirements:
             if not isinstance(requirement, OctavePackageRequirement):
                 missing.append(requirement)
+            else:
+                octave_reqs.append(requirement)
+        if octave_reqs:
+            octave = BinaryRequirement("octave-cli")
+            if not octave.try_met(self.session):
+                missing.extend(octave_reqs)
+                octave_reqs = []
+        for requirement in octave_reqs:
+            if requirement.try_met(self.session):
                 continue
             cmd = self._cmd(requirement)
             logging.info("Octave: running %r", cmd)
@@ -267,6 +524,9 @@ class OctaveForgeResolver(Resolver):
 
 
 class CRANResolver(RResolver):
+
+    repository = "cran"
+
     def __init__(self, session, user_local=False):
         super(CRANResolver, self).__init__(
             session, "http://cran.r-project.org", user_local=user_local
@@ -274,11 +534,24 @@ class CRANResolver(RResolver):
 
 
 class BioconductorResolver(RResolver):
+    """Install R packages from Bioconductor, using BiocManager."""
+
+    repository = "bioconductor"
+
     def __init__(self, session, user_local=False):
         super(BioconductorResolver, self).__init__(
-            session, "https://hedgehog.fhcrc.org/bioconductor", user_local=user_local
+            session, "https://bioconductor.org", user_local=user_local
         )
 
+    def _cmd(self, req):
+        return [
+            "R",
+            "-e",
+            "if (!requireNamespace('BiocManager', quietly=TRUE)) "
+            "install.packages('BiocManager', repos='https://cloud.r-project.org'); "
+            "BiocManager::install('%s', update=FALSE, ask=FALSE)" % req.package,
+        ]
+
 
 class HackageResolver(Resolver):
     def __init__(self, session, user_local=False):
@@ -340,6 +613,13 @@ class PypiResolver(Resolver):
         return "%s(%r)" % (type(self).__name__, self.session)
 
     def _cmd(self, reqs):
+        from ..virtualenv import active_virtualenv
+
+        if active_virtualenv(self.session):
+            # The pip script may not be in the virtualenv, but the system
+            # pip module installs into it all the same.
+            return ["python3", "-m", "pip", "install"] + [
+                req.package for req in reqs]
         extra_args = []
         if self.user_local:
             extra_args.append("--user")
@@ -347,8 +627,9 @@ class PypiResolver(Resolver):
 
     def install(self, requirements):
         from ..requirements import PythonPackageRequirement
+        from ..virtualenv import active_virtualenv
 
-        if self.user_local:
+        if self.user_local or active_virtualenv(self.session):
             user = None
         else:
             user = "root"
@@ -379,7 +660,134 @@ class PypiResolver(Resolver):
             yield (self._cmd(pyreqs), pyreqs)
 
 
+# Python command-line tools, by the name of the binary, and the PyPI
+# package that provides them. Only tools that do not need to be importable
+# from the project's Python environment belong here.
+PYPI_COMMAND_PACKAGES = {
+    "black": "black",
+    "http": "httpie",
+    "https": "httpie",
+    "flake8": "flake8",
+    "isort": "isort",
+    "ruff": "ruff",
+    "tox": "tox",
+    "nox": "nox",
+    "poetry": "poetry",
+    "pdm": "pdm",
+    "hatch": "hatch",
+    "twine": "twine",
+    "pre-commit": "pre-commit",
+    "codespell": "codespell",
+    "yamllint": "yamllint",
+    "mkdocs": "mkdocs",
+    "cookiecutter": "cookiecutter",
+    "bump2version": "bump2version",
+    "pyproject-build": "build",
+}
+
+
+class PipxResolver(Resolver):
+    """Install Python command-line tools in isolated environments with pipx.
+
+    Only used if pipx is available. Tools are installed for the current
+    user if user_local is set, and system-wide under /opt/pipx with the
+    binaries in /usr/local/bin otherwise.
+    """
+
+    def __init__(self, session, user_local=False):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "pipx"
+
+    def __repr__(self):
+        return "%s(%r, user_local=%r)" % (
+            type(self).__name__, self.session, self.user_local)
+
+    def _packages(self, requirements):
+        from ..requirements import BinaryRequirement
+
+        packages = []
+        missing = []
+        for requirement in requirements:
+            if (isinstance(requirement, BinaryRequirement)
+                    and requirement.binary_name in PYPI_COMMAND_PACKAGES):
+                packages.append(
+                    (requirement, PYPI_COMMAND_PACKAGES[requirement.binary_name]))
+            else:
+                missing.append(requirement)
+        return packages, missing
+
+    def _available(self):
+        from ..requirements import BinaryRequirement
+
+        return BinaryRequirement("pipx").try_met(self.session)
+
+    def _env(self):
+        if self.user_local:
+            return {}
+        return {"PIPX_HOME": "/opt/pipx", "PIPX_BIN_DIR": "/usr/local/bin"}
+
+    def _cmd(self, package):
+        env = ["%s=%s" % item for item in sorted(self._env().items())]
+        return (["env"] + env if env else []) + ["pipx", "install", package]
+
+    def install(self, requirements):
+        packages, missing = self._packages(requirements)
+        if packages and not self._available():
+            missing.extend([req for (req, package) in packages])
+            packages = []
+        if packages and self.user_local:
+            # pipx links binaries into ~/.local/bin by default
+            bindir = self.session.check_output(
+                ["sh", "-c", "echo ${PIPX_BIN_DIR:-$HOME/.local/bin}"],
+                cwd="/").decode().strip()
+            prepend_path(self.session, bindir)
+        user = None if self.user_local else "root"
+        for package in sorted(set(package for (req, package) in packages)):
+            cmd = ["pipx", "install", package]
+            logging.info("pipx: running %r", cmd)
+            run_detecting_problems(
+                self.session, cmd, env=self._env(), user=user)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        packages, unused_missing = self._packages(requirements)
+        for requirement, package in packages:
+            yield (self._cmd(package), [requirement])
+
+
+# Go tools, by the name of the binary, and the package to "go install"
+GO_COMMAND_PACKAGES = {
+    "golangci-lint": "github.com/golangci/golangci-lint/cmd/golangci-lint",
+    "goimports": "golang.org/x/tools/cmd/goimports",
+    "gopls": "golang.org/x/tools/gopls",
+    "stringer": "golang.org/x/tools/cmd/stringer",
+    "staticcheck": "honnef.co/go/tools/cmd/staticcheck",
+    "gofumpt": "mvdan.cc/gofumpt",
+    "golint": "golang.org/x/lint/golint",
+    "revive": "github.com/mgechev/revive",
+    "gotestsum": "gotest.tools/gotestsum",
+    "mockgen": "go.uber.org/mock/mockgen",
+    "ginkgo": "github.com/onsi/ginkgo/v2/ginkgo",
+    "swag": "github.com/swaggo/swag/cmd/swag",
+    "protoc-gen-go": "google.golang.org/protobuf/cmd/protoc-gen-go",
+    "protoc-gen-go-grpc": "google.golang.org/grpc/cmd/protoc-gen-go-grpc",
+}
+
+
 class GoResolver(Resolver):
+    """Install Go packages and tools.
+
+    Missing packages are fetched with "go get", which adds them to the
+    current module if there is one. Packages at a specific version (e.g.
+    declared in go.mod) are only downloaded into the module cache, so that
+    go.mod and go.sum are left alone. Known Go tools are installed with
+    "go install", with the binaries installed into GOBIN.
+    """
+
     def __init__(self, session, user_local):
         self.session = session
         self.user_local = user_local
@@ -390,36 +798,158 @@ class GoResolver(Resolver):
     def __repr__(self):
         return "%s(%r)" % (type(self).__name__, self.session)
 
-    def install(self, requirements):
+    def _gobin(self):
+        if not self.user_local:
+            return "/usr/local/bin"
+        return posixpath.join(get_home(self.session), "go", "bin")
+
+    def _cmd(self, requirement):
         from ..requirements import GoPackageRequirement
 
-        if self.user_local:
-            env = {}
-        else:
-            # TODO(jelmer): Isn't this Debian-specific?
-            env = {"GOPATH": "/usr/share/gocode"}
+        if isinstance(requirement, GoPackageRequirement):
+            if requirement.version:
+                return ["go", "mod", "download", "%s@v%s" % (
+                    requirement.package, requirement.version)]
+            return ["go", "get", requirement.package]
+        package = GO_COMMAND_PACKAGES[requirement.binary_name]
+        return ["go", "install", "%s@latest" % package]
+
+    def _goreqs(self, requirements):
+        from ..requirements import BinaryRequirement, GoPackageRequirement
 
+        goreqs = []
         missing = []
         for requirement in requirements:
-            if not isinstance(requirement, GoPackageRequirement):
+            if isinstance(requirement, GoPackageRequirement) or (
+                    isinstance(requirement, BinaryRequirement)
+                    and requirement.binary_name in GO_COMMAND_PACKAGES):
+                goreqs.append(requirement)
+            else:
                 missing.append(requirement)
-                continue
-            cmd = ["go", "get", requirement.package]
+        return goreqs, missing
+
+    def install(self, requirements):
+        goreqs, missing = self._goreqs(requirements)
+        for requirement in goreqs:
+            cmd = self._cmd(requirement)
+            if cmd[1] != "install":
+                if self.user_local:
+                    env = {}
+                else:
+                    # TODO(jelmer): Isn't this Debian-specific?
+                    env = {"GOPATH": "/usr/share/gocode"}
+                user = None
+            else:
+                gobin = self._gobin()
+                env = {"GOBIN": gobin}
+                if self.user_local:
+                    user = None
+                    prepend_path(self.session, gobin)
+                else:
+                    user = "root"
             logging.info("go: running %r", cmd)
-            run_detecting_problems(self.session, cmd, env=env)
+            run_detecting_problems(self.session, cmd, env=env, user=user)
         if missing:
             raise UnsatisfiedRequirements(missing)
 
     def explain(self, requirements):
-        from ..requirements import GoPackageRequirement
+        goreqs, unused_missing = self._goreqs(requirements)
+        for requirement in goreqs:
+            yield (self._cmd(requirement), [requirement])
+
+
+# Rust tools, by the name of the binary, and the crate to "cargo install"
+CARGO_COMMAND_CRATES = {
+    "cargo-audit": "cargo-audit",
+    "cargo-deny": "cargo-deny",
+    "cargo-outdated": "cargo-outdated",
+    "cargo-tarpaulin": "cargo-tarpaulin",
+    "cargo-llvm-cov": "cargo-llvm-cov",
+    "cargo-nextest": "cargo-nextest",
+    "cargo-about": "cargo-about",
+    "cargo-set-version": "cargo-edit",
+    "cargo-upgrade": "cargo-edit",
+    "mdbook": "mdbook",
+    "cbindgen": "cbindgen",
+    "bindgen": "bindgen-cli",
+    "wasm-pack": "wasm-pack",
+    "wasm-bindgen": "wasm-bindgen-cli",
+    "cross": "cross",
+    "just": "just",
+    "maturin": "maturin",
+    "sccache": "sccache",
+}
 
-        goreqs = []
+
+class CargoResolver(Resolver):
+    """Install Rust tools with "cargo install"."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "cargo"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _crate(self, requirement):
+        from ..requirements import BinaryRequirement, CargoCrateRequirement
+
+        if isinstance(requirement, BinaryRequirement):
+            crate = CARGO_COMMAND_CRATES.get(requirement.binary_name)
+            if crate is None:
+                return None
+            return CargoCrateRequirement(crate)
+        # Only crates with a binary target can be installed
+        if (isinstance(requirement, CargoCrateRequirement)
+                and requirement.crate in CARGO_COMMAND_CRATES.values()):
+            return requirement
+        return None
+
+    def _cmd(self, crate, root=None):
+        cmd = ["cargo", "install"]
+        if root is not None:
+            cmd.extend(["--root", root])
+        if crate.version:
+            cmd.extend(["--version", crate.version])
+        if crate.features:
+            cmd.extend(["--features", ",".join(sorted(crate.features))])
+        cmd.append(crate.crate)
+        return cmd
+
+    def install(self, requirements):
+        missing = []
+        crates = []
         for requirement in requirements:
-            if not isinstance(requirement, GoPackageRequirement):
-                continue
-            goreqs.append(requirement)
-        if goreqs:
-            yield (["go", "get"] + [req.package for req in goreqs], goreqs)
+            crate = self._crate(requirement)
+            if crate is None:
+                missing.append(requirement)
+            else:
+                crates.append(crate)
+        if crates:
+            if self.user_local:
+                # cargo installs into ~/.cargo/bin by default
+                user = None
+                root = None
+                prepend_path(self.session, posixpath.join(
+                    get_home(self.session), ".cargo", "bin"))
+            else:
+                user = "root"
+                root = "/usr/local"
+            for crate in crates:
+                cmd = self._cmd(crate, root)
+                logging.info("cargo: running %r", cmd)
+                run_detecting_problems(self.session, cmd, user=user)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        for requirement in requirements:
+            crate = self._crate(requirement)
+            if crate is not None:
+                yield (self._cmd(crate), [requirement])
 
 
 NPM_COMMAND_PACKAGES = {
@@ -429,6 +959,310 @@ NPM_COMMAND_PACKAGES = {
 }
 
 
+class HexResolver(Resolver):
+    """Fetch hex.pm packages declared in a mix project."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "hex"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def install(self, requirements):
+        from ..requirements import HexPackageRequirement
+
+        hexreqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, HexPackageRequirement):
+                hexreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if hexreqs and self.session.exists("mix.exs"):
+            # mix can only fetch the dependencies that are declared in
+            # mix.exs, and fetches all of them at once
+            # Otherwise mix prompts to install the hex client
+            run_detecting_problems(
+                self.session, ["mix", "local.hex", "--force", "--if-missing"])
+            cmd = ["mix", "deps.get"]
+            logging.info("hex: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+            missing.extend([
+                req for req in hexreqs if req.try_met(self.session) is False])
+        else:
+            missing.extend(hexreqs)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import HexPackageRequirement
+
+        hexreqs = [
+            requirement for requirement in requirements
+            if isinstance(requirement, HexPackageRequirement)]
+        if hexreqs:
+            yield (["mix", "deps.get"], hexreqs)
+
+
+class PubResolver(Resolver):
+    """Fetch or add Dart packages with "dart pub"."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "pub"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _cmd(self, req):
+        spec = req.package
+        if req.version:
+            spec += ":" + req.version
+        return ["dart", "pub", "add", spec]
+
+    def install(self, requirements):
+        from ..requirements import DartPackageRequirement
+
+        dartreqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, DartPackageRequirement):
+                dartreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if dartreqs and not self.session.exists("pubspec.yaml"):
+            # Packages can only be added to a project
+            missing.extend(dartreqs)
+            dartreqs = []
+        if dartreqs:
+            # The package may already be declared, but not fetched yet
+            cmd = ["dart", "pub", "get"]
+            logging.info("pub: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        for requirement in dartreqs:
+            if requirement.try_met(self.session):
+                continue
+            cmd = self._cmd(requirement)
+            logging.info("pub: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import DartPackageRequirement
+
+        for requirement in requirements:
+            if isinstance(requirement, DartPackageRequirement):
+                yield (self._cmd(requirement), [requirement])
+
+
+class NuGetResolver(Resolver):
+    """Restore the NuGet packages declared in a .NET project."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "nuget"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _has_project(self):
+        return any(
+            entry.name.endswith((".sln", ".csproj", ".fsproj", ".vbproj"))
+            for entry in self.session.scandir("."))
+
+    def install(self, requirements):
+        from ..requirements import BinaryRequirement, NuGetPackageRequirement
+
+        nugetreqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, NuGetPackageRequirement):
+                nugetreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if nugetreqs and (
+                not BinaryRequirement("dotnet").try_met(self.session)
+                or not self._has_project()):
+            missing.extend(nugetreqs)
+            nugetreqs = []
+        if nugetreqs:
+            # NuGet can only restore the packages that the project declares,
+            # and restores all of them at once
+            cmd = ["dotnet", "restore"]
+            logging.info("nuget: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import NuGetPackageRequirement
+
+        nugetreqs = [
+            requirement for requirement in requirements
+            if isinstance(requirement, NuGetPackageRequirement)]
+        if nugetreqs:
+            yield (["dotnet", "restore"], nugetreqs)
+
+
+class NimbleResolver(Resolver):
+    """Install Nim packages with nimble."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "nimble"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _cmd(self, req):
+        spec = req.package
+        if req.version:
+            spec += "@" + req.version
+        return ["nimble", "install", "-y", spec]
+
+    def install(self, requirements):
+        from ..requirements import BinaryRequirement, NimblePackageRequirement
+
+        nimblereqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, NimblePackageRequirement):
+                nimblereqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if nimblereqs and not BinaryRequirement("nimble").try_met(self.session):
+            missing.extend(nimblereqs)
+            nimblereqs = []
+        # nimble always installs into the user's package directory
+        for requirement in nimblereqs:
+            cmd = self._cmd(requirement)
+            logging.info("nimble: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import NimblePackageRequirement
+
+        for requirement in requirements:
+            if isinstance(requirement, NimblePackageRequirement):
+                yield (self._cmd(requirement), [requirement])
+
+
+class CondaResolver(Resolver):
+    """Install packages into the active conda environment."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "conda"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def _cmd(self, reqs):
+        specs = []
+        for req in reqs:
+            if req.version:
+                specs.append("%s %s" % (req.package, req.version))
+            else:
+                specs.append(req.package)
+        return ["conda", "install", "-y"] + specs
+
+    def install(self, requirements):
+        from ..requirements import BinaryRequirement, CondaPackageRequirement
+
+        condareqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, CondaPackageRequirement):
+                condareqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if condareqs and not BinaryRequirement("conda").try_met(self.session):
+            missing.extend(condareqs)
+            condareqs = []
+        if condareqs:
+            cmd = self._cmd(condareqs)
+            logging.info("conda: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import CondaPackageRequirement
+
+        condareqs = [
+            requirement for requirement in requirements
+            if isinstance(requirement, CondaPackageRequirement)]
+        if condareqs:
+            yield (self._cmd(condareqs), condareqs)
+
+
+class BazelResolver(Resolver):
+    """Fetch the external modules declared in a Bazel workspace."""
+
+    def __init__(self, session, user_local):
+        self.session = session
+        self.user_local = user_local
+
+    def __str__(self):
+        return "bazel"
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.session)
+
+    def install(self, requirements):
+        from ..requirements import BazelModuleRequirement, BinaryRequirement
+
+        bazelreqs = []
+        missing = []
+        for requirement in requirements:
+            if isinstance(requirement, BazelModuleRequirement):
+                bazelreqs.append(requirement)
+            else:
+                missing.append(requirement)
+        if bazelreqs and (
+                not BinaryRequirement("bazel").try_met(self.session)
+                or not (self.session.exists("MODULE.bazel")
+                        or self.session.exists("WORKSPACE"))):
+            missing.extend(bazelreqs)
+            bazelreqs = []
+        if bazelreqs:
+            # Modules are fetched from the registry into bazel's repository
+            # cache; they can only be fetched for the workspace as a whole
+            cmd = ["bazel", "fetch", "//..."]
+            logging.info("bazel: running %r", cmd)
+            run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        from ..requirements import BazelModuleRequirement
+
+        bazelreqs = [
+            requirement for requirement in requirements
+            if isinstance(requirement, BazelModuleRequirement)]
+        if bazelreqs:
+            yield (["bazel", "fetch", "//..."], bazelreqs)
+
+
 class NpmResolver(Resolver):
     def __init__(self, session, user_local=False):
         self.session = session
@@ -514,8 +1348,24 @@ class StackedResolver(Resolver):
         return ret
 
     def explain(self, requirements):
+        # Like install, only pass on the requirements that earlier resolvers
+        # can not handle, so that each requirement is only explained once.
+        remaining = list(requirements)
         for sub in self.subs:
-            yield from sub.explain(requirements)
+            if not remaining:
+                break
+            explained = []
+            for command, reqs in sub.explain(remaining):
+                explained.extend(reqs)
+                yield (command, reqs)
+            remaining = [
+                r for r in remaining if not any(r is e for e in explained)]
+
+    def trace(self, requirement):
+        ret = []
+        for sub in self.subs:
+            ret.extend(sub.trace(requirement))
+        return ret
 
     def install(self, requirements):
         for sub in self.subs:
@@ -530,11 +1380,20 @@ class StackedResolver(Resolver):
 
 
 NATIVE_RESOLVER_CLS = [
+    CpanmResolver,
     CPANResolver,
     CTANResolver,
+    PipxResolver,
     PypiResolver,
     NpmResolver,
     GoResolver,
+    CargoResolver,
+    HexResolver,
+    PubResolver,
+    NuGetResolver,
+    NimbleResolver,
+    CondaResolver,
+    BazelResolver,
     HackageResolver,
     CRANResolver,
     BioconductorResolver,
@@ -546,7 +1405,7 @@ def native_resolvers(session, user_local):
     return StackedResolver([kls(session, user_local) for kls in NATIVE_RESOLVER_CLS])
 
 
-def auto_resolver(session, explain=False):
+def auto_resolver(session, explain=False, host_arch=None):
     # if session is SchrootSession or if we're root, use apt
     from ..session.schroot import SchrootSession
     from ..session import get_user
@@ -559,12 +1418,41 @@ def auto_resolver(session, explain=False):
         user_local = False
     else:
         user_local = True
-    if not user_local:
+    from .nix import NixResolver, is_nixos
+
+    from .apk import ApkResolver, is_alpine
+    from .dnf import DnfResolver, is_fedora_like
+    from .freebsd import FreeBSDPkgResolver, is_freebsd
+
+    if is_nixos(session):
+        # apt is of no use on NixOS
+        resolvers.append(NixResolver(session, user_local))
+    elif is_alpine(session):
+        if not user_local:
+            resolvers.append(ApkResolver.from_session(session))
+    elif is_fedora_like(session):
+        if not user_local:
+            resolvers.append(DnfResolver.from_session(session))
+    elif is_freebsd(session):
+        if not user_local:
+            resolvers.append(FreeBSDPkgResolver.from_session(session))
+    elif not user_local:
         try:
             from .apt import AptResolver
         except ModuleNotFoundError:
             pass
         else:
-            resolvers.append(AptResolver.from_session(session))
+            resolvers.append(
+                AptResolver.from_session(session, host_arch=host_arch))
     resolvers.extend([kls(session, user_local) for kls in NATIVE_RESOLVER_CLS])
+    from ..requirements import BinaryRequirement
+    from .conan import ConanResolver
+    from .vcpkg import VcpkgResolver
+
+    # Only fall back to building C/C++ libraries with vcpkg or from
+    # ConanCenter if there is no system package for them.
+    if not explain:
+        for kls, binary in [(VcpkgResolver, "vcpkg"), (ConanResolver, "conan")]:
+            if BinaryRequirement(binary).try_met(session):
+                resolvers.append(kls(session, user_local))
     return StackedResolver(resolvers)
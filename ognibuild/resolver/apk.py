@@ -0,0 +1,112 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install requirements from the Alpine Linux repositories."""
+
+import logging
+import subprocess
+from typing import List, Optional
+
+from . import PackageManager, PackageManagerResolver
+from ..requirements import (
+    ApkPackageRequirement,
+    BinaryRequirement,
+    LibraryRequirement,
+    PerlModuleRequirement,
+    PkgConfigRequirement,
+    PythonPackageRequirement,
+    Requirement,
+    RubyGemRequirement,
+)
+
+
+def is_alpine(session) -> bool:
+    return session.exists("/etc/alpine-release")
+
+
+class ApkManager(PackageManager):
+    """Search and install packages with apk.
+
+    Alpine packages declare what they provide in the APKINDEX, e.g.
+    "cmd:gcc" for binaries, "pc:zlib" for pkg-config files and
+    "so:libz.so.1" for shared libraries, which "apk search" can query.
+    """
+
+    name = "apk"
+
+    def _provides(self, query: str) -> List[str]:
+        """Find the packages that have a name or provide matching query.
+
+        Queries with glob characters (e.g. "so:libz.so*") are matched as
+        patterns, others exactly.
+        """
+        args = ["apk", "search", "-q"]
+        if not any(c in query for c in "*?["):
+            args.append("-x")
+        try:
+            output = self.session.check_output(args + [query], cwd="/")
+        except subprocess.CalledProcessError:
+            return []
+        return [line for line in output.decode().splitlines() if line]
+
+    def install_command(self, packages: List[str]) -> List[str]:
+        return self.prefix + ["apk", "add"] + packages
+
+
+def _apk_queries(req: Requirement) -> List[str]:
+    """Find the apk search queries for a requirement, in order of preference."""
+    if isinstance(req, BinaryRequirement):
+        return ["cmd:%s" % req.binary_name]
+    elif isinstance(req, PkgConfigRequirement):
+        return ["pc:%s" % req.module]
+    elif isinstance(req, LibraryRequirement):
+        # Prefer the -dev package with the unversioned symlink
+        return ["%s-dev" % req.library,
+                "lib%s-dev" % req.library,
+                "so:lib%s.so*" % req.library]
+    elif isinstance(req, PythonPackageRequirement):
+        return ["py3-%s" % req.package.lower().replace("_", "-")]
+    elif isinstance(req, PerlModuleRequirement):
+        return ["perl-%s" % req.module.lower().replace("::", "-")]
+    elif isinstance(req, RubyGemRequirement):
+        return ["ruby-%s" % req.gem.lower()]
+    return []
+
+
+def resolve_requirement_apk(
+        apk_mgr: ApkManager, req: Requirement) -> Optional[ApkPackageRequirement]:
+    """Find the Alpine package for a requirement, if any."""
+    if isinstance(req, ApkPackageRequirement):
+        return req
+    for query in _apk_queries(req):
+        packages = apk_mgr.provides(query)
+        if packages:
+            if len(packages) > 1:
+                logging.info(
+                    "Multiple packages match %s: %r, picking first",
+                    query, packages)
+            return ApkPackageRequirement(packages[0])
+    return None
+
+
+class ApkResolver(PackageManagerResolver):
+    """Install requirements with apk, e.g. inside Alpine containers."""
+
+    manager_cls = ApkManager
+
+    def resolve(self, req: Requirement):
+        return resolve_requirement_apk(self.manager, req)
@@ -39,10 +39,14 @@ from ..requirements import (
     ValaPackageRequirement,
     RubyGemRequirement,
     GoPackageRequirement,
+    HexPackageRequirement,
+    OcamlFindlibRequirement,
+    OctavePackageRequirement,
     GoRequirement,
     DhAddonRequirement,
     PhpClassRequirement,
     PhpPackageRequirement,
+    PhpExtensionRequirement,
     RPackageRequirement,
     NodeModuleRequirement,
     NodePackageRequirement,
@@ -54,6 +58,7 @@ from ..requirements import (
     SprocketsFileRequirement,
     JavaClassRequirement,
     CMakefileRequirement,
+    HaskellModuleRequirement,
     HaskellPackageRequirement,
     MavenArtifactRequirement,
     GnomeCommonRequirement,
@@ -61,6 +66,7 @@ from ..requirements import (
     JDKRequirement,
     JRERequirement,
     QTRequirement,
+    QtModuleRequirement,
     X11Requirement,
     PerlModuleRequirement,
     PerlFileRequirement,
@@ -96,6 +102,13 @@ class AptRequirement(Requirement):
     def pkg_relation_str(self):
         return PkgRelation.str(self.relations)
 
+    def json(self):
+        return {"family": self.family, "payload": self.pkg_relation_str()}
+
+    @classmethod
+    def from_json(cls, payload):
+        return cls.from_str(payload)
+
     def __hash__(self):
         return hash((type(self), self.pkg_relation_str()))
 
@@ -108,6 +121,12 @@ class AptRequirement(Requirement):
     def __repr__(self):
         return "%s.from_str(%r)" % (type(self).__name__, self.pkg_relation_str())
 
+    def for_architecture(self, arch):
+        """Return a copy of this requirement for packages built for arch."""
+        return AptRequirement(
+            [[dict(entry, archqual=arch) for entry in rel]
+             for rel in self.relations])
+
     def package_names(self):
         for rel in self.relations:
             for entry in rel:
@@ -348,12 +367,11 @@ def resolve_vague_dep_req(apt_mgr, req):
 
 def resolve_binary_req(apt_mgr, req):
     if posixpath.isabs(req.binary_name):
-        paths = [req.binary_name]
-    else:
-        paths = [
-            posixpath.join(dirname, req.binary_name) for dirname in ["/usr/bin", "/bin"]
-        ]
-    return find_reqs_simple(apt_mgr, paths)
+        return find_reqs_simple(apt_mgr, [req.binary_name])
+    return [
+        AptRequirement.simple(package)
+        for package in apt_mgr.get_packages_for_binary(req.binary_name)
+    ]
 
 
 def resolve_pkg_config_req(apt_mgr, req):
@@ -423,6 +441,8 @@ def resolve_go_package_req(apt_mgr, req):
 
 
 def resolve_go_req(apt_mgr, req):
+    if req.version is None:
+        return [AptRequirement.simple("golang-go")]
     return [AptRequirement.simple("golang-go", minimum_version="2:%s" % req.version)]
 
 
@@ -442,6 +462,10 @@ def resolve_php_package_req(apt_mgr, req):
     ]
 
 
+def resolve_php_extension_req(apt_mgr, req):
+    return [AptRequirement.simple("php-%s" % req.extension.lower())]
+
+
 def resolve_r_package_req(apt_mgr, req):
     paths = [
         posixpath.join("/usr/lib/R/site-library", req.package, "DESCRIPTION")
@@ -449,6 +473,14 @@ def resolve_r_package_req(apt_mgr, req):
     return find_reqs_simple(apt_mgr, paths, minimum_version=req.minimum_version)
 
 
+def resolve_octave_package_req(apt_mgr, req):
+    paths = [
+        "/usr/share/octave/packages/%s-[^/]+/DESCRIPTION" % re.escape(req.package)
+    ]
+    return find_reqs_simple(
+        apt_mgr, paths, regex=True, minimum_version=req.minimum_version)
+
+
 def resolve_node_module_req(apt_mgr, req):
     paths = [
         "/usr/share/nodejs/.*/node_modules/%s/index.js" % re.escape(req.module),
@@ -548,6 +580,12 @@ def resolve_haskell_package_req(apt_mgr, req):
     return find_reqs_simple(apt_mgr, [path], regex=True)
 
 
+def resolve_haskell_module_req(apt_mgr, req):
+    path = "/usr/lib/haskell-packages/ghc/lib/.*/%s\\.hi" % re.escape(
+        req.module.replace(".", "/"))
+    return find_reqs_simple(apt_mgr, [path], regex=True)
+
+
 def resolve_maven_artifact_req(apt_mgr, req):
     if req.version is None:
         version = ".*"
@@ -597,6 +635,12 @@ def resolve_qt_req(apt_mgr, req):
     return find_reqs_simple(apt_mgr, ["/usr/lib/.*/qt[0-9]+/bin/qmake"], regex=True)
 
 
+def resolve_qt_module_req(apt_mgr, req):
+    path = "/usr/lib/.*/qt[0-9]+/mkspecs/modules/" + re.escape(
+        "qt_lib_%s.pri" % req.module)
+    return find_reqs_simple(apt_mgr, [path], regex=True)
+
+
 def resolve_libtool_req(apt_mgr, req):
     return [AptRequirement.simple("libtool")]
 
@@ -703,6 +747,31 @@ def resolve_boost_component_req(apt_mgr, req):
         regex=True)
 
 
+def _ocaml_library_paths(name):
+    # Libraries live in /usr/lib/ocaml, or in a multiarch directory on
+    # newer releases. Dune-built packages also ship their opam file.
+    return [posixpath.join(
+        "/usr/lib", "([^/]+/)?ocaml(/[^/]+)?", re.escape(name), "(META|opam)")]
+
+
+def resolve_ocaml_findlib_req(apt_mgr, req):
+    # Subpackages (e.g. lwt.unix) are described in the META file of the
+    # top-level package
+    return find_reqs_simple(
+        apt_mgr, _ocaml_library_paths(req.package.split(".")[0]), regex=True)
+
+
+def resolve_hex_package_req(apt_mgr, req):
+    # Erlang applications are packaged in Debian as erlang-<name>, and
+    # install an .app file
+    return find_reqs_simple(
+        apt_mgr,
+        [posixpath.join(
+            "/usr/lib/erlang/lib", re.escape(req.package) + "-[^/]+", "ebin",
+            re.escape(req.package) + r"\.app")],
+        regex=True)
+
+
 APT_REQUIREMENT_RESOLVERS = [
     (AptRequirement, resolve_apt_req),
     (BinaryRequirement, resolve_binary_req),
@@ -716,10 +785,14 @@ APT_REQUIREMENT_RESOLVERS = [
     (RubyGemRequirement, resolve_ruby_gem_req),
     (GoPackageRequirement, resolve_go_package_req),
     (GoRequirement, resolve_go_req),
+    (HexPackageRequirement, resolve_hex_package_req),
+    (OcamlFindlibRequirement, resolve_ocaml_findlib_req),
     (DhAddonRequirement, resolve_dh_addon_req),
     (PhpClassRequirement, resolve_php_class_req),
     (PhpPackageRequirement, resolve_php_package_req),
+    (PhpExtensionRequirement, resolve_php_extension_req),
     (RPackageRequirement, resolve_r_package_req),
+    (OctavePackageRequirement, resolve_octave_package_req),
     (NodeModuleRequirement, resolve_node_module_req),
     (NodePackageRequirement, resolve_node_package_req),
     (LibraryRequirement, resolve_library_req),
@@ -729,6 +802,7 @@ APT_REQUIREMENT_RESOLVERS = [
     (SprocketsFileRequirement, resolve_sprockets_file_req),
     (JavaClassRequirement, resolve_java_class_req),
     (CMakefileRequirement, resolve_cmake_file_req),
+    (HaskellModuleRequirement, resolve_haskell_module_req),
     (HaskellPackageRequirement, resolve_haskell_package_req),
     (MavenArtifactRequirement, resolve_maven_artifact_req),
     (GnomeCommonRequirement, resolve_gnome_common_req),
@@ -736,6 +810,7 @@ APT_REQUIREMENT_RESOLVERS = [
     (JDKRequirement, resolve_jdk_req),
     (JRERequirement, resolve_jre_req),
     (QTRequirement, resolve_qt_req),
+    (QtModuleRequirement, resolve_qt_module_req),
     (X11Requirement, resolve_x11_req),
     (LibtoolRequirement, resolve_libtool_req),
     (PerlModuleRequirement, resolve_perl_module_req),
@@ -771,30 +846,53 @@ def default_tie_breakers(session):
         ]
 
 
+# Requirement families that have to be met for the architecture that is
+# being built for (rather than the one doing the build) when cross-building.
+HOST_ARCH_FAMILIES = {
+    "boost-component",
+    "c-header",
+    "cmake-file",
+    "lib",
+    "pkg-config",
+    "static-lib",
+    "x11",
+}
+
+
 class AptResolver(Resolver):
-    def __init__(self, apt, tie_breakers=None):
+    """Install requirements with apt.
+
+    Args:
+      apt: AptManager to use
+      tie_breakers: Functions to pick between candidate packages
+      host_arch: Debian architecture that is being cross-built for, if any;
+        libraries are installed for this architecture
+    """
+
+    def __init__(self, apt, tie_breakers=None, host_arch=None):
         self.apt = apt
         if tie_breakers is None:
             tie_breakers = default_tie_breakers(apt.session)
         self.tie_breakers = tie_breakers
+        self.host_arch = host_arch
 
     def __str__(self):
         return "apt"
 
     def __repr__(self):
-        return "%s(%r, %r)" % (type(self).__name__, self.apt, self.tie_breakers)
+        return "%s(%r, %r, host_arch=%r)" % (
+            type(self).__name__, self.apt, self.tie_breakers, self.host_arch)
 
     @classmethod
-    def from_session(cls, session, tie_breakers=None):
-        return cls(AptManager.from_session(session), tie_breakers=tie_breakers)
+    def from_session(cls, session, tie_breakers=None, host_arch=None):
+        return cls(
+            AptManager.from_session(session), tie_breakers=tie_breakers,
+            host_arch=host_arch)
 
     def install(self, requirements):
         missing = []
         for req in requirements:
-            try:
-                if not req.met(self.apt.session):
-                    missing.append(req)
-            except NotImplementedError:
+            if not req.try_met(self.apt.session):
                 missing.append(req)
         if not missing:
             return
@@ -807,6 +905,9 @@ class AptResolver(Resolver):
             else:
                 apt_requirements.append(apt_req)
         if apt_requirements:
+            if self.host_arch is not None and any(
+                    m.family in HOST_ARCH_FAMILIES for m in missing):
+                self.apt.add_architecture(self.host_arch)
             self.apt.satisfy(
                 [PkgRelation.str(chain(*[r.relations for r in apt_requirements]))]
             )
@@ -831,8 +932,53 @@ class AptResolver(Resolver):
                 [o for o, r in apt_requirements],
             )
 
+    def trace(self, requirement):
+        from .explanation import ResolutionStep
+
+        self.apt.search_log = []
+        try:
+            try:
+                candidates = resolve_requirement_apt(self.apt, requirement)
+            except NotImplementedError:
+                return [ResolutionStep(
+                    str(self), "unsupported",
+                    "no mapping from %s requirements to packages"
+                    % requirement.family)]
+        finally:
+            searches = self.apt.search_log
+            self.apt.search_log = None
+        ret = []
+        for search in searches:
+            ret.append(ResolutionStep(
+                str(self), "file-search",
+                "searched for %s: %s" % (
+                    ", ".join(search["query"]),
+                    ", ".join(search["packages"]) or "no packages"),
+                search))
+        if not candidates:
+            ret.append(ResolutionStep(
+                str(self), "no-candidate", "no package provides the requirement"))
+        for candidate in candidates:
+            relation = PkgRelation.str(candidate.relations)
+            ret.append(ResolutionStep(
+                str(self), "candidate", "package candidate: %s" % relation,
+                {"relation": relation}))
+        return ret
+
     def resolve(self, req: Requirement):
-        ret = resolve_requirement_apt(self.apt, req)
+        apt_req = self._resolve(req)
+        if (apt_req is not None and self.host_arch is not None
+                and req.family in HOST_ARCH_FAMILIES):
+            apt_req = apt_req.for_architecture(self.host_arch)
+        return apt_req
+
+    def _resolve(self, req: Requirement):
+        try:
+            ret = resolve_requirement_apt(self.apt, req)
+        except NotImplementedError:
+            logging.debug("No mapping from %s requirements to packages",
+                          req.family)
+            return None
         if not ret:
             return None
         if len(ret) == 1:
@@ -0,0 +1,282 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install C/C++ requirements from ConanCenter."""
+
+import logging
+import os
+import re
+from typing import List, Optional, Set
+
+from . import PackageMappingResolver, UnsatisfiedRequirements
+from .. import DetailedFailure, UnidentifiedError
+from ..fix_build import run_detecting_problems
+from ..requirements import (
+    CMakefileRequirement,
+    ConanPackageRequirement,
+    LibraryRequirement,
+    PkgConfigRequirement,
+    Requirement,
+)
+
+
+# pkg-config modules whose ConanCenter package has a different name
+CONAN_PKG_CONFIG_MAP = {
+    "glib-2.0": "glib",
+    "gio-2.0": "glib",
+    "gobject-2.0": "glib",
+    "libxml-2.0": "libxml2",
+    "libcrypto": "openssl",
+    "libssl": "openssl",
+    "sqlite3": "sqlite3",
+    "gtest": "gtest",
+    "gmock": "gtest",
+    "libzstd": "zstd",
+    "liblzma": "xz_utils",
+    "yaml-cpp": "yaml-cpp",
+    "libcurl": "libcurl",
+    "libpcre2-8": "pcre2",
+    "uuid": "libuuid",
+    "bzip2": "bzip2",
+}
+
+# Library names (as passed to -l) whose ConanCenter package is named
+# differently
+CONAN_LIBRARY_MAP = {
+    "z": "zlib",
+    "ssl": "openssl",
+    "crypto": "openssl",
+    "curl": "libcurl",
+    "png": "libpng",
+    "jpeg": "libjpeg",
+    "bz2": "bzip2",
+    "lzma": "xz_utils",
+    "zstd": "zstd",
+    "sqlite3": "sqlite3",
+    "uuid": "libuuid",
+    "ffi": "libffi",
+    "xml2": "libxml2",
+    "yaml": "libyaml",
+}
+
+
+def _cmake_package_name(filename: str) -> Optional[str]:
+    """Find the CMake package name for a Find*.cmake or config file."""
+    if filename.startswith("Find") and filename.endswith(".cmake"):
+        return filename[len("Find"):-len(".cmake")]
+    for suffix in ("Config.cmake", "-config.cmake"):
+        if filename.endswith(suffix):
+            return filename[:-len(suffix)]
+    return None
+
+
+def resolve_requirement_conan(
+        req: Requirement) -> Optional[ConanPackageRequirement]:
+    """Find the ConanCenter package for a requirement, if known."""
+    if isinstance(req, ConanPackageRequirement):
+        return req
+    elif isinstance(req, PkgConfigRequirement):
+        package = CONAN_PKG_CONFIG_MAP.get(req.module)
+        if package is None:
+            package = req.module.lower()
+            if package.startswith("lib") and package[3:] in CONAN_LIBRARY_MAP:
+                package = CONAN_LIBRARY_MAP[package[3:]]
+        version = None
+        if req.minimum_version:
+            version = "[>=%s]" % req.minimum_version
+        return ConanPackageRequirement(package, version)
+    elif isinstance(req, CMakefileRequirement):
+        name = _cmake_package_name(req.filename)
+        if name is None:
+            return None
+        version = None
+        if req.version:
+            version = "[>=%s]" % req.version
+        return ConanPackageRequirement(name.lower(), version)
+    elif isinstance(req, LibraryRequirement):
+        return ConanPackageRequirement(
+            CONAN_LIBRARY_MAP.get(req.library, req.library))
+    return None
+
+
+def find_unresolved_packages(lines: List[str]) -> Set[str]:
+    """Find the packages that "conan install" could not find in its remotes."""
+    ret = set()
+    for line in lines:
+        for m in re.finditer(
+                r"Unable to find '([^/']+)|"
+                r"from requirement '([^/']+)/[^']*' .* could not be resolved",
+                line):
+            ret.add(m.group(1) or m.group(2))
+    return ret
+
+
+def generate_conanfile(requirements: List[ConanPackageRequirement]) -> str:
+    """Generate a conanfile.txt for a set of requirements.
+
+    Both CMake and pkg-config files are generated, so that the packages can be
+    found from CMake as well as from Meson and autoconf.
+    """
+    lines = ["[requires]"]
+    lines.extend(req.reference for req in requirements)
+    lines.extend(["", "[generators]", "CMakeDeps", "CMakeToolchain",
+                  "PkgConfigDeps", ""])
+    return "\n".join(lines)
+
+
+class ConanResolver(PackageMappingResolver):
+    """Install C/C++ requirements from ConanCenter.
+
+    Requirements are collected in a conanfile.txt, after which
+    "conan install" builds or fetches them. The generated CMake toolchain
+    and pkg-config files are then made available to subsequent commands
+    through CMAKE_TOOLCHAIN_FILE and PKG_CONFIG_PATH, which are picked up by
+    the CMake and Meson build systems respectively.
+
+    Args:
+      session: Session to install in
+      user_local: Ignored; conan packages are always in the user's cache
+      conan_dir: Directory (inside the session) to write the conanfile
+        and generated files to; defaults to a temporary directory
+    """
+
+    def __init__(self, session, user_local=True, conan_dir: Optional[str] = None):
+        self.session = session
+        self.user_local = user_local
+        self.conan_dir = conan_dir
+        self._packages: List[ConanPackageRequirement] = []
+        self._profile_detected = False
+        self._user_toolchain: Optional[str] = None
+        self._toolchain_file: Optional[str] = None
+
+    def __str__(self):
+        return "conan"
+
+    def __repr__(self):
+        return "%s(%r, conan_dir=%r)" % (
+            type(self).__name__, self.session, self.conan_dir)
+
+    def resolve(self, requirement):
+        return resolve_requirement_conan(requirement)
+
+    def _install_cmd(self):
+        cmd = ["conan", "install", self.conan_dir,
+               "--output-folder=%s" % os.path.join(self.conan_dir, "build"),
+               "--build=missing"]
+        if self._user_toolchain is None:
+            # Remember the toolchain set up before conan's, e.g. by vcpkg
+            self._user_toolchain = self.session.getenv("CMAKE_TOOLCHAIN_FILE") or ""
+        if self._user_toolchain:
+            cmd.append(
+                "--conf=tools.cmake.cmaketoolchain:user_toolchain=[%r]"
+                % self._user_toolchain)
+        return cmd
+
+    def _explain_command(self, conan_req):
+        return ["conan", "install", "--requires=%s" % conan_req.reference,
+                "--build=missing"]
+
+    def _conan_install(
+            self, packages: List[ConanPackageRequirement], new: Set[str]) -> Set[str]:
+        """Run "conan install" for a set of packages.
+
+        Args:
+          packages: All packages to install
+          new: Names of the packages that were not previously installed
+        Returns: names of new packages that could not be found
+        """
+        if self.conan_dir is None:
+            from ..session import create_temporary_directory
+
+            self.conan_dir = create_temporary_directory(
+                self.session, "ognibuild-conan")
+        self._write_conanfile(packages)
+        if not self._profile_detected:
+            run_detecting_problems(
+                self.session, ["conan", "profile", "detect", "--exist-ok"])
+            self._profile_detected = True
+        cmd = self._install_cmd()
+        logging.info("conan: running %r", cmd)
+        output: List[str] = []
+
+        def check_success(retcode, contents):
+            output.extend(contents)
+            return retcode == 0
+
+        try:
+            run_detecting_problems(self.session, cmd, check_success=check_success)
+        except (DetailedFailure, UnidentifiedError):
+            unresolved = find_unresolved_packages(
+                "".join(output).splitlines()) & new
+            if not unresolved:
+                raise
+            return unresolved
+        return set()
+
+    def install(self, requirements):
+        resolved, missing = self._resolve_all(requirements)
+        while resolved:
+            packages = list(self._packages)
+            for requirement, conan_req in resolved:
+                if conan_req.package not in [p.package for p in packages]:
+                    packages.append(conan_req)
+            unresolved = self._conan_install(
+                packages, {p.package for p in packages[len(self._packages):]})
+            if not unresolved:
+                for package in packages[len(self._packages):]:
+                    logging.info("conan: added %s", package.reference)
+                self._packages = packages
+                self._setup_environment()
+                break
+            # The package names are guesses for some requirements, so a
+            # package that doesn't exist just means the requirement can't be
+            # satisfied with conan.
+            not_found = [
+                (requirement, conan_req) for (requirement, conan_req) in resolved
+                if conan_req.package in unresolved]
+            for requirement, conan_req in not_found:
+                logging.info("conan: package %s not found", conan_req.package)
+                resolved.remove((requirement, conan_req))
+                missing.append(requirement)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def _write_conanfile(self, packages: List[ConanPackageRequirement]) -> None:
+        path = self.session.external_path(self.conan_dir)
+        os.makedirs(path, exist_ok=True)
+        with open(os.path.join(path, "conanfile.txt"), "w") as f:
+            f.write(generate_conanfile(packages))
+
+    def _setup_environment(self) -> None:
+        # Build systems may run commands from other directories (e.g. the
+        # CMake build directory), so use absolute paths.
+        output_dir = self.session.check_output(
+            ["realpath", os.path.join(self.conan_dir, "build")]).decode().strip()
+        pkg_config_path = self.session.getenv("PKG_CONFIG_PATH")
+        if not pkg_config_path:
+            self.session.setenv("PKG_CONFIG_PATH", output_dir)
+        elif output_dir not in pkg_config_path.split(":"):
+            self.session.setenv(
+                "PKG_CONFIG_PATH", output_dir + ":" + pkg_config_path)
+        # Passed on by the CMake build system when configuring. If another
+        # resolver (e.g. vcpkg) has since set its own toolchain file, that
+        # one chain-loads ours.
+        toolchain_file = os.path.join(output_dir, "conan_toolchain.cmake")
+        current = self.session.getenv("CMAKE_TOOLCHAIN_FILE")
+        if self._toolchain_file is None or current == self._toolchain_file:
+            self.session.setenv("CMAKE_TOOLCHAIN_FILE", toolchain_file)
+            self._toolchain_file = toolchain_file
@@ -0,0 +1,127 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install requirements with dnf, on Fedora and RHEL-like systems."""
+
+import logging
+import posixpath
+import subprocess
+from typing import List, Optional
+
+from . import PackageManager, PackageManagerResolver
+from ..requirements import (
+    BinaryRequirement,
+    CHeaderRequirement,
+    LibraryRequirement,
+    PathRequirement,
+    PerlModuleRequirement,
+    PkgConfigRequirement,
+    PythonPackageRequirement,
+    Requirement,
+    RpmPackageRequirement,
+    RubyGemRequirement,
+)
+
+
+def is_fedora_like(session) -> bool:
+    return session.exists("/etc/redhat-release") or session.exists(
+        "/etc/fedora-release")
+
+
+class DnfManager(PackageManager):
+    """Search and install packages with dnf."""
+
+    name = "dnf"
+
+    def _provides(self, query: str) -> List[str]:
+        """Find the packages that provide an RPM capability or file.
+
+        The query may contain glob characters.
+        """
+        args = ["dnf", "repoquery", "-q", "--whatprovides", query,
+                "--qf", "%{name}\\n"]
+        try:
+            output = self.session.check_output(args, cwd="/")
+        except subprocess.CalledProcessError:
+            return []
+        packages: List[str] = []
+        for line in output.decode().splitlines():
+            line = line.strip()
+            if line and line not in packages:
+                packages.append(line)
+        return packages
+
+    def install_command(self, packages: List[str]) -> List[str]:
+        return self.prefix + ["dnf", "install", "-y"] + packages
+
+
+LIBDIRS = ["/usr/lib64", "/usr/lib"]
+
+
+def rpm_provides_for_requirement(req: Requirement) -> List[str]:
+    """Find the RPM capabilities or paths that satisfy a requirement.
+
+    Returns: list of queries for "dnf repoquery --whatprovides", in order of
+        preference
+    """
+    if isinstance(req, BinaryRequirement):
+        if req.binary_name.startswith("/"):
+            return [req.binary_name]
+        return [posixpath.join(d, req.binary_name)
+                for d in ["/usr/bin", "/usr/sbin"]]
+    elif isinstance(req, PathRequirement):
+        return [req.path]
+    elif isinstance(req, CHeaderRequirement):
+        return [posixpath.join("/usr/include", req.header)]
+    elif isinstance(req, PkgConfigRequirement):
+        if req.minimum_version:
+            return ["pkgconfig(%s) >= %s" % (req.module, req.minimum_version)]
+        return ["pkgconfig(%s)" % req.module]
+    elif isinstance(req, LibraryRequirement):
+        return [posixpath.join(d, "lib%s.so" % req.library) for d in LIBDIRS]
+    elif isinstance(req, PythonPackageRequirement):
+        return ["python3dist(%s)" % req.package.lower()]
+    elif isinstance(req, PerlModuleRequirement):
+        return ["perl(%s)" % req.module]
+    elif isinstance(req, RubyGemRequirement):
+        return ["rubygem(%s)" % req.gem]
+    return []
+
+
+def resolve_requirement_dnf(
+        dnf_mgr: DnfManager, req: Requirement) -> Optional[RpmPackageRequirement]:
+    """Find the RPM package for a requirement, if any."""
+    if isinstance(req, RpmPackageRequirement):
+        return req
+    for query in rpm_provides_for_requirement(req):
+        packages = dnf_mgr.provides(query)
+        if packages:
+            if len(packages) > 1:
+                logging.info(
+                    "Multiple packages provide %s: %r, picking first",
+                    query, packages)
+            return RpmPackageRequirement(packages[0])
+    return None
+
+
+class DnfResolver(PackageManagerResolver):
+    """Install requirements with dnf."""
+
+    manager_cls = DnfManager
+
+    def resolve(self, req: Requirement):
+        return resolve_requirement_dnf(self.manager, req)
@@ -0,0 +1,118 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Explain how requirements were (or failed to be) resolved.
+
+When a build fails because a requirement could not be installed, the
+explanation lists what was tried: whether the requirement was found to be
+present, which resolvers could handle its family, which file searches were
+run and which packages were candidates.
+"""
+
+from typing import List, Optional
+
+from .. import Requirement
+
+
+class ResolutionStep(object):
+    """A single step taken while resolving a requirement.
+
+    Attributes:
+      source: What took the step, e.g. the name of a resolver
+      kind: "met-check", "unsupported", "file-search", "candidate" or
+        "no-candidate"
+      description: Human-readable description of the step
+      details: Optional JSON-serializable details
+    """
+
+    def __init__(
+        self, source: str, kind: str, description: str,
+        details: Optional[dict] = None
+    ):
+        self.source = source
+        self.kind = kind
+        self.description = description
+        self.details = details
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__, self.source, self.kind, self.description)
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    def json(self):
+        ret = {
+            "source": self.source,
+            "kind": self.kind,
+            "description": self.description,
+        }
+        if self.details is not None:
+            ret["details"] = self.details
+        return ret
+
+
+class ResolutionExplanation(object):
+    """The steps that were taken to resolve a requirement."""
+
+    def __init__(self, requirement: Requirement, steps: List[ResolutionStep]):
+        self.requirement = requirement
+        self.steps = steps
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.requirement, self.steps)
+
+    @property
+    def resolvable(self) -> bool:
+        """Whether any resolver found a way to install the requirement."""
+        return any(step.kind == "candidate" for step in self.steps)
+
+    def json(self):
+        return {
+            "requirement": str(self.requirement),
+            "family": self.requirement.family,
+            "resolvable": self.resolvable,
+            "steps": [step.json() for step in self.steps],
+        }
+
+    def __str__(self):
+        lines = ["%s:" % self.requirement]
+        for step in self.steps:
+            lines.append("  [%s] %s" % (step.source, step.description))
+        if not self.resolvable:
+            lines.append("  No way to install this requirement was found.")
+        return "\n".join(lines) + "\n"
+
+
+def explain_resolution(session, resolver, requirement) -> ResolutionExplanation:
+    """Explain how a resolver would go about resolving a requirement."""
+    steps = []
+    met = requirement.try_met(session)
+    if met is None:
+        steps.append(ResolutionStep(
+            "session", "met-check",
+            "unable to check whether the requirement is present"))
+    else:
+        steps.append(ResolutionStep(
+            "session", "met-check",
+            "requirement is present" if met else "requirement is not present"))
+    steps.extend(resolver.trace(requirement))
+    return ResolutionExplanation(requirement, steps)
+
+
+def format_explanations(explanations: List[ResolutionExplanation]) -> str:
+    return "".join(str(explanation) for explanation in explanations)
@@ -0,0 +1,148 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install requirements from the FreeBSD package repositories."""
+
+import logging
+import posixpath
+import re
+import subprocess
+from typing import List, Optional
+
+from . import PackageManager, PackageManagerResolver
+from ..requirements import (
+    BinaryRequirement,
+    CHeaderRequirement,
+    FreeBSDPackageRequirement,
+    LibraryRequirement,
+    PathRequirement,
+    PkgConfigRequirement,
+    Requirement,
+)
+
+
+# Third-party software is installed under /usr/local on FreeBSD
+LOCALBASE = "/usr/local"
+
+
+def is_freebsd(session) -> bool:
+    try:
+        output = session.check_output(["uname", "-s"], cwd="/")
+    except (subprocess.CalledProcessError, FileNotFoundError):
+        return False
+    return output.decode().strip() == "FreeBSD"
+
+
+def parse_pkg_provides(lines: List[str]) -> List[str]:
+    """Parse the output of "pkg provides".
+
+    Returns: list of package names, without versions
+    """
+    ret = []
+    for line in lines:
+        key, sep, value = line.partition(":")
+        if not sep or key.strip() != "Name":
+            continue
+        name = value.strip().rsplit("-", 1)[0]
+        if name not in ret:
+            ret.append(name)
+    return ret
+
+
+class PkgManager(PackageManager):
+    """Search and install packages with FreeBSD's pkg.
+
+    File searches use the pkg-provides plugin.
+    """
+
+    name = "pkg"
+
+    def __init__(self, session, prefix=None):
+        super(PkgManager, self).__init__(session, prefix=prefix)
+        self._provides_updated = False
+
+    def update_provides(self) -> None:
+        """Fetch the pkg-provides database, if it has not been yet."""
+        if self._provides_updated:
+            return
+        self._provides_updated = True
+        args = self.prefix + ["pkg", "provides", "-u"]
+        logging.info("pkg: running %r", args)
+        try:
+            self.session.check_call(args, cwd="/", user="root")
+        except subprocess.CalledProcessError:
+            logging.warning("Unable to update the pkg-provides database")
+
+    def _provides(self, path: str) -> List[str]:
+        """Find the packages that contain a file, by absolute path."""
+        self.update_provides()
+        # pkg-provides stores paths without the leading slash
+        try:
+            output = self.session.check_output(
+                ["pkg", "provides", "^%s$" % re.escape(path.lstrip("/"))],
+                cwd="/")
+        except subprocess.CalledProcessError:
+            return []
+        return parse_pkg_provides(output.decode().splitlines())
+
+    def install_command(self, packages: List[str]) -> List[str]:
+        return self.prefix + ["pkg", "install", "-y"] + packages
+
+
+def freebsd_paths_for_requirement(req: Requirement) -> List[str]:
+    """Find the paths of files that would satisfy a requirement."""
+    if isinstance(req, BinaryRequirement):
+        if req.binary_name.startswith("/"):
+            return [req.binary_name]
+        return [posixpath.join(LOCALBASE, d, req.binary_name)
+                for d in ["bin", "sbin"]]
+    elif isinstance(req, PathRequirement):
+        return [req.path]
+    elif isinstance(req, CHeaderRequirement):
+        return [posixpath.join(LOCALBASE, "include", req.header)]
+    elif isinstance(req, PkgConfigRequirement):
+        return [posixpath.join(
+            LOCALBASE, "libdata", "pkgconfig", req.module + ".pc")]
+    elif isinstance(req, LibraryRequirement):
+        return [posixpath.join(LOCALBASE, "lib", "lib%s.so" % req.library)]
+    return []
+
+
+def resolve_requirement_freebsd(
+        pkg_mgr: PkgManager, req: Requirement
+) -> Optional[FreeBSDPackageRequirement]:
+    """Find the FreeBSD package for a requirement, if any."""
+    if isinstance(req, FreeBSDPackageRequirement):
+        return req
+    for path in freebsd_paths_for_requirement(req):
+        packages = pkg_mgr.provides(path)
+        if packages:
+            if len(packages) > 1:
+                logging.info(
+                    "Multiple packages provide %s: %r, picking first",
+                    path, packages)
+            return FreeBSDPackageRequirement(packages[0])
+    return None
+
+
+class FreeBSDPkgResolver(PackageManagerResolver):
+    """Install requirements with FreeBSD's pkg."""
+
+    manager_cls = PkgManager
+
+    def resolve(self, req: Requirement):
+        return resolve_requirement_freebsd(self.manager, req)
@@ -0,0 +1,109 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install requirements from nixpkgs, e.g. on NixOS hosts."""
+
+import logging
+import os
+from typing import List, Optional
+
+from . import PackageMappingResolver, UnsatisfiedRequirements
+from ..fix_build import run_detecting_problems
+from ..requirements import NixPackageRequirement
+
+
+# The nix command and flakes are still experimental, and disabled unless
+# enabled in nix.conf.
+NIX_COMMAND = ["nix", "--extra-experimental-features", "nix-command flakes"]
+
+
+class NixResolver(PackageMappingResolver):
+    """Install requirements from nixpkgs.
+
+    By default, packages are installed into the user's profile with
+    "nix profile install". Alternatively, packages are added to a nix-shell
+    that all subsequent commands run in; the packages are also written to
+    a shell.nix file, so the same environment can be entered later.
+
+    Args:
+      session: Session to install in
+      user_local: Ignored; nix profiles are always per user
+      shell_nix: If set, path of the shell.nix file (inside the session)
+        to write, rather than installing into the profile
+    """
+
+    def __init__(self, session, user_local=True, shell_nix: Optional[str] = None):
+        self.session = session
+        self.user_local = user_local
+        self.shell_nix = shell_nix
+        self._shell = None
+
+    def __str__(self):
+        return "nix-shell" if self.shell_nix else "nix"
+
+    def __repr__(self):
+        return "%s(%r, shell_nix=%r)" % (
+            type(self).__name__, self.session, self.shell_nix)
+
+    def resolve(self, requirement):
+        from ..envfile import resolve_requirement_nix
+
+        attribute = resolve_requirement_nix(requirement)
+        if attribute is None:
+            return None
+        return NixPackageRequirement(attribute)
+
+    def _explain_command(self, nix_req):
+        if self.shell_nix:
+            return ["nix-shell", "-p", nix_req.attribute]
+        return self._profile_install_command(nix_req)
+
+    def _profile_install_command(self, nix_req):
+        return NIX_COMMAND + ["profile", "install", "nixpkgs#" + nix_req.attribute]
+
+    def install(self, requirements):
+        resolved, missing = self._resolve_all(requirements)
+        if self.shell_nix:
+            self._add_to_shell([nix_req.attribute for (req, nix_req) in resolved])
+        else:
+            for requirement, nix_req in resolved:
+                cmd = self._profile_install_command(nix_req)
+                logging.info("nix: running %r", cmd)
+                run_detecting_problems(self.session, cmd)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def _add_to_shell(self, attributes: List[str]) -> None:
+        from ..envfile import generate_shell_nix
+        from ..session.wrappers import NixShell
+
+        if self._shell is None:
+            self._shell = NixShell()
+            self.session.add_command_wrapper(self._shell)
+        for attribute in attributes:
+            if attribute not in self._shell.attributes:
+                logging.info("nix: adding %s to shell", attribute)
+                self._shell.attributes.append(attribute)
+        path = self.session.external_path(self.shell_nix)
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w") as f:
+            f.write(generate_shell_nix(
+                [NixPackageRequirement(a) for a in self._shell.attributes]))
+
+
+def is_nixos(session) -> bool:
+    return session.exists("/etc/NIXOS")
@@ -0,0 +1,122 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install missing binaries from the Snap Store.
+
+Snaps run confined, so a tool installed as a snap may not be able to access
+files outside of the user's home directory. This resolver is therefore
+never used unless explicitly requested.
+"""
+
+import logging
+import subprocess
+from typing import List, Optional
+
+from . import Resolver, UnsatisfiedRequirements
+from ..requirements import BinaryRequirement, SnapPackageRequirement
+from ..session import get_user
+
+
+def parse_snap_confinement(lines: List[str]) -> Optional[str]:
+    """Find the confinement of the stable channel in "snap info" output."""
+    for line in lines:
+        line = line.strip()
+        if line.startswith("latest/stable:"):
+            fields = line.split()
+            if len(fields) < 3:
+                # The channel is closed
+                return None
+            if fields[-1] in ("classic", "devmode"):
+                return fields[-1]
+            return "strict"
+    return None
+
+
+class SnapResolver(Resolver):
+    """Install binaries as snaps.
+
+    Args:
+      session: Session to install in
+      allow_classic: Whether to install snaps that require classic
+        confinement, i.e. that have unrestricted access to the system
+    """
+
+    def __init__(self, session, allow_classic: bool = False):
+        self.session = session
+        self.allow_classic = allow_classic
+        if get_user(session) != "root":
+            self.prefix = ["sudo"]
+        else:
+            self.prefix = []
+
+    def __str__(self):
+        return "snap"
+
+    def __repr__(self):
+        return "%s(%r, allow_classic=%r)" % (
+            type(self).__name__, self.session, self.allow_classic)
+
+    def resolve(self, requirement) -> Optional[SnapPackageRequirement]:
+        if isinstance(requirement, SnapPackageRequirement):
+            return requirement
+        if not isinstance(requirement, BinaryRequirement):
+            return None
+        if requirement.binary_name.startswith("/"):
+            return None
+        try:
+            output = self.session.check_output(
+                ["snap", "info", requirement.binary_name], cwd="/")
+        except subprocess.CalledProcessError:
+            return None
+        confinement = parse_snap_confinement(output.decode().splitlines())
+        if confinement is None:
+            # Not published in the stable channel
+            return None
+        if confinement == "devmode" or (
+                confinement == "classic" and not self.allow_classic):
+            logging.info(
+                "Not installing snap %s, which requires %s confinement",
+                requirement.binary_name, confinement)
+            return None
+        return SnapPackageRequirement(
+            requirement.binary_name, classic=(confinement == "classic"))
+
+    def _cmd(self, snap_req):
+        cmd = self.prefix + ["snap", "install"]
+        if snap_req.classic:
+            cmd.append("--classic")
+        cmd.append(snap_req.snap)
+        return cmd
+
+    def install(self, requirements):
+        missing = []
+        for requirement in requirements:
+            snap_req = self.resolve(requirement)
+            if snap_req is None:
+                missing.append(requirement)
+                continue
+            cmd = self._cmd(snap_req)
+            logging.info("snap: running %r", cmd)
+            self.session.check_call(cmd, cwd="/", user="root")
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def explain(self, requirements):
+        for requirement in requirements:
+            snap_req = self.resolve(requirement)
+            if snap_req is not None:
+                yield (self._cmd(snap_req), [requirement])
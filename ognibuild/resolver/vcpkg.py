@@ -0,0 +1,267 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Install C/C++ requirements with vcpkg."""
+
+import logging
+import posixpath
+import re
+from typing import List, Optional, Set
+
+from . import PackageMappingResolver, UnsatisfiedRequirements
+from .. import DetailedFailure, UnidentifiedError
+from ..fix_build import run_detecting_problems
+from ..requirements import (
+    CHeaderRequirement,
+    PkgConfigRequirement,
+    Requirement,
+    VcpkgPortRequirement,
+)
+
+
+# pkg-config modules whose vcpkg port has a different name
+VCPKG_PKG_CONFIG_MAP = {
+    "glib-2.0": "glib",
+    "gio-2.0": "glib",
+    "gobject-2.0": "glib",
+    "libxml-2.0": "libxml2",
+    "libcurl": "curl",
+    "libcrypto": "openssl",
+    "libssl": "openssl",
+    "libpng": "libpng",
+    "libjpeg": "libjpeg-turbo",
+    "libzstd": "zstd",
+    "liblzma": "liblzma",
+    "libpcre2-8": "pcre2",
+    "yaml-0.1": "libyaml",
+    "gtest": "gtest",
+    "gmock": "gtest",
+}
+
+# Headers (or header directories) and the vcpkg port that provides them
+VCPKG_HEADER_MAP = {
+    "zlib.h": "zlib",
+    "png.h": "libpng",
+    "jpeglib.h": "libjpeg-turbo",
+    "sqlite3.h": "sqlite3",
+    "yaml.h": "libyaml",
+    "lzma.h": "liblzma",
+    "zstd.h": "zstd",
+    "bzlib.h": "bzip2",
+    "ffi.h": "libffi",
+    "expat.h": "expat",
+    "openssl": "openssl",
+    "curl": "curl",
+    "libxml": "libxml2",
+    "glib": "glib",
+    "gtest": "gtest",
+    "gmock": "gtest",
+    "fmt": "fmt",
+    "spdlog": "spdlog",
+    "nlohmann": "nlohmann-json",
+    "yaml-cpp": "yaml-cpp",
+    "Eigen": "eigen3",
+    "eigen3": "eigen3",
+}
+
+
+# Architectures as reported by "uname -m", and their vcpkg names
+VCPKG_ARCHITECTURES = {
+    "x86_64": "x64",
+    "amd64": "x64",
+    "i386": "x86",
+    "i686": "x86",
+    "aarch64": "arm64",
+    "arm64": "arm64",
+    "armv7l": "arm",
+    "ppc64le": "ppc64le",
+    "s390x": "s390x",
+    "riscv64": "riscv64",
+}
+
+# Operating systems as reported by "uname -s", and their vcpkg names
+VCPKG_SYSTEMS = {
+    "Linux": "linux",
+    "Darwin": "osx",
+    "FreeBSD": "freebsd",
+    "OpenBSD": "openbsd",
+}
+
+
+def vcpkg_triplet(system: str, machine: str) -> Optional[str]:
+    """Determine the vcpkg triplet from the output of "uname -s" and "uname -m"."""
+    try:
+        return "%s-%s" % (VCPKG_ARCHITECTURES[machine], VCPKG_SYSTEMS[system])
+    except KeyError:
+        return None
+
+
+def find_missing_ports(lines: List[str]) -> Set[str]:
+    """Find the ports that "vcpkg install" reported as not existing."""
+    ret = set()
+    for line in lines:
+        m = re.match(r"[Ee]rror: ([a-z0-9-]+) does not exist", line)
+        if m:
+            ret.add(m.group(1))
+            continue
+        m = re.search(r"[Pp]ort directory \(.*/ports/([a-z0-9-]+)\) does not exist", line)
+        if m:
+            ret.add(m.group(1))
+    return ret
+
+
+def resolve_requirement_vcpkg(
+        req: Requirement) -> Optional[VcpkgPortRequirement]:
+    """Find the vcpkg port for a requirement, if known."""
+    if isinstance(req, VcpkgPortRequirement):
+        return req
+    elif isinstance(req, PkgConfigRequirement):
+        return VcpkgPortRequirement(
+            VCPKG_PKG_CONFIG_MAP.get(req.module, req.module.lower()))
+    elif isinstance(req, CHeaderRequirement):
+        parts = req.header.split("/")
+        if parts[0] == "boost" and len(parts) > 1:
+            # Boost is split up into one port per library
+            return VcpkgPortRequirement(
+                "boost-%s" % parts[1].split(".")[0].replace("_", "-"))
+        try:
+            return VcpkgPortRequirement(VCPKG_HEADER_MAP[parts[0]])
+        except KeyError:
+            return None
+    return None
+
+
+class VcpkgResolver(PackageMappingResolver):
+    """Install C/C++ requirements with "vcpkg install".
+
+    Ports are installed in classic mode, into the vcpkg root. Afterwards the
+    vcpkg CMake toolchain file is set as CMAKE_TOOLCHAIN_FILE, which the CMake
+    build system passes on when configuring, and the pkg-config files of
+    installed ports are added to PKG_CONFIG_PATH.
+
+    Args:
+      session: Session to install in
+      user_local: Ignored; ports are installed into the vcpkg root
+      triplet: vcpkg triplet to install ports for; defaults to the
+        one matching the session's architecture
+    """
+
+    def __init__(self, session, user_local=True, triplet: Optional[str] = None):
+        self.session = session
+        self.user_local = user_local
+        self._triplet = triplet
+        self._toolchain_file: Optional[str] = None
+
+    def __str__(self):
+        return "vcpkg"
+
+    def __repr__(self):
+        return "%s(%r, triplet=%r)" % (
+            type(self).__name__, self.session, self._triplet)
+
+    @property
+    def triplet(self) -> str:
+        if self._triplet is None:
+            system, machine = self.session.check_output(
+                ["uname", "-sm"]).decode().split()
+            self._triplet = vcpkg_triplet(system, machine)
+            if self._triplet is None:
+                raise NotImplementedError(
+                    "no vcpkg triplet known for %s on %s" % (system, machine))
+        return self._triplet
+
+    def resolve(self, requirement):
+        return resolve_requirement_vcpkg(requirement)
+
+    def _cmd(self, ports):
+        return ["vcpkg", "install", "--triplet=%s" % self.triplet] + ports
+
+    def _explain_command(self, vcpkg_req):
+        return self._cmd([vcpkg_req.port])
+
+    def _vcpkg_install(self, ports: List[str]) -> Set[str]:
+        """Install ports.
+
+        Returns: ports that do not exist
+        """
+        cmd = self._cmd(ports)
+        logging.info("vcpkg: running %r", cmd)
+        output: List[str] = []
+
+        def check_success(retcode, contents):
+            output.extend(contents)
+            return retcode == 0
+
+        try:
+            run_detecting_problems(self.session, cmd, check_success=check_success)
+        except (DetailedFailure, UnidentifiedError):
+            not_found = find_missing_ports("".join(output).splitlines()) & set(ports)
+            if not not_found:
+                raise
+            return not_found
+        return set()
+
+    def install(self, requirements):
+        resolved, missing = self._resolve_all(requirements)
+        while resolved:
+            ports = []
+            for requirement, vcpkg_req in resolved:
+                if vcpkg_req.port not in ports:
+                    ports.append(vcpkg_req.port)
+            not_found = self._vcpkg_install(ports)
+            if not not_found:
+                self._setup_environment()
+                break
+            # Port names are guessed for e.g. pkg-config modules, so a port
+            # that doesn't exist just means vcpkg can't satisfy the
+            # requirement.
+            for requirement, vcpkg_req in list(resolved):
+                if vcpkg_req.port in not_found:
+                    logging.info("vcpkg: port %s does not exist", vcpkg_req.port)
+                    resolved.remove((requirement, vcpkg_req))
+                    missing.append(requirement)
+        if missing:
+            raise UnsatisfiedRequirements(missing)
+
+    def vcpkg_root(self) -> str:
+        """Find the vcpkg root directory inside the session."""
+        root = self.session.getenv("VCPKG_ROOT")
+        if root:
+            return root
+        return posixpath.dirname(self.session.check_output(
+            ["sh", "-c", "realpath \"$(command -v vcpkg)\""]).decode().strip())
+
+    def _setup_environment(self) -> None:
+        root = self.vcpkg_root()
+        toolchain_file = posixpath.join(root, "scripts", "buildsystems", "vcpkg.cmake")
+        # Only set the toolchain file once; if another resolver has set its
+        # own since, that one includes ours.
+        if self._toolchain_file is None:
+            current = self.session.getenv("CMAKE_TOOLCHAIN_FILE")
+            if current and current != toolchain_file:
+                # e.g. set by conan; vcpkg.cmake includes it
+                self.session.setenv("VCPKG_CHAINLOAD_TOOLCHAIN_FILE", current)
+            self.session.setenv("CMAKE_TOOLCHAIN_FILE", toolchain_file)
+            self._toolchain_file = toolchain_file
+        pkgconfig_dir = posixpath.join(
+            root, "installed", self.triplet, "lib", "pkgconfig")
+        pkg_config_path = self.session.getenv("PKG_CONFIG_PATH")
+        if not pkg_config_path:
+            self.session.setenv("PKG_CONFIG_PATH", pkgconfig_dir)
+        elif pkgconfig_dir not in pkg_config_path.split(":"):
+            self.session.setenv(
+                "PKG_CONFIG_PATH", pkgconfig_dir + ":" + pkg_config_path)
@@ -0,0 +1,185 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Create minimal build scaffolding for projects that lack it.
+
+This modifies the source tree, so callers should only use it when
+explicitly asked to.
+"""
+
+import logging
+import os
+import re
+from typing import List, Optional
+
+
+def _project_name(path, name=None):
+    """Determine the name to use for a project.
+
+    Args:
+      path: Path of the project
+      name: Name of the directory the project came from, if path is a copy
+        (e.g. exported into a session, where it is always called "package")
+    """
+    if name is None:
+        name = os.path.basename(os.path.abspath(path))
+    return re.sub(r"[^A-Za-z0-9_.-]", "-", name) or "project"
+
+
+class Scaffolder(object):
+    """Creates build scaffolding of a particular kind."""
+
+    name: str
+
+    def __str__(self):
+        return self.name
+
+    @classmethod
+    def probe(cls, path) -> Optional["Scaffolder"]:
+        return None
+
+    def create(self, path, name=None) -> List[str]:
+        """Create the scaffolding.
+
+        Args:
+          path: Path of the project
+          name: Name of the project's original directory, if known
+        Returns: list of paths (relative to path) that were created
+        """
+        raise NotImplementedError(self.create)
+
+
+class PyprojectScaffolder(Scaffolder):
+    """Create a pyproject.toml for a tree with Python modules."""
+
+    name = "pyproject.toml"
+
+    TEMPLATE = """\
+[build-system]
+requires = ["setuptools>=61", "wheel"]
+build-backend = "setuptools.build_meta"
+
+[project]
+name = "%(name)s"
+version = "%(version)s"
+"""
+
+    def __init__(self, version="0.0.0"):
+        self.version = version
+
+    @classmethod
+    def probe(cls, path):
+        if os.path.exists(os.path.join(path, "pyproject.toml")):
+            return None
+        for entry in os.scandir(path):
+            if entry.name.endswith(".py"):
+                return cls()
+            if entry.is_dir() and os.path.exists(
+                os.path.join(entry.path, "__init__.py")
+            ):
+                return cls()
+        return None
+
+    @staticmethod
+    def _package_name(path):
+        """Find the name of the only Python package in path, if any."""
+        packages = [
+            entry.name for entry in os.scandir(path)
+            if entry.is_dir()
+            and os.path.exists(os.path.join(entry.path, "__init__.py"))]
+        if len(packages) == 1:
+            return packages[0]
+        return None
+
+    def create(self, path, name=None):
+        name = self._package_name(path) or _project_name(path, name)
+        with open(os.path.join(path, "pyproject.toml"), "w") as f:
+            f.write(self.TEMPLATE % {"name": name, "version": self.version})
+        return ["pyproject.toml"]
+
+
+class MakefileScaffolder(Scaffolder):
+    """Create a basic Makefile for a tree with C sources."""
+
+    name = "Makefile"
+
+    TEMPLATE = """\
+CC ?= cc
+CFLAGS ?= -O2 -Wall
+PREFIX ?= /usr/local
+SOURCES = $(wildcard *.c)
+OBJECTS = $(SOURCES:.c=.o)
+NAME = %(name)s
+
+all: $(NAME)
+
+$(NAME): $(OBJECTS)
+\t$(CC) $(CFLAGS) -o $@ $^ $(LDFLAGS) $(LDLIBS)
+
+install: $(NAME)
+\tinstall -D $(NAME) $(DESTDIR)$(PREFIX)/bin/$(NAME)
+
+dist:
+\ttar czf $(NAME).tar.gz --exclude=$(NAME).tar.gz --transform 's,^\\.,$(NAME),' .
+
+check:
+
+clean:
+\trm -f $(NAME) $(OBJECTS)
+
+.PHONY: all install dist check clean
+"""
+
+    @classmethod
+    def probe(cls, path):
+        if any(
+            os.path.exists(os.path.join(path, n))
+            for n in ["Makefile", "GNUmakefile", "makefile"]
+        ):
+            return None
+        for entry in os.scandir(path):
+            if entry.name.endswith(".c"):
+                return cls()
+        return None
+
+    def create(self, path, name=None):
+        with open(os.path.join(path, "Makefile"), "w") as f:
+            f.write(self.TEMPLATE % {"name": _project_name(path, name)})
+        return ["Makefile"]
+
+
+SCAFFOLDER_CLSES = [
+    PyprojectScaffolder,
+    MakefileScaffolder,
+]
+
+
+def create_scaffolding(path, name=None) -> List[str]:
+    """Create build scaffolding in path, using the first matching scaffolder.
+
+    Args:
+      path: Path of the project
+      name: Name of the project's original directory, if path is a copy
+    Returns: list of created files
+    """
+    for cls in SCAFFOLDER_CLSES:
+        scaffolder = cls.probe(path)
+        if scaffolder is None:
+            continue
+        logging.info("Creating build scaffolding: %s", scaffolder)
+        return scaffolder.create(path, name)
+    return []
@@ -16,9 +16,18 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
-from typing import Optional, List, Dict, Tuple
+from typing import Optional, List, Dict, Tuple, Callable, Any, Union
+import logging
+import os
+import re
+import shlex
+import shutil
+import signal
+import stat
 import sys
 import subprocess
+import threading
+import time
 
 
 class NoSessionOpen(Exception):
@@ -35,13 +44,440 @@ class SessionAlreadyOpen(Exception):
         self.session = session
 
 
+class SessionLost(Exception):
+    """The session went away while it was in use."""
+
+    def __init__(self, session, reason: Optional[str] = None):
+        self.session = session
+        self.reason = reason
+
+    def __str__(self):
+        if self.reason:
+            return "Session %r lost: %s" % (self.session, self.reason)
+        return "Session %r lost" % (self.session,)
+
+
+class SessionTeardownFailure(Exception):
+    """Session could not be cleaned up completely."""
+
+    def __init__(self, session, errors: List[str]):
+        self.session = session
+        self.errors = errors
+
+    def __str__(self):
+        return "Failed to tear down %r: %s" % (self.session, "; ".join(self.errors))
+
+
+# Prefix for the names of sessions and temporary directories created by
+# ognibuild, so that leftovers from crashed runs can be found.
+ORPHAN_PREFIX = "ognibuild-"
+
+
+def owned_name_prefix() -> str:
+    """Return a name prefix identifying resources owned by this process."""
+    return "%s%d-" % (ORPHAN_PREFIX, os.getpid())
+
+
+def is_orphan_name(name: str) -> bool:
+    """Check whether name belongs to a process that no longer exists."""
+    m = re.match(re.escape(ORPHAN_PREFIX) + r"([0-9]+)-", name)
+    if not m:
+        return False
+    try:
+        os.kill(int(m.group(1)), 0)
+    except ProcessLookupError:
+        return True
+    except PermissionError:
+        pass
+    return False
+
+
+def default_cache_dir() -> str:
+    """Directory on the host for caches that are shared between sessions."""
+    return os.path.join(
+        os.environ.get("XDG_CACHE_HOME") or os.path.expanduser("~/.cache"),
+        "ognibuild")
+
+
+def default_work_dir(kind: Optional[str] = None) -> Optional[str]:
+    """Directory on the host for temporary projects and build directories.
+
+    This is read from $OGNIBUILD_<KIND>_WORK_DIR (e.g.
+    $OGNIBUILD_UNSHARE_WORK_DIR) or $OGNIBUILD_WORK_DIR. Point it at a
+    scratch disk or a size-limited tmpfs to keep large builds out of /tmp.
+
+    Returns: path, or None to use the system temporary directory
+    """
+    if kind is not None:
+        path = os.environ.get("OGNIBUILD_%s_WORK_DIR" % kind.upper())
+        if path:
+            return path
+    return os.environ.get("OGNIBUILD_WORK_DIR") or None
+
+
+def mount_size_limited_tmpfs(path: str, size: str) -> None:
+    """Mount a size-limited tmpfs on a work directory.
+
+    This caps the disk space that temporary projects and build directories
+    can use, so that a large build fails rather than filling up the host.
+    Mounting requires root, or CAP_SYS_ADMIN.
+
+    Args:
+      path: Directory to mount the tmpfs on; created if it does not exist
+      size: Maximum size, in a format understood by mount(8), e.g. "20G"
+    """
+    os.makedirs(path, exist_ok=True)
+    subprocess.check_call(
+        ["mount", "-t", "tmpfs", "-o", "size=%s,mode=1777" % size,
+         "ognibuild-work", path])
+
+
+def unmount_tmpfs(path: str) -> None:
+    """Unmount a tmpfs mounted with mount_size_limited_tmpfs."""
+    # Lazily, since processes left behind by a build may still use it
+    if subprocess.call(["umount", "--lazy", path]) != 0:
+        logging.warning("Unable to unmount %s", path)
+
+
+def cgroups_v2_available() -> bool:
+    """Check whether commands can be run in a transient cgroup (v2) scope."""
+    if not os.path.exists("/sys/fs/cgroup/cgroup.controllers"):
+        return False
+    if shutil.which("systemd-run") is None:
+        return False
+    # Unprivileged users need their own systemd instance to delegate to
+    return os.getuid() == 0 or "XDG_RUNTIME_DIR" in os.environ
+
+
+class ResourceLimits(object):
+    """Limits to apply to commands run in a session.
+
+    Memory and process limits are enforced with a cgroup v2 scope, so they
+    apply to the command and its children rather than to the address space
+    of each process (which breaks e.g. the JVM, Go and AddressSanitizer) or
+    to all processes of the user. Without cgroups v2 they are not enforced.
+
+    Args:
+      memory: Maximum amount of memory, in bytes
+      cpu_time: Maximum CPU time per process, in seconds
+      pids: Maximum number of processes and threads
+    """
+
+    def __init__(
+        self,
+        memory: Optional[int] = None,
+        cpu_time: Optional[int] = None,
+        pids: Optional[int] = None,
+    ):
+        self.memory = memory
+        self.cpu_time = cpu_time
+        self.pids = pids
+        self._warned = False
+
+    def __repr__(self):
+        return "%s(memory=%r, cpu_time=%r, pids=%r)" % (
+            type(self).__name__,
+            self.memory,
+            self.cpu_time,
+            self.pids,
+        )
+
+    def __bool__(self):
+        return any(x is not None for x in [self.memory, self.cpu_time, self.pids])
+
+    def setrlimits(self) -> None:
+        """Apply the per-process limits to the current process."""
+        import resource
+
+        if self.cpu_time is not None:
+            resource.setrlimit(resource.RLIMIT_CPU, (self.cpu_time, self.cpu_time))
+
+    def prlimit_argv(self) -> List[str]:
+        """Return a prlimit(1) invocation that applies the per-process limits.
+
+        Returns an empty list if there are no per-process limits.
+        """
+        if self.cpu_time is None:
+            return []
+        return ["prlimit", "--cpu=%d" % self.cpu_time, "--"]
+
+    def cgroup_properties(self) -> List[str]:
+        ret = []
+        if self.memory is not None:
+            ret.append("MemoryMax=%d" % self.memory)
+        if self.pids is not None:
+            ret.append("TasksMax=%d" % self.pids)
+        return ret
+
+    def cgroup_argv(self) -> List[str]:
+        """Return a systemd-run(1) invocation that runs a host command in a
+        transient cgroup with the memory and process limits.
+
+        Returns an empty list if there are no such limits, or if they can
+        not be enforced on this host.
+        """
+        properties = self.cgroup_properties()
+        if not properties:
+            return []
+        if not cgroups_v2_available():
+            if not self._warned:
+                logging.warning(
+                    "cgroups v2 are not available; not enforcing %s",
+                    ", ".join(properties))
+                self._warned = True
+            return []
+        ret = ["systemd-run", "--scope", "--quiet", "--collect"]
+        if os.getuid() != 0:
+            ret.append("--user")
+        for prop in properties:
+            ret.extend(["-p", prop])
+        ret.append("--")
+        return ret
+
+
+class DirEntry(object):
+    """Entry in a directory listing in a session.
+
+    This mirrors the parts of os.DirEntry that callers use, but does not
+    require the directory to be accessible on the local filesystem.
+
+    Attributes:
+      name: Name of the entry
+      path: Path of the entry, as seen from inside the session
+      file_type: One of "file", "dir", "symlink" or "other"; symlinks are
+        not followed
+      metadata: os.stat_result-like object for the entry (following
+        symlinks), or None if not available
+    """
+
+    __slots__ = ("name", "path", "file_type", "metadata")
+
+    def __init__(self, name: str, path: str, file_type: str, metadata=None):
+        self.name = name
+        self.path = path
+        self.file_type = file_type
+        self.metadata = metadata
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__, self.name, self.path, self.file_type)
+
+    def _is_type(self, file_type, test, follow_symlinks):
+        if self.file_type == file_type:
+            return True
+        if follow_symlinks and self.file_type == "symlink" and self.metadata:
+            return test(self.metadata.st_mode)
+        return False
+
+    def is_dir(self, follow_symlinks: bool = True) -> bool:
+        return self._is_type("dir", stat.S_ISDIR, follow_symlinks)
+
+    def is_file(self, follow_symlinks: bool = True) -> bool:
+        return self._is_type("file", stat.S_ISREG, follow_symlinks)
+
+    def is_symlink(self) -> bool:
+        return self.file_type == "symlink"
+
+    def stat(self):
+        if self.metadata is None:
+            raise FileNotFoundError(self.path)
+        return self.metadata
+
+    @classmethod
+    def from_os_direntry(cls, entry: os.DirEntry, path: str) -> "DirEntry":
+        if entry.is_symlink():
+            file_type = "symlink"
+        elif entry.is_dir(follow_symlinks=False):
+            file_type = "dir"
+        elif entry.is_file(follow_symlinks=False):
+            file_type = "file"
+        else:
+            file_type = "other"
+        try:
+            metadata = entry.stat()
+        except FileNotFoundError:
+            metadata = None
+        return cls(entry.name, path, file_type, metadata)
+
+
+def scandir_external(external_path: str, path: str) -> List[DirEntry]:
+    """List a session directory that is accessible on the local filesystem.
+
+    Args:
+      external_path: Path of the directory on the local filesystem
+      path: Path of the directory inside the session
+    """
+    with os.scandir(external_path) as it:
+        return [
+            DirEntry.from_os_direntry(entry, os.path.join(path, entry.name))
+            for entry in it
+        ]
+
+
 class Session(object):
+
+    limits: Optional[ResourceLimits] = None
+
+    _env: Optional[Dict[str, str]] = None
+
+    metrics: Optional[Any] = None
+
+    output_forwarder: Optional[Any] = None
+
+    _wrappers: Optional[List[Any]] = None
+
+    kind: Optional[str] = None
+
+    work_dir: Optional[str] = None
+
+    # Whether commands can be run from several threads at once; sessions
+    # that e.g. reconnect when they go away are not
+    thread_safe: bool = False
+
+    def add_command_wrapper(self, wrapper) -> None:
+        """Wrap all subsequent commands, e.g. with nice or eatmydata.
+
+        Wrappers that are added first end up outermost.
+
+        Args:
+          wrapper: A CommandWrapper
+        """
+        if self._wrappers is None:
+            self._wrappers = []
+        self._wrappers.append(wrapper)
+
+    def _wrap_argv(self, argv: List[str]) -> List[str]:
+        for wrapper in reversed(self._wrappers or []):
+            argv = wrapper.wrap(argv)
+        return argv
+
+    def set_metrics_collector(self, collector) -> None:
+        """Collect metrics for commands run with run_with_tee/run_with_callback.
+
+        Args:
+          collector: A MetricsCollector, or None to stop collecting metrics
+        """
+        self.metrics = collector
+
+    def set_output_forwarder(self, forwarder) -> None:
+        """Set how run_with_tee forwards the output of commands.
+
+        Args:
+          forwarder: An OutputForwarder, or None to copy output to stdout as is
+        """
+        self.output_forwarder = forwarder
+
+    def set_work_dir(self, path: Optional[str]) -> None:
+        """Set the host directory for temporary projects and build directories.
+
+        Args:
+          path: Directory to use, or None to fall back to default_work_dir()
+        """
+        self.work_dir = path
+
+    def _temporary_parent(self) -> Optional[str]:
+        """Parent directory for new temporary directories on the host."""
+        path = self.work_dir or default_work_dir(self.kind)
+        if path is not None:
+            os.makedirs(path, exist_ok=True)
+        return path
+
+    def set_resource_limits(self, limits: Optional[ResourceLimits]) -> None:
+        """Set the resource limits for subsequent commands."""
+        self.limits = limits
+
+    def _limit_argv(self, argv: List[str]) -> List[str]:
+        """Run a host command in a cgroup with the session's resource limits."""
+        if not self.limits:
+            return argv
+        return self.limits.cgroup_argv() + argv
+
+    def setenv(self, key: str, value: Optional[str]) -> None:
+        """Set an environment variable for all subsequent commands.
+
+        Variables passed to individual commands take precedence.
+
+        Args:
+          key: Name of the variable
+          value: Value to set, or None to stop setting the variable
+        """
+        if self._env is None:
+            self._env = {}
+        if value is None:
+            self._env.pop(key, None)
+        else:
+            self._env[key] = value
+
+    def getenv(self, key: str) -> Optional[str]:
+        """Get a variable set with setenv, or None if it is not set."""
+        if self._env is None:
+            return None
+        return self._env.get(key)
+
+    def _merge_env(
+        self, env: Optional[Dict[str, str]]
+    ) -> Optional[Dict[str, str]]:
+        """Combine the session environment with per-command variables."""
+        if not self._env:
+            return env
+        ret = dict(self._env)
+        if env:
+            ret.update(env)
+        return ret
+
     def __enter__(self) -> "Session":
         return self
 
     def __exit__(self, exc_type, exc_val, exc_tb):
         return False
 
+    _teardown_hooks: Optional[List[Callable[["Session"], None]]] = None
+
+    def on_teardown(self, callback: Callable[["Session"], None]) -> None:
+        """Register a callback to run before the session is torn down.
+
+        This can be used to copy logs, caches or core dumps out of the
+        session before it is destroyed. Callbacks are called with the
+        session, in reverse order of registration. Failing callbacks are
+        reported, but do not prevent the teardown.
+        """
+        if self._teardown_hooks is None:
+            self._teardown_hooks = []
+        self._teardown_hooks.append(callback)
+
+    def _run_teardown_hooks(self) -> List[str]:
+        """Run the teardown callbacks.
+
+        Returns: list of error messages for callbacks that failed
+        """
+        errors = []
+        hooks, self._teardown_hooks = self._teardown_hooks or [], None
+        for callback in reversed(hooks):
+            try:
+                callback(self)
+            except Exception as e:
+                logging.warning("Teardown callback %r failed: %s", callback, e)
+                errors.append("teardown callback %r failed: %s" % (callback, e))
+        return errors
+
+    def close(self) -> None:
+        """Close the session and clean up any resources it holds.
+
+        Unlike leaving the context manager, this reports failures.
+
+        Raises:
+          SessionTeardownFailure: if cleaning up failed
+        """
+        self.__exit__(None, None, None)
+
+    def ping(self) -> bool:
+        """Check whether the session is still usable."""
+        try:
+            self.check_output(["true"])
+        except (subprocess.CalledProcessError, FileNotFoundError, NoSessionOpen):
+            return False
+        return True
+
     def chdir(self, cwd: str) -> None:
         raise NotImplementedError(self.chdir)
 
@@ -86,7 +522,8 @@ class Session(object):
         """Check whether a path exists in the chroot."""
         raise NotImplementedError(self.exists)
 
-    def scandir(self, path: str):
+    def scandir(self, path: str) -> List[DirEntry]:
+        """List the entries in a directory in the session."""
         raise NotImplementedError(self.scandir)
 
     def setup_from_vcs(
@@ -103,30 +540,478 @@ class Session(object):
     def rmtree(self, path: str) -> str:
         raise NotImplementedError
 
+    def share_host_directory(
+        self, source: str, target: str
+    ) -> Optional[str]:
+        """Make a host directory available inside the session.
+
+        This has to be called before the directory is used by any command.
+
+        Args:
+          source: Path of the directory on the host
+          target: Preferred path inside the session
+        Returns: path of the directory inside the session, or None if this
+            session can not share host directories
+        """
+        return None
+
+    # Paths (relative to the root) that are left out of exported images
+    image_excludes = ["./proc", "./sys", "./dev", "./run", "./tmp", "./build"]
+
+    def export_image(self, path: str) -> None:
+        """Export the session filesystem as a gzipped tarball.
+
+        This is meant to be called after dependencies have been installed, so
+        that later sessions can start from exactly the same environment. The
+        tarball can also be used as an OCI image layer.
+
+        Args:
+          path: Path on the host to write the tarball to
+        """
+        argv = ["tar", "--one-file-system", "--numeric-owner"]
+        argv.extend(["--exclude=%s" % p for p in self.image_excludes])
+        argv.extend(["-C", "/", "-czf", "-", "."])
+        logging.info("Exporting session image to %s", path)
+        with open(path, "wb") as f:
+            p = self.Popen(argv, cwd="/", user="root", stdout=f)
+            if p.wait() != 0:
+                raise CommandFailed(p.returncode, argv, cwd="/")
+
     is_temporary: bool
 
 
+class CommandFailed(subprocess.CalledProcessError):
+    """A command run in a session failed.
+
+    Besides the exit status, this records the working directory, the signal
+    that killed the command (if any) and the tail of its output (if it was
+    captured), so that errors can be diagnosed from the message alone.
+    """
+
+    # Number of lines of captured output to include in the message
+    TAIL_LINES = 20
+
+    def __init__(self, returncode, cmd, output=None, stderr=None, cwd=None):
+        super(CommandFailed, self).__init__(returncode, cmd, output, stderr)
+        self.cwd = cwd
+
+    @classmethod
+    def from_error(cls, e, argv=None, cwd=None):
+        """Create from a CalledProcessError, replacing the command."""
+        return cls(
+            e.returncode, e.cmd if argv is None else argv, e.output, e.stderr,
+            cwd=cwd)
+
+    @property
+    def signal(self) -> Optional[int]:
+        if self.returncode is not None and self.returncode < 0:
+            return -self.returncode
+        return None
+
+    def tail(self) -> List[str]:
+        """The last lines of captured output, stderr first."""
+        lines: List[str] = []
+        for data in [self.stderr, self.output]:
+            if not data:
+                continue
+            if isinstance(data, bytes):
+                data = data.decode("utf-8", "replace")
+            lines.extend(data.splitlines())
+        return lines[-self.TAIL_LINES:]
+
+    def __str__(self):
+        if isinstance(self.cmd, list):
+            command = shlex.join(self.cmd)
+        else:
+            command = str(self.cmd)
+        ret = "Command '%s'" % command
+        if self.cwd:
+            ret += " (in %s)" % self.cwd
+        if self.signal is not None:
+            try:
+                name = signal.Signals(self.signal).name
+            except ValueError:
+                name = "signal %d" % self.signal
+            ret += " died with %s." % name
+        else:
+            ret += " returned non-zero exit status %d." % self.returncode
+        tail = self.tail()
+        if tail:
+            ret += "\nLast output:\n" + "\n".join(tail)
+        return ret
+
+
 class SessionSetupFailure(Exception):
     """Session failed to be set up."""
 
 
-def run_with_tee(session: Session, args: List[str], **kwargs):
+class TimedOut(Exception):
+    """Command did not finish within the allotted time."""
+
+    def __init__(self, argv, timeout):
+        self.argv = argv
+        self.timeout = timeout
+
+    def __str__(self):
+        return "Command %r timed out after %s seconds" % (self.argv, self.timeout)
+
+
+def _wait_with_rusage(p) -> Optional[int]:
+    """Wait for a process to exit.
+
+    Returns: maximum resident set size of the process and the descendants it
+        waited for, in kilobytes, or None if unknown
+    """
+    if not isinstance(p, subprocess.Popen) or p.returncode is not None:
+        p.wait()
+        return None
+    try:
+        unused_pid, status, rusage = os.wait4(p.pid, 0)
+    except ChildProcessError:
+        p.wait()
+        return None
+    p.returncode = os.waitstatus_to_exitcode(status)
+    return rusage.ru_maxrss
+
+
+def _kill_process_group(p) -> None:
+    """Kill a process started with start_new_session, and its descendants.
+
+    Killing just the process would leave e.g. the compilers started by make
+    running, and keeping the output pipe open.
+    """
+    pid = getattr(p, "pid", None)
+    if pid is None:
+        p.kill()
+        return
+    try:
+        os.killpg(pid, signal.SIGKILL)
+    except ProcessLookupError:
+        pass
+    except PermissionError:
+        # e.g. the command was started with sudo
+        p.kill()
+
+
+def run_with_tee(
+    session: Session,
+    args: List[str],
+    timeout: Optional[float] = None,
+    separate_streams: bool = False,
+    **kwargs
+):
+    """Run a command, copying its output to stdout and collecting it.
+
+    Args:
+      session: Session to run the command in
+      args: Command to run
+      timeout: Optional timeout, in seconds
+      separate_streams: If True, keep stdout and stderr apart; the returned
+        lines are then OutputLine objects, in the order they were read
+    Returns: tuple with exit code and list of lines
+    """
+    forwarder = session.output_forwarder
+    if separate_streams:
+        contents: List[Any] = []
+
+        def tee(line):
+            data = line.line.encode("utf-8", "surrogateescape")
+            if forwarder is not None:
+                forwarder.forward(line.stream, data, line.timestamp)
+            else:
+                if line.stream == "stderr":
+                    f = sys.stderr.buffer
+                else:
+                    f = sys.stdout.buffer
+                f.write(data)
+                f.flush()
+            contents.append(line)
+
+        retcode = run_with_callback(session, args, tee, timeout=timeout, **kwargs)
+        return retcode, contents
     if "stdin" not in kwargs:
         kwargs["stdin"] = subprocess.DEVNULL
+    if timeout is not None:
+        # Run in a separate process group, so that the whole group can be
+        # killed when the timeout is reached
+        kwargs["start_new_session"] = True
+    start = time.monotonic()
     p = session.Popen(args, stdout=subprocess.PIPE, stderr=subprocess.STDOUT, **kwargs)
+    timer = None
+    timed_out = threading.Event()
+    if timeout is not None:
+
+        def kill():
+            timed_out.set()
+            _kill_process_group(p)
+
+        timer = threading.Timer(timeout, kill)
+        timer.start()
     contents = []
-    while p.poll() is None:
-        line = p.stdout.readline()
-        sys.stdout.buffer.write(line)
-        sys.stdout.buffer.flush()
+
+    def tee_line(line):
+        if forwarder is not None:
+            forwarder.forward("output", line)
+        else:
+            sys.stdout.buffer.write(line)
+            sys.stdout.buffer.flush()
         contents.append(line.decode("utf-8", "surrogateescape"))
+
+    try:
+        if session.metrics is None:
+            while p.poll() is None:
+                tee_line(p.stdout.readline())
+        else:
+            # Read until EOF rather than polling, so that the process can be
+            # reaped with its resource usage.
+            for line in iter(p.stdout.readline, b""):
+                tee_line(line)
+            max_rss = _wait_with_rusage(p)
+            session.metrics.record(
+                args, time.monotonic() - start, max_rss, p.returncode)
+    finally:
+        if timer is not None:
+            timer.cancel()
+    if timed_out.is_set() and p.returncode != 0:
+        raise TimedOut(args, timeout)
     return p.returncode, contents
 
 
+class OutputLine(object):
+    """A line of output from a command.
+
+    Attributes:
+      stream: Name of the stream the line came from ("stdout" or "stderr")
+      line: The line itself, including trailing newline
+      timestamp: Time at which the line was read, in seconds since the epoch
+    """
+
+    __slots__ = ("stream", "line", "timestamp")
+
+    def __init__(self, stream: str, line: str, timestamp: float):
+        self.stream = stream
+        self.line = line
+        self.timestamp = timestamp
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__,
+            self.stream,
+            self.line,
+            self.timestamp,
+        )
+
+    def __eq__(self, other):
+        return (
+            isinstance(other, type(self))
+            and self.stream == other.stream
+            and self.line == other.line
+            and self.timestamp == other.timestamp
+        )
+
+
+def output_lines(
+    lines: List[OutputLine], stream: Optional[str] = None
+) -> List[str]:
+    """Extract the text of output lines, optionally only from one stream.
+
+    Args:
+      lines: Lines as returned by run_with_tee with separate_streams
+      stream: Stream to return lines for, or None for all lines, in the
+        order in which they were read
+    Returns: list of lines, without trailing newlines
+    """
+    return "".join(
+        [line.line for line in lines if stream is None or line.stream == stream]
+    ).splitlines(False)
+
+
+def run_with_callback(
+    session: Session,
+    args: List[str],
+    callback: Callable[[OutputLine], None],
+    timeout: Optional[float] = None,
+    **kwargs
+) -> int:
+    """Run a command, calling callback for each line of output as it arrives.
+
+    Returns: the exit code of the command
+    """
+    import queue
+
+    if "stdin" not in kwargs:
+        kwargs["stdin"] = subprocess.DEVNULL
+    if timeout is not None:
+        kwargs["start_new_session"] = True
+    start = time.monotonic()
+    p = session.Popen(args, stdout=subprocess.PIPE, stderr=subprocess.PIPE, **kwargs)
+    timer = None
+    timed_out = threading.Event()
+    if timeout is not None:
+
+        def kill():
+            timed_out.set()
+            _kill_process_group(p)
+
+        timer = threading.Timer(timeout, kill)
+        timer.start()
+    # Read each stream in its own thread; mixing buffered readline() with
+    # select() can leave lines sitting in the buffer while select() blocks.
+    lines: "queue.Queue[Optional[Tuple[str, bytes]]]" = queue.Queue()
+
+    def reader(name, f):
+        try:
+            for line in iter(f.readline, b""):
+                lines.put((name, line))
+        finally:
+            lines.put(None)
+
+    readers = [
+        threading.Thread(target=reader, args=(name, f), daemon=True)
+        for (name, f) in [("stdout", p.stdout), ("stderr", p.stderr)]
+    ]
+    try:
+        for t in readers:
+            t.start()
+        remaining = len(readers)
+        while remaining:
+            item = lines.get()
+            if item is None:
+                remaining -= 1
+                continue
+            name, line = item
+            callback(
+                OutputLine(name, line.decode("utf-8", "surrogateescape"), time.time())
+            )
+        for t in readers:
+            t.join()
+        if session.metrics is None:
+            p.wait()
+        else:
+            max_rss = _wait_with_rusage(p)
+            session.metrics.record(
+                args, time.monotonic() - start, max_rss, p.returncode)
+    finally:
+        if timer is not None:
+            timer.cancel()
+        p.stdout.close()
+        p.stderr.close()
+    if timed_out.is_set() and p.returncode != 0:
+        raise TimedOut(args, timeout)
+    return p.returncode
+
+
+class CommandSpec(object):
+    """A command to run with run_parallel.
+
+    Args:
+      argv: Command to run
+      cwd: Directory to run in
+      user: User to run as
+      env: Additional environment variables
+      stdin: Data to pass on standard input
+    """
+
+    def __init__(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+        stdin: Optional[bytes] = None,
+    ):
+        self.argv = argv
+        self.cwd = cwd
+        self.user = user
+        self.env = env
+        self.stdin = stdin
+
+    def __repr__(self):
+        return "%s(%r, cwd=%r, user=%r, env=%r)" % (
+            type(self).__name__, self.argv, self.cwd, self.user, self.env)
+
+
+# Default number of commands to run concurrently in run_parallel
+DEFAULT_PARALLELISM = os.cpu_count() or 4
+
+
+def _run_spec(session: Session, spec: CommandSpec) -> subprocess.CompletedProcess:
+    kwargs = {}
+    if spec.env is not None:
+        kwargs["env"] = spec.env
+    p = session.Popen(
+        spec.argv,
+        cwd=spec.cwd,
+        user=spec.user,
+        stdin=(subprocess.PIPE if spec.stdin is not None else subprocess.DEVNULL),
+        stdout=subprocess.PIPE,
+        stderr=subprocess.PIPE,
+        **kwargs
+    )
+    stdout, stderr = p.communicate(spec.stdin)
+    return subprocess.CompletedProcess(spec.argv, p.returncode, stdout, stderr)
+
+
+def run_parallel(
+    session: Session,
+    commands: List[CommandSpec],
+    max_workers: Optional[int] = None,
+) -> List[Union[subprocess.CompletedProcess, Exception]]:
+    """Run independent commands concurrently in a session.
+
+    Args:
+      session: Session to run the commands in
+      commands: Commands to run
+      max_workers: Maximum number of commands to run at the same time
+    Returns: list with, for each command, either a CompletedProcess or the
+        exception raised while trying to run it
+    """
+    from concurrent.futures import ThreadPoolExecutor
+
+    if max_workers is None:
+        max_workers = DEFAULT_PARALLELISM
+    if not session.thread_safe:
+        max_workers = 1
+    if not commands:
+        return []
+    with ThreadPoolExecutor(max_workers=max_workers) as executor:
+        futures = [executor.submit(_run_spec, session, spec) for spec in commands]
+    ret: List[Union[subprocess.CompletedProcess, Exception]] = []
+    for future in futures:
+        e = future.exception()
+        if e is not None:
+            ret.append(e)  # type: ignore
+        else:
+            ret.append(future.result())
+    return ret
+
+
 def get_user(session):
     return session.check_output(["sh", "-c", "echo $USER"], cwd="/").decode().strip()
 
 
+def get_home(session):
+    return session.check_output(["sh", "-c", "echo $HOME"], cwd="/").decode().strip()
+
+
+def prepend_path(session, path: str, variable: str = "PATH") -> None:
+    """Add a directory to the front of a search path for subsequent commands.
+
+    Args:
+      session: Session to set the variable in
+      path: Directory to add; nothing happens if it is already present
+      variable: Name of the colon-separated search path variable
+    """
+    current = session.getenv(variable)
+    if current is None:
+        current = session.check_output(
+            ["sh", "-c", "echo ${%s}" % variable], cwd="/").decode().strip()
+    if not current:
+        session.setenv(variable, path)
+    elif path not in current.split(":"):
+        session.setenv(variable, "%s:%s" % (path, current))
+
+
 def which(session, name):
     try:
         ret = session.check_output(["which", name], cwd="/").decode().strip()
@@ -137,3 +1022,20 @@ def which(session, name):
     if not ret:
         return None
     return ret
+
+
+def create_temporary_directory(session: Session, prefix: str = "ognibuild") -> str:
+    """Create a temporary directory inside a session.
+
+    The directory is removed when the session is torn down.
+
+    Returns: path of the directory inside the session
+    """
+    path = session.check_output(
+        ["mktemp", "-d", "-t", "%s.XXXXXXXX" % prefix], cwd="/").decode().strip()
+
+    def remove(session):
+        session.check_call(["rm", "-rf", path], cwd="/")
+
+    session.on_teardown(remove)
+    return path
@@ -0,0 +1,154 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Clean up sessions and temporary directories left behind by crashed runs.
+
+Resources are recognized as leftovers if their name starts with the prefix
+from owned_name_prefix() and the process that created them no longer exists.
+"""
+
+import logging
+import os
+import shutil
+import signal
+import subprocess
+import tempfile
+from typing import List, Optional
+
+from . import default_work_dir, is_orphan_name
+
+
+def _orphan_schroot_sessions() -> List[str]:
+    try:
+        output = subprocess.check_output(
+            ["schroot", "--list", "--all-sessions"], stderr=subprocess.DEVNULL
+        )
+    except (FileNotFoundError, subprocess.CalledProcessError):
+        return []
+    ret = []
+    for line in output.decode().splitlines():
+        name = line.strip()
+        if name.startswith("session:"):
+            name = name[len("session:"):]
+        if is_orphan_name(name):
+            ret.append(name)
+    return ret
+
+
+def _orphan_directories(directory: str) -> List[str]:
+    try:
+        entries = list(os.scandir(directory))
+    except FileNotFoundError:
+        return []
+    return [
+        entry.path
+        for entry in entries
+        if entry.is_dir(follow_symlinks=False) and is_orphan_name(entry.name)
+    ]
+
+
+def _read_process(path: str):
+    with open(os.path.join(path, "cmdline"), "rb") as f:
+        argv = f.read().decode(errors="replace").split("\0")
+    with open(os.path.join(path, "stat"), "r") as f:
+        ppid = int(f.read().rsplit(")", 1)[1].split()[1])
+    try:
+        root: Optional[str] = os.readlink(os.path.join(path, "root"))
+    except OSError:
+        root = None
+    return argv, ppid, root
+
+
+def _orphan_unshare_processes(roots: List[str], proc: str = "/proc") -> List[int]:
+    """Find unshare processes for roots whose parent has gone away.
+
+    unshare(1) forks before changing root, so the orphaned unshare process
+    is recognized by the root directory of its child.
+    """
+    roots = [os.path.realpath(root) for root in roots]
+    processes = {}
+    for entry in os.scandir(proc):
+        if not entry.name.isdigit():
+            continue
+        try:
+            processes[int(entry.name)] = _read_process(entry.path)
+        except (FileNotFoundError, ProcessLookupError, PermissionError):
+            continue
+    ret = []
+    for pid, (argv, ppid, root) in processes.items():
+        if root not in roots or ppid not in processes:
+            continue
+        parent_argv, parent_ppid, unused_root = processes[ppid]
+        if os.path.basename(parent_argv[0]) != "unshare" or parent_ppid != 1:
+            continue
+        if ppid not in ret:
+            ret.append(ppid)
+    return ret
+
+
+def cleanup_orphans(
+    unshare_roots: Optional[List[str]] = None,
+    work_dirs: Optional[List[str]] = None,
+    dry_run: bool = False,
+) -> List[str]:
+    """Find and remove leftovers from crashed ognibuild runs.
+
+    This ends stray schroot sessions, kills unshare(1) processes (and
+    thereby their namespaces) that were orphaned, and removes temporary
+    project directories.
+
+    Args:
+      unshare_roots: Root directories used with UnshareSession
+      work_dirs: Work directories to look for temporary directories in
+        (defaults to the system temporary directory and default_work_dir())
+      dry_run: Only report what would be cleaned up
+    Returns: list of descriptions of the leftovers that were found
+    """
+    if unshare_roots is None:
+        unshare_roots = []
+    found = []
+    for session_id in _orphan_schroot_sessions():
+        found.append("schroot session %s" % session_id)
+        if not dry_run:
+            logging.info("Ending stray schroot session %s", session_id)
+            subprocess.call(["schroot", "-c", "session:" + session_id, "-e"])
+    if unshare_roots:
+        for pid in _orphan_unshare_processes(unshare_roots):
+            found.append("unshare process %d" % pid)
+            if not dry_run:
+                logging.info("Killing orphaned unshare process %d", pid)
+                try:
+                    os.kill(pid, signal.SIGKILL)
+                except ProcessLookupError:
+                    pass
+    if work_dirs is None:
+        work_dirs = [tempfile.gettempdir()]
+        for kind in [None, "plain", "unshare", "proot"]:
+            path = default_work_dir(kind)
+            if path is not None and path not in work_dirs:
+                work_dirs.append(path)
+    directories = []
+    for work_dir in work_dirs:
+        directories.extend(_orphan_directories(work_dir))
+    for root in unshare_roots:
+        directories.extend(_orphan_directories(os.path.join(root, "build")))
+    for path in directories:
+        found.append("directory %s" % path)
+        if not dry_run:
+            logging.info("Removing stray directory %s", path)
+            shutil.rmtree(path, ignore_errors=True)
+    return found
@@ -0,0 +1,144 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Collect timing and resource usage of commands run in sessions.
+
+Metrics are collected for commands run with run_with_tee and
+run_with_callback, once a collector has been set on the session with
+Session.set_metrics_collector.
+"""
+
+from contextlib import contextmanager
+import threading
+from typing import Dict, List, Optional
+
+
+class CommandMetrics(object):
+    """Resource usage of a single command.
+
+    Attributes:
+      argv: The command that was run
+      phase: Phase the command was run in (e.g. "build"), if any
+      wall_time: Wall clock time in seconds
+      max_rss: Maximum resident set size in kilobytes, if known
+      returncode: Exit code of the command
+    """
+
+    def __init__(
+        self,
+        argv: List[str],
+        phase: Optional[str],
+        wall_time: float,
+        max_rss: Optional[int],
+        returncode: Optional[int],
+    ):
+        self.argv = argv
+        self.phase = phase
+        self.wall_time = wall_time
+        self.max_rss = max_rss
+        self.returncode = returncode
+
+    def __repr__(self):
+        return "%s(%r, %r, %r, %r, %r)" % (
+            type(self).__name__, self.argv, self.phase, self.wall_time,
+            self.max_rss, self.returncode)
+
+    def json(self):
+        return dict(self.__dict__)
+
+
+class PhaseMetrics(object):
+    """Aggregated metrics for all commands in a phase."""
+
+    def __init__(self, phase: Optional[str]):
+        self.phase = phase
+        self.commands = 0
+        self.failures = 0
+        self.wall_time = 0.0
+        self.max_rss: Optional[int] = None
+
+    def add(self, metrics: CommandMetrics) -> None:
+        self.commands += 1
+        if metrics.returncode != 0:
+            self.failures += 1
+        self.wall_time += metrics.wall_time
+        if metrics.max_rss is not None:
+            self.max_rss = max(self.max_rss or 0, metrics.max_rss)
+
+    def json(self):
+        return dict(self.__dict__)
+
+
+class MetricsCollector(object):
+    """Collects metrics for commands, grouped by phase."""
+
+    def __init__(self):
+        self.commands: List[CommandMetrics] = []
+        self._phases: List[str] = []
+        self._lock = threading.Lock()
+
+    @property
+    def current_phase(self) -> Optional[str]:
+        return self._phases[-1] if self._phases else None
+
+    @contextmanager
+    def phase(self, name: str):
+        """Attribute commands run in this context to a phase."""
+        self._phases.append(name)
+        try:
+            yield
+        finally:
+            self._phases.pop()
+
+    def record(self, argv, wall_time, max_rss, returncode) -> CommandMetrics:
+        metrics = CommandMetrics(
+            list(argv), self.current_phase, wall_time, max_rss, returncode)
+        with self._lock:
+            self.commands.append(metrics)
+        return metrics
+
+    def report(self) -> Dict[Optional[str], PhaseMetrics]:
+        """Aggregate the collected metrics per phase."""
+        ret: Dict[Optional[str], PhaseMetrics] = {}
+        for metrics in self.commands:
+            ret.setdefault(metrics.phase, PhaseMetrics(metrics.phase)).add(metrics)
+        return ret
+
+    def json(self):
+        return {
+            "phases": [p.json() for p in self.report().values()],
+            "commands": [c.json() for c in self.commands],
+        }
+
+    def format_report(self) -> str:
+        lines = ["%-12s %8s %10s %12s" % (
+            "phase", "commands", "time (s)", "max rss (kB)")]
+        for phase in self.report().values():
+            lines.append("%-12s %8d %10.1f %12s" % (
+                phase.phase or "-", phase.commands, phase.wall_time,
+                phase.max_rss if phase.max_rss is not None else "?"))
+        return "\n".join(lines) + "\n"
+
+
+@contextmanager
+def metrics_phase(session, name: str):
+    """Attribute commands run in session to a phase, if metrics are collected."""
+    if session.metrics is None:
+        yield
+    else:
+        with session.metrics.phase(name):
+            yield
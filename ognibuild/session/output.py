@@ -0,0 +1,96 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Forward the output of commands to the terminal and log files.
+
+By default, run_with_tee copies output to stdout as is. Once a forwarder
+has been set on the session with Session.set_output_forwarder, lines can
+be prefixed with the time they were read and the stream they came from,
+and a copy can be written to a log file, so that the output of long builds
+is still useful after the fact.
+"""
+
+import sys
+import threading
+import time
+from datetime import datetime, timezone
+from typing import BinaryIO, Optional
+
+
+class OutputForwarder(object):
+    """Forwards lines of command output.
+
+    Args:
+      timestamps: Prefix lines with the (UTC) time at which they were read
+      tags: Prefix lines with the name of the stream they came from
+      sink: Binary file to write a copy of the (prefixed) output to
+      console: Whether to write output to stdout and stderr
+    """
+
+    def __init__(
+        self, timestamps: bool = False, tags: bool = False,
+        sink: Optional[BinaryIO] = None, console: bool = True
+    ):
+        self.timestamps = timestamps
+        self.tags = tags
+        self.sink = sink
+        self.console = console
+        self._lock = threading.Lock()
+
+    def __repr__(self):
+        return "%s(timestamps=%r, tags=%r, sink=%r)" % (
+            type(self).__name__, self.timestamps, self.tags, self.sink)
+
+    def format(self, stream: str, line: bytes, timestamp: float) -> bytes:
+        prefix = []
+        if self.timestamps:
+            prefix.append(
+                datetime.fromtimestamp(timestamp, timezone.utc).isoformat(
+                    timespec="milliseconds").replace("+00:00", "Z"))
+        if self.tags:
+            prefix.append("[%s]" % stream)
+        if not prefix:
+            return line
+        return (" ".join(prefix) + " ").encode("utf-8") + line
+
+    def forward(
+        self, stream: str, line: bytes, timestamp: Optional[float] = None
+    ) -> None:
+        """Forward a line of output.
+
+        Args:
+          stream: Stream the line came from: "stdout", "stderr" or, if the
+            streams were merged, "output"
+          line: The line, including trailing newline
+          timestamp: Time at which the line was read; defaults to now
+        """
+        if not line:
+            return
+        if timestamp is None:
+            timestamp = time.time()
+        formatted = self.format(stream, line, timestamp)
+        with self._lock:
+            if self.console:
+                if stream == "stderr":
+                    f = sys.stderr.buffer
+                else:
+                    f = sys.stdout.buffer
+                f.write(formatted)
+                f.flush()
+            if self.sink is not None:
+                self.sink.write(formatted)
+                self.sink.flush()
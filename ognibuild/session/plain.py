@@ -16,7 +16,14 @@
 # along with this program; if not, write to the Free Software
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
-from . import Session, NoSessionOpen, SessionAlreadyOpen
+from . import (
+    CommandFailed,
+    Session,
+    NoSessionOpen,
+    SessionAlreadyOpen,
+    scandir_external,
+    owned_name_prefix,
+)
 
 import contextlib
 import os
@@ -31,6 +38,10 @@ class PlainSession(Session):
 
     location = "/"
 
+    kind = "plain"
+
+    thread_safe = True
+
     def __init__(self):
         self.es = None
 
@@ -57,6 +68,7 @@ class PlainSession(Session):
     def __exit__(self, exc_type, exc_val, exc_tb):
         if self.es is None:
             raise NoSessionOpen(self)
+        self._run_teardown_hooks()
         self.es.__exit__(exc_type, exc_val, exc_tb)
         self.es = None
         return False
@@ -64,6 +76,29 @@ class PlainSession(Session):
     def create_home(self):
         pass
 
+    def ping(self):
+        return self.es is not None
+
+    def share_host_directory(self, source, target):
+        return source
+
+    def export_image(self, path):
+        raise NotImplementedError(
+            "exporting the host system as an image is not supported")
+
+    def _full_env(self, env):
+        env = self._merge_env(env)
+        if not env:
+            return None
+        ret = dict(os.environ)
+        ret.update(env)
+        return ret
+
+    def _preexec_fn(self):
+        if not self.limits:
+            return None
+        return self.limits.setrlimits
+
     def check_call(
         self,
         argv: List[str],
@@ -72,8 +107,14 @@ class PlainSession(Session):
         env: Optional[Dict[str, str]] = None,
         close_fds: bool = True,
     ):
-        argv = self._prepend_user(user, argv)
-        return subprocess.check_call(argv, cwd=cwd, env=env, close_fds=close_fds)
+        full_argv = self._limit_argv(
+            self._prepend_user(user, self._wrap_argv(argv)))
+        try:
+            return subprocess.check_call(
+                full_argv, cwd=cwd, env=self._full_env(env), close_fds=close_fds,
+                preexec_fn=self._preexec_fn())
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or os.getcwd())
 
     def check_output(
         self,
@@ -82,12 +123,23 @@ class PlainSession(Session):
         user: Optional[str] = None,
         env: Optional[Dict[str, str]] = None,
     ) -> bytes:
-        argv = self._prepend_user(user, argv)
-        return subprocess.check_output(argv, cwd=cwd, env=env)
-
-    def Popen(self, args, stdout=None, stderr=None, stdin=None, user=None, cwd=None, env=None):
-        args = self._prepend_user(user, args)
-        return subprocess.Popen(args, stdout=stdout, stderr=stderr, stdin=stdin, cwd=cwd, env=env)
+        full_argv = self._limit_argv(
+            self._prepend_user(user, self._wrap_argv(argv)))
+        try:
+            return subprocess.check_output(
+                full_argv, cwd=cwd, env=self._full_env(env),
+                preexec_fn=self._preexec_fn())
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or os.getcwd())
+
+    def Popen(self, args, stdout=None, stderr=None, stdin=None, user=None, cwd=None, env=None,
+              start_new_session=False):
+        args = self._limit_argv(self._prepend_user(user, self._wrap_argv(args)))
+        return subprocess.Popen(
+            args, stdout=stdout, stderr=stderr, stdin=stdin, cwd=cwd,
+            env=self._full_env(env),
+            preexec_fn=self._preexec_fn(),
+            start_new_session=start_new_session)
 
     def exists(self, path):
         return os.path.exists(path)
@@ -96,7 +148,7 @@ class PlainSession(Session):
         return shutil.rmtree(path)
 
     def scandir(self, path):
-        return os.scandir(path)
+        return scandir_external(path, path)
 
     def chdir(self, path):
         os.chdir(path)
@@ -113,11 +165,15 @@ class PlainSession(Session):
         if include_controldir is False or (
             not hasattr(tree, "base") and include_controldir is None
         ):
-            td = self.es.enter_context(tempfile.TemporaryDirectory())
+            td = self.es.enter_context(
+                tempfile.TemporaryDirectory(
+                    prefix=owned_name_prefix(), dir=self._temporary_parent()))
             export_vcs_tree(tree, td)
             return td, td
         elif not hasattr(tree, "base"):
-            td = self.es.enter_context(tempfile.TemporaryDirectory())
+            td = self.es.enter_context(
+                tempfile.TemporaryDirectory(
+                    prefix=owned_name_prefix(), dir=self._temporary_parent()))
             dupe_vcs_tree(tree, td)
             return td, td
         else:
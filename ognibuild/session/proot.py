@@ -0,0 +1,88 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import logging
+import os
+import shutil
+from typing import Dict, List, Optional
+
+from . import NoSessionOpen, SessionLost, SessionSetupFailure
+from .unshare import UnshareSession
+
+
+class ProotSession(UnshareSession):
+    """Session that runs commands in a root directory using proot.
+
+    This works in environments where user namespaces are not available, at
+    the cost of speed, since proot intercepts system calls with ptrace.
+    Commands run as fake root.
+
+    proot can not isolate the network or make bind mounts read-only;
+    no_network and read_only are ignored with a warning.
+
+    Args:
+      root: Path to the root filesystem to use
+      bind_mounts: Host directories to bind into the root
+    """
+
+    kind = "proot"
+
+    # Host directories that are always made available
+    DEFAULT_BINDS = ["/dev", "/proc", "/sys"]
+
+    def __enter__(self):
+        if shutil.which("proot") is None:
+            raise SessionSetupFailure("proot is not installed")
+        return super(ProotSession, self).__enter__()
+
+    def _run_argv(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+    ):
+        if not self._open:
+            raise NoSessionOpen(self)
+        if not os.path.isdir(self.root):
+            raise SessionLost(self, "root %s has disappeared" % self.root)
+        if user is not None and user != "root":
+            logging.debug(
+                "Ignoring user %s; proot sessions only support root", user)
+        if self.no_network:
+            logging.warning("proot can not isolate the network; ignoring.")
+        if cwd is None:
+            cwd = self._cwd
+        env = self._merge_env(env)
+        base_argv = ["proot", "-0", "-r", self.root]
+        targets = set(mount.target for mount in self.bind_mounts)
+        for path in self.DEFAULT_BINDS:
+            # e.g. bootstrap() already binds /dev
+            if path not in targets:
+                base_argv.extend(["-b", path])
+        for mount in self.bind_mounts:
+            if mount.read_only:
+                logging.warning(
+                    "proot can not make %s read-only; ignoring.", mount.target)
+            base_argv.extend(["-b", "%s:%s" % (mount.source, mount.target)])
+        base_argv.extend(["-w", cwd or "/"])
+        argv = self._wrap_argv(argv)
+        if self.limits:
+            argv = self.limits.prlimit_argv() + argv
+        if env:
+            argv = ["env"] + ["%s=%s" % (k, v) for (k, v) in env.items()] + argv
+        return self._limit_argv(base_argv + argv)
@@ -0,0 +1,513 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Recording and replaying of session commands.
+
+RecordingSession wraps another session and records every command that is
+run, along with its input, output and exit code. The recording can later be
+loaded into a ReplaySession, which returns the recorded results without
+running anything. This makes it possible to test build systems and fixers
+deterministically, without network access or toolchains installed.
+
+Only commands are recorded; filesystem operations on a ReplaySession act
+on the local filesystem, like PlainSession.
+"""
+
+import json
+import os
+import subprocess
+import sys
+import threading
+from typing import Optional, List, Dict, Any
+
+from . import CommandFailed, Session, DirEntry
+from .plain import PlainSession
+
+
+class ReplayMismatch(Exception):
+    """A command was run that does not match the recording."""
+
+    def __init__(self, expected, argv):
+        self.expected = expected
+        self.argv = argv
+
+    def __str__(self):
+        if self.expected is None:
+            return "Unexpected command %r: recording exhausted" % (self.argv,)
+        return "Expected command %r, got %r" % (self.expected, self.argv)
+
+
+def _encode(data: Optional[bytes]) -> Optional[str]:
+    if data is None:
+        return None
+    return data.decode("utf-8", "surrogateescape")
+
+
+def _decode(data: Optional[str]) -> bytes:
+    if data is None:
+        return b""
+    return data.encode("utf-8", "surrogateescape")
+
+
+class _ReplayStream(object):
+    """Readable pipe that is fed with recorded output."""
+
+    def __init__(self, data: bytes):
+        r, w = os.pipe()
+        self._f = os.fdopen(r, "rb")
+        self.eof = False
+
+        def feed():
+            with os.fdopen(w, "wb") as f:
+                try:
+                    f.write(data)
+                except BrokenPipeError:
+                    pass
+
+        self._thread = threading.Thread(target=feed, daemon=True)
+        self._thread.start()
+
+    def _check(self, ret):
+        if not ret:
+            self.close()
+        return ret
+
+    def fileno(self):
+        return self._f.fileno()
+
+    def read(self, *args):
+        return self._check(self._f.read(*args))
+
+    def readline(self, *args):
+        return self._check(self._f.readline(*args))
+
+    def __iter__(self):
+        return iter(self.readline, b"")
+
+    def close(self):
+        if not self.eof:
+            self.eof = True
+            self._f.close()
+
+
+class _ReplayedProcess(object):
+    """Stand-in for subprocess.Popen that returns a recorded result.
+
+    poll() reports the process as running until its piped output has been
+    read, so that callers that read output while polling see all of it.
+    """
+
+    def __init__(self, args, entry, stdout=None, stderr=None):
+        self.args = args
+        self.returncode: Optional[int] = None
+        self._entry = entry
+        self.stdin = None
+        self.stdout = None
+        self.stderr = None
+        stdout_data = _decode(entry["stdout"])
+        stderr_data = _decode(entry["stderr"])
+        if stderr == subprocess.STDOUT:
+            stdout_data += stderr_data
+            stderr_data = b""
+        if stdout == subprocess.PIPE:
+            self.stdout = _ReplayStream(stdout_data)
+        elif stdout is None:
+            sys.stdout.buffer.write(stdout_data)
+            sys.stdout.buffer.flush()
+        if stderr == subprocess.PIPE:
+            self.stderr = _ReplayStream(stderr_data)
+        elif stderr is None:
+            sys.stderr.buffer.write(stderr_data)
+            sys.stderr.buffer.flush()
+
+    def _pipes(self):
+        return [f for f in [self.stdout, self.stderr] if f is not None]
+
+    def poll(self):
+        if self.returncode is None and all(f.eof for f in self._pipes()):
+            self.returncode = self._entry["returncode"]
+        return self.returncode
+
+    def wait(self, timeout=None):
+        self.returncode = self._entry["returncode"]
+        return self.returncode
+
+    def communicate(self, input=None, timeout=None):
+        stdout = self.stdout.read() if self.stdout is not None else None
+        stderr = self.stderr.read() if self.stderr is not None else None
+        for f in self._pipes():
+            f.close()
+        self.wait()
+        return stdout, stderr
+
+    def kill(self):
+        pass
+
+    terminate = kill
+
+
+class _RecordingStdin(object):
+    """Stand-in for the stdin pipe of a process, that collects the input.
+
+    The input is passed to the process when it is run to completion.
+    """
+
+    def __init__(self):
+        self._chunks: List[bytes] = []
+        self.closed = False
+
+    def write(self, data):
+        self._chunks.append(data)
+        return len(data)
+
+    def flush(self):
+        pass
+
+    def close(self):
+        self.closed = True
+
+    def getvalue(self) -> bytes:
+        return b"".join(self._chunks)
+
+
+def _read_stdin(stdin) -> bytes:
+    """Read the input from a file object or descriptor passed as stdin."""
+    if isinstance(stdin, int):
+        with os.fdopen(os.dup(stdin), "rb") as f:
+            return f.read()
+    data = stdin.read()
+    if isinstance(data, str):
+        data = data.encode()
+    return data
+
+
+class _RecordingProcess(object):
+    """Wrapper around a process that records its result once it finishes.
+
+    The command runs to completion the first time its output or exit code is
+    requested, so output is not streamed while recording. Input written to
+    stdin is collected until then.
+    """
+
+    def __init__(self, session, args, p, stdout, stderr, entry, stdin=None,
+                 input=None):
+        self._session = session
+        self.args = args
+        self._p = p
+        self._stdout = stdout
+        self._stderr = stderr
+        self._entry = entry
+        self._input = input
+        self._stdin = (
+            _RecordingStdin()
+            if stdin == subprocess.PIPE and input is None else None)
+        self._replayed: Optional[_ReplayedProcess] = None
+
+    def _finish(self, input=None):
+        if self._replayed is None:
+            if input is None:
+                if self._stdin is not None:
+                    input = self._stdin.getvalue()
+                else:
+                    input = self._input
+            stdout, stderr = self._p.communicate(input)
+            self._entry["stdin"] = _encode(input)
+            self._entry["returncode"] = self._p.returncode
+            self._entry["stdout"] = _encode(stdout)
+            self._entry["stderr"] = _encode(stderr)
+            self._session._append(self._entry)
+            self._replayed = _ReplayedProcess(
+                self.args, self._entry, self._stdout, self._stderr)
+        return self._replayed
+
+    @property
+    def returncode(self):
+        if self._replayed is None:
+            return None
+        return self._replayed.returncode
+
+    @property
+    def pid(self):
+        return self._p.pid
+
+    @property
+    def stdin(self):
+        return self._stdin
+
+    @property
+    def stdout(self):
+        return self._finish().stdout
+
+    @property
+    def stderr(self):
+        return self._finish().stderr
+
+    def poll(self):
+        return self._finish().poll()
+
+    def wait(self, timeout=None):
+        return self._finish().wait()
+
+    def communicate(self, input=None, timeout=None):
+        return self._finish(input).communicate()
+
+    def kill(self):
+        if self._replayed is None:
+            self._p.kill()
+
+    terminate = kill
+
+
+class RecordingSession(Session):
+    """Session that records all commands run in another session.
+
+    Args:
+      session: The session to run commands in
+      path: Path of the JSON file to write the recording to when the
+        session is closed
+    """
+
+    def __init__(self, session: Session, path: Optional[str] = None):
+        self.session = session
+        self.path = path
+        self.entries: List[Dict[str, Any]] = []
+        self._lock = threading.Lock()
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.session, self.path)
+
+    @property
+    def thread_safe(self):  # type: ignore
+        return self.session.thread_safe
+
+    def __enter__(self) -> "Session":
+        self.session.__enter__()
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        if self.path is not None:
+            self.save(self.path)
+        return self.session.__exit__(exc_type, exc_val, exc_tb)
+
+    def _append(self, entry):
+        with self._lock:
+            self.entries.append(entry)
+
+    def save(self, path: str) -> None:
+        with open(path, "w") as f:
+            json.dump(self.entries, f, indent=2)
+
+    def set_resource_limits(self, limits):
+        self.session.set_resource_limits(limits)
+
+    def setenv(self, key, value):
+        self.session.setenv(key, value)
+
+    def on_teardown(self, callback):
+        self.session.on_teardown(lambda session: callback(self))
+
+    def add_command_wrapper(self, wrapper):
+        self.session.add_command_wrapper(wrapper)
+
+    def ping(self):
+        return self.session.ping()
+
+    def share_host_directory(self, source, target):
+        return self.session.share_host_directory(source, target)
+
+    def export_image(self, path):
+        self.session.export_image(path)
+
+    def _entry(self, method, argv, cwd, user, env):
+        return {
+            "method": method,
+            "argv": list(argv),
+            "cwd": cwd,
+            "user": user,
+            "env": env,
+        }
+
+    def _run(self, method, argv, cwd=None, user=None, env=None):
+        kwargs = {}
+        if env is not None:
+            kwargs["env"] = env
+        p = self.session.Popen(
+            argv, cwd=cwd, user=user, stdin=subprocess.DEVNULL,
+            stdout=subprocess.PIPE, stderr=subprocess.PIPE, **kwargs)
+        entry = self._entry(method, argv, cwd, user, env)
+        return _RecordingProcess(self, argv, p, None, None, entry)
+
+    def check_call(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+        close_fds: bool = True,
+    ):
+        p = self._run("check_call", argv, cwd, user, env)
+        if p.wait() != 0:
+            raise CommandFailed(p.returncode, argv, cwd=cwd)
+
+    def check_output(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+    ) -> bytes:
+        p = self._run("check_output", argv, cwd, user, env)
+        p._stdout = subprocess.PIPE
+        stdout, unused_stderr = p.communicate()
+        if p.returncode != 0:
+            raise CommandFailed(p.returncode, argv, stdout, cwd=cwd)
+        return stdout
+
+    def call(
+        self, argv: List[str], cwd: Optional[str] = None, user: Optional[str] = None
+    ):
+        return self._run("call", argv, cwd, user).wait()
+
+    def Popen(
+        self, argv, cwd: Optional[str] = None, user: Optional[str] = None,
+        stdout=None, stderr=None, stdin=None, env=None, **kwargs
+    ):
+        if env is not None:
+            kwargs["env"] = env
+        input = None
+        if stdin not in (None, subprocess.PIPE, subprocess.DEVNULL):
+            # Read input from files up front, so that it is recorded
+            input = _read_stdin(stdin)
+            stdin = subprocess.PIPE
+        p = self.session.Popen(
+            argv, cwd=cwd, user=user, stdin=stdin, stdout=subprocess.PIPE,
+            stderr=(
+                subprocess.STDOUT if stderr == subprocess.STDOUT
+                else subprocess.PIPE),
+            **kwargs)
+        entry = self._entry("Popen", argv, cwd, user, env)
+        return _RecordingProcess(
+            self, argv, p, stdout, stderr, entry, stdin=stdin, input=input)
+
+    def chdir(self, cwd: str) -> None:
+        self.session.chdir(cwd)
+
+    @property
+    def location(self) -> str:
+        return self.session.location
+
+    def create_home(self) -> None:
+        self.session.create_home()
+
+    def exists(self, path: str) -> bool:
+        return self.session.exists(path)
+
+    def scandir(self, path: str) -> List[DirEntry]:
+        return self.session.scandir(path)
+
+    def mkdir(self, path: str):
+        return self.session.mkdir(path)
+
+    def rmtree(self, path: str):
+        return self.session.rmtree(path)
+
+    def external_path(self, path: str) -> str:
+        return self.session.external_path(path)
+
+    def setup_from_vcs(self, tree, include_controldir=None, subdir="package"):
+        return self.session.setup_from_vcs(
+            tree, include_controldir=include_controldir, subdir=subdir)
+
+    def setup_from_directory(self, path, *args, **kwargs):
+        return self.session.setup_from_directory(path, *args, **kwargs)
+
+    @property
+    def is_temporary(self):  # type: ignore
+        return self.session.is_temporary
+
+
+class ReplaySession(PlainSession):
+    """Session that replays commands from a recording.
+
+    Each command is answered with the first unused recorded entry for the
+    same argv, so commands that were run concurrently can be replayed in a
+    different order. ReplayMismatch is raised if there is no such entry.
+
+    Args:
+      entries: Recorded entries, as created by RecordingSession
+    """
+
+    def __init__(self, entries: List[Dict[str, Any]]):
+        super(ReplaySession, self).__init__()
+        self.entries = list(entries)
+        self._lock = threading.Lock()
+
+    @classmethod
+    def from_path(cls, path: str) -> "ReplaySession":
+        with open(path, "r") as f:
+            return cls(json.load(f))
+
+    def __repr__(self):
+        return "%s(<%d entries>)" % (type(self).__name__, len(self.entries))
+
+    def _next(self, argv):
+        with self._lock:
+            if not self.entries:
+                raise ReplayMismatch(None, argv)
+            for i, entry in enumerate(self.entries):
+                if entry["argv"] == list(argv):
+                    return self.entries.pop(i)
+            raise ReplayMismatch(self.entries[0]["argv"], argv)
+
+    def check_call(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+        close_fds: bool = True,
+    ):
+        entry = self._next(argv)
+        _ReplayedProcess(argv, entry)
+        if entry["returncode"] != 0:
+            raise CommandFailed(entry["returncode"], argv, cwd=cwd)
+
+    def check_output(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+    ) -> bytes:
+        entry = self._next(argv)
+        stdout = _decode(entry["stdout"])
+        if entry["returncode"] != 0:
+            raise CommandFailed(entry["returncode"], argv, stdout, cwd=cwd)
+        return stdout
+
+    def call(
+        self, argv: List[str], cwd: Optional[str] = None, user: Optional[str] = None
+    ):
+        entry = self._next(argv)
+        _ReplayedProcess(argv, entry)
+        return entry["returncode"]
+
+    def Popen(
+        self, args, stdout=None, stderr=None, stdin=None, user=None, cwd=None,
+        env=None, **kwargs
+    ):
+        return _ReplayedProcess(args, self._next(args), stdout, stderr)
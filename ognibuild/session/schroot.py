@@ -17,30 +17,249 @@
 
 import logging
 import os
+import re
 import shlex
 import subprocess
 import tempfile
+import uuid
 
-from typing import Optional, List, Dict
+from typing import Optional, List, Dict, Tuple, Callable
 
 
-from . import Session, SessionSetupFailure, NoSessionOpen, SessionAlreadyOpen
+from . import (
+    CommandFailed,
+    Session,
+    SessionSetupFailure,
+    NoSessionOpen,
+    SessionAlreadyOpen,
+    SessionTeardownFailure,
+    SessionLost,
+    DirEntry,
+    scandir_external,
+    owned_name_prefix,
+)
+from .unshare import BindMount
+
+
+DEFAULT_CHROOT_DIR = "/srv/chroot"
+
+
+class ChrootCreationFailed(SessionSetupFailure):
+    """Creating or updating a source chroot failed."""
+
+    def __init__(self, chroot: str, reason: str):
+        self.chroot = chroot
+        self.reason = reason
+
+    def __str__(self):
+        return "Unable to create chroot %s: %s" % (self.chroot, self.reason)
+
+
+def chroot_exists(chroot: str) -> bool:
+    """Check whether schroot knows about a chroot."""
+    return subprocess.call(
+        ["schroot", "-i", "-c", chroot],
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL) == 0
+
+
+def parse_sbuild_chroot_name(chroot: str) -> Tuple[str, str]:
+    """Split a chroot name as created by sbuild-createchroot.
+
+    Returns: tuple with suite and architecture
+    Raises:
+      ValueError: if the name does not follow the SUITE-ARCH-sbuild pattern
+    """
+    if chroot.startswith("chroot:"):
+        chroot = chroot[len("chroot:"):]
+    m = re.fullmatch(r"(.+)-([^-]+)-sbuild", chroot)
+    if not m:
+        raise ValueError(chroot)
+    return m.group(1), m.group(2)
+
+
+def create_chroot(
+    chroot: str, mirror: Optional[str] = None,
+    chroot_dir: str = DEFAULT_CHROOT_DIR
+) -> None:
+    """Create a source chroot using sbuild-createchroot.
+
+    The suite and architecture are derived from the chroot name, which has
+    to be of the form SUITE-ARCH-sbuild. This requires root privileges;
+    sudo is used when not running as root.
+    """
+    try:
+        suite, arch = parse_sbuild_chroot_name(chroot)
+    except ValueError:
+        raise ChrootCreationFailed(
+            chroot, "name does not follow the SUITE-ARCH-sbuild pattern")
+    argv = [
+        "sbuild-createchroot", "--arch=%s" % arch, suite,
+        os.path.join(chroot_dir, "%s-%s-sbuild" % (suite, arch))]
+    if mirror:
+        argv.append(mirror)
+    if os.getuid() != 0:
+        argv = ["sudo"] + argv
+    logging.info("Creating chroot %s: %s", chroot, shlex.join(argv))
+    try:
+        subprocess.check_call(argv)
+    except FileNotFoundError as e:
+        raise ChrootCreationFailed(chroot, "%s not found" % e.filename)
+    except subprocess.CalledProcessError as e:
+        raise ChrootCreationFailed(
+            chroot, "sbuild-createchroot exited with code %d" % e.returncode)
+
+
+def update_chroot(chroot: str) -> None:
+    """Update and upgrade the packages in a source chroot."""
+    argv = ["sbuild-update", "--update", "--dist-upgrade", "--clean",
+            "--autoclean", "--autoremove", chroot]
+    if os.getuid() != 0:
+        argv = ["sudo"] + argv
+    logging.info("Updating chroot %s", chroot)
+    try:
+        subprocess.check_call(argv)
+    except (FileNotFoundError, subprocess.CalledProcessError) as e:
+        raise ChrootCreationFailed(chroot, "update failed: %s" % e)
+
+
+class SchrootConfig(object):
+    """Configuration of a chroot, as resolved by schroot.
+
+    Attributes:
+      name: Name of the chroot
+      type: Chroot type, e.g. "directory", "file" or "btrfs-snapshot"
+      union_type: Union filesystem used for sessions, e.g. "overlay", or None
+      directory: Directory of the chroot, if it is directory based
+      profile: Setup profile, e.g. "sbuild"
+      aliases: Alternative names of the chroot
+      location: Mount location of the session, if this is the configuration
+        of a running session
+      settings: All settings, as strings
+    """
+
+    def __init__(self, name: str, settings: Dict[str, str],
+                 location: Optional[str] = None):
+        self.name = name
+        self.settings = settings
+        self.location = location
+
+    def __repr__(self):
+        return "%s(%r, %r)" % (type(self).__name__, self.name, self.settings)
+
+    @property
+    def type(self) -> Optional[str]:
+        return self.settings.get("type")
+
+    @property
+    def union_type(self) -> Optional[str]:
+        union_type = self.settings.get("union-type")
+        if union_type in (None, "none"):
+            return None
+        return union_type
+
+    @property
+    def directory(self) -> Optional[str]:
+        return self.settings.get("directory")
+
+    @property
+    def profile(self) -> Optional[str]:
+        return self.settings.get("profile")
+
+    @property
+    def aliases(self) -> List[str]:
+        return [a for a in self.settings.get("aliases", "").split(",") if a]
+
+    @classmethod
+    def parse(cls, text: str) -> "SchrootConfig":
+        """Parse the output of schroot --config for a single chroot."""
+        name = None
+        settings = {}
+        for line in text.splitlines():
+            line = line.strip()
+            if not line or line.startswith("#"):
+                continue
+            if line.startswith("[") and line.endswith("]"):
+                if name is not None:
+                    break
+                name = line[1:-1]
+                continue
+            key, value = line.split("=", 1)
+            settings[key] = value
+        if name is None:
+            raise ValueError("no chroot in schroot configuration")
+        return cls(name, settings)
+
+
+def read_schroot_config(chroot: str) -> SchrootConfig:
+    """Read the configuration of a chroot (or session, as session:ID)."""
+    return SchrootConfig.parse(subprocess.check_output(
+        ["schroot", "--config", "-c", chroot]).decode())
+
+
+def _as_root(argv: List[str]) -> List[str]:
+    if os.getuid() != 0:
+        return ["sudo"] + argv
+    return argv
 
 
 class SchrootSession(Session):
+    """Session that runs commands in a schroot session.
+
+    Args:
+      chroot: Name of the chroot to start a session from
+      auto_reconnect: If the schroot session disappears (e.g. because it was
+        ended externally or expired), start a new session and set up the
+        build directories again, at the same paths inside the session.
+        Packages installed in the old session are lost, and external
+        paths returned earlier no longer work. If this is not set,
+        SessionLost is raised instead.
+      retry_safe: Function that decides whether a command that failed
+        because the session was lost can be run again in the new session,
+        e.g. because it is read-only. Other commands raise SessionLost
+        after reconnecting. By default no commands are retried.
+      create_chroot: If the chroot does not exist, create it with
+        sbuild-createchroot rather than failing. The chroot name has to be
+        of the form SUITE-ARCH-sbuild.
+      mirror: Mirror to use when creating the chroot
+      update_chroot: Update the packages in the chroot before starting the
+        session, with sbuild-update
+      session_prefix: Prefix for the name of the schroot session, e.g. to
+        identify the build service or job it belongs to
+      bind_mounts: Host directories to bind mount into the session, in
+        addition to those in the chroot's fstab (e.g. caches or output
+        directories). Mounting requires root privileges; sudo is used when
+        not running as root.
+    """
 
     _cwd: Optional[str]
     _location: Optional[str]
     chroot: str
     session_id: Optional[str]
 
-    def __init__(self, chroot: str):
+    def __init__(
+        self, chroot: str, auto_reconnect: bool = False,
+        create_chroot: bool = False, mirror: Optional[str] = None,
+        update_chroot: bool = False, session_prefix: Optional[str] = None,
+        bind_mounts: Optional[List[BindMount]] = None,
+        retry_safe: Optional[Callable[[List[str]], bool]] = None
+    ):
         if not isinstance(chroot, str):
             raise TypeError("not a valid chroot: %r" % chroot)
         self.chroot = chroot
+        self.auto_reconnect = auto_reconnect
+        self.retry_safe = retry_safe
+        self.create_chroot = create_chroot
+        self.mirror = mirror
+        self.update_chroot = update_chroot
+        self.session_prefix = session_prefix
+        self.bind_mounts = list(bind_mounts or [])
+        self._config: Optional[SchrootConfig] = None
         self._location = None
         self._cwd = None
         self.session_id = None
+        self._setups: List[Tuple[str, Callable[[str], None]]] = []
+        # Bind mount targets on the host, for the current session
+        self._mounted: List[str] = []
 
     def _get_location(self) -> str:
         if self.session_id is None:
@@ -53,32 +272,110 @@ class SchrootSession(Session):
             .decode()
         )
 
+    @property
+    def config(self) -> SchrootConfig:
+        """The configuration of the chroot, as resolved by schroot.
+
+        While a session is open, this is the configuration of the session,
+        including its location.
+        """
+        if self.session_id is None:
+            return read_schroot_config(self.chroot)
+        if self._config is None:
+            self._config = read_schroot_config("session:" + self.session_id)
+            self._config.location = self.location
+        return self._config
+
+    def _mount_bind_mounts(self) -> None:
+        try:
+            for mount in self.bind_mounts:
+                target = os.path.join(self.location, mount.target.lstrip("/"))
+                subprocess.check_call(_as_root(["mkdir", "-p", target]))
+                subprocess.check_call(
+                    _as_root(["mount", "--bind", mount.source, target]))
+                self._mounted.append(target)
+                if mount.read_only:
+                    subprocess.check_call(_as_root(
+                        ["mount", "-o", "remount,bind,ro", target]))
+                logging.debug("Bind mounted %s at %s", mount.source, mount.target)
+        except (FileNotFoundError, subprocess.CalledProcessError) as e:
+            self._unmount_bind_mounts()
+            self._end_session()
+            raise SessionSetupFailure(
+                "unable to bind mount into schroot session: %s" % e)
+
+    def _unmount_bind_mounts(self) -> None:
+        targets, self._mounted = self._mounted, []
+        for target in reversed(targets):
+            subprocess.check_call(_as_root(["umount", target]))
+
     def _end_session(self) -> bool:
+        session_id = self.session_id
+        try:
+            self.close()
+        except SessionTeardownFailure as e:
+            for error in e.errors:
+                logging.error("%s", error)
+            logging.warning(
+                "Failed to close schroot session %s, leaving stray.", session_id)
+            return False
+        return True
+
+    def close(self) -> None:
         if self.session_id is None:
             raise NoSessionOpen(self)
+        hook_errors = self._run_teardown_hooks()
+        try:
+            self._unmount_bind_mounts()
+        except (FileNotFoundError, subprocess.CalledProcessError) as e:
+            hook_errors.append("unable to unmount bind mounts: %s" % e)
+        session_id = self.session_id
+        self.session_id = None
+        self._location = None
+        self._config = None
         try:
             subprocess.check_output(
-                ["schroot", "-c", "session:" + self.session_id, "-e"],
+                ["schroot", "-c", "session:" + session_id, "-e"],
                 stderr=subprocess.PIPE,
             )
         except subprocess.CalledProcessError as e:
-            for line in e.stderr.splitlines(False):
-                if line.startswith(b"E: "):
-                    logging.error("%s", line[3:].decode(errors="replace"))
+            errors = [
+                line[3:].decode(errors="replace")
+                for line in e.stderr.splitlines(False)
+                if line.startswith(b"E: ")
+            ]
+            raise SessionTeardownFailure(
+                self, hook_errors + (errors or [
+                    "schroot -e for session %s exited with code %d"
+                    % (session_id, e.returncode)]))
+        if hook_errors:
+            raise SessionTeardownFailure(self, hook_errors)
+
+    def __del__(self):
+        # Don't run subprocesses from a finalizer; "ognibuild cleanup-orphans"
+        # can end the session later.
+        if getattr(self, "session_id", None) is not None:
             logging.warning(
-                "Failed to close schroot session %s, leaving stray.", self.session_id
-            )
-            self.session_id = None
-            return False
-        self.session_id = None
-        return True
+                "schroot session %s was not closed, leaving stray.", self.session_id)
 
     def __enter__(self) -> "Session":
         if self.session_id is not None:
             raise SessionAlreadyOpen(self)
+        # Only once; sessions started after reconnecting use the same chroot
+        if self.create_chroot and not chroot_exists(self.chroot):
+            create_chroot(self.chroot, mirror=self.mirror)
+        elif self.update_chroot:
+            update_chroot(self.chroot)
+        self._begin_session()
+        return self
+
+    def _begin_session(self) -> None:
         try:
             self.session_id = (
-                subprocess.check_output(["schroot", "-c", self.chroot, "-b"])
+                subprocess.check_output([
+                    "schroot", "-c", self.chroot, "-b",
+                    "-n", owned_name_prefix() + (self.session_prefix or "")
+                    + uuid.uuid4().hex[:8]])
                 .strip()
                 .decode()
             )
@@ -88,7 +385,66 @@ class SchrootSession(Session):
         logging.info(
             "Opened schroot session %s (from %s)", self.session_id, self.chroot
         )
-        return self
+        self._mount_bind_mounts()
+
+    def ping(self) -> bool:
+        if self.session_id is None:
+            return False
+        return subprocess.call(
+            ["schroot", "-r", "-c", "session:" + self.session_id, "--", "true"],
+            stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL) == 0
+
+    def _session_lost(self) -> None:
+        lost_id = self.session_id
+        self.session_id = None
+        self._location = None
+        self._config = None
+        if not self.auto_reconnect:
+            raise SessionLost(self, "schroot session %s has gone away" % lost_id)
+        logging.warning(
+            "schroot session %s has gone away, starting a new one.", lost_id)
+        # Bind mounts are set up again for the new session; release the old
+        # ones, if they still exist
+        for target in reversed(self._mounted):
+            subprocess.call(
+                _as_root(["umount", "--lazy", target]),
+                stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
+        self._mounted = []
+        self._begin_session()
+        for reldir, populate in self._setups:
+            directory = os.path.join(self.location, reldir.lstrip("/"))
+            os.makedirs(os.path.dirname(directory), exist_ok=True)
+            populate(directory)
+
+    def _command_lost(self, argv: List[str]) -> None:
+        """Reconnect after a command failed because the session went away.
+
+        Raises:
+          SessionLost: if the command should not be run again
+        """
+        self._session_lost()
+        if self.retry_safe is None or not self.retry_safe(argv):
+            raise SessionLost(
+                self, "session was replaced while running %r" % (argv,))
+
+    def share_host_directory(self, source, target):
+        for mount in self.bind_mounts:
+            if os.path.abspath(source) == mount.source:
+                return mount.target
+        # schroot sessions can not get new mounts, but the chroot
+        # configuration often bind mounts e.g. /home, in which case the
+        # directory is already visible at the same path.
+        try:
+            if os.path.samefile(source, self.external_path(source)):
+                return source
+        except FileNotFoundError:
+            pass
+        return None
+
+    def _check_alive(self) -> None:
+        # Checking for the mount point is cheap, so do it before every command
+        if self._location is not None and not os.path.isdir(self._location):
+            self._session_lost()
 
     def __exit__(self, exc_type, exc_val, exc_tb):
         self._end_session()
@@ -112,13 +468,20 @@ class SchrootSession(Session):
     ):
         if self.session_id is None:
             raise NoSessionOpen(self)
+        self._check_alive()
         base_argv = ["schroot", "-r", "-c", "session:" + self.session_id]
         if cwd is None:
             cwd = self._cwd
+        env = self._merge_env(env)
         if cwd is not None:
             base_argv.extend(["-d", cwd])
         if user is not None:
             base_argv.extend(["-u", user])
+        argv = self._wrap_argv(argv)
+        if self.limits:
+            # Per-process limits are applied with prlimit(1) from util-linux
+            # inside the chroot; the cgroup is set up on the host.
+            argv = self.limits.prlimit_argv() + argv
         if env:
             argv = [
                 "sh",
@@ -131,7 +494,7 @@ class SchrootSession(Session):
                     + [shlex.quote(arg) for arg in argv]
                 ),
             ]
-        return base_argv + ["--"] + argv
+        return self._limit_argv(base_argv + ["--"] + argv)
 
     def check_call(
         self,
@@ -145,8 +508,18 @@ class SchrootSession(Session):
             subprocess.check_call(
                 self._run_argv(argv, cwd, user, env=env), close_fds=close_fds
             )
+            return
         except subprocess.CalledProcessError as e:
-            raise subprocess.CalledProcessError(e.returncode, argv)
+            if self.ping():
+                raise CommandFailed.from_error(e, argv, cwd or self._cwd)
+            self._command_lost(argv)
+        # The session was re-established; retry once
+        try:
+            subprocess.check_call(
+                self._run_argv(argv, cwd, user, env=env), close_fds=close_fds
+            )
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or self._cwd)
 
     def check_output(
         self,
@@ -158,12 +531,20 @@ class SchrootSession(Session):
         try:
             return subprocess.check_output(self._run_argv(argv, cwd, user, env=env))
         except subprocess.CalledProcessError as e:
-            raise subprocess.CalledProcessError(e.returncode, argv)
+            if self.ping():
+                raise CommandFailed.from_error(e, argv, cwd or self._cwd)
+            self._command_lost(argv)
+        # The session was re-established; retry once
+        try:
+            return subprocess.check_output(self._run_argv(argv, cwd, user, env=env))
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or self._cwd)
 
     def Popen(
-        self, argv, cwd: Optional[str] = None, user: Optional[str] = None, **kwargs
+        self, argv, cwd: Optional[str] = None, user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None, **kwargs
     ):
-        return subprocess.Popen(self._run_argv(argv, cwd, user), **kwargs)
+        return subprocess.Popen(self._run_argv(argv, cwd, user, env=env), **kwargs)
 
     def call(
         self, argv: List[str], cwd: Optional[str] = None, user: Optional[str] = None
@@ -195,9 +576,9 @@ class SchrootSession(Session):
         fullpath = self.external_path(path)
         return os.path.exists(fullpath)
 
-    def scandir(self, path: str):
+    def scandir(self, path: str) -> List[DirEntry]:
         fullpath = self.external_path(path)
-        return os.scandir(fullpath)
+        return scandir_external(fullpath, path)
 
     def mkdir(self, path: str):
         fullpath = self.external_path(path)
@@ -213,27 +594,29 @@ class SchrootSession(Session):
     ):
         from ..vcs import dupe_vcs_tree, export_vcs_tree
 
-        build_dir = os.path.join(self.location, "build")
+        def populate(export_directory):
+            if not include_controldir:
+                export_vcs_tree(tree, export_directory)
+            else:
+                dupe_vcs_tree(tree, export_directory)
 
+        return self._setup(populate, subdir)
+
+    def _setup(self, populate, subdir):
+        build_dir = os.path.join(self.location, "build")
         directory = tempfile.mkdtemp(dir=build_dir)
         reldir = "/" + os.path.relpath(directory, self.location)
-
         export_directory = os.path.join(directory, subdir)
-        if not include_controldir:
-            export_vcs_tree(tree, export_directory)
-        else:
-            dupe_vcs_tree(tree, export_directory)
-
+        populate(export_directory)
+        self._setups.append((os.path.join(reldir, subdir), populate))
         return export_directory, os.path.join(reldir, subdir)
 
     def setup_from_directory(self, path, subdir="package"):
         import shutil
 
-        build_dir = os.path.join(self.location, "build")
-        directory = tempfile.mkdtemp(dir=build_dir)
-        reldir = "/" + os.path.relpath(directory, self.location)
-        export_directory = os.path.join(directory, subdir)
-        shutil.copytree(path, export_directory, dirs_exist_ok=True)
-        return export_directory, os.path.join(reldir, subdir)
+        def populate(export_directory):
+            shutil.copytree(path, export_directory, dirs_exist_ok=True)
+
+        return self._setup(populate, subdir)
 
     is_temporary = True
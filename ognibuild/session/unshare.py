@@ -0,0 +1,475 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import logging
+import os
+import shlex
+import shutil
+import subprocess
+import tempfile
+import time
+
+from typing import Optional, List, Dict
+
+
+from . import (
+    CommandFailed,
+    Session,
+    SessionSetupFailure,
+    NoSessionOpen,
+    SessionAlreadyOpen,
+    SessionTeardownFailure,
+    SessionLost,
+    DirEntry,
+    scandir_external,
+    owned_name_prefix,
+    default_cache_dir,
+    default_work_dir,
+)
+
+
+class BindMount(object):
+    """A host directory to make available inside a session.
+
+    Args:
+      source: Path on the host
+      target: Path inside the session
+      read_only: Whether to make the mount read-only
+    """
+
+    def __init__(self, source: str, target: str, read_only: bool = False):
+        self.source = source
+        self.target = target
+        self.read_only = read_only
+
+    def __repr__(self):
+        return "%s(%r, %r, read_only=%r)" % (
+            type(self).__name__, self.source, self.target, self.read_only)
+
+    def __eq__(self, other):
+        return (
+            isinstance(other, type(self))
+            and self.source == other.source
+            and self.target == other.target
+            and self.read_only == other.read_only
+        )
+
+    @classmethod
+    def parse(cls, text: str) -> "BindMount":
+        """Parse a bind mount specification, SOURCE[:TARGET][:ro]."""
+        parts = text.split(":")
+        read_only = False
+        if len(parts) > 1 and parts[-1] in ("ro", "rw"):
+            read_only = parts.pop() == "ro"
+        if len(parts) == 1:
+            parts.append(parts[0])
+        if len(parts) != 2 or not os.path.isabs(parts[1]):
+            raise ValueError("invalid bind mount %r" % text)
+        return cls(os.path.abspath(parts[0]), parts[1], read_only)
+
+
+# Rebuild cached bootstrap tarballs after a week
+DEFAULT_BOOTSTRAP_MAX_AGE = 7 * 24 * 60 * 60
+
+
+def bootstrap_tarball(
+    suite: str, arch: Optional[str] = None, mirror: Optional[str] = None,
+    include: Optional[List[str]] = None, cache_dir: Optional[str] = None,
+    max_age: Optional[float] = DEFAULT_BOOTSTRAP_MAX_AGE
+) -> str:
+    """Create a root filesystem tarball with mmdebstrap, or reuse a cached one.
+
+    Args:
+      suite: Suite to bootstrap, e.g. "unstable"
+      arch: Architecture to bootstrap (defaults to the host architecture)
+      mirror: Mirror to use (defaults to mmdebstrap's default)
+      include: Additional packages to install
+      cache_dir: Directory to store tarballs in
+      max_age: Maximum age in seconds of a cached tarball before it is
+        recreated, or None to always reuse cached tarballs
+    Returns: path to the tarball
+    """
+    if cache_dir is None:
+        cache_dir = os.path.join(default_cache_dir(), "bootstrap")
+    if arch is None:
+        arch = subprocess.check_output(
+            ["dpkg", "--print-architecture"]).decode().strip()
+    include = sorted(include or [])
+    name = "%s-%s" % (suite, arch)
+    if include or mirror:
+        import hashlib
+
+        key = hashlib.sha256(
+            repr((include, mirror)).encode("utf-8")).hexdigest()[:12]
+        name += "-" + key
+    path = os.path.join(cache_dir, name + ".tar.gz")
+    try:
+        age = time.time() - os.path.getmtime(path)
+    except FileNotFoundError:
+        pass
+    else:
+        if max_age is None or age < max_age:
+            logging.info("Using cached bootstrap tarball %s", path)
+            return path
+    os.makedirs(cache_dir, exist_ok=True)
+    argv = ["mmdebstrap", "--mode=unshare", "--variant=minbase",
+            "--architectures=%s" % arch]
+    if include:
+        argv.append("--include=%s" % ",".join(include))
+    # Write to a temporary file first, so that concurrent and interrupted
+    # runs never leave a partial tarball in the cache.
+    fd, tmppath = tempfile.mkstemp(
+        dir=cache_dir, prefix=owned_name_prefix(), suffix=".tar.gz")
+    os.close(fd)
+    argv.extend([suite, tmppath])
+    if mirror:
+        argv.append(mirror)
+    logging.info("Bootstrapping %s for %s", suite, arch)
+    try:
+        subprocess.check_call(argv)
+    except FileNotFoundError:
+        os.unlink(tmppath)
+        raise SessionSetupFailure("mmdebstrap is not installed")
+    except subprocess.CalledProcessError as e:
+        os.unlink(tmppath)
+        raise SessionSetupFailure(
+            "mmdebstrap exited with code %d" % e.returncode)
+    except BaseException:
+        os.unlink(tmppath)
+        raise
+    os.rename(tmppath, path)
+    return path
+
+
+class UnshareSession(Session):
+    """Session that runs commands in a root directory using user namespaces.
+
+    Commands run as (fake) root inside new user, mount and pid namespaces,
+    using unshare(1) from util-linux.
+
+    Args:
+      root: Path to the root filesystem to use
+      no_network: Whether to run commands in a new network namespace that
+        only has a loopback device. This can be changed between commands,
+        e.g. to allow dependency installation but build offline.
+      bind_mounts: Host directories to bind mount into the root, e.g. to
+        share caches like ~/.cargo/registry or a local apt mirror
+    """
+
+    _cwd: Optional[str]
+    root: str
+
+    kind = "unshare"
+
+    def __init__(
+        self,
+        root: str,
+        no_network: bool = False,
+        bind_mounts: Optional[List[BindMount]] = None,
+    ):
+        self.root = root
+        self.no_network = no_network
+        self.bind_mounts = list(bind_mounts or [])
+        self._cwd = None
+        self._open = False
+        self._build_dirs: List[str] = []
+        self._remove_root = False
+
+    @classmethod
+    def from_image(
+        cls, path: str, work_dir: Optional[str] = None, **kwargs
+    ) -> "UnshareSession":
+        """Create a session from an image created by export_image.
+
+        The image is extracted into a temporary root directory, which is
+        removed again when the session is closed.
+
+        Args:
+          path: Path to the image
+          work_dir: Host directory to create the root directory in
+            (defaults to default_work_dir())
+        """
+        if work_dir is None:
+            work_dir = default_work_dir(cls.kind)
+        if work_dir is not None:
+            os.makedirs(work_dir, exist_ok=True)
+        root = tempfile.mkdtemp(prefix=owned_name_prefix(), dir=work_dir)
+        logging.info("Extracting session image %s to %s", path, root)
+        try:
+            subprocess.check_call(
+                ["tar", "--no-same-owner", "--exclude=./dev/*", "-C", root,
+                 "-xaf", path])
+        except BaseException:
+            shutil.rmtree(root)
+            raise
+        for name in ["proc", "sys", "dev", "run", "tmp", "build"]:
+            os.makedirs(os.path.join(root, name), exist_ok=True)
+        os.chmod(os.path.join(root, "tmp"), 0o1777)
+        session = cls(root, **kwargs)
+        session.work_dir = work_dir
+        session._remove_root = True
+        return session
+
+    @classmethod
+    def bootstrap(
+        cls, suite: str, arch: Optional[str] = None, mirror: Optional[str] = None,
+        include: Optional[List[str]] = None, cache_dir: Optional[str] = None,
+        max_age: Optional[float] = DEFAULT_BOOTSTRAP_MAX_AGE, **kwargs
+    ) -> "UnshareSession":
+        """Create a session with a fresh Debian root filesystem.
+
+        The root filesystem is created with mmdebstrap; see bootstrap_tarball
+        for the arguments. /dev from the host is bind mounted into the session.
+        """
+        tarball = bootstrap_tarball(
+            suite, arch=arch, mirror=mirror, include=include,
+            cache_dir=cache_dir, max_age=max_age)
+        bind_mounts = kwargs.pop("bind_mounts", None) or []
+        if not any(mount.target == "/dev" for mount in bind_mounts):
+            bind_mounts = [BindMount("/dev", "/dev")] + bind_mounts
+        return cls.from_image(tarball, bind_mounts=bind_mounts, **kwargs)
+
+    def __repr__(self):
+        return "%s(%r, no_network=%r, bind_mounts=%r)" % (
+            type(self).__name__, self.root, self.no_network, self.bind_mounts)
+
+    def __enter__(self) -> "Session":
+        if self._open:
+            raise SessionAlreadyOpen(self)
+        if not os.path.isdir(self.root):
+            raise SessionSetupFailure("root %s does not exist" % self.root)
+        for mount in self.bind_mounts:
+            if not os.path.exists(mount.source):
+                raise SessionSetupFailure(
+                    "bind mount source %s does not exist" % mount.source)
+        self._open = True
+        logging.info("Opened %s session in %s", self.kind, self.root)
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        try:
+            self.close()
+        except SessionTeardownFailure as e:
+            for error in e.errors:
+                logging.warning("%s", error)
+        return False
+
+    def close(self) -> None:
+        if not self._open:
+            raise NoSessionOpen(self)
+        errors = self._run_teardown_hooks()
+        self._open = False
+        while self._build_dirs:
+            path = self._build_dirs.pop()
+            try:
+                shutil.rmtree(path)
+            except FileNotFoundError:
+                pass
+            except OSError as e:
+                errors.append("unable to remove %s: %s" % (path, e))
+        if self._remove_root:
+            try:
+                shutil.rmtree(self.root)
+            except OSError as e:
+                errors.append("unable to remove %s: %s" % (self.root, e))
+        if errors:
+            raise SessionTeardownFailure(self, errors)
+
+    def __del__(self):
+        if getattr(self, "_open", False):
+            logging.warning(
+                "%s session in %s was not closed, leaving stray.", self.kind,
+                self.root)
+
+    def ping(self) -> bool:
+        return self._open and os.path.isdir(self.root)
+
+    def share_host_directory(self, source, target):
+        mount = BindMount(source, target)
+        if mount not in self.bind_mounts:
+            self.bind_mounts.append(mount)
+        return target
+
+    @property
+    def image_excludes(self):  # type: ignore
+        return Session.image_excludes + [
+            "." + mount.target for mount in self.bind_mounts]
+
+    def chdir(self, cwd: str) -> None:
+        self._cwd = cwd
+
+    @property
+    def location(self) -> str:
+        return self.root
+
+    def _run_argv(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+    ):
+        if not self._open:
+            raise NoSessionOpen(self)
+        if not os.path.isdir(self.root):
+            raise SessionLost(self, "root %s has disappeared" % self.root)
+        if user is not None and user != "root":
+            logging.debug(
+                "Ignoring user %s; %s sessions only support root", user,
+                self.kind)
+        if cwd is None:
+            cwd = self._cwd
+        env = self._merge_env(env)
+        base_argv = ["unshare", "--map-root-user", "--fork", "--pid", "--mount"]
+        if self.no_network:
+            base_argv.append("--net")
+        root_argv = ["--root=%s" % self.root]
+        if cwd is not None:
+            root_argv.append("--wd=%s" % cwd)
+        argv = self._wrap_argv(argv)
+        if self.limits:
+            argv = self.limits.prlimit_argv() + argv
+        if env:
+            argv = ["env"] + ["%s=%s" % (k, v) for (k, v) in env.items()] + argv
+        if self.no_network:
+            # New network namespaces start out with loopback down.
+            argv = [
+                "sh",
+                "-c",
+                "ip link set lo up 2>/dev/null; exec " + shlex.join(argv),
+            ]
+        if not self.bind_mounts:
+            return self._limit_argv(
+                base_argv + ["--mount-proc"] + root_argv + ["--"] + argv)
+        # The bind mounts have to be set up inside the new mount namespace
+        # but before changing root, so use a second unshare invocation that
+        # only changes root.
+        script = [
+            "set -e",
+            "mount -t proc proc %s" % shlex.quote(os.path.join(self.root, "proc")),
+        ]
+        for mount in self.bind_mounts:
+            target = shlex.quote(os.path.join(self.root, mount.target.lstrip("/")))
+            script.append("mkdir -p %s" % target)
+            script.append(
+                "mount --rbind %s %s" % (shlex.quote(mount.source), target))
+            if mount.read_only:
+                script.append("mount -o remount,bind,ro %s" % target)
+        script.append("exec " + shlex.join(["unshare"] + root_argv + ["--"] + argv))
+        return self._limit_argv(
+            base_argv + ["--", "sh", "-c", "\n".join(script)])
+
+    def check_call(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+        close_fds: bool = True,
+    ):
+        try:
+            subprocess.check_call(
+                self._run_argv(argv, cwd, user, env=env), close_fds=close_fds
+            )
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or self._cwd)
+
+    def check_output(
+        self,
+        argv: List[str],
+        cwd: Optional[str] = None,
+        user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+    ) -> bytes:
+        try:
+            return subprocess.check_output(self._run_argv(argv, cwd, user, env=env))
+        except subprocess.CalledProcessError as e:
+            raise CommandFailed.from_error(e, argv, cwd or self._cwd)
+
+    def Popen(
+        self, argv, cwd: Optional[str] = None, user: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None, **kwargs
+    ):
+        return subprocess.Popen(self._run_argv(argv, cwd, user, env=env), **kwargs)
+
+    def call(
+        self, argv: List[str], cwd: Optional[str] = None, user: Optional[str] = None
+    ):
+        return subprocess.call(self._run_argv(argv, cwd, user))
+
+    def create_home(self) -> None:
+        """Create the user's home directory."""
+        home = (
+            self.check_output(["sh", "-c", "echo $HOME"], cwd="/").decode().rstrip("\n")
+        )
+        logging.info("Creating directory %s in %s session.", home, self.kind)
+        self.check_call(["mkdir", "-p", home], cwd="/")
+
+    def external_path(self, path: str) -> str:
+        if os.path.isabs(path):
+            return os.path.join(self.location, path.lstrip("/"))
+        if self._cwd is None:
+            raise ValueError("no cwd set")
+        return os.path.join(self.location, os.path.join(self._cwd, path).lstrip("/"))
+
+    def exists(self, path: str) -> bool:
+        fullpath = self.external_path(path)
+        return os.path.exists(fullpath)
+
+    def scandir(self, path: str) -> List[DirEntry]:
+        fullpath = self.external_path(path)
+        return scandir_external(fullpath, path)
+
+    def mkdir(self, path: str):
+        fullpath = self.external_path(path)
+        return os.mkdir(fullpath)
+
+    def rmtree(self, path: str):
+        fullpath = self.external_path(path)
+        return shutil.rmtree(fullpath)
+
+    def _mkbuilddir(self):
+        build_dir = os.path.join(self.location, "build")
+        os.makedirs(build_dir, exist_ok=True)
+        directory = tempfile.mkdtemp(dir=build_dir, prefix=owned_name_prefix())
+        self._build_dirs.append(directory)
+        return directory
+
+    def setup_from_vcs(
+        self, tree, include_controldir: Optional[bool] = None, subdir="package"
+    ):
+        from ..vcs import dupe_vcs_tree, export_vcs_tree
+
+        directory = self._mkbuilddir()
+        reldir = "/" + os.path.relpath(directory, self.location)
+
+        export_directory = os.path.join(directory, subdir)
+        if not include_controldir:
+            export_vcs_tree(tree, export_directory)
+        else:
+            dupe_vcs_tree(tree, export_directory)
+
+        return export_directory, os.path.join(reldir, subdir)
+
+    def setup_from_directory(self, path, subdir="package"):
+        directory = self._mkbuilddir()
+        reldir = "/" + os.path.relpath(directory, self.location)
+        export_directory = os.path.join(directory, subdir)
+        shutil.copytree(path, export_directory, dirs_exist_ok=True)
+        return export_directory, os.path.join(reldir, subdir)
+
+    is_temporary = True
@@ -0,0 +1,195 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Wrappers that are transparently applied to commands run in a session.
+
+The wrapper commands have to be available inside the session.
+"""
+
+import shlex
+from typing import Dict, List, Optional, Type
+
+
+class CommandWrapper(object):
+    """Prefixes commands with another command."""
+
+    name: str
+
+    def __repr__(self):
+        return "%s()" % type(self).__name__
+
+    def __eq__(self, other):
+        return isinstance(other, type(self)) and self.__dict__ == other.__dict__
+
+    @classmethod
+    def from_arg(cls, arg: Optional[str]) -> "CommandWrapper":
+        """Create a wrapper from its configuration string, if any."""
+        if arg is not None:
+            raise ValueError("%s does not take an argument" % cls.name)
+        return cls()
+
+    def wrap(self, argv: List[str]) -> List[str]:
+        raise NotImplementedError(self.wrap)
+
+
+class EatMyData(CommandWrapper):
+    """Disable fsync() and friends, which speeds up e.g. dpkg considerably."""
+
+    name = "eatmydata"
+
+    def wrap(self, argv):
+        return ["eatmydata"] + argv
+
+
+class Nice(CommandWrapper):
+
+    name = "nice"
+
+    def __init__(self, adjustment: int = 10):
+        self.adjustment = adjustment
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.adjustment)
+
+    @classmethod
+    def from_arg(cls, arg):
+        if arg is None:
+            return cls()
+        return cls(int(arg))
+
+    def wrap(self, argv):
+        return ["nice", "-n", str(self.adjustment)] + argv
+
+
+class IONice(CommandWrapper):
+
+    name = "ionice"
+
+    CLASSES = {"realtime": "1", "best-effort": "2", "idle": "3"}
+
+    def __init__(self, io_class: str = "idle", level: Optional[int] = None):
+        if io_class not in self.CLASSES:
+            raise ValueError("unknown I/O scheduling class %r" % io_class)
+        self.io_class = io_class
+        self.level = level
+
+    def __repr__(self):
+        return "%s(%r, level=%r)" % (type(self).__name__, self.io_class, self.level)
+
+    @classmethod
+    def from_arg(cls, arg):
+        if arg is None:
+            return cls()
+        io_class, sep, level = arg.partition(":")
+        return cls(io_class, int(level) if sep else None)
+
+    def wrap(self, argv):
+        ret = ["ionice", "-c", self.CLASSES[self.io_class]]
+        if self.level is not None:
+            ret.extend(["-n", str(self.level)])
+        return ret + argv
+
+
+class Timeout(CommandWrapper):
+    """Kill commands that run for longer than a number of seconds."""
+
+    name = "timeout"
+
+    def __init__(self, duration: int, kill_after: int = 30):
+        self.duration = duration
+        self.kill_after = kill_after
+
+    def __repr__(self):
+        return "%s(%r, kill_after=%r)" % (
+            type(self).__name__, self.duration, self.kill_after)
+
+    @classmethod
+    def from_arg(cls, arg):
+        if arg is None:
+            raise ValueError("timeout requires a duration")
+        return cls(int(arg))
+
+    def wrap(self, argv):
+        return [
+            "timeout", "--kill-after=%d" % self.kill_after, str(self.duration)
+        ] + argv
+
+
+class Proot(CommandWrapper):
+    """Run commands under proot, e.g. to fake root privileges."""
+
+    name = "proot"
+
+    def __init__(self, args: Optional[List[str]] = None):
+        if args is None:
+            args = ["-0"]
+        self.args = args
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.args)
+
+    @classmethod
+    def from_arg(cls, arg):
+        if arg is None:
+            return cls()
+        return cls(arg.split())
+
+    def wrap(self, argv):
+        return ["proot"] + self.args + argv
+
+
+class NixShell(CommandWrapper):
+    """Run commands in a nix-shell with additional packages."""
+
+    name = "nix-shell"
+
+    def __init__(self, attributes: Optional[List[str]] = None):
+        self.attributes = list(attributes or [])
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.attributes)
+
+    @classmethod
+    def from_arg(cls, arg):
+        if arg is None:
+            return cls()
+        return cls(arg.split(","))
+
+    def wrap(self, argv):
+        if not self.attributes:
+            return argv
+        return (["nix-shell", "-p"] + self.attributes
+                + ["--run", shlex.join(argv)])
+
+
+WRAPPER_CLSES: Dict[str, Type[CommandWrapper]] = {
+    cls.name: cls for cls in [EatMyData, Nice, IONice, Timeout, Proot, NixShell]
+}
+
+
+def parse_wrapper(text: str) -> CommandWrapper:
+    """Parse a wrapper specification like "nice" or "timeout=3600".
+
+    Raises:
+      ValueError: if the specification is invalid
+    """
+    name, sep, arg = text.partition("=")
+    try:
+        cls = WRAPPER_CLSES[name]
+    except KeyError:
+        raise ValueError("unknown command wrapper %r" % name)
+    return cls.from_arg(arg if sep else None)
@@ -0,0 +1,210 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Per-project state, kept between runs.
+
+The state directory (.ognibuild/ in the project by default) records which
+build systems were detected, which dependencies were resolved, which fixes
+were applied and the hashes of the artifacts that were created.
+"""
+
+import hashlib
+import json
+import logging
+import os
+import shutil
+from typing import Dict, List, Optional, Tuple
+
+from buildlog_consultant import Problem
+
+from .fix_build import BuildFixer
+
+
+DEFAULT_STATE_DIR = ".ognibuild"
+
+STATE_FILES = {
+    "detection": "detection.json",
+    "dependencies": "dependencies.json",
+    "fix_history": "fix-history.json",
+    "artifacts": "artifacts.json",
+}
+
+
+def default_state_dir(directory: str) -> str:
+    return os.path.join(directory, DEFAULT_STATE_DIR)
+
+
+class ProjectState(object):
+    """State of a project, as recorded by earlier runs.
+
+    Attributes:
+      path: Path to the state directory
+      detection: Fingerprint of the project and the names of the build
+        system classes that were detected, or None
+      dependencies: Requirements that were resolved, as strings
+      fix_history: Fixes that were applied, as dictionaries with phase,
+        problem and fixer; the phase is None if it was not known
+      artifacts: Dictionary mapping artifact names to SHA256 hashes
+    """
+
+    def __init__(self, path: str):
+        self.path = path
+        self.detection: Optional[Dict] = None
+        self.dependencies: List[str] = []
+        self.fix_history: List[Dict[str, Optional[str]]] = []
+        self.artifacts: Dict[str, str] = {}
+
+    def __repr__(self):
+        return "%s(%r)" % (type(self).__name__, self.path)
+
+    @classmethod
+    def load(cls, path: str) -> "ProjectState":
+        """Load the state from a directory; missing files are ignored."""
+        state = cls(path)
+        for attr, filename in STATE_FILES.items():
+            try:
+                with open(os.path.join(path, filename), "r") as f:
+                    setattr(state, attr, json.load(f))
+            except FileNotFoundError:
+                pass
+            except ValueError as e:
+                logging.warning(
+                    "Ignoring corrupt state file %s: %s", filename, e)
+        return state
+
+    def save(self) -> None:
+        os.makedirs(self.path, exist_ok=True)
+        # Keep the state out of version control and dist tarballs
+        with open(os.path.join(self.path, ".gitignore"), "w") as f:
+            f.write("*\n")
+        for attr, filename in STATE_FILES.items():
+            value = getattr(self, attr)
+            path = os.path.join(self.path, filename)
+            if value is None:
+                if os.path.exists(path):
+                    os.unlink(path)
+                continue
+            with open(path + ".new", "w") as f:
+                json.dump(value, f, indent=2, sort_keys=True)
+            os.rename(path + ".new", path)
+
+    def clear(self) -> None:
+        """Remove the state directory."""
+        shutil.rmtree(self.path, ignore_errors=True)
+        self.detection = None
+        self.dependencies = []
+        self.fix_history = []
+        self.artifacts = {}
+
+    def record_dependencies(self, requirements) -> None:
+        for req in requirements:
+            if str(req) not in self.dependencies:
+                self.dependencies.append(str(req))
+
+    def record_fix(self, phase: Optional[Tuple[str, ...]], problem: Problem,
+                   fixer) -> None:
+        self.fix_history.append({
+            "phase": "/".join(phase) if phase is not None else None,
+            "problem": str(problem),
+            "fixer": str(fixer),
+        })
+
+    def record_artifact(self, path: str) -> None:
+        h = hashlib.sha256()
+        with open(path, "rb") as f:
+            for chunk in iter(lambda: f.read(65536), b""):
+                h.update(chunk)
+        self.artifacts[os.path.basename(path)] = h.hexdigest()
+
+
+def project_fingerprint(directory: str) -> str:
+    """Fingerprint of the top level of a project.
+
+    Build systems are detected by the files in the top-level directory, so
+    detection only needs to happen again if these change.
+    """
+    h = hashlib.sha256()
+    for entry in sorted(os.scandir(directory), key=lambda e: e.name):
+        if entry.name == DEFAULT_STATE_DIR:
+            continue
+        h.update(entry.name.encode("utf-8", "surrogateescape"))
+        h.update(b"\0%d\0" % int(entry.stat(follow_symlinks=False).st_mtime_ns))
+    return h.hexdigest()
+
+
+def detect_buildsystems_with_state(
+    directory: str, state: ProjectState,
+    override: Optional[List[str]] = None,
+    priority: Optional[List[str]] = None,
+):
+    """Detect build systems, only probing the ones found earlier if possible."""
+    from .buildsystem import (
+        BUILDSYSTEM_CLSES,
+        BUILDSYSTEM_OVERRIDE_ENV,
+        BUILDSYSTEM_PRIORITY_ENV,
+        buildsystem_names_from_env,
+        detect_buildsystems,
+    )
+
+    if override is None:
+        override = buildsystem_names_from_env(BUILDSYSTEM_OVERRIDE_ENV)
+    if priority is None:
+        priority = buildsystem_names_from_env(BUILDSYSTEM_PRIORITY_ENV)
+    fingerprint = project_fingerprint(directory)
+    if (state.detection
+            and state.detection.get("fingerprint") == fingerprint
+            and state.detection.get("override") == override
+            and state.detection.get("priority") == priority):
+        names = state.detection.get("buildsystems", [])
+        by_name = {cls.__name__: cls for cls in BUILDSYSTEM_CLSES}
+        candidates = [by_name[name] for name in names if name in by_name]
+        bss = [cls.probe(directory) for cls in candidates]
+        if len(candidates) == len(names) and None not in bss:
+            logging.debug("Using cached build system detection")
+            return bss
+    bss = list(detect_buildsystems(
+        directory, override=override, priority=priority))
+    state.detection = {
+        "fingerprint": fingerprint,
+        "buildsystems": [type(bs).__name__ for bs in bss],
+        "override": override,
+        "priority": priority,
+    }
+    return bss
+
+
+class RecordingFixer(BuildFixer):
+    """Wrap a fixer, recording the fixes it applies in the project state."""
+
+    def __init__(self, fixer: BuildFixer, state: ProjectState):
+        self.fixer = fixer
+        self.state = state
+
+    def __str__(self):
+        return str(self.fixer)
+
+    def __repr__(self):
+        return repr(self.fixer)
+
+    def can_fix(self, problem: Problem):
+        return self.fixer.can_fix(problem)
+
+    def fix(self, problem: Problem, phase: Optional[Tuple[str, ...]]):
+        ret = self.fixer.fix(problem, phase)
+        if ret:
+            self.state.record_fix(phase, problem, self.fixer)
+        return ret
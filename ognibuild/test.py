@@ -16,6 +16,7 @@
 # Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
 
 from .buildsystem import NoBuildToolsFound
+from .session.metrics import metrics_phase
 
 
 def run_test(session, buildsystems, resolver, fixers):
@@ -24,7 +25,8 @@ def run_test(session, buildsystems, resolver, fixers):
     session.create_home()
 
     for buildsystem in buildsystems:
-        buildsystem.test(session, resolver, fixers)
+        with metrics_phase(session, "test"):
+            buildsystem.test(session, resolver, fixers)
         return
 
     raise NoBuildToolsFound()
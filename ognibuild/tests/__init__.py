@@ -23,10 +23,40 @@ import unittest
 
 def test_suite():
     names = [
+        "abi",
+        "audit",
+        "buildenv",
+        "buildlog",
+        "buildsystem",
+        "chain",
+        "changelog",
+        "compiler_cache",
+        "containerfile",
+        "coverage",
+        "dist_catcher",
+        "envfile",
+        "fix_build",
+        "lint",
+        "maven",
+        "outdated",
+        "problems",
+        "remote",
+        "requirements",
+        "resolver",
+        "scaffold",
+        "session",
+        "state",
+        "virtualenv",
     ]
     if os.path.exists("/usr/bin/dpkg-architecture"):
+        names.append("debian_apt")
         names.append("debian_build")
+        names.append("debian_build_deps")
+        names.append("debian_contents_cache")
+        names.append("debian_dep14")
+        names.append("debian_file_search")
         names.append("debian_fix_build")
+        names.append("debian_presence")
     module_names = ["ognibuild.tests.test_" + name for name in names]
     loader = unittest.TestLoader()
     return loader.loadTestsFromNames(module_names)
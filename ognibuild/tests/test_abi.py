@@ -0,0 +1,43 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+from unittest import TestCase
+
+from ..abi import AbiChange, AbiSnapshot, compare_abi
+
+
+class CompareAbiTests(TestCase):
+    def test_identical(self):
+        snapshot = AbiSnapshot({"libfoo.so.1": ["foo_init@@FOO_1", "foo_run"]})
+        self.assertEqual(
+            [], compare_abi(snapshot, AbiSnapshot.from_json(snapshot.json())))
+
+    def test_changes(self):
+        old = AbiSnapshot({
+            "libfoo.so.1": ["foo_init", "foo_run"],
+            "libbar.so.2": ["bar"]})
+        new = AbiSnapshot({
+            "libfoo.so.1": ["foo_init", "foo_stop"],
+            "libbaz.so.0": ["baz"]})
+        changes = compare_abi(old, new)
+        self.assertEqual([
+            AbiChange("removed-library", "libbar.so.2"),
+            AbiChange("added-library", "libbaz.so.0"),
+            AbiChange("removed-symbol", "libfoo.so.1", "foo_run"),
+            AbiChange("added-symbol", "libfoo.so.1", "foo_stop"),
+        ], changes)
+        self.assertEqual(
+            [True, False, True, False], [c.is_break for c in changes])
@@ -0,0 +1,101 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import json
+from unittest import TestCase
+
+from ..audit import (
+    BundlerAuditor,
+    CargoAuditor,
+    Finding,
+    NpmAuditor,
+    PipAuditor,
+    format_audit_report,
+)
+
+
+class ParseTests(TestCase):
+    def test_cargo(self):
+        output = json.dumps({"vulnerabilities": {"found": True, "count": 1, "list": [{
+            "advisory": {
+                "id": "RUSTSEC-2020-0071", "title": "Potential segfault",
+                "url": "https://example.com/"},
+            "versions": {"patched": [">=0.2.23"]},
+            "package": {"name": "time", "version": "0.1.44"}}]}})
+        self.assertEqual(
+            [Finding("cargo-audit", "time", "RUSTSEC-2020-0071",
+                     version="0.1.44", title="Potential segfault",
+                     fixed_versions=[">=0.2.23"], url="https://example.com/")],
+            CargoAuditor().parse(output.encode()))
+
+    def test_npm(self):
+        output = json.dumps({"vulnerabilities": {
+            "minimist": {"name": "minimist", "via": [{
+                "source": 1179, "title": "Prototype Pollution",
+                "severity": "critical", "range": "<1.2.6",
+                "url": "https://example.com/"}]},
+            "mkdirp": {"name": "mkdirp", "via": ["minimist"]}}})
+        self.assertEqual(
+            [Finding("npm-audit", "minimist", "1179", version="<1.2.6",
+                     title="Prototype Pollution", severity="critical",
+                     url="https://example.com/")],
+            NpmAuditor().parse(output.encode()))
+
+    def test_pip(self):
+        output = json.dumps({"dependencies": [
+            {"name": "flask", "version": "0.5", "vulns": [{
+                "id": "PYSEC-2019-179", "fix_versions": ["1.0"],
+                "description": "Denial of service"}]},
+            {"name": "six", "version": "1.16.0", "vulns": []}]})
+        self.assertEqual(
+            [Finding("pip-audit", "flask", "PYSEC-2019-179", version="0.5",
+                     title="Denial of service", fixed_versions=["1.0"])],
+            PipAuditor(["."]).parse(output.encode()))
+
+    def test_bundler(self):
+        output = json.dumps({"results": [
+            {"type": "insecure_source", "source": "http://rubygems.org/"},
+            {"type": "unpatched_gem",
+             "gem": {"name": "rack", "version": "2.0.0"},
+             "advisory": {"id": "CVE-2022-1234", "cve": "2022-1234",
+                          "title": "Rack issue", "criticality": "high",
+                          "patched_versions": [">= 2.2.3"]}}]})
+        self.assertEqual(
+            [Finding("bundler-audit", "rack", "CVE-2022-1234", version="2.0.0",
+                     title="Rack issue", severity="high",
+                     fixed_versions=[">= 2.2.3"])],
+            BundlerAuditor().parse(output.encode()))
+
+    def test_invalid(self):
+        self.assertRaises(ValueError, CargoAuditor().parse, b"error: no lock")
+
+
+class FormatAuditReportTests(TestCase):
+    def test_empty(self):
+        self.assertEqual(
+            "No known vulnerabilities found.\n", format_audit_report([]))
+
+    def test_finding(self):
+        self.assertEqual(
+            "time 0.1.44: RUSTSEC-2020-0071 (high)\n"
+            "  Potential segfault\n"
+            "  Fixed in: >=0.2.23\n"
+            "  Reported by: cargo-audit\n",
+            format_audit_report([Finding(
+                "cargo-audit", "time", "RUSTSEC-2020-0071", version="0.1.44",
+                title="Potential segfault", severity="high",
+                fixed_versions=[">=0.2.23"])]))
@@ -0,0 +1,63 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+from unittest import TestCase
+
+from ..buildenv import (
+    BuildEnvironment,
+    EnvironmentDifference,
+    compare_build_environments,
+)
+
+
+class CompareBuildEnvironmentsTests(TestCase):
+    def test_identical(self):
+        env = BuildEnvironment({"gcc": "12.2.0-14"}, {"LANG": "C"}, {"cc": "gcc 12"})
+        self.assertEqual(
+            [], compare_build_environments(
+                env, BuildEnvironment.from_json(env.json())))
+
+    def test_differences(self):
+        old = BuildEnvironment(
+            {"gcc": "12.2.0-14", "make": "4.3-4"},
+            {"LANG": "C", "PWD": "/a"}, {"cc": "gcc 12"})
+        new = BuildEnvironment(
+            {"gcc": "13.2.0-1", "cmake": "3.27.4-1"},
+            {"LANG": "C", "PWD": "/b", "CFLAGS": "-O2"}, {"cc": "gcc 13"})
+        self.assertEqual([
+            EnvironmentDifference("toolchain", "cc", "gcc 12", "gcc 13"),
+            EnvironmentDifference("package", "cmake", None, "3.27.4-1"),
+            EnvironmentDifference("package", "gcc", "12.2.0-14", "13.2.0-1"),
+            EnvironmentDifference("package", "make", "4.3-4", None),
+            EnvironmentDifference("environment", "CFLAGS", None, "-O2"),
+        ], compare_build_environments(old, new))
+
+
+class EnvironmentTests(TestCase):
+    def test_allowlist(self):
+        from ..buildenv import _environment
+
+        class DummySession(object):
+            def check_output(self, argv, cwd=None):
+                return (
+                    b"PATH=/usr/bin\0CFLAGS=-O2\0AWS_SECRET_ACCESS_KEY=xyz\0"
+                    b"GITHUB_TOKEN=abc\0DEB_BUILD_OPTIONS=nocheck\0")
+
+        self.assertEqual({
+            "PATH": "/usr/bin",
+            "CFLAGS": "-O2",
+            "DEB_BUILD_OPTIONS": "nocheck",
+        }, _environment(DummySession()))
@@ -0,0 +1,55 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import threading
+from unittest import TestCase
+
+from ..buildlog import requirements_met
+from ..session.plain import PlainSession
+from ..session.record import RecordingSession
+
+
+class DummyRequirement(object):
+    def __init__(self, met):
+        self.met = met
+        self.threads = []
+
+    def try_met(self, session):
+        self.threads.append(threading.current_thread())
+        return self.met
+
+
+class DummySession(PlainSession):
+
+    thread_safe = False
+
+
+class RequirementsMetTests(TestCase):
+    def test_met(self):
+        with PlainSession() as session:
+            self.assertEqual(
+                [True, False, True],
+                requirements_met(session, [
+                    DummyRequirement(True), DummyRequirement(False),
+                    DummyRequirement(True)]))
+
+    def test_not_thread_safe(self):
+        reqs = [DummyRequirement(True), DummyRequirement(False)]
+        with RecordingSession(DummySession()) as session:
+            self.assertEqual([True, False], requirements_met(session, reqs))
+        self.assertEqual(
+            [[threading.current_thread()]] * 2, [req.threads for req in reqs])
@@ -0,0 +1,1462 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import io
+import json
+import os
+import shutil
+import tempfile
+from unittest import TestCase, mock
+
+from .. import UnidentifiedError
+from ..buildsystem import (
+    Autotools,
+    Bazel,
+    BuildConfig,
+    Bundler,
+    Cargo,
+    CMake,
+    CondaBuild,
+    Dotnet,
+    Golang,
+    Hatch,
+    InstallTarget,
+    Make,
+    Maturin,
+    Meson,
+    MesonOptions,
+    Nimble,
+    NoBuildToolsFound,
+    Npm,
+    _apply_conda_selectors,
+    _declared_deps_from_cabal_file,
+    _declared_deps_from_configure_ac,
+    _find_node_workspace_members,
+    _poetry_constraint_to_specs,
+    Pdm,
+    Please,
+    Poetry,
+    R,
+    SetupPy,
+    Target,
+    Waf,
+    default_jobs,
+    parse_extra_args,
+    version_from_git_describe,
+    detect_node_package_manager,
+    parse_make_database,
+    run_for_each,
+)
+from ..requirements import BazelModuleRequirement, MavenArtifactRequirement
+from ..session.plain import PlainSession
+
+
+class BuildConfigTests(TestCase):
+    def setUp(self):
+        super(BuildConfigTests, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+
+    def test_per_instance(self):
+        a = Make(self.test_dir)
+        b = Make(self.test_dir)
+        a.build_config = BuildConfig(build_dir="build")
+        self.assertIsNone(b.build_config.build_dir)
+
+    def test_setup_cfg_kept(self):
+        with open(os.path.join(self.test_dir, "setup.py"), "w") as f:
+            f.write("from setuptools import setup\nsetup()\n")
+        with open(os.path.join(self.test_dir, "setup.cfg"), "w") as f:
+            f.write("[metadata]\nname = foo\n")
+        bs = SetupPy(self.test_dir)
+        bs.build_config = BuildConfig(build_dir="build")
+        self.assertEqual("foo", bs.config["metadata"]["name"])
+
+    def test_jobs_args(self):
+        self.assertEqual([], BuildConfig().jobs_args())
+        self.assertEqual(["-j4"], BuildConfig(jobs=4).jobs_args())
+
+    def test_extra_args_for(self):
+        config = BuildConfig(extra_args={"meson": ["-Ddocs=false"]})
+        self.assertEqual(["-Ddocs=false"], config.extra_args_for("meson"))
+        self.assertEqual([], config.extra_args_for("cmake"))
+
+    def test_extra_args_for_copy(self):
+        config = BuildConfig(extra_args={"meson": ["-Ddocs=false"]})
+        config.extra_args_for("meson").append("-Dtests=true")
+        self.assertEqual(["-Ddocs=false"], config.extra_args_for("meson"))
+
+
+
+class ParseExtraArgsTests(TestCase):
+    def test_extra_args(self):
+        self.assertEqual(
+            {"autoconf": ["--without-docs", "--with-x=no"],
+             "cargo": ["--features", "a b"]},
+            parse_extra_args([
+                "autoconf=--without-docs", "cargo=--features 'a b'",
+                "autoconf=--with-x=no"]))
+
+    def test_meson_options(self):
+        self.assertEqual(
+            {"meson": ["--werror", "-Ddocs=false", "-Dtests=true"]},
+            parse_extra_args(
+                ["meson=--werror"], ["docs=false", "tests=true"]))
+
+    def test_invalid_extra_args(self):
+        self.assertRaises(ValueError, parse_extra_args, ["--without-docs"])
+        self.assertRaises(ValueError, parse_extra_args, ["=--without-docs"])
+
+    def test_invalid_meson_option(self):
+        self.assertRaises(ValueError, parse_extra_args, [], ["docs"])
+
+class TargetTests(TestCase):
+    def test_from_debian_arch_known(self):
+        with mock.patch("subprocess.check_output") as check_output:
+            target = Target.from_debian_arch("armhf")
+        self.assertEqual("arm-linux-gnueabihf", target.triple)
+        check_output.assert_not_called()
+
+    def test_from_debian_arch_unknown(self):
+        with mock.patch(
+                "subprocess.check_output",
+                return_value=b"loongarch64-linux-gnu\n") as check_output:
+            target = Target.from_debian_arch("loong64")
+        self.assertEqual(Target("loong64", "loongarch64-linux-gnu"), target)
+        self.assertEqual(
+            ["dpkg-architecture", "-aloong64", "-qDEB_HOST_GNU_TYPE"],
+            check_output.call_args.args[0])
+
+    def test_meson_cross_file_unknown(self):
+        contents = Target("loong64", "loongarch64-linux-gnu").meson_cross_file()
+        self.assertIn("c = 'loongarch64-linux-gnu-gcc'\n", contents)
+        self.assertIn("cpu_family = 'loongarch64'\n", contents)
+        self.assertIn("endian = 'little'\n", contents)
+
+
+class DefaultJobsTests(TestCase):
+    def test_affinity(self):
+        with mock.patch("os.sched_getaffinity", return_value={0, 2, 3}):
+            self.assertEqual(3, default_jobs())
+
+    def test_no_affinity(self):
+        with mock.patch("os.sched_getaffinity", side_effect=AttributeError), \
+                mock.patch("os.cpu_count", return_value=8):
+            self.assertEqual(8, default_jobs())
+
+    def test_unknown_cpu_count(self):
+        with mock.patch("os.sched_getaffinity", side_effect=AttributeError), \
+                mock.patch("os.cpu_count", return_value=None):
+            self.assertEqual(1, default_jobs())
+
+
+class BuildSystemTestCase(TestCase):
+    def setUp(self):
+        super(BuildSystemTestCase, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+
+    def write(self, name, contents):
+        path = os.path.join(self.test_dir, name)
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w") as f:
+            f.write(contents)
+
+
+class RDeclaredDependenciesTests(BuildSystemTestCase):
+    def test_stages(self):
+        self.write("DESCRIPTION", """\
+Package: foo
+Depends: R (>= 3.5)
+Imports: dplyr
+Suggests: testthat, knitr
+VignetteBuilder: knitr
+""")
+        self.write("NAMESPACE", "")
+        deps = [
+            (stage, req.package)
+            for (stage, req) in R(self.test_dir).get_declared_dependencies(None)]
+        self.assertEqual([
+            ("test", "testthat"),
+            ("test", "knitr"),
+            ("build", "knitr"),
+            ("build", "R"),
+            ("build", "dplyr"),
+        ], deps)
+
+
+class RunForEachTests(TestCase):
+    def setUp(self):
+        super(RunForEachTests, self).setUp()
+        self.session = PlainSession()
+
+    def test_no_buildsystems(self):
+        self.assertRaises(
+            NoBuildToolsFound, run_for_each, self.session, [], "lint",
+            lambda bs: None)
+
+    def test_none_supported(self):
+        def action(bs):
+            raise NotImplementedError
+
+        self.assertRaises(
+            NotImplementedError, run_for_each, self.session, ["a", "b"], "lint",
+            action)
+
+    def test_some_supported(self):
+        done = []
+
+        def action(bs):
+            if bs == "a":
+                raise NotImplementedError
+            done.append(bs)
+
+        run_for_each(self.session, ["a", "b", "c"], "lint", action)
+        self.assertEqual(["b", "c"], done)
+
+
+class DotnetTests(BuildSystemTestCase):
+    SOLUTION = """\
+Microsoft Visual Studio Solution File, Format Version 12.00
+Project("{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}") = "App", "src\\App\\App.csproj", "{1}"
+EndProject
+Project("{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}") = "Lib", "src\\Lib\\Lib.fsproj", "{2}"
+EndProject
+Project("{2150E333-8FDC-42A3-9474-1A3956D46DE8}") = "docs", "docs", "{3}"
+EndProject
+"""
+
+    def test_declared_dependencies(self):
+        self.write("Foo.sln", self.SOLUTION)
+        self.write("src/App/App.csproj", """\
+<Project Sdk="Microsoft.NET.Sdk">
+  <ItemGroup>
+    <PackageReference Include="Newtonsoft.Json" Version="13.0.1" />
+    <PackageReference Include="Serilog">
+      <Version>3.1.0</Version>
+    </PackageReference>
+  </ItemGroup>
+</Project>
+""")
+        self.write("src/Lib/Lib.fsproj", """\
+<Project ToolsVersion="15.0" \
+xmlns="http://schemas.microsoft.com/developer/msbuild/2003">
+  <ItemGroup>
+    <PackageReference Include="Newtonsoft.Json" Version="13.0.1" />
+    <PackageReference Include="FSharp.Core" />
+  </ItemGroup>
+</Project>
+""")
+        bs = Dotnet.probe(self.test_dir)
+        self.assertEqual([
+            ("build", "Newtonsoft.Json", "13.0.1"),
+            ("build", "Serilog", "3.1.0"),
+            ("build", "FSharp.Core", None),
+        ], [(kind, req.package, req.version)
+            for (kind, req) in bs.get_declared_dependencies(None)])
+
+    def test_build(self):
+        self.write("Foo.csproj", "<Project />\n")
+        bs = Dotnet.probe(self.test_dir)
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="dotnet"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.build(None, None, [], "release")
+        self.assertEqual(
+            ["dotnet", "build", "-c", "Release"], run.call_args.args[1])
+
+    def test_dist(self):
+        self.write("Foo.csproj", "<Project />\n")
+        bs = Dotnet.probe(self.test_dir)
+        target_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, target_dir)
+
+        def run(session, argv, fixers, **kwargs):
+            outdir = os.path.join(self.test_dir, argv[-1])
+            for name in ["Foo.1.0.0.nupkg", "Foo.1.0.0.symbols.nupkg"]:
+                with open(os.path.join(outdir, name), "w"):
+                    pass
+
+        session = mock.Mock()
+        session.external_path.side_effect = lambda p: os.path.join(
+            self.test_dir, p)
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="dotnet"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers", run):
+            self.assertEqual(
+                "Foo.1.0.0.nupkg", bs.dist(session, None, [], target_dir))
+        self.assertEqual(["Foo.1.0.0.nupkg"], os.listdir(target_dir))
+
+
+class BundlerTests(BuildSystemTestCase):
+    def run_action(self, action):
+        self.write("Gemfile", "source 'https://rubygems.org'\n")
+        bs = Bundler.probe(self.test_dir)
+        with mock.patch.object(Bundler, "setup"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            getattr(bs, action)(None, None, [])
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_build(self):
+        self.assertEqual([["bundle", "install"]], self.run_action("build"))
+
+    def test_test_without_rakefile(self):
+        self.assertRaises(NotImplementedError, self.run_action, "test")
+
+    def test_test(self):
+        self.write("Rakefile", "")
+        self.assertEqual(
+            [["bundle", "exec", "rake", "test"]], self.run_action("test"))
+
+
+class SetupPyLintTests(BuildSystemTestCase):
+    def lint(self):
+        bs = SetupPy(self.test_dir)
+        bs.use_virtualenv = False
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.lint(mock.Mock(), None, [])
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_flake8(self):
+        self.write("setup.py", "from setuptools import setup\nsetup()\n")
+        self.assertEqual([["flake8"]], self.lint())
+
+    def test_ruff_toml(self):
+        self.write("setup.py", "from setuptools import setup\nsetup()\n")
+        self.write("ruff.toml", "line-length = 79\n")
+        self.assertEqual([["ruff", "check", "."]], self.lint())
+
+
+class NpmTests(BuildSystemTestCase):
+    def run_action(self, action):
+        bs = Npm.probe(self.test_dir)
+        with mock.patch(
+                "ognibuild.buildsystem.BinaryRequirement.met",
+                return_value=True), mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                side_effect=lambda session, resolver, name: "/usr/bin/" + name
+                ), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            getattr(bs, action)(mock.Mock(), None, [])
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_build_yarn_berry(self):
+        self.write("package.json", json.dumps({
+            "packageManager": "yarn@4.1.0",
+            "scripts": {"build": "tsc"}}))
+        self.assertEqual([
+            ["corepack", "prepare", "yarn@4.1.0", "--activate"],
+            ["corepack", "yarn", "install"],
+            ["corepack", "yarn", "run", "build"],
+        ], self.run_action("build"))
+
+    def test_test_pnpm(self):
+        self.write("package.json", json.dumps({"scripts": {"test": "jest"}}))
+        self.write("pnpm-lock.yaml", "lockfileVersion: '6.0'\n")
+        # Missing packages are left to the resolver
+        self.assertEqual(
+            [["/usr/bin/pnpm", "run", "test"]], self.run_action("test"))
+
+    def test_build_npm(self):
+        self.write("package.json", json.dumps({"scripts": {"build": "tsc"}}))
+        self.assertEqual([["tsc"]], self.run_action("build"))
+
+    def test_build_yarnrc(self):
+        self.write("package.json", json.dumps({"scripts": {"build": "tsc"}}))
+        self.write("yarn.lock", "")
+        self.write(".yarnrc.yml", "nodeLinker: pnp\n")
+        self.assertEqual([
+            ["corepack", "yarn", "install"],
+            ["corepack", "yarn", "run", "build"],
+        ], self.run_action("build"))
+
+    def test_build_yarn_classic(self):
+        self.write("package.json", json.dumps({
+            "packageManager": "yarn@1.22.19",
+            "scripts": {"build": "tsc"}}))
+        self.assertEqual([
+            ["corepack", "prepare", "yarn@1.22.19", "--activate"],
+            ["corepack", "yarn", "run", "build"],
+        ], self.run_action("build"))
+
+    def test_already_installed(self):
+        self.write("package.json", json.dumps({"scripts": {"test": "jest"}}))
+        os.mkdir(os.path.join(self.test_dir, "node_modules"))
+        self.assertEqual([["jest"]], self.run_action("test"))
+
+    def test_clean_does_not_install(self):
+        self.write("package.json", json.dumps({"scripts": {"clean": "rm -rf dist"}}))
+        self.assertEqual([["rm", "-rf", "dist"]], self.run_action("clean"))
+
+    def test_lint_script(self):
+        self.write("package.json", json.dumps({"scripts": {"lint": "eslint src"}}))
+        os.mkdir(os.path.join(self.test_dir, "node_modules"))
+        self.assertEqual([["eslint", "src"]], self.run_action("lint"))
+
+    def test_lint_eslint_config(self):
+        self.write("package.json", json.dumps({}))
+        self.write("eslint.config.js", "export default [];\n")
+        os.mkdir(os.path.join(self.test_dir, "node_modules"))
+        self.assertEqual([["eslint", "."]], self.run_action("lint"))
+
+    def test_lint_unknown(self):
+        self.write("package.json", json.dumps({}))
+        os.mkdir(os.path.join(self.test_dir, "node_modules"))
+        self.assertRaises(NotImplementedError, self.run_action, "lint")
+
+
+class NimbleTests(BuildSystemTestCase):
+    def setUp(self):
+        super(NimbleTests, self).setUp()
+        self.write("foo.nimble", """\
+# Package
+
+version       = "0.3.1"
+author        = "Somebody"
+bin           = @["foo"]
+
+# Dependencies
+
+requires "nim >= 1.6.0", "cligen >= 1.5"
+requires "jsony#head"
+requires(
+  "zippy ^= 0.10",
+)
+""")
+        self.bs = Nimble.probe(self.test_dir)
+
+    def run_action(self, action, *args):
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="nimble"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            getattr(self.bs, action)(None, None, [], *args)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_declared_dependencies(self):
+        self.assertEqual([
+            ("build", "binary", "nim"),
+            ("core", "nimble-package", ("cligen", ">= 1.5")),
+            ("core", "nimble-package", ("jsony", "#head")),
+            ("core", "nimble-package", ("zippy", "^= 0.10")),
+        ], [(kind, req.family,
+             getattr(req, "binary_name", None)
+             or (req.package, req.version))
+            for (kind, req) in self.bs.get_declared_dependencies(None)])
+
+    def test_project_version(self):
+        self.assertEqual("0.3.1", self.bs.project_version(None))
+
+    def test_install(self):
+        install_target = InstallTarget()
+        install_target.user = False
+        install_target.prefix = None
+        self.assertEqual(
+            [["nimble", "install", "-y"]],
+            self.run_action("install", install_target))
+        install_target.prefix = "/opt/chain"
+        self.assertEqual(
+            [["nimble", "--nimbleDir:/opt/chain", "install", "-y"]],
+            self.run_action("install", install_target))
+
+
+class NodeWorkspaceTests(BuildSystemTestCase):
+    def setUp(self):
+        super(NodeWorkspaceTests, self).setUp()
+        self.write("packages/a/package.json", json.dumps({
+            "name": "a", "scripts": {"test": "jest"},
+            "dependencies": {"b": "*", "left-pad": "^1.0"}}))
+        self.write("packages/b/package.json", json.dumps({"name": "b"}))
+        self.write("packages/internal/package.json", json.dumps(
+            {"name": "internal"}))
+        self.write("packages/docs/README", "")
+
+    def test_members(self):
+        self.assertEqual(
+            [("packages/a", "a"), ("packages/b", "b")],
+            [(relpath, package["name"])
+             for (relpath, package) in _find_node_workspace_members(
+                 {"workspaces": ["packages/*", "!packages/internal"]},
+                 self.test_dir)])
+
+    def test_yarn_nohoist(self):
+        self.assertEqual(
+            ["packages/a"],
+            [relpath for (relpath, package) in _find_node_workspace_members(
+                {"workspaces": {"packages": ["packages/a"], "nohoist": []}},
+                self.test_dir)])
+
+    def test_pnpm_workspace(self):
+        self.write(
+            "pnpm-workspace.yaml", "packages:\n  - 'packages/b'\n")
+        self.assertEqual(
+            ["packages/b"],
+            [relpath for (relpath, package) in _find_node_workspace_members(
+                {}, self.test_dir)])
+
+    def test_package_manager(self):
+        self.assertEqual(
+            ("yarn", "yarn@3.6.1"),
+            detect_node_package_manager(
+                {"packageManager": "yarn@3.6.1"}, self.test_dir))
+        self.assertEqual(
+            ("npm", None), detect_node_package_manager({}, self.test_dir))
+        self.write("yarn.lock", "")
+        self.assertEqual(
+            ("yarn", None), detect_node_package_manager({}, self.test_dir))
+
+    def test_declared_dependencies(self):
+        self.write("package.json", json.dumps(
+            {"private": True, "workspaces": ["packages/*"]}))
+        bs = Npm.probe(self.test_dir)
+        self.assertEqual(
+            [("core", "left-pad")],
+            [(kind, req.package)
+             for (kind, req) in bs.get_declared_dependencies(None)])
+
+    def test_test_members(self):
+        self.write("package.json", json.dumps(
+            {"private": True, "workspaces": ["packages/*"]}))
+        os.mkdir(os.path.join(self.test_dir, "node_modules"))
+        bs = Npm.probe(self.test_dir)
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="/usr/bin/npm"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.test(mock.Mock(), None, [])
+        self.assertEqual(
+            [(["jest"], "packages/a")],
+            [(c.args[1], c.kwargs["cwd"]) for c in run.call_args_list])
+
+    def test_select_workspace(self):
+        self.write("package.json", json.dumps(
+            {"private": True, "workspaces": ["packages/*"]}))
+        bs = Npm.probe(self.test_dir)
+        bs.select_workspace("b")
+        self.assertEqual(["packages/b"], [r for (r, p) in bs._members()])
+        self.assertRaises(ValueError, bs.select_workspace, "c")
+
+
+class SetupPyDeclaredDependenciesTests(BuildSystemTestCase):
+    def test_no_virtualenv(self):
+        self.write("pyproject.toml", """\
+[project]
+name = "foo"
+dependencies = ["requests"]
+""")
+        bs = SetupPy(self.test_dir)
+        with mock.patch.object(SetupPy, "setup_virtualenv") as setup:
+            deps = [(stage, req.package)
+                    for (stage, req) in bs.get_declared_dependencies(
+                        mock.Mock())]
+        self.assertEqual([("core", "requests")], deps)
+        setup.assert_not_called()
+
+
+class Pep517InstallTests(BuildSystemTestCase):
+    def run_install(self, cls, pyproject, user=False, prefix=None):
+        self.write("pyproject.toml", pyproject)
+        bs = cls.probe(self.test_dir)
+        self.assertIsInstance(bs, cls)
+        install_target = InstallTarget()
+        install_target.user = user
+        install_target.prefix = prefix
+        with mock.patch.object(SetupPy, "setup_virtualenv"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.install(None, None, [], install_target)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_poetry(self):
+        self.assertEqual(
+            [["python3", "-m", "pip", "install", ".", "--user"]],
+            self.run_install(
+                Poetry, '[tool.poetry]\nname = "foo"\n', user=True))
+
+    def test_hatch(self):
+        self.assertEqual(
+            [["python3", "-m", "pip", "install", ".", "--prefix=/opt"]],
+            self.run_install(
+                Hatch, '[build-system]\nbuild-backend = "hatchling.build"\n',
+                prefix="/opt"))
+
+    def test_pdm(self):
+        self.assertEqual(
+            [["python3", "-m", "pip", "install", "."]],
+            self.run_install(
+                Pdm, '[build-system]\nbuild-backend = "pdm.backend"\n'))
+
+
+class MaturinTests(BuildSystemTestCase):
+    def setUp(self):
+        super(MaturinTests, self).setUp()
+        self.write("pyproject.toml", """\
+[build-system]
+requires = ["maturin>=1.0"]
+build-backend = "maturin"
+
+[project]
+name = "foo"
+dependencies = ["attrs"]
+""")
+        self.write("Cargo.toml", """\
+[package]
+name = "foo"
+
+[dependencies]
+pyo3 = "0.20"
+""")
+
+    def run_action(self, action, *args):
+        bs = Maturin.probe(self.test_dir)
+        with mock.patch.object(SetupPy, "setup_virtualenv"), mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="maturin"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            getattr(bs, action)(None, None, [], *args)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_probe(self):
+        self.assertIsInstance(Maturin.probe(self.test_dir), Maturin)
+
+    def test_probe_tool_table(self):
+        self.write("pyproject.toml", "[tool.maturin]\nbindings = \"pyo3\"\n")
+        self.assertIsInstance(Maturin.probe(self.test_dir), Maturin)
+
+    def test_build(self):
+        self.assertEqual([["maturin", "build"]], self.run_action("build"))
+        self.assertEqual(
+            [["maturin", "build", "--release"]],
+            self.run_action("build", "release"))
+
+    def test_test(self):
+        self.assertEqual(
+            [["maturin", "develop"], ["python3", "-m", "pytest"]],
+            self.run_action("test"))
+
+    def test_declared_dependencies(self):
+        bs = Maturin.probe(self.test_dir)
+        deps = [(kind, req.family, getattr(
+                    req, "package", getattr(req, "crate", None)))
+                for (kind, req) in bs.get_declared_dependencies(None)]
+        self.assertIn(("core", "python-package", "attrs"), deps)
+        self.assertIn(("build", "cargo-crate", "pyo3"), deps)
+        self.assertIn(("build", "binary", None), deps)
+
+
+class PoetryConstraintTests(TestCase):
+    def test_caret(self):
+        self.assertEqual(
+            [(">=", "1.2.3"), ("<", "2")], _poetry_constraint_to_specs("^1.2.3"))
+        self.assertEqual(
+            [(">=", "0.2.3"), ("<", "0.3")],
+            _poetry_constraint_to_specs("^0.2.3"))
+
+    def test_tilde(self):
+        self.assertEqual(
+            [(">=", "1.2"), ("<", "1.3")], _poetry_constraint_to_specs("~1.2"))
+
+    def test_multiple(self):
+        self.assertEqual(
+            [(">=", "1.2"), ("<", "1.5")],
+            _poetry_constraint_to_specs(">= 1.2, < 1.5"))
+        self.assertEqual(
+            [(">=", "1.2"), ("<", "1.5")],
+            _poetry_constraint_to_specs(">=1.2 <1.5"))
+
+    def test_union(self):
+        self.assertEqual(
+            [(">=", "1.2"), ("<", "3")],
+            _poetry_constraint_to_specs("^1.2 || ^2.0"))
+        self.assertEqual(
+            [(">=", "1.0"), ("<=", "2.0")],
+            _poetry_constraint_to_specs("1.0 | 2.0"))
+
+    def test_union_unbounded(self):
+        self.assertEqual(
+            [(">=", "1.4")], _poetry_constraint_to_specs("~1.4 || >=3"))
+        self.assertEqual([], _poetry_constraint_to_specs("^0.3 || *"))
+        self.assertEqual([], _poetry_constraint_to_specs("!=1.1 || ^1.0"))
+
+
+class PleaseTests(BuildSystemTestCase):
+    def run_build(self, bs):
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.build(None, None, [])
+        return run.call_args.args[1]
+
+    def test_wrapper(self):
+        self.write(".plzconfig", "")
+        self.write("pleasew", "#!/usr/bin/env bash\n")
+        os.chmod(os.path.join(self.test_dir, "pleasew"), 0o755)
+        bs = Please.probe(self.test_dir)
+        self.assertEqual("./pleasew", bs.executable)
+        self.assertEqual(["./pleasew", "build", "//..."], self.run_build(bs))
+
+    def test_wrapper_not_executable(self):
+        self.write(".plzconfig", "")
+        self.write("pleasew", "#!/usr/bin/env bash\n")
+        bs = Please.probe(self.test_dir)
+        self.assertEqual(
+            ["bash", "./pleasew", "build", "//..."], self.run_build(bs))
+
+    def test_plz(self):
+        self.write(".plzconfig", "")
+        bs = Please.probe(self.test_dir)
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="/usr/bin/plz"):
+            self.assertEqual(
+                ["/usr/bin/plz", "build", "//..."], self.run_build(bs))
+
+
+class WafTests(BuildSystemTestCase):
+    def setUp(self):
+        super(WafTests, self).setUp()
+        self.session = PlainSession()
+        self.session.__enter__()
+        self.addCleanup(self.session.__exit__, None, None, None)
+
+    def run_commands(self, bs, action, *args):
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            getattr(bs, action)(self.session, None, [], *args)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_info(self):
+        self.write("waf", """\
+#!/usr/bin/env python3
+VERSION="2.0.26"
+#==>
+#BZh91AY
+""")
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual("2.0.26", bs.version)
+        self.assertEqual("python3", bs.python)
+        self.assertTrue(bs.has_archive)
+
+    def test_old_version(self):
+        self.write("waf", "#!/usr/bin/env python\nVERSION='1.5.19'\n")
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual("python2", bs.python)
+        self.assertFalse(bs.has_archive)
+
+    def test_unpack_once(self):
+        self.write("waf", "#!/usr/bin/env python3\n#==>\n#BZh91AY\n")
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual([
+            ["python3", "./waf", "--help"],
+            ["python3", "./waf", "configure"],
+            ["python3", "./waf", "build"]], self.run_commands(bs, "build"))
+        self.assertEqual(
+            [["python3", "./waf", "distclean"]],
+            self.run_commands(bs, "clean"))
+
+    def test_unpacked(self):
+        self.write("waf", "#!/usr/bin/env python3\n#==>\n#BZh91AY\n")
+        os.makedirs(os.path.join(self.test_dir, ".waf3-2.0.26-abc", "waflib"))
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual(
+            [["python3", "./waf", "distclean"]],
+            self.run_commands(bs, "clean"))
+
+    def test_stripped(self):
+        # Without the archive, waf reports that waflib is missing itself
+        self.write("waf", "#!/usr/bin/env python3\n")
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual([
+            ["python3", "./waf", "--help"],
+            ["python3", "./waf", "distclean"]], self.run_commands(bs, "clean"))
+
+    def test_waflib(self):
+        self.write("waf", "#!/usr/bin/env python3\n")
+        self.write("waflib/__init__.py", "")
+        bs = Waf.probe(self.test_dir)
+        self.assertEqual(
+            [["python3", "./waf", "distclean"]],
+            self.run_commands(bs, "clean"))
+
+
+class ParseMakeDatabaseTests(TestCase):
+    def test_parse(self):
+        self.assertEqual(["check", "foo.o", "all"], parse_make_database("""\
+# GNU Make 4.3
+# Variables
+
+VAR := 1
+
+# Files
+
+check:
+#  Phony target (prerequisite of .PHONY).
+#  recipe to execute (from 'Makefile', line 5):
+\ttrue
+
+foo.o: foo.c
+#  Implicit rule search has not been done.
+
+# Not a target:
+Makefile:
+#  Implicit rule search has been done.
+
+# Not a target:
+::
+#  Command line target.
+
+# Not a target:
+.DEFAULT:
+#  Implicit rule search has not been done.
+
+all: foo
+#  recipe to execute (from 'Makefile', line 2):
+\techo hi
+
+# Not a target:
+foo.c:
+#  Implicit rule search has not been done.
+
+.PHONY: check
+
+# files hash-table stats:
+# Finished Make data base on Fri Oct 16 20:31:39 2026
+""".splitlines()))
+
+    def test_pattern_rules(self):
+        self.assertEqual([], parse_make_database("""\
+# Files
+
+%.x: %.y
+\tcp $< $@
+
+# Finished Make data base
+""".splitlines()))
+
+
+class MakeTargetsTests(BuildSystemTestCase):
+    def test_targets(self):
+        self.write("Makefile", "all: foo\n\techo hi\ncheck:\n\ttrue\n")
+        bs = Make(self.test_dir)
+        session = mock.Mock()
+        session.exists.return_value = False
+        session.Popen.return_value.communicate.return_value = (
+            b"# Files\n\ncheck:\n\ttrue\n\nall: foo\n", None)
+        self.assertEqual(["check", "all"], bs.targets(session))
+        self.assertEqual(
+            ["make", "-pRrq", ":"], session.Popen.call_args.args[0])
+
+    def test_build_profile(self):
+        self.write("Makefile", "all:\n")
+        bs = Make(self.test_dir)
+        session = mock.Mock()
+        session.exists.return_value = False
+        session.scandir.return_value = []
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.build(session, None, [], profile="debug")
+        self.assertEqual(["make", "all"], run.call_args.args[1])
+        self.assertEqual(
+            {"CFLAGS": "-g -O0", "CXXFLAGS": "-g -O0"},
+            run.call_args.kwargs["env"])
+
+    def test_build_without_profile(self):
+        self.write("Makefile", "all:\n")
+        bs = Make(self.test_dir)
+        session = mock.Mock()
+        session.exists.return_value = False
+        session.scandir.return_value = []
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.build(session, None, [])
+        self.assertEqual({}, run.call_args.kwargs["env"])
+
+
+class BazelDeclaredDependenciesTests(BuildSystemTestCase):
+    def dependencies(self):
+        bs = Bazel(self.test_dir)
+        return [(stage, str(req))
+                for (stage, req) in bs.get_declared_dependencies(None)]
+
+    def test_module(self):
+        self.write("MODULE.bazel", """\
+module(name = "foo", version = "1.0")
+
+bazel_dep(name = "rules_cc", version = "0.0.9")
+# bazel_dep(name = "commented_out", version = "1")
+bazel_dep(
+    name = "googletest",
+    version = "1.14.0",
+    dev_dependency = True,
+)
+""")
+        self.assertEqual([
+            ("build", str(BazelModuleRequirement("rules_cc", "0.0.9"))),
+            ("test", str(BazelModuleRequirement("googletest", "1.14.0")))],
+            self.dependencies())
+
+    def test_workspace(self):
+        self.write("MODULE.bazel", 'bazel_dep(name = "rules_cc")\n')
+        self.write("WORKSPACE", """\
+http_archive(
+    name = "rules_cc",
+    urls = ["https://example.com/rules_cc.tar.gz"],
+)
+git_repository(name = "abseil", remote = "https://example.com/abseil")
+maven_install(artifacts = ["junit:junit:4.13.2"])
+""")
+        self.assertEqual([
+            ("build", str(BazelModuleRequirement("rules_cc"))),
+            ("build", str(BazelModuleRequirement("abseil"))),
+            ("build", str(MavenArtifactRequirement.from_str(
+                "junit:junit:4.13.2")))],
+            self.dependencies())
+
+    def test_none(self):
+        self.write("BUILD", "")
+        self.assertEqual([], self.dependencies())
+
+
+class GolangDeclaredDependenciesTests(BuildSystemTestCase):
+    def dependencies(self):
+        bs = Golang(self.test_dir)
+        return [(stage, str(req))
+                for (stage, req) in bs.get_declared_dependencies(None)]
+
+    def test_require(self):
+        self.write("go.mod", """\
+module example.com/foo
+
+go 1.21
+
+require (
+\tgithub.com/foo/bar v1.2.0
+\tgithub.com/foo/baz v0.3.1 // indirect
+)
+""")
+        self.assertEqual([
+            ("build", "go 1.21"),
+            ("build", "go package: github.com/foo/bar (>= 1.2.0)"),
+            ("build", "go package: github.com/foo/baz (>= 0.3.1)")],
+            self.dependencies())
+
+    def test_replace(self):
+        self.write("go.mod", """\
+module example.com/foo
+
+go 1.21
+
+require github.com/foo/bar v1.2.0
+
+replace github.com/foo/bar => github.com/fork/bar v1.2.1
+""")
+        self.assertEqual([
+            ("build", "go 1.21"),
+            ("build", "go package: github.com/fork/bar (>= 1.2.1)")],
+            self.dependencies())
+
+    def test_replace_version(self):
+        self.write("go.mod", """\
+module example.com/foo
+
+go 1.21
+
+require (
+\tgithub.com/foo/bar v1.2.0
+\tgithub.com/foo/baz v0.3.1
+)
+
+replace (
+\tgithub.com/foo/bar v1.2.0 => github.com/fork/bar v1.2.1
+\tgithub.com/foo/baz v0.1.0 => github.com/fork/baz v0.1.1
+)
+""")
+        self.assertEqual([
+            ("build", "go 1.21"),
+            ("build", "go package: github.com/fork/bar (>= 1.2.1)"),
+            ("build", "go package: github.com/foo/baz (>= 0.3.1)")],
+            self.dependencies())
+
+    def test_replace_local(self):
+        self.write("go.mod", """\
+module example.com/foo
+
+go 1.21
+
+require (
+\tgithub.com/foo/bar v1.2.0
+\texample.com/foo/sub v0.0.0
+)
+
+replace example.com/foo/sub => ./sub
+""")
+        self.assertEqual([
+            ("build", "go 1.21"),
+            ("build", "go package: github.com/foo/bar (>= 1.2.0)")],
+            self.dependencies())
+
+    def test_toolchain(self):
+        self.write("go.mod", """\
+module example.com/foo
+
+go 1.21
+
+toolchain go1.21.3
+""")
+        self.assertEqual([("build", "go 1.21")], self.dependencies())
+
+
+class CabalDeclaredDependenciesTests(TestCase):
+    def test_build_depends(self):
+        f = io.StringIO("""\
+name: foo
+version: 0.1
+
+library
+  build-depends: base >= 4.7 && < 5,
+                 text ^>=1.2.4, containers
+  -- comment
+executable foo
+  build-depends:
+    foo,
+    bytestring>=0.10&&<0.12
+""")
+        deps = [(kind, req.package, req.specs)
+                for (kind, req) in _declared_deps_from_cabal_file(f)]
+        self.assertEqual([
+            ("build", "base", [">=4.7", "<5"]),
+            ("build", "text", [">=1.2.4", "<1.3"]),
+            ("build", "containers", None),
+            ("build", "bytestring", [">=0.10", "<0.12"]),
+        ], deps)
+
+
+class CondaSelectorTests(TestCase):
+    NAMESPACE = {"linux": True, "win": False, "unix": True, "py": 311}
+
+    def test_selectors(self):
+        self.assertEqual("""\
+  - numpy
+  - pyinotify  # [linux]
+  - typing  # [py<312]
+""", _apply_conda_selectors("""\
+  - numpy
+  - pywin32  # [win]
+  - pyinotify  # [linux]
+  - typing  # [py<312]
+  - tomli  # [py>=312 and unix]
+""", self.NAMESPACE))
+
+    def test_unknown(self):
+        self.assertEqual(
+            "  - foo  # [cuda_compiler_version != 'None']\n",
+            _apply_conda_selectors(
+                "  - foo  # [cuda_compiler_version != 'None']\n",
+                self.NAMESPACE))
+
+    def test_not(self):
+        self.assertEqual(
+            "  - bar  # [not win]\n",
+            _apply_conda_selectors(
+                "  - foo  # [not linux]\n  - bar  # [not win]\n",
+                self.NAMESPACE))
+
+    def test_not_evaluated(self):
+        # Selectors are not evaluated as Python code
+        marker = os.path.join(tempfile.mkdtemp(), "marker")
+        self.addCleanup(shutil.rmtree, os.path.dirname(marker))
+        line = (
+            "  - foo  # [[c for c in ().__class__.__base__.__subclasses__() "
+            "if c.__name__ == '_wrap_close'][0].__init__.__globals__"
+            "['system']('touch %s')]\n" % marker)
+        self.assertEqual(
+            line, _apply_conda_selectors(line, self.NAMESPACE))
+        self.assertFalse(os.path.exists(marker))
+        self.assertEqual(
+            "  - foo  # [linux.__class__]\n",
+            _apply_conda_selectors(
+                "  - foo  # [linux.__class__]\n", self.NAMESPACE))
+
+
+class CondaBuildTests(BuildSystemTestCase):
+    def test_declared_dependencies(self):
+        self.write("recipe/meta.yaml", """\
+{% set version = "1.0" %}
+package:
+  name: foo
+  version: {{ version }}
+requirements:
+  build:
+    - {{ compiler('c') }}
+  host:
+    - python
+    - pywin32  # [win]
+  run:
+    - numpy >=1.20
+    - pyinotify  # [not win]
+test:
+  requires:
+    - pytest
+""")
+        bs = CondaBuild.probe(self.test_dir)
+        with mock.patch(
+                "ognibuild.buildsystem._conda_selector_namespace",
+                return_value={"win": False}):
+            self.assertEqual([
+                ("build", "python"),
+                ("core", "numpy"),
+                ("core", "pyinotify"),
+                ("test", "pytest")],
+                [(stage, req.package) for (stage, req)
+                 in bs.get_declared_dependencies(None)])
+
+    def run_test(self, exists):
+        self.write("meta.yaml", "package:\n  name: foo\n")
+        bs = CondaBuild.probe(self.test_dir)
+        session = mock.Mock()
+        session.external_path.return_value = self.test_dir
+        session.check_output.return_value = b"/opt/conda/conda-bld/noarch/foo-1.0-0.tar.bz2\n"
+        session.exists.return_value = exists
+        with mock.patch(
+                "ognibuild.buildsystem.guaranteed_which",
+                return_value="conda"), \
+                mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.test(session, None, [])
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_test_built(self):
+        self.assertEqual(
+            [["conda", "build", "--test",
+              "/opt/conda/conda-bld/noarch/foo-1.0-0.tar.bz2"]],
+            self.run_test(True))
+
+    def test_test_unbuilt(self):
+        self.assertEqual([
+            ["conda", "build", "--no-test", "."],
+            ["conda", "build", "--test",
+             "/opt/conda/conda-bld/noarch/foo-1.0-0.tar.bz2"]],
+            self.run_test(False))
+
+
+class AutotoolsMakeTests(BuildSystemTestCase):
+    def test_jobs_after_configure(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        bs = Autotools(self.test_dir)
+        bs.build_config = BuildConfig(build_dir="build", jobs=4)
+        calls = []
+
+        def run(session, argv, fixers, cwd=None, **kwargs):
+            calls.append((argv, cwd))
+            if len(calls) == 1:
+                raise UnidentifiedError(
+                    2, argv, ["Please run ./configure first"])
+
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers", run), \
+                mock.patch.object(Autotools, "configure") as configure:
+            bs._run_make(None, ["check"], [])
+        configure.assert_called_once_with(None, [], prefix=None)
+        self.assertEqual([
+            (["make", "-j4", "check"], "build"),
+            (["make", "-j4", "check"], "build")], calls)
+
+
+class AutotoolsDeclaredDependenciesTests(TestCase):
+    def deps(self, text):
+        return [
+            (kind, req.family, req.json()["payload"])
+            for (kind, req) in _declared_deps_from_configure_ac(
+                io.StringIO(text))]
+
+    def test_programs(self):
+        self.assertEqual([
+            ("build", "binary", {"binary_name": "python3"}),
+            ("build", "binary", {"binary_name": "python"}),
+            ("build", "binary", {"binary_name": "xsltproc"}),
+        ], self.deps(
+            "AC_INIT([foo], [1.0])\n"
+            "AC_CHECK_PROGS([PYTHON], [python3 python])\n"
+            "AC_PATH_PROG(XSLTPROC, xsltproc)\n"))
+
+    def test_headers_and_libraries(self):
+        self.assertEqual([
+            ("build", "c-header", {"header": "zlib.h"}),
+            ("build", "c-header", {"header": "sys/epoll.h"}),
+            ("build", "lib", {"library": "z"}),
+            ("build", "lib", {"library": "dl"}),
+            ("build", "lib", {"library": "dld"}),
+        ], self.deps(
+            "AC_CHECK_HEADERS([zlib.h sys/epoll.h])\n"
+            "AC_CHECK_LIB([z], [inflate])\n"
+            "AC_SEARCH_LIBS([dlopen], [dl dld])\n"))
+
+    def test_pkg_config(self):
+        self.assertEqual([
+            ("build", "pkg-config",
+             {"module": "glib-2.0", "minimum_version": "2.40"}),
+            ("build", "pkg-config",
+             {"module": "gio-2.0", "minimum_version": None}),
+        ], self.deps(
+            "PKG_CHECK_MODULES([GLIB], [glib-2.0 >= 2.40 gio-2.0])\n"
+            "PKG_CHECK_MODULES(FOO, [foo-$FOO_VERSION])\n"))
+
+    def test_comments(self):
+        self.assertEqual([], self.deps(
+            "dnl AC_CHECK_PROG([X], [ignored])\n"
+            "# AC_CHECK_HEADERS([ignored.h])\n"))
+
+
+class AutotoolsTests(BuildSystemTestCase):
+    def run_action(self, bs, action, *args, failures=None):
+        calls = []
+
+        def run(session, argv, fixers, cwd=None, **kwargs):
+            calls.append(argv)
+            if failures and argv[-1] in failures:
+                raise UnidentifiedError(2, argv, [failures[argv[-1]]])
+
+        session = mock.Mock()
+        session.exists.side_effect = lambda path: os.path.exists(
+            os.path.join(self.test_dir, path))
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers", run):
+            getattr(bs, action)(session, *args)
+        return calls
+
+    def test_autoreconf(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        bs = Autotools.probe(self.test_dir)
+        self.assertEqual("autoconf", bs.name)
+        self.assertEqual(
+            [["autoreconf", "-fi"], ["./configure", "--prefix=/usr"]],
+            self.run_action(bs, "setup", None, [], "/usr"))
+
+    def test_configured(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        self.write("Makefile", "all:\n")
+        bs = Autotools.probe(self.test_dir)
+        self.assertEqual([], self.run_action(bs, "setup", None, [], "/usr"))
+
+    def test_configure_args(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        self.write("Makefile.am", "")
+        bs = Autotools(self.test_dir, configure_args=["--disable-docs"])
+        bs.build_config = BuildConfig(extra_args={"automake": ["--with-x"]})
+        self.assertEqual("automake", bs.name)
+        self.assertEqual(
+            [["./configure", "--disable-docs", "--with-x"]],
+            self.run_action(bs, "configure", []))
+
+    def test_dist_without_distcheck(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        self.write("Makefile", "all:\n")
+        bs = Autotools.probe(self.test_dir)
+        failures = {
+            "distcheck": "make: *** No rule to make target 'distcheck'.  Stop."}
+        with mock.patch("ognibuild.buildsystem.DistCatcher"):
+            calls = self.run_action(
+                bs, "dist", None, [], self.test_dir, failures=failures)
+        self.assertEqual([["make", "distcheck"], ["make", "dist"]], calls)
+
+    def test_build_profile(self):
+        self.write("configure.ac", "AC_INIT([foo], [1.0])\n")
+        self.write("Makefile", "all:\n")
+        bs = Autotools.probe(self.test_dir)
+        self.assertEqual(
+            [["make", "all", "CFLAGS=-O2 -DNDEBUG",
+              "CXXFLAGS=-O2 -DNDEBUG"]],
+            self.run_action(bs, "build", None, [], "release"))
+
+
+class CrossBuildTests(BuildSystemTestCase):
+    def setUp(self):
+        super(CrossBuildTests, self).setUp()
+        self.addCleanup(os.chdir, os.getcwd())
+        os.chdir(self.test_dir)
+        self.session = PlainSession()
+        self.session.__enter__()
+        self.addCleanup(self.session.__exit__, None, None, None)
+        self.build_config = BuildConfig(target=Target.from_arch("armhf"))
+
+    def run_commands(self, fn, *args):
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            fn(*args)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_meson_cross_file(self):
+        self.write("meson.build", "project('foo', 'c')\n")
+        bs = Meson(os.path.join(self.test_dir, "meson.build"))
+        bs.build_config = self.build_config
+        self.assertEqual(
+            [["meson", "setup", "build", "--cross-file",
+              "build/meson-cross-armhf.ini"]],
+            self.run_commands(bs._setup, self.session, []))
+        with open(os.path.join(self.test_dir, "build", "meson-cross-armhf.ini")) as f:
+            self.assertIn("cpu_family = 'arm'\n", f.read())
+        self.assertFalse(os.path.exists(os.path.join(self.test_dir, ".ognibuild")))
+
+    def test_make_configure(self):
+        self.write("configure", "#!/bin/sh\n")
+        bs = Make(self.test_dir)
+        bs.build_config = self.build_config
+        self.assertEqual(
+            [["./configure", "--prefix=/usr", "--host=arm-linux-gnueabihf"]],
+            self.run_commands(bs.setup, self.session, None, [], "/usr"))
+
+    def test_cmake(self):
+        bs = CMake(self.test_dir)
+        bs.build_config = self.build_config
+        self.assertEqual([
+            "-DCMAKE_SYSTEM_NAME=Linux",
+            "-DCMAKE_SYSTEM_PROCESSOR=arm",
+            "-DCMAKE_C_COMPILER=arm-linux-gnueabihf-gcc",
+            "-DCMAKE_CXX_COMPILER=arm-linux-gnueabihf-g++"], bs._target_args())
+
+    def test_cargo(self):
+        self.write("Cargo.toml", "[package]\nname = \"foo\"\n")
+        bs = Cargo(os.path.join(self.test_dir, "Cargo.toml"))
+        bs.build_config = self.build_config
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            bs.build(self.session, None, [])
+        self.assertEqual(
+            ["cargo", "build", "--target", "armv7-unknown-linux-gnueabihf"],
+            run.call_args.args[1])
+        self.assertEqual(
+            {"CARGO_TARGET_ARMV7_UNKNOWN_LINUX_GNUEABIHF_LINKER":
+             "arm-linux-gnueabihf-gcc"}, run.call_args.kwargs["env"])
+
+
+
+class MesonTests(BuildSystemTestCase):
+    def setUp(self):
+        super(MesonTests, self).setUp()
+        self.addCleanup(os.chdir, os.getcwd())
+        os.chdir(self.test_dir)
+        self.session = PlainSession()
+        self.session.__enter__()
+        self.addCleanup(self.session.__exit__, None, None, None)
+        self.write("meson.build", "project('foo', 'c')\n")
+        self.bs = Meson(os.path.join(self.test_dir, "meson.build"))
+        self.bs.build_config = BuildConfig(
+            extra_args={"meson": ["-Ddocs=false"]})
+
+    def setup_commands(self, options=None):
+        with mock.patch("ognibuild.buildsystem.run_with_build_fixers") as run:
+            self.bs._setup(self.session, [], options)
+        return [c.args[1] for c in run.call_args_list]
+
+    def test_extra_args(self):
+        self.bs.setup_options = MesonOptions(native_files=["native.ini"])
+        self.assertEqual(
+            [["meson", "setup", "build", "--buildtype=debug", "-Ddocs=false",
+              "--native-file", "native.ini"]],
+            self.setup_commands(["--buildtype=debug"]))
+
+    def test_cross_files(self):
+        self.bs.build_config = BuildConfig(target=Target.from_arch("armhf"))
+        self.bs.setup_options = MesonOptions(cross_files=["cross.ini"])
+        self.assertEqual(
+            [["meson", "setup", "build", "--cross-file", "cross.ini"]],
+            self.setup_commands())
+        self.assertFalse(os.path.exists(
+            os.path.join(self.test_dir, "build", "meson-cross-armhf.ini")))
+
+    def test_extra_args_configured(self):
+        os.makedirs(os.path.join(self.test_dir, "build", "meson-private"))
+        self.bs.setup_options = MesonOptions(native_files=["native.ini"])
+        self.assertEqual(
+            [["meson", "setup", "--reconfigure", "build", "-Ddocs=false"]],
+            self.setup_commands())
+
+    def test_configured_without_options(self):
+        os.makedirs(os.path.join(self.test_dir, "build", "meson-private"))
+        self.bs.build_config = BuildConfig()
+        self.assertEqual([["meson", "setup", "build"]], self.setup_commands())
+
+
+class ProjectVersionTests(BuildSystemTestCase):
+    def git_session(self, output, returncode=0):
+        session = mock.Mock()
+        session.exists.return_value = True
+        session.Popen.return_value.communicate.return_value = (output, None)
+        session.Popen.return_value.returncode = returncode
+        return session
+
+    def test_git_describe(self):
+        self.assertEqual(
+            "1.2.0-3-gabcdef0", version_from_git_describe(
+                self.git_session(b"v1.2.0-3-gabcdef0\n")))
+        self.assertEqual(
+            "2.0", version_from_git_describe(
+                self.git_session(b"release-2.0\n")))
+        self.assertEqual(
+            "stable", version_from_git_describe(self.git_session(b"stable\n")))
+
+    def test_git_describe_no_tags(self):
+        self.assertIsNone(version_from_git_describe(
+            self.git_session(b"", returncode=128)))
+
+    def test_git_describe_no_git(self):
+        session = mock.Mock()
+        session.exists.return_value = False
+        self.assertIsNone(version_from_git_describe(session))
+        session.Popen.assert_not_called()
+
+    def test_setup_cfg(self):
+        self.write("setup.py", "from setuptools import setup\nsetup()\n")
+        self.write("setup.cfg", "[metadata]\nname = foo\nversion = 1.4\n")
+        self.assertEqual("1.4", SetupPy(self.test_dir).project_version(None))
+
+    def test_meson(self):
+        self.write(
+            "meson.build",
+            "project('foo', 'c',\n  default_options: ['warning_level=3'],\n"
+            "  version: '0.5.1')\n")
+        bs = Meson(os.path.join(self.test_dir, "meson.build"))
+        self.assertEqual("0.5.1", bs.project_version(None))
+
+    def test_cargo_workspace_version(self):
+        self.write(
+            "Cargo.toml",
+            "[package]\nname = \"foo\"\nversion.workspace = true\n")
+        bs = Cargo(os.path.join(self.test_dir, "Cargo.toml"))
+        session = mock.Mock()
+        session.exists.return_value = False
+        self.assertIsNone(bs.project_version(session))
+
+
+class CargoToolTests(BuildSystemTestCase):
+    def setUp(self):
+        super(CargoToolTests, self).setUp()
+        self.write("Cargo.toml", "[package]\nname = \"foo\"\n")
+        self.bs = Cargo(os.path.join(self.test_dir, "Cargo.toml"))
+        self.resolver = mock.Mock()
+
+    def test_lint_unable_to_check(self):
+        # A failure to check for clippy should not abort the lint run
+        with mock.patch(
+                "ognibuild.buildsystem.BinaryRequirement.met",
+                side_effect=FileNotFoundError("which")), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers") as run:
+            self.bs.lint(mock.Mock(), self.resolver, [])
+        [[req]] = self.resolver.install.call_args.args
+        self.assertEqual("cargo-clippy", req.binary_name)
+        self.assertEqual(["cargo", "clippy"], run.call_args.args[1])
+
+    def test_coverage_present(self):
+        with mock.patch(
+                "ognibuild.buildsystem.BinaryRequirement.met",
+                return_value=True), mock.patch(
+                "ognibuild.buildsystem._coverage_path",
+                return_value="/tmp/cargo.info"), mock.patch(
+                "ognibuild.buildsystem.run_with_build_fixers"):
+            self.bs.coverage(mock.Mock(), self.resolver, [])
+        self.resolver.install.assert_not_called()
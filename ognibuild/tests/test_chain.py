@@ -0,0 +1,110 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+import os
+import shutil
+import tempfile
+from unittest import TestCase, mock
+
+from ..chain import (
+    ChainCycle,
+    ChainedProject,
+    base_environment,
+    chain_environment,
+    linear_chain,
+    order_chain,
+)
+
+
+class OrderChainTests(TestCase):
+    def test_dependencies_first(self):
+        projects = [
+            ChainedProject("app", "app", ["bindings"]),
+            ChainedProject("lib", "lib"),
+            ChainedProject("bindings", "bindings", ["lib"]),
+        ]
+        self.assertEqual(
+            ["lib", "bindings", "app"],
+            [project.name for project in order_chain(projects)])
+
+    def test_linear(self):
+        projects = linear_chain(["src/lib", "src/app"])
+        self.assertEqual([
+            ChainedProject("lib", "src/lib", []),
+            ChainedProject("app", "src/app", ["lib"])], projects)
+        self.assertEqual(projects, order_chain(projects))
+
+    def test_unknown(self):
+        self.assertRaises(
+            ValueError, order_chain, [ChainedProject("app", "app", ["lib"])])
+
+    def test_cycle(self):
+        self.assertRaises(ChainCycle, order_chain, [
+            ChainedProject("a", "a", ["b"]), ChainedProject("b", "b", ["a"])])
+
+
+class ChainEnvironmentTests(TestCase):
+    def setUp(self):
+        super(ChainEnvironmentTests, self).setUp()
+        self.root = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.root)
+        self.session = mock.Mock()
+        self.session.external_path.side_effect = (
+            lambda path: os.path.join(self.root, path.lstrip("/")))
+
+    def test_empty_base(self):
+        env = chain_environment(
+            self.session, "/opt/chain", {"PATH": "/usr/bin"})
+        self.assertEqual("/opt/chain/bin:/usr/bin", env["PATH"])
+        self.assertEqual(
+            "/opt/chain/lib/pkgconfig:/opt/chain/share/pkgconfig",
+            env["PKG_CONFIG_PATH"])
+        self.assertEqual("/opt/chain/lib", env["LD_LIBRARY_PATH"])
+        self.assertNotIn("PYTHONPATH", env)
+
+    def test_prepends(self):
+        env = chain_environment(self.session, "/opt/chain", {
+            "PATH": "/usr/bin",
+            "PKG_CONFIG_PATH": "/usr/local/lib/pkgconfig",
+            "CPATH": "/usr/local/include",
+            "LD_LIBRARY_PATH": "/usr/local/lib:/opt/chain/lib",
+        })
+        self.assertEqual(
+            "/opt/chain/lib/pkgconfig:/opt/chain/share/pkgconfig:"
+            "/usr/local/lib/pkgconfig", env["PKG_CONFIG_PATH"])
+        self.assertEqual(
+            "/opt/chain/include:/usr/local/include", env["CPATH"])
+        self.assertEqual(
+            "/opt/chain/lib:/usr/local/lib", env["LD_LIBRARY_PATH"])
+
+    def test_site_packages(self):
+        os.makedirs(os.path.join(
+            self.root, "opt/chain/lib/python3.11/site-packages"))
+        env = chain_environment(
+            self.session, "/opt/chain", {"PYTHONPATH": "/srv/python"})
+        self.assertEqual(
+            "/opt/chain/lib/python3.11/site-packages:/srv/python",
+            env["PYTHONPATH"])
+
+    def test_base_environment(self):
+        self.session.getenv.side_effect = lambda name: {
+            "PATH": "/usr/bin"}.get(name)
+        self.session.check_output.side_effect = lambda argv, cwd=None: (
+            b"/usr/local/include\n" if argv[-1] == "echo ${CPATH}" else b"\n")
+        env = base_environment(self.session)
+        self.assertEqual("/usr/bin", env["PATH"])
+        self.assertEqual("/usr/local/include", env["CPATH"])
+        self.assertEqual("", env["LD_LIBRARY_PATH"])
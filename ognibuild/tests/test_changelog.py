@@ -0,0 +1,120 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from datetime import date
+import os
+import shutil
+import tempfile
+from unittest import TestCase
+
+from ..changelog import (
+    KeepAChangelogFile,
+    NewsFile,
+    NoChangelogFound,
+    find_changelog,
+    update_changelog,
+)
+
+
+class ChangelogTestCase(TestCase):
+    def setUp(self):
+        super(ChangelogTestCase, self).setUp()
+        self.path = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.path)
+
+    def write(self, name, contents):
+        with open(os.path.join(self.path, name), "w") as f:
+            f.write(contents)
+
+    def read(self, name):
+        with open(os.path.join(self.path, name), "r") as f:
+            return f.read()
+
+
+class FindChangelogTests(ChangelogTestCase):
+    def test_none(self):
+        self.assertRaises(NoChangelogFound, find_changelog, self.path)
+
+    def test_keepachangelog(self):
+        self.write("CHANGELOG.md", "# Changelog\n\n## [Unreleased]\n")
+        self.assertIsInstance(find_changelog(self.path), KeepAChangelogFile)
+
+    def test_news(self):
+        self.write("NEWS", "0.1 (2020-01-01)\n")
+        self.assertIsInstance(find_changelog(self.path), NewsFile)
+
+
+class KeepAChangelogTests(ChangelogTestCase):
+    def test_unreleased(self):
+        self.write(
+            "CHANGELOG.md",
+            """\
+# Changelog
+
+## [Unreleased]
+### Added
+- Feature
+
+## [1.0.0] - 2021-01-01
+- Initial release
+
+[Unreleased]: https://example.com/r/compare/v1.0.0...HEAD
+[1.0.0]: https://example.com/r/releases/tag/v1.0.0
+""",
+        )
+        update_changelog(self.path, "1.1.0", ["Fix"], date=date(2021, 6, 1))
+        self.assertEqual(
+            """\
+# Changelog
+
+## [Unreleased]
+
+## [1.1.0] - 2021-06-01
+### Added
+- Feature
+- Fix
+
+## [1.0.0] - 2021-01-01
+- Initial release
+
+[Unreleased]: https://example.com/r/compare/v1.1.0...HEAD
+[1.1.0]: https://example.com/r/compare/v1.0.0...v1.1.0
+[1.0.0]: https://example.com/r/releases/tag/v1.0.0
+""",
+            self.read("CHANGELOG.md"),
+        )
+
+    def test_no_unreleased(self):
+        self.write(
+            "CHANGELOG.md", "# Changelog\n\n## [1.0.0] - 2021-01-01\n- Initial\n")
+        update_changelog(self.path, "1.1.0", ["Fix"], date=date(2021, 6, 1))
+        self.assertEqual(
+            "# Changelog\n\n## [1.1.0] - 2021-06-01\n\n- Fix\n\n"
+            "## [1.0.0] - 2021-01-01\n- Initial\n",
+            self.read("CHANGELOG.md"),
+        )
+
+
+class NewsFileTests(ChangelogTestCase):
+    def test_add(self):
+        self.write("NEWS", "0.1 (2020-01-01)\n\n * Initial\n")
+        update_changelog(self.path, "0.2", ["Fix", "Feature"], date=date(2021, 6, 1))
+        self.assertEqual(
+            "0.2 (2021-06-01)\n\n * Fix\n * Feature\n\n"
+            "0.1 (2020-01-01)\n\n * Initial\n",
+            self.read("NEWS"),
+        )
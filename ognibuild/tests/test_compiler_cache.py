@@ -0,0 +1,116 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+import shutil
+import tempfile
+from unittest import TestCase, mock
+
+from ..compiler_cache import enable_compiler_caches
+from ..session.plain import PlainSession
+from ..session.unshare import BindMount, UnshareSession
+
+
+class EnableCompilerCachesTests(TestCase):
+    def setUp(self):
+        super(EnableCompilerCachesTests, self).setUp()
+        self.cache_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.cache_dir)
+        self.installed = {"ccache", "sccache"}
+
+    def which(self, session, name):
+        if name in self.installed:
+            return "/usr/bin/" + name
+        return None
+
+    def enable(self, session, **kwargs):
+        with mock.patch("ognibuild.compiler_cache.which", self.which):
+            return enable_compiler_caches(
+                session, cache_dir=self.cache_dir, **kwargs)
+
+    def test_ccache(self):
+        with PlainSession() as session:
+            session.setenv("PATH", "/usr/bin:/bin")
+            self.assertEqual(["ccache"], self.enable(session, names=["ccache"]))
+            source = os.path.join(self.cache_dir, "ccache")
+            self.assertTrue(os.path.isdir(source))
+            self.assertEqual(source, session.getenv("CCACHE_DIR"))
+            self.assertEqual(
+                "/usr/lib/ccache:/usr/bin:/bin", session.getenv("PATH"))
+            self.assertEqual(
+                "ccache", session.getenv("CMAKE_C_COMPILER_LAUNCHER"))
+            self.assertEqual(
+                "ccache", session.getenv("CMAKE_CXX_COMPILER_LAUNCHER"))
+            self.assertIsNone(session.getenv("RUSTC_WRAPPER"))
+
+    def test_sccache(self):
+        with PlainSession() as session:
+            self.assertEqual(
+                ["sccache"], self.enable(session, names=["sccache"]))
+            self.assertEqual(
+                os.path.join(self.cache_dir, "sccache"),
+                session.getenv("SCCACHE_DIR"))
+            self.assertEqual("sccache", session.getenv("RUSTC_WRAPPER"))
+            self.assertIsNone(session.getenv("CCACHE_DIR"))
+
+    def test_bind_mount(self):
+        session = UnshareSession("/nonexistent")
+        with mock.patch.object(
+                session, "check_output", return_value=b"/usr/bin:/bin\n"):
+            self.assertEqual(["ccache", "sccache"], self.enable(session))
+        self.assertEqual([
+            BindMount(os.path.join(self.cache_dir, "ccache"),
+                      "/var/cache/ognibuild/ccache"),
+            BindMount(os.path.join(self.cache_dir, "sccache"),
+                      "/var/cache/ognibuild/sccache")],
+            session.bind_mounts)
+        self.assertEqual(
+            "/var/cache/ognibuild/ccache", session.getenv("CCACHE_DIR"))
+        self.assertEqual(
+            "/var/cache/ognibuild/sccache", session.getenv("SCCACHE_DIR"))
+
+    def test_not_shared(self):
+        with PlainSession() as session, mock.patch.object(
+                session, "share_host_directory", return_value=None):
+            self.assertEqual([], self.enable(session))
+            self.assertIsNone(session.getenv("CCACHE_DIR"))
+            self.assertIsNone(session.getenv("SCCACHE_DIR"))
+
+    def test_not_installed(self):
+        self.installed = {"sccache"}
+        with PlainSession() as session:
+            self.assertEqual(["sccache"], self.enable(session))
+            self.assertIsNone(session.getenv("CCACHE_DIR"))
+
+    def test_install(self):
+        self.installed = set()
+        resolver = mock.Mock()
+        with PlainSession() as session:
+            self.assertEqual(
+                ["ccache"],
+                self.enable(session, resolver=resolver, names=["ccache"]))
+        [req] = resolver.install.call_args.args[0]
+        self.assertEqual("ccache", req.binary_name)
+
+    def test_install_fails(self):
+        self.installed = set()
+        resolver = mock.Mock()
+        resolver.install.side_effect = Exception("no ccache")
+        with PlainSession() as session:
+            self.assertEqual(
+                [], self.enable(session, resolver=resolver, names=["ccache"]))
+            self.assertIsNone(session.getenv("CCACHE_DIR"))
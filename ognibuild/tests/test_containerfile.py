@@ -0,0 +1,81 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from ..buildsystem import Gradle, Please
+from ..containerfile import base_requirements, build_commands, install_commands
+from ..requirements import BinaryRequirement
+from ..resolver import Resolver, StackedResolver
+
+
+class DummyResolver(Resolver):
+    def __init__(self, name, known):
+        self.name = name
+        self.known = known
+
+    def __str__(self):
+        return self.name
+
+    def explain(self, requirements):
+        for req in requirements:
+            if req.binary_name in self.known:
+                yield (["sudo", self.name, "install", req.binary_name], [req])
+
+
+class InstallCommandsTests(TestCase):
+    def test_noninteractive(self):
+        resolver = DummyResolver("apt", ["make"])
+        self.assertEqual(
+            ["sudo apt install -y make"],
+            install_commands(resolver, [BinaryRequirement("make")]))
+
+    def test_satisfy(self):
+        class SatisfyResolver(Resolver):
+            def explain(self, requirements):
+                yield (["apt", "satisfy", "make, gcc"], list(requirements))
+
+        self.assertEqual(
+            ["apt satisfy -y 'make, gcc'"],
+            install_commands(SatisfyResolver(), [BinaryRequirement("make")]))
+
+    def test_stacked_explains_once(self):
+        resolver = StackedResolver([
+            DummyResolver("apt", ["make"]),
+            DummyResolver("dnf", ["make", "gcc"])])
+        self.assertEqual(
+            ["sudo apt install -y make", "sudo dnf install -y gcc"],
+            install_commands(
+                resolver,
+                [BinaryRequirement("make"), BinaryRequirement("gcc")]))
+
+
+class WrapperScriptTests(TestCase):
+    def test_please(self):
+        bs = Please("/src")
+        self.assertEqual([["plz", "build", "//..."]], build_commands(bs))
+        self.assertEqual(
+            ["plz"], [req.binary_name for req in base_requirements(bs)])
+
+    def test_pleasew(self):
+        bs = Please("/src", "./pleasew")
+        self.assertEqual([["./pleasew", "build", "//..."]], build_commands(bs))
+        self.assertEqual([], base_requirements(bs))
+
+    def test_gradlew(self):
+        bs = Gradle("/src", "./gradlew")
+        self.assertEqual([["./gradlew", "build"]], build_commands(bs))
@@ -0,0 +1,73 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+from io import StringIO
+from unittest import TestCase
+
+from ..coverage import CoverageReport, parse_lcov, write_cobertura, write_lcov
+
+
+LCOV = """\
+TN:
+SF:src/foo.c
+DA:1,3
+DA:2,0
+DA:4,1
+end_of_record
+SF:src/bar.c
+DA:10,0
+end_of_record
+"""
+
+
+class ParseLcovTests(TestCase):
+    def test_parse(self):
+        report = parse_lcov(StringIO(LCOV))
+        self.assertEqual(
+            CoverageReport({
+                "src/foo.c": {1: 3, 2: 0, 4: 1},
+                "src/bar.c": {10: 0}}), report)
+        self.assertEqual(4, report.lines_total)
+        self.assertEqual(2, report.lines_covered)
+        self.assertEqual(50.0, report.percent)
+
+    def test_roundtrip(self):
+        report = parse_lcov(StringIO(LCOV))
+        f = StringIO()
+        write_lcov(report, f)
+        self.assertEqual(report, parse_lcov(StringIO(f.getvalue())))
+
+    def test_empty(self):
+        report = parse_lcov(StringIO(""))
+        self.assertEqual(0, report.lines_total)
+        self.assertIsNone(report.percent)
+
+
+class WriteCoberturaTests(TestCase):
+    def test_write(self):
+        import xml.etree.ElementTree as ET
+
+        f = StringIO()
+        write_cobertura(parse_lcov(StringIO(LCOV)), f)
+        root = ET.fromstring(f.getvalue())
+        self.assertEqual("0.5000", root.get("line-rate"))
+        self.assertEqual(
+            ["src/bar.c", "src/foo.c"],
+            [c.get("filename") for c in root.iter("class")])
+        self.assertEqual(
+            [("1", "3"), ("2", "0"), ("4", "1")],
+            [(line.get("number"), line.get("hits"))
+             for line in root.find(".//class[@filename='src/foo.c']").iter("line")])
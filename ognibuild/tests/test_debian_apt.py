@@ -0,0 +1,156 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase, mock
+
+from ..debian.apt import AptManager, find_transient_apt_failure, run_apt
+from ..requirements import BinaryRequirement, PkgConfigRequirement
+from ..resolver.apt import AptRequirement, AptResolver
+
+
+class DummyAptManager(AptManager):
+    def __init__(self):
+        super(DummyAptManager, self).__init__(None)
+        self.satisfied = []
+        self.architectures = []
+
+    def add_architecture(self, arch):
+        self.architectures.append(arch)
+
+    def satisfy(self, deps):
+        self.satisfied.extend(deps)
+
+
+PACKAGES = {
+    "pkg-config": "libfoo-dev",
+    "binary": "foo-tools",
+}
+
+
+def resolve_requirement_apt(apt_mgr, req):
+    return [AptRequirement.simple(PACKAGES[req.family])]
+
+
+class DummyPkgConfigRequirement(PkgConfigRequirement):
+    def met(self, session):
+        return False
+
+
+class DummyBinaryRequirement(BinaryRequirement):
+    def met(self, session):
+        return False
+
+
+class CrossArchitectureTests(TestCase):
+    def setUp(self):
+        super(CrossArchitectureTests, self).setUp()
+        patcher = mock.patch(
+            "ognibuild.resolver.apt.resolve_requirement_apt",
+            resolve_requirement_apt)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+        self.apt = DummyAptManager()
+
+    def test_for_architecture(self):
+        self.assertEqual(
+            "libfoo-dev:armhf (>= 1.0)",
+            AptRequirement.simple("libfoo-dev", "1.0").for_architecture(
+                "armhf").pkg_relation_str())
+
+    def test_native(self):
+        resolver = AptResolver(self.apt, tie_breakers=[])
+        self.assertEqual(
+            "libfoo-dev",
+            resolver.resolve(PkgConfigRequirement("foo")).pkg_relation_str())
+
+    def test_library_for_host(self):
+        resolver = AptResolver(self.apt, tie_breakers=[], host_arch="armhf")
+        self.assertEqual(
+            "libfoo-dev:armhf",
+            resolver.resolve(PkgConfigRequirement("foo")).pkg_relation_str())
+
+    def test_tool_for_build(self):
+        resolver = AptResolver(self.apt, tie_breakers=[], host_arch="armhf")
+        self.assertEqual(
+            "foo-tools",
+            resolver.resolve(BinaryRequirement("foo")).pkg_relation_str())
+
+    def test_install(self):
+        resolver = AptResolver(self.apt, tie_breakers=[], host_arch="armhf")
+        resolver.install(
+            [DummyPkgConfigRequirement("foo"), DummyBinaryRequirement("foo")])
+        self.assertEqual(["armhf"], self.apt.architectures)
+        self.assertEqual(["libfoo-dev:armhf, foo-tools"], self.apt.satisfied)
+
+    def test_install_tools_only(self):
+        resolver = AptResolver(self.apt, tie_breakers=[], host_arch="armhf")
+        resolver.install([DummyBinaryRequirement("foo")])
+        self.assertEqual([], self.apt.architectures)
+        self.assertEqual(["foo-tools"], self.apt.satisfied)
+
+
+class FindTransientAptFailureTests(TestCase):
+    def test_lock(self):
+        self.assertEqual("lock", find_transient_apt_failure([
+            "E: Could not get lock /var/lib/dpkg/lock-frontend. It is held "
+            "by process 1234 (apt)\n"]))
+
+    def test_network(self):
+        self.assertEqual("network", find_transient_apt_failure([
+            "E: Failed to fetch http://deb.debian.org/debian/pool/main/f/foo/"
+            "foo_1.0_amd64.deb  503  Service Unavailable [IP: 1.2.3.4 80]"]))
+
+    def test_other(self):
+        self.assertIsNone(find_transient_apt_failure([
+            "E: Unable to locate package foo"]))
+
+
+class RunAptTests(TestCase):
+    def run_apt(self, results, args, prefix=None):
+        calls = []
+
+        def run_with_tee(session, argv, **kwargs):
+            calls.append(argv)
+            return results.pop(0)
+
+        with mock.patch(
+                "ognibuild.debian.apt.run_with_tee",
+                side_effect=run_with_tee), mock.patch("time.sleep"):
+            run_apt(None, args, prefix=prefix, retries=2)
+        return calls
+
+    def test_success(self):
+        self.assertEqual(
+            [["sudo", "apt", "-y", "install", "foo"]],
+            self.run_apt([(0, [])], ["install", "foo"], prefix=["sudo"]))
+
+    def test_retry_lock(self):
+        self.assertEqual(
+            [["sudo", "apt", "-y", "install", "foo"]] * 2,
+            self.run_apt(
+                [(100, ["E: Could not get lock /var/lib/dpkg/lock"]), (0, [])],
+                ["install", "foo"], prefix=["sudo"]))
+
+    def test_retry_network(self):
+        failure = "E: Unable to fetch some archives, maybe run apt-get update"
+        self.assertEqual([
+            ["sudo", "apt", "-y", "install", "foo"],
+            ["sudo", "apt", "-y", "update"],
+            ["sudo", "apt", "-y", "install", "foo"],
+        ], self.run_apt(
+            [(100, [failure]), (0, []), (0, [])],
+            ["install", "foo"], prefix=["sudo"]))
@@ -18,9 +18,14 @@
 import datetime
 import os
 
-from ..debian.build import add_dummy_changelog_entry, get_build_architecture
+from ..debian.build import (
+    add_dummy_changelog_entry,
+    deb_build_options,
+    get_build_architecture,
+    write_meson_cross_file,
+)
 
-from breezy.tests import TestCaseWithTransport, TestCase
+from breezy.tests import TestCaseInTempDir, TestCaseWithTransport, TestCase
 
 
 class AddDummyChangelogEntryTests(TestCaseWithTransport):
@@ -150,3 +155,31 @@ class BuildArchitectureTests(TestCase):
 
     def test_is_str(self):
         self.assertIsInstance(get_build_architecture(), str)
+
+
+class WriteMesonCrossFileTests(TestCaseInTempDir):
+    def test_known(self):
+        path = write_meson_cross_file(self.test_dir, "arm64")
+        self.assertEqual(
+            os.path.join(self.test_dir, "meson-cross-arm64.ini"), path)
+        with open(path) as f:
+            self.assertIn("c = 'aarch64-linux-gnu-gcc'\n", f.read())
+
+
+class DebBuildOptionsTests(TestCase):
+    def test_unchanged(self):
+        self.assertEqual("nocheck", deb_build_options("nocheck"))
+
+    def test_jobs(self):
+        self.assertEqual(
+            "nocheck parallel=4", deb_build_options("parallel=2 nocheck", jobs=4))
+
+    def test_debug(self):
+        self.assertEqual(
+            "nocheck noopt nostrip",
+            deb_build_options("nocheck noopt", profile="debug"))
+
+    def test_release(self):
+        self.assertEqual(
+            "nocheck", deb_build_options("noopt nocheck nostrip",
+                                         profile="release"))
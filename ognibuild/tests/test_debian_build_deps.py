@@ -0,0 +1,62 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from ..debian.build_deps import BuildDependsPlan
+from ..resolver.apt import AptRequirement
+
+
+CONTROL = """\
+Source: foo
+Build-Depends: debhelper-compat (= 13)
+
+Package: foo
+Architecture: any
+Description: Foo
+ Foo.
+"""
+
+
+class BuildDependsPlanTests(TestCase):
+    def test_build_depends(self):
+        plan = BuildDependsPlan()
+        plan.add(("build", ), AptRequirement.simple("libfoo-dev"))
+        plan.add(("build", ), AptRequirement.simple(
+            "libfoo-dev", minimum_version="1.2"))
+        plan.add(
+            ("autopkgtest", "pytest"), AptRequirement.simple("python3-pytest"))
+        self.assertEqual("libfoo-dev (>= 1.2)", plan.build_depends())
+
+    def test_empty(self):
+        plan = BuildDependsPlan()
+        self.assertEqual("", plan.build_depends())
+        self.assertEqual("", plan.patch(CONTROL))
+
+    def test_patch(self):
+        plan = BuildDependsPlan()
+        plan.add(("build", ), AptRequirement.simple("libfoo-dev"))
+        patch = plan.patch(CONTROL)
+        self.assertTrue(patch.startswith(
+            "--- a/debian/control\n+++ b/debian/control\n"), patch)
+        self.assertIn("-Build-Depends: debhelper-compat (= 13)\n", patch)
+        self.assertIn("libfoo-dev", patch)
+
+    def test_patch_already_present(self):
+        plan = BuildDependsPlan()
+        plan.add(("build", ), AptRequirement.simple("debhelper-compat"))
+        self.assertEqual("", plan.patch(CONTROL))
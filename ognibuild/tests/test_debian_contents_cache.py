@@ -0,0 +1,168 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import gzip
+import hashlib
+import os
+import shutil
+import tempfile
+from io import BytesIO
+from unittest import TestCase, mock
+
+from ..debian.contents_cache import (
+    ContentsCache,
+    ContentsReleaseInfo,
+    PatchFailed,
+    apply_ed_patch,
+)
+
+
+def sha256(data):
+    return hashlib.sha256(data).hexdigest()
+
+
+class ApplyEdPatchTests(TestCase):
+    def apply(self, lines, patch):
+        return list(apply_ed_patch(iter(lines), patch))
+
+    def test_change(self):
+        self.assertEqual(
+            [b"a\n", b"B\n", b"c\n"],
+            self.apply([b"a\n", b"b\n", b"c\n"], b"2c\nB\n.\n"))
+
+    def test_delete_range(self):
+        self.assertEqual(
+            [b"a\n", b"d\n"],
+            self.apply([b"a\n", b"b\n", b"c\n", b"d\n"], b"2,3d\n"))
+
+    def test_append_at_start(self):
+        self.assertEqual(
+            [b"new\n", b"a\n"], self.apply([b"a\n"], b"0a\nnew\n.\n"))
+
+    def test_multiple(self):
+        # As generated by diff --ed: from the end of the file to the start
+        self.assertEqual(
+            [b"a\n", b"c\n", b"d\n", b"e\n", b"f\n"],
+            self.apply(
+                [b"a\n", b"b\n", b"c\n", b"d\n"],
+                b"4a\ne\nf\n.\n2d\n"))
+
+    def test_out_of_range(self):
+        self.assertRaises(
+            PatchFailed, self.apply, [b"a\n"], b"3c\nc\n.\n")
+
+    def test_invalid_command(self):
+        self.assertRaises(PatchFailed, self.apply, [b"a\n"], b"1x\n")
+
+    def test_unterminated(self):
+        self.assertRaises(PatchFailed, self.apply, [b"a\n"], b"1a\nb\n")
+
+    def test_wrong_order(self):
+        self.assertRaises(
+            PatchFailed, self.apply, [b"a\n", b"b\n", b"c\n"], b"1d\n3d\n")
+
+
+class DummyContentsCache(ContentsCache):
+    def _cache_path(self, url):
+        return os.path.join(self.path, url.replace("/", "_") + ".gz")
+
+    def _load_url_exact(self, url):
+        raise FileNotFoundError(url)
+
+
+class ContentsCacheTests(TestCase):
+    url = "http://deb.debian.org/debian/dists/sid/main/Contents-amd64"
+
+    def setUp(self):
+        super(ContentsCacheTests, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+        self.files = {}
+        self.loaded = []
+        self.cache = DummyContentsCache(self.test_dir, self.load_url)
+
+    def load_url(self, url):
+        self.loaded.append(url)
+        try:
+            return BytesIO(self.files[url])
+        except KeyError:
+            raise FileNotFoundError(url)
+
+    def load(self, contents):
+        with self.cache.load(
+                self.url, ContentsReleaseInfo(sha256(contents), {})) as f:
+            return f.read()
+
+    def test_download(self):
+        contents = b"usr/bin/make devel/make\n"
+        self.files[self.url] = contents
+        self.assertEqual(contents, self.load(contents))
+        with gzip.open(self.cache._cache_path(self.url), "rb") as f:
+            self.assertEqual(contents, f.read())
+
+    def test_cached(self):
+        contents = b"usr/bin/make devel/make\n"
+        self.files[self.url] = contents
+        self.load(contents)
+        self.loaded = []
+        self.assertEqual(contents, self.load(contents))
+        self.assertEqual([], self.loaded)
+
+    def test_by_hash_missing(self):
+        from urllib.error import HTTPError
+
+        contents = b"usr/bin/make devel/make\n"
+        self.files[self.url] = contents
+        by_hash_url = (
+            "http://deb.debian.org/debian/dists/sid/main/by-hash/SHA256/"
+            + sha256(contents))
+
+        def urlopen(request):
+            raise HTTPError(request.full_url, 404, "Not Found", {}, None)
+
+        with mock.patch.object(
+                DummyContentsCache, "_load_url_exact",
+                ContentsCache._load_url_exact), \
+                mock.patch("urllib.request.urlopen", urlopen):
+            with self.cache.load(self.url, ContentsReleaseInfo(
+                    sha256(contents), {".gz": by_hash_url})) as f:
+                self.assertEqual(contents, f.read())
+        self.assertEqual([self.url], self.loaded)
+
+    def test_mismatch_not_cached(self):
+        self.files[self.url] = b"usr/bin/make devel/make\n"
+        self.assertEqual(
+            b"usr/bin/make devel/make\n", self.load(b"something else\n"))
+        self.assertEqual([], os.listdir(self.test_dir))
+
+    def test_pdiff(self):
+        old = b"usr/bin/gcc devel/gcc\nusr/bin/make devel/make\n"
+        new = b"usr/bin/gcc devel/gcc\nusr/bin/make devel/make-dfsg\n"
+        patch = b"2c\nusr/bin/make devel/make-dfsg\n.\n"
+        self.files[self.url] = old
+        self.load(old)
+        self.files[self.url + ".diff/Index"] = (
+            "SHA256-Current: %s %d\n"
+            "SHA256-History:\n %s %d T-1\n"
+            "SHA256-Patches:\n %s %d T-1\n" % (
+                sha256(new), len(new), sha256(old), len(old),
+                sha256(patch), len(patch))).encode()
+        self.files[self.url + ".diff/T-1"] = patch
+        self.loaded = []
+        self.assertEqual(new, self.load(new))
+        self.assertNotIn(self.url, self.loaded)
+        self.assertEqual(new, self.load(new))
@@ -0,0 +1,76 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+
+from breezy.tests import TestCaseWithTransport
+
+from ..debian.dep14 import (
+    add_upstream_sources,
+    is_overlay_tree,
+    is_packaging_tree,
+    packaging_branch_names,
+)
+
+
+class PackagingBranchNamesTests(TestCaseWithTransport):
+    def test_vendor(self):
+        self.assertEqual("ubuntu/latest", packaging_branch_names("ubuntu")[0])
+
+
+class TreeKindTests(TestCaseWithTransport):
+    def test_overlay(self):
+        tree = self.make_branch_and_tree(".")
+        self.build_tree(["debian/", "debian/changelog", ".gitignore"])
+        tree.add(["debian", "debian/changelog", ".gitignore"])
+        self.assertTrue(is_packaging_tree(tree))
+        self.assertTrue(is_overlay_tree(tree))
+
+    def test_merge_mode(self):
+        tree = self.make_branch_and_tree(".")
+        self.build_tree(["debian/", "debian/changelog", "setup.py"])
+        tree.add(["debian", "debian/changelog", "setup.py"])
+        self.assertTrue(is_packaging_tree(tree))
+        self.assertFalse(is_overlay_tree(tree))
+
+    def test_not_packaging(self):
+        tree = self.make_branch_and_tree(".")
+        self.build_tree(["setup.py"])
+        tree.add(["setup.py"])
+        self.assertFalse(is_packaging_tree(tree))
+
+
+class AddUpstreamSourcesTests(TestCaseWithTransport):
+    def test_add(self):
+        upstream = self.make_branch_and_tree("upstream")
+        self.build_tree([
+            "upstream/setup.py", "upstream/src/", "upstream/src/foo.py",
+            "upstream/debian/", "upstream/debian/rules"])
+        upstream.add(["setup.py", "src", "src/foo.py", "debian", "debian/rules"])
+        upstream.commit("upstream")
+        packaging = self.make_branch_and_tree("packaging")
+        self.build_tree_contents([
+            ("packaging/debian/",), ("packaging/debian/changelog", "")])
+        packaging.add(["debian", "debian/changelog"])
+        packaging.commit("packaging")
+        add_upstream_sources(packaging, upstream.basis_tree())
+        self.assertTrue(os.path.exists("packaging/setup.py"))
+        self.assertTrue(os.path.exists("packaging/src/foo.py"))
+        # The upstream debian/ directory is not used
+        self.assertFalse(os.path.exists("packaging/debian/rules"))
+        # The upstream sources are not versioned
+        self.assertFalse(packaging.is_versioned("setup.py"))
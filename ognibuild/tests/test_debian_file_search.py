@@ -0,0 +1,67 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from ..debian.file_search import GeneratedFileSearcher
+
+
+class SearchBinaryTests(TestCase):
+    def search_binary(self, name, db):
+        return list(GeneratedFileSearcher(db).search_binary(name))
+
+    def test_bin_path(self):
+        self.assertEqual(
+            ["make"], self.search_binary("make", [("/usr/bin/make", "make")]))
+
+    def test_alternative(self):
+        self.assertEqual(
+            ["gawk"], self.search_binary("awk", [
+                ("/usr/bin/gawk", "gawk"), ("/usr/bin/mawk", "mawk")]))
+
+    def test_alternative_second_choice(self):
+        self.assertEqual(
+            ["mawk"], self.search_binary("awk", [("/usr/bin/mawk", "mawk")]))
+
+    def test_alternatives_bin_path(self):
+        self.assertEqual(
+            ["openjdk-17-jdk-headless"], self.search_binary("jar", [
+                ("/usr/lib/jvm/java-17-openjdk-amd64/bin/jar",
+                 "openjdk-17-jdk-headless")]))
+
+    def test_alternatives_bin_path_exact(self):
+        self.assertEqual(
+            [], self.search_binary("jar", [
+                ("/usr/lib/jvm/java-17-openjdk-amd64/bin/jarsigner",
+                 "openjdk-17-jdk-headless")]))
+
+    def test_bin_path_preferred(self):
+        self.assertEqual(
+            ["clang"], self.search_binary("clang", [
+                ("/usr/bin/clang", "clang"),
+                ("/usr/lib/llvm-15/bin/clang", "clang-15")]))
+
+    def test_shipped_before_alternative(self):
+        self.assertEqual(
+            ["original-awk"], self.search_binary("awk", [
+                ("/usr/bin/awk", "original-awk"), ("/usr/bin/gawk", "gawk")]))
+
+    def test_alternative_before_alternatives_bin_path(self):
+        self.assertEqual(
+            ["gcc"], self.search_binary("cc", [
+                ("/usr/bin/gcc", "gcc"),
+                ("/usr/lib/llvm-15/bin/cc", "clang-15")]))
@@ -135,6 +135,24 @@ blah (0.1) UNRELEASED; urgency=medium
         self.assertFalse(self.resolve(MissingCommand("brz")))
         self.assertEqual("libc6, brz", self.get_build_deps())
 
+    def test_missing_command_sbin(self):
+        self._apt_files = {
+            "/usr/sbin/dmsetup": "dmsetup",
+        }
+        self.overrideEnv("DEBEMAIL", "jelmer@debian.org")
+        self.overrideEnv("DEBFULLNAME", "Jelmer Vernooĳ")
+        self.assertTrue(self.resolve(MissingCommand("dmsetup")))
+        self.assertEqual("libc6, dmsetup", self.get_build_deps())
+
+    def test_missing_command_alternative(self):
+        self._apt_files = {
+            "/usr/lib/jvm/java-17-openjdk-amd64/bin/jar": "openjdk-17-jdk-headless",
+        }
+        self.overrideEnv("DEBEMAIL", "jelmer@debian.org")
+        self.overrideEnv("DEBFULLNAME", "Jelmer Vernooĳ")
+        self.assertTrue(self.resolve(MissingCommand("jar")))
+        self.assertEqual("libc6, openjdk-17-jdk-headless", self.get_build_deps())
+
     def test_missing_command_ps(self):
         self._apt_files = {
             "/bin/ps": "procps",
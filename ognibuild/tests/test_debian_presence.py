@@ -0,0 +1,97 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from ..debian.presence import (
+    MISSING,
+    OUTDATED,
+    SATISFIED,
+    PackageVersionSource,
+    format_presence_report,
+    presence_report,
+    unavailable_requirements,
+)
+from ..resolver.apt import AptRequirement
+
+
+class DummyVersionSource(PackageVersionSource):
+    def __init__(self, versions):
+        self.versions = versions
+        self.calls = []
+
+    def get_versions(self, packages, releases):
+        self.calls.append((packages, releases))
+        return self.versions
+
+
+RELEASES = ["bookworm", "trixie", "experimental"]
+
+
+class PresenceReportTests(TestCase):
+    def setUp(self):
+        super(PresenceReportTests, self).setUp()
+        self.source = DummyVersionSource({
+            ("libfoo-dev", "bookworm"): ["1.0-1"],
+            ("libfoo-dev", "trixie"): ["2.0-1"],
+            ("libfoo-dev", "experimental"): ["2.1-1"],
+            ("libbar-dev", "experimental"): ["0.1-1"],
+            ("libbaz-dev", "trixie"): ["3.0-2"],
+        })
+
+    def test_single_lookup(self):
+        presence_report(
+            [AptRequirement.simple("libfoo-dev"),
+             AptRequirement.simple("libbar-dev")], RELEASES, self.source)
+        self.assertEqual(
+            [(["libbar-dev", "libfoo-dev"], RELEASES)], self.source.calls)
+
+    def test_versioned(self):
+        req = AptRequirement.simple("libfoo-dev", minimum_version="2.0")
+        report = presence_report([req], RELEASES, self.source)
+        self.assertEqual(
+            {"bookworm": OUTDATED, "trixie": SATISFIED,
+             "experimental": SATISFIED}, report[req])
+
+    def test_missing(self):
+        req = AptRequirement.simple("libbar-dev")
+        report = presence_report([req], RELEASES, self.source)
+        self.assertEqual(
+            {"bookworm": MISSING, "trixie": MISSING,
+             "experimental": SATISFIED}, report[req])
+        self.assertEqual(
+            [(req, ["experimental"])],
+            list(unavailable_requirements(report, RELEASES)))
+
+    def test_alternatives(self):
+        req = AptRequirement([[{"name": "libbar-dev"}, {"name": "libbaz-dev"}]])
+        report = presence_report([req], RELEASES, self.source)
+        self.assertEqual(
+            {"bookworm": MISSING, "trixie": SATISFIED,
+             "experimental": SATISFIED}, report[req])
+
+    def test_no_requirements(self):
+        self.assertEqual({}, presence_report([], RELEASES, self.source))
+        self.assertEqual([], self.source.calls)
+
+    def test_format(self):
+        req = AptRequirement.simple("libbar-dev")
+        report = presence_report([req], ["trixie", "experimental"], self.source)
+        self.assertEqual(
+            "requirement trixie    experimental\n"
+            "libbar-dev  missing   satisfied\n",
+            format_presence_report(report, ["trixie", "experimental"]))
@@ -0,0 +1,89 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+import shutil
+import tempfile
+import time
+from unittest import TestCase
+
+from ..dist_catcher import (
+    DistCatcher,
+    DistCatcherEvent,
+    DistNoTarball,
+    dist_events,
+)
+
+
+class DistCatcherTests(TestCase):
+    def setUp(self):
+        super(DistCatcherTests, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+        self.target_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.target_dir)
+
+    def touch(self, name):
+        path = os.path.join(self.test_dir, name)
+        with open(path, "w"):
+            pass
+        return path
+
+    def test_new_tarball(self):
+        events = []
+        with DistCatcher([self.test_dir], event_cb=events.append) as dc:
+            path = self.touch("foo-1.0.tar.gz")
+            self.touch("README")
+        self.assertEqual("foo-1.0.tar.gz", dc.copy_single(self.target_dir))
+        self.assertTrue(
+            os.path.exists(os.path.join(self.target_dir, "foo-1.0.tar.gz")))
+        self.assertEqual(
+            [(DistCatcherEvent.DISCOVERED, path),
+             (DistCatcherEvent.COPIED, path)],
+            [(e.kind, e.path) for e in events])
+
+    def test_existing_tarball(self):
+        path = self.touch("foo-1.0.tar.gz")
+        old = time.time() - 60
+        os.utime(path, (old, old))
+        events = []
+        with DistCatcher([self.test_dir], event_cb=events.append) as dc:
+            pass
+        self.assertRaises(DistNoTarball, dc.copy_single, self.target_dir)
+        self.assertEqual(
+            [(DistCatcherEvent.REJECTED, path,
+              "already present and not modified")],
+            [(e.kind, e.path, e.reason) for e in events])
+
+    def test_find_files_matching(self):
+        path = self.touch("foo-1.0.tar.gz")
+        self.touch("foo.zip")
+        dc = DistCatcher([self.test_dir, os.path.join(self.test_dir, "dist")])
+        self.assertEqual([path], dc.find_files_matching("*.tar.gz"))
+
+    def test_dist_events(self):
+        events = []
+        with dist_events(events.append):
+            with DistCatcher([self.test_dir]):
+                path = self.touch("foo-1.0.tar.gz")
+        self.assertEqual(
+            [(DistCatcherEvent.DISCOVERED, path)],
+            [(e.kind, e.path) for e in events])
+        # The callback is no longer called after the block
+        with DistCatcher([self.test_dir]) as dc:
+            self.touch("foo-1.1.tar.gz")
+        self.assertEqual(1, len(events))
@@ -0,0 +1,103 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase, mock
+
+from ..envfile import generate_shell_nix, resolve_requirement_nix
+from ..requirements import (
+    BinaryRequirement,
+    PkgConfigRequirement,
+    PythonPackageRequirement,
+)
+from ..resolver.nix import NixResolver
+
+
+class ResolveRequirementNixTests(TestCase):
+    def test_pkg_config(self):
+        self.assertEqual(
+            "glib", resolve_requirement_nix(PkgConfigRequirement("glib-2.0")))
+        self.assertEqual(
+            "glib", resolve_requirement_nix(PkgConfigRequirement("gio-2.0")))
+        self.assertEqual(
+            "libxml2",
+            resolve_requirement_nix(PkgConfigRequirement("libxml-2.0")))
+        self.assertEqual(
+            "openssl", resolve_requirement_nix(PkgConfigRequirement("libssl")))
+        self.assertEqual(
+            "xorg.libX11", resolve_requirement_nix(PkgConfigRequirement("x11")))
+
+    def test_pkg_config_unknown(self):
+        self.assertEqual(
+            "libarchive",
+            resolve_requirement_nix(PkgConfigRequirement("libarchive")))
+        self.assertEqual(
+            "libfoo", resolve_requirement_nix(PkgConfigRequirement("libfoo-1.0")))
+
+    def test_binary(self):
+        self.assertEqual(
+            "maven", resolve_requirement_nix(BinaryRequirement("mvn")))
+
+    def test_python(self):
+        self.assertEqual(
+            "python3Packages.typing-extensions",
+            resolve_requirement_nix(
+                PythonPackageRequirement("typing_extensions")))
+
+    def test_resolver(self):
+        self.assertEqual(
+            "gtk3",
+            NixResolver(None).resolve(PkgConfigRequirement("gtk+-3.0")).attribute)
+
+
+class GenerateShellNixTests(TestCase):
+    def test_deduplicated(self):
+        self.assertEqual(
+            "{ pkgs ? import <nixpkgs> {} }:\n"
+            "\n"
+            "pkgs.mkShell {\n"
+            "  buildInputs = with pkgs; [\n"
+            "    glib\n"
+            "    maven\n"
+            "  ];\n"
+            "}\n",
+            generate_shell_nix([
+                PkgConfigRequirement("glib-2.0"),
+                PkgConfigRequirement("gobject-2.0"),
+                BinaryRequirement("mvn")]))
+
+
+class NixResolverTests(TestCase):
+    def test_install(self):
+        commands = []
+
+        def run(session, argv, **kwargs):
+            commands.append(argv)
+            return []
+
+        with mock.patch("ognibuild.resolver.nix.run_detecting_problems", run):
+            NixResolver(None).install([PkgConfigRequirement("zlib")])
+        self.assertEqual(
+            [["nix", "--extra-experimental-features", "nix-command flakes",
+              "profile", "install", "nixpkgs#zlib"]], commands)
+
+    def test_explain(self):
+        self.assertEqual(
+            [(["nix", "--extra-experimental-features", "nix-command flakes",
+               "profile", "install", "nixpkgs#maven"],
+              ["mvn"])],
+            [(cmd, [r.binary_name for r in reqs]) for (cmd, reqs) in
+             NixResolver(None).explain([BinaryRequirement("mvn")])])
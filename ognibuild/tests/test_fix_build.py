@@ -0,0 +1,62 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from .. import DetailedFailure
+from ..fix_build import (
+    BuildFixer,
+    FixerOscillation,
+    iterate_with_build_fixers,
+)
+
+
+class AlwaysFixer(BuildFixer):
+    def can_fix(self, problem):
+        return True
+
+    def _fix(self, problem, phase):
+        return True
+
+
+class IterateWithBuildFixersTests(TestCase):
+    def run_with_problems(self, problems, history):
+        problems = list(problems)
+
+        def cb():
+            if problems:
+                raise DetailedFailure(1, ["make"], problems.pop(0))
+            return "done"
+
+        return iterate_with_build_fixers([AlwaysFixer()], cb, history=history)
+
+    def test_fixed(self):
+        history = []
+        self.assertEqual("done", self.run_with_problems(["a", "b"], history))
+        self.assertEqual(["a", "b"], [a.problem for a in history])
+
+    def test_persisted(self):
+        history = []
+        self.assertRaises(
+            DetailedFailure, self.run_with_problems, ["a", "a"], history)
+        self.assertEqual(["a"], [a.problem for a in history])
+
+    def test_oscillation(self):
+        history = []
+        with self.assertRaises(FixerOscillation) as cm:
+            self.run_with_problems(["a", "b", "c", "b"], history)
+        self.assertEqual(["b", "c"], [a.problem for a in cm.exception.cycle])
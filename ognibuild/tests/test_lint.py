@@ -0,0 +1,46 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase, mock
+
+from ..buildsystem import NoBuildToolsFound
+from ..lint import run_lint
+
+
+class RunLintTests(TestCase):
+    def setUp(self):
+        super(RunLintTests, self).setUp()
+        self.session = mock.Mock()
+        self.session.metrics = None
+
+    def test_no_buildsystems(self):
+        self.assertRaises(
+            NoBuildToolsFound, run_lint, self.session, [], None, [])
+
+    def test_no_linters(self):
+        bs = mock.Mock()
+        bs.lint.side_effect = NotImplementedError
+        self.assertRaises(
+            NotImplementedError, run_lint, self.session, [bs], None, [])
+        self.session.create_home.assert_called_once_with()
+
+    def test_some_linters(self):
+        unsupported = mock.Mock()
+        unsupported.lint.side_effect = NotImplementedError
+        supported = mock.Mock()
+        run_lint(self.session, [unsupported, supported], None, [])
+        supported.lint.assert_called_once_with(self.session, None, [])
@@ -0,0 +1,177 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import hashlib
+import io
+import os
+import shutil
+import tempfile
+from unittest import TestCase, mock
+
+from ..maven import (
+    ChecksumMismatch,
+    _download,
+    populate_repository,
+    read_gradle_lockfile,
+    read_maven_dependency_list,
+    read_pom_references,
+)
+from ..requirements import MavenArtifactRequirement
+
+
+MIRROR = "https://repo.example.com/maven2"
+
+
+def _coordinates(reqs):
+    return [(r.group_id, r.artifact_id, r.version, r.kind) for r in reqs]
+
+
+def _pom(body):
+    return (
+        '<?xml version="1.0"?>\n'
+        '<project xmlns="http://maven.apache.org/POM/4.0.0">%s</project>'
+        % body).encode()
+
+
+class ReadArtifactsTests(TestCase):
+    def test_gradle_lockfile(self):
+        self.assertEqual(
+            [("org.slf4j", "slf4j-api", "2.0.9", "jar")],
+            _coordinates(read_gradle_lockfile(io.StringIO(
+                "# comment\n"
+                "org.slf4j:slf4j-api:2.0.9=compileClasspath\n"
+                "empty=annotationProcessor\n"))))
+
+    def test_dependency_list(self):
+        self.assertEqual(
+            [("junit", "junit", "4.13.2", "jar")],
+            _coordinates(read_maven_dependency_list(io.StringIO(
+                "[INFO] The following files have been resolved:\n"
+                "[INFO]    junit:junit:jar:4.13.2:test -- module junit\n"
+                "[INFO]    org.foo:bar:jar:tests:1.0:test\n"))))
+
+
+class ReadPomReferencesTests(TestCase):
+    def test_parent_and_bom(self):
+        pom = _pom(
+            "<parent><groupId>org.foo</groupId><artifactId>foo-parent</artifactId>"
+            "<version>3</version></parent>"
+            "<properties><jackson.version>2.15.2</jackson.version></properties>"
+            "<dependencyManagement><dependencies>"
+            "<dependency><groupId>com.fasterxml.jackson</groupId>"
+            "<artifactId>jackson-bom</artifactId>"
+            "<version>${jackson.version}</version>"
+            "<type>pom</type><scope>import</scope></dependency>"
+            "<dependency><groupId>org.foo</groupId><artifactId>foo-bom</artifactId>"
+            "<version>${project.version}</version>"
+            "<type>pom</type><scope>import</scope></dependency>"
+            "<dependency><groupId>junit</groupId><artifactId>junit</artifactId>"
+            "<version>4.13.2</version></dependency>"
+            "</dependencies></dependencyManagement>")
+        self.assertEqual([
+            ("org.foo", "foo-parent", "3", "pom"),
+            ("com.fasterxml.jackson", "jackson-bom", "2.15.2", "pom"),
+            ("org.foo", "foo-bom", "3", "pom"),
+        ], _coordinates(read_pom_references(io.BytesIO(pom))))
+
+    def test_unresolved_property(self):
+        pom = _pom(
+            "<dependencyManagement><dependencies>"
+            "<dependency><groupId>org.foo</groupId><artifactId>foo-bom</artifactId>"
+            "<version>${foo.version}</version>"
+            "<type>pom</type><scope>import</scope></dependency>"
+            "</dependencies></dependencyManagement>")
+        self.assertEqual([], read_pom_references(io.BytesIO(pom)))
+
+
+class PopulateRepositoryTests(TestCase):
+    def setUp(self):
+        self.repository = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.repository)
+        self.files = {}
+        self.fetched = []
+        patcher = mock.patch("ognibuild.maven._fetch", self._fetch)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def _fetch(self, url):
+        self.fetched.append(url)
+        try:
+            return self.files[url]
+        except KeyError:
+            raise FileNotFoundError(url)
+
+    def add_file(self, relpath, data, checksum=True):
+        url = "%s/%s" % (MIRROR, relpath)
+        self.files[url] = data
+        if checksum:
+            self.files[url + ".sha1"] = hashlib.sha1(data).hexdigest().encode()
+
+    def test_checksum_mismatch(self):
+        url = MIRROR + "/foo.jar"
+        self.files[url] = b"data"
+        self.files[url + ".sha256"] = b"0000  foo.jar\n"
+        path = os.path.join(self.repository, "foo.jar")
+        self.assertRaises(ChecksumMismatch, _download, url, path)
+        self.assertFalse(os.path.exists(path))
+
+    def test_sha256(self):
+        url = MIRROR + "/foo.jar"
+        self.files[url] = b"data"
+        self.files[url + ".sha256"] = (
+            hashlib.sha256(b"data").hexdigest() + "  foo.jar\n").encode()
+        path = os.path.join(self.repository, "foo.jar")
+        _download(url, path)
+        with open(path, "rb") as f:
+            self.assertEqual(b"data", f.read())
+
+    def test_parent_pom(self):
+        self.add_file("org/foo/foo/1.0/foo-1.0.pom", _pom(
+            "<parent><groupId>org.foo</groupId><artifactId>parent</artifactId>"
+            "<version>2</version></parent>"))
+        self.add_file("org/foo/foo/1.0/foo-1.0.jar", b"jar")
+        self.add_file("org/foo/parent/2/parent-2.pom", _pom(""))
+        self.assertEqual([], populate_repository(
+            self.repository,
+            [MavenArtifactRequirement("org.foo", "foo", "1.0", "jar")],
+            mirror=MIRROR))
+        self.assertTrue(os.path.exists(os.path.join(
+            self.repository, "org/foo/parent/2/parent-2.pom")))
+
+    def test_cached(self):
+        self.add_file("org/foo/foo/1.0/foo-1.0.pom", _pom(""))
+        req = MavenArtifactRequirement("org.foo", "foo", "1.0", "pom")
+        populate_repository(self.repository, [req], mirror=MIRROR)
+        self.fetched = []
+        populate_repository(self.repository, [req], mirror=MIRROR)
+        self.assertEqual([], self.fetched)
+
+    def test_snapshot_expires(self):
+        relpath = "org/foo/foo/1.0-SNAPSHOT/foo-1.0-SNAPSHOT.pom"
+        self.add_file(relpath, _pom(""))
+        req = MavenArtifactRequirement("org.foo", "foo", "1.0-SNAPSHOT", "pom")
+        populate_repository(self.repository, [req], mirror=MIRROR)
+        path = os.path.join(self.repository, relpath)
+        os.utime(path, (0, 0))
+        self.fetched = []
+        populate_repository(self.repository, [req], mirror=MIRROR)
+        self.assertIn("%s/%s" % (MIRROR, relpath), self.fetched)
+
+    def test_missing(self):
+        req = MavenArtifactRequirement("org.foo", "foo", "1.0", "jar")
+        self.assertEqual(
+            [req], populate_repository(self.repository, [req], mirror=MIRROR))
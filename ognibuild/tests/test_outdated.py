@@ -0,0 +1,46 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+from unittest import TestCase
+
+from ..outdated import version_key
+
+
+class VersionKeyTests(TestCase):
+    def assertOrdered(self, versions):
+        self.assertEqual(versions, sorted(versions, key=version_key))
+        self.assertEqual(
+            versions, sorted(reversed(versions), key=version_key))
+
+    def test_numeric(self):
+        self.assertOrdered(["1.0", "1.0.1", "1.2", "1.9", "1.10", "2.0"])
+
+    def test_prerelease(self):
+        self.assertOrdered([
+            "1.0.dev1", "1.0a1", "1.0b2", "1.0rc1", "1.0rc2", "1.0", "1.0.1"])
+
+    def test_prerelease_separators(self):
+        self.assertOrdered(["2.0-alpha.1", "2.0-beta.2", "2.0-rc.1", "2.0"])
+        self.assertEqual(version_key("1.0rc1"), version_key("1.0~rc1"))
+
+    def test_letter_suffix(self):
+        # e.g. OpenSSL, where 1.1.1a comes after 1.1.1
+        self.assertOrdered(["1.1.1", "1.1.1a", "1.1.1b", "1.1.2"])
+
+    def test_v_prefix(self):
+        self.assertEqual(version_key("1.2.3"), version_key("v1.2.3"))
+        self.assertOrdered(["v1.9", "v1.10"])
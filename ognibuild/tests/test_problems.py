@@ -0,0 +1,156 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+from unittest import TestCase
+
+from ..buildlog import problem_to_upstream_requirement
+from ..problems import (
+    GhcVersionMismatch,
+    HiddenHaskellPackage,
+    MissingDartPackage,
+    MissingHaskellModule,
+    MissingHexPackages,
+    MissingOcamlFindlibPackage,
+    MissingQtModules,
+    find_extra_problem,
+)
+
+
+class GhcProblemTests(TestCase):
+    def test_missing_module(self):
+        self.assertEqual(
+            MissingHaskellModule("Data.Text"),
+            find_extra_problem([
+                "src/Foo.hs:5:1: error:",
+                "    Could not find module ‘Data.Text’",
+                "    Use -v (or `:set -v` in ghci) to see a list of the files "
+                "searched for.",
+            ]))
+
+    def test_hidden_package(self):
+        self.assertEqual(
+            HiddenHaskellPackage("containers", "Data.Map"),
+            find_extra_problem([
+                "src/Foo.hs:3:1: error:",
+                "    Could not load module ‘Data.Map’",
+                "    It is a member of the hidden package "
+                "‘containers-0.6.5.1’.",
+                "    Perhaps you need to add ‘containers’ to the build-depends "
+                "in your .cabal file.",
+            ]))
+
+    def test_no_compiler(self):
+        self.assertEqual(
+            GhcVersionMismatch("9.2.7"),
+            find_extra_problem([
+                "No compiler found, expected minor version match with "
+                "ghc-9.2.7 (x86_64) (based on resolver setting in "
+                "/build/stack.yaml).",
+            ]))
+
+    def test_compiler_mismatch(self):
+        self.assertEqual(
+            GhcVersionMismatch("9.2.7", "9.0.2"),
+            find_extra_problem([
+                "Compiler version mismatched, found ghc-9.0.2 (x86_64), but "
+                "expected minor version match with ghc-9.2.7 (x86_64) (based "
+                "on resolver setting in /build/stack.yaml).",
+            ]))
+
+    def test_unknown(self):
+        self.assertIsNone(find_extra_problem(["make: *** [all] Error 1"]))
+
+
+class GhcRequirementTests(TestCase):
+    def test_missing_module(self):
+        req = problem_to_upstream_requirement(MissingHaskellModule("Data.Map"))
+        self.assertEqual("Data.Map", req.module)
+
+    def test_hidden_package(self):
+        # Already installed, so there is nothing to install
+        self.assertIs(None, problem_to_upstream_requirement(
+            HiddenHaskellPackage("containers", "Data.Map")))
+
+    def test_version_mismatch(self):
+        self.assertIs(None, problem_to_upstream_requirement(
+            GhcVersionMismatch("9.2.7", "9.0.2")))
+
+
+class OcamlProblemTests(TestCase):
+    def test_ocamlfind(self):
+        self.assertEqual(
+            MissingOcamlFindlibPackage("yojson"),
+            find_extra_problem(["ocamlfind: Package `yojson' not found"]))
+
+    def test_dune(self):
+        self.assertEqual(
+            MissingOcamlFindlibPackage("lwt.unix"),
+            find_extra_problem([
+                "File \"bin/dune\", line 4, characters 12-20:",
+                "4 |  (libraries lwt.unix))",
+                "Error: Library \"lwt.unix\" not found.",
+            ]))
+
+
+class DartProblemTests(TestCase):
+    def test_compile(self):
+        self.assertEqual(
+            MissingDartPackage("http"),
+            find_extra_problem([
+                "bin/main.dart:1:8: Error: Couldn't resolve the package 'http' "
+                "in 'package:http/http.dart'.",
+            ]))
+
+    def test_analyze(self):
+        self.assertEqual(
+            MissingDartPackage("path"),
+            find_extra_problem([
+                "  error - lib/foo.dart:3:8 - Target of URI doesn't exist: "
+                "'package:path/path.dart'. Try creating the file referenced by "
+                "the URI, or try using a URI for a file that does exist. - "
+                "uri_does_not_exist",
+            ]))
+
+
+class MixProblemTests(TestCase):
+    def test_unchecked_dependencies(self):
+        self.assertEqual(
+            MissingHexPackages(["jason", "plug"]),
+            find_extra_problem([
+                "Unchecked dependencies for environment dev:",
+                "* jason (Hex package)",
+                "  the dependency is not available, run \"mix deps.get\"",
+                "* plug (Hex package)",
+                "  the dependency is not available, run \"mix deps.get\"",
+                "** (Mix) Can't continue due to errors on dependencies",
+            ]))
+
+    def test_git_dependency(self):
+        self.assertIs(None, find_extra_problem([
+            "Unchecked dependencies for environment dev:",
+            "* foo (https://github.com/example/foo.git)",
+            "  the dependency is not available, run \"mix deps.get\"",
+        ]))
+
+
+class QmakeProblemTests(TestCase):
+    def test_unknown_modules(self):
+        self.assertEqual(
+            MissingQtModules(["svg", "xmlpatterns"]),
+            find_extra_problem([
+                "Info: creating stash file /build/.qmake.stash",
+                "Project ERROR: Unknown module(s) in QT: svg xmlpatterns",
+            ]))
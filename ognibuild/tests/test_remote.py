@@ -0,0 +1,155 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import base64
+import io
+import json
+import os
+import tarfile
+import tempfile
+from unittest import TestCase
+
+from ..remote import (
+    ProtocolError,
+    RemoteWorker,
+    pack_project,
+    serve,
+    unpack_project,
+)
+
+
+class PackProjectTests(TestCase):
+    def test_roundtrip(self):
+        with tempfile.TemporaryDirectory() as td:
+            os.mkdir(os.path.join(td, "src"))
+            with open(os.path.join(td, "src", "main.c"), "w") as f:
+                f.write("int main() { return 0; }\n")
+            data = pack_project(td)
+            with tempfile.TemporaryDirectory() as target:
+                unpack_project(data, target)
+                with open(os.path.join(target, "src", "main.c")) as f:
+                    self.assertEqual("int main() { return 0; }\n", f.read())
+
+    def _tarball(self, name, **kwargs):
+        f = io.BytesIO()
+        with tarfile.open(fileobj=f, mode="w:gz") as tf:
+            info = tarfile.TarInfo(name)
+            for k, v in kwargs.items():
+                setattr(info, k, v)
+            tf.addfile(info, io.BytesIO(b""))
+        return base64.b64encode(f.getvalue()).decode("ascii")
+
+    def test_refuse_parent(self):
+        with tempfile.TemporaryDirectory() as td:
+            target = os.path.join(td, "project")
+            os.mkdir(target)
+            self.assertRaises(
+                ProtocolError, unpack_project,
+                self._tarball("../project-evil/x"), target)
+            self.assertFalse(os.path.exists(os.path.join(td, "project-evil")))
+
+    def test_refuse_symlink_out(self):
+        with tempfile.TemporaryDirectory() as td:
+            self.assertRaises(
+                ProtocolError, unpack_project,
+                self._tarball("link", type=tarfile.SYMTYPE, linkname="/etc"),
+                td)
+
+    def test_absolute(self):
+        with tempfile.TemporaryDirectory() as td:
+            target = os.path.join(td, "project")
+            os.mkdir(target)
+            evil = os.path.join(td, "project-evil")
+            try:
+                unpack_project(self._tarball(evil), target)
+            except ProtocolError:
+                pass
+            self.assertFalse(os.path.exists(evil))
+
+    def test_refuse_hardlink_out(self):
+        with tempfile.TemporaryDirectory() as td:
+            self.assertRaises(
+                ProtocolError, unpack_project,
+                self._tarball("link", type=tarfile.LNKTYPE,
+                              linkname="../etc/passwd"),
+                td)
+
+    def test_refuse_device(self):
+        with tempfile.TemporaryDirectory() as td:
+            self.assertRaises(
+                ProtocolError, unpack_project,
+                self._tarball("null", type=tarfile.CHRTYPE,
+                              devmajor=1, devminor=3),
+                td)
+
+    def test_symlink_inside(self):
+        with tempfile.TemporaryDirectory() as td:
+            unpack_project(
+                self._tarball("src/link", type=tarfile.SYMTYPE,
+                              linkname="../README"),
+                td)
+            self.assertEqual(
+                "../README", os.readlink(os.path.join(td, "src", "link")))
+
+
+class PackProjectFallbackTests(PackProjectTests):
+    """Run the same tests on Python versions without extraction filters."""
+
+    def setUp(self):
+        super(PackProjectFallbackTests, self).setUp()
+        data_filter = getattr(tarfile, "data_filter", None)
+        if data_filter is not None:
+            del tarfile.data_filter
+            self.addCleanup(setattr, tarfile, "data_filter", data_filter)
+
+
+class ServeTests(TestCase):
+    def test_invalid_request(self):
+        outf = io.StringIO()
+        serve(io.StringIO("not json\n"), outf)
+        events = [json.loads(line) for line in outf.getvalue().splitlines()]
+        self.assertEqual(["error", "done"], [e["event"] for e in events])
+
+
+class DummyProcess(object):
+    def __init__(self, lines):
+        self.stdout = iter([json.dumps(line) + "\n" for line in lines])
+
+
+class RemoteWorkerTests(TestCase):
+    def test_results(self):
+        worker = RemoteWorker(["true"])
+        worker._p = DummyProcess([
+            {"id": 1, "event": "log", "line": "building\n"},
+            {"id": 1, "event": "result", "action": "build",
+             "status": "success"},
+            {"id": 1, "event": "done"}])
+        lines = []
+        self.assertEqual(
+            [{"id": 1, "event": "result", "action": "build",
+              "status": "success"}],
+            worker._read_results(1, lines.append))
+        self.assertEqual(["building\n"], lines)
+
+    def test_error_drains_done(self):
+        worker = RemoteWorker(["true"])
+        worker._p = DummyProcess([
+            {"id": 1, "event": "error", "description": "no such schroot"},
+            {"id": 1, "event": "done"},
+            {"id": 2, "event": "done"}])
+        self.assertRaises(ProtocolError, worker._read_results, 1)
+        self.assertEqual([], worker._read_results(2))
@@ -0,0 +1,390 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+import json
+import os
+import shutil
+import subprocess
+import tempfile
+from unittest import TestCase, mock
+
+from .. import Requirement
+
+from ..requirements import (
+    BinaryRequirement,
+    BoostComponentRequirement,
+    CargoCrateRequirement,
+    CMakefileRequirement,
+    GoRequirement,
+    HaskellPackageRequirement,
+    HexPackageRequirement,
+    JDKRequirement,
+    MavenArtifactRequirement,
+    OctavePackageRequirement,
+    PerlModuleRequirement,
+    PhpExtensionRequirement,
+    PythonPackageRequirement,
+    QtModuleRequirement,
+    UnknownRequirementFamily,
+    VagueDependencyRequirement,
+    parse_ld_search_dirs,
+    requirement_from_json,
+    shared_library_cached,
+)
+from ..session.plain import PlainSession
+
+
+class ParseLdSearchDirsTests(TestCase):
+    def test_parse(self):
+        self.assertEqual(
+            ["/usr/local/lib/x86_64-linux-gnu", "/lib/x86_64-linux-gnu",
+             "/usr/lib"],
+            parse_ld_search_dirs(
+                'SEARCH_DIR("=/usr/local/lib/x86_64-linux-gnu"); '
+                'SEARCH_DIR("=/lib/x86_64-linux-gnu"); SEARCH_DIR("/usr/lib");'
+                ' SEARCH_DIR("=/usr/lib");'))
+
+    def test_empty(self):
+        self.assertEqual([], parse_ld_search_dirs("GNU ld (GNU Binutils)"))
+
+
+class RequirementJsonTests(TestCase):
+    def roundtrip(self, req):
+        return requirement_from_json(json.loads(json.dumps(req.json())))
+
+    def test_binary(self):
+        self.assertEqual(
+            {"family": "binary", "payload": {"binary_name": "make"}},
+            BinaryRequirement("make").json())
+        self.assertEqual(
+            repr(BinaryRequirement("make")),
+            repr(self.roundtrip(BinaryRequirement("make"))))
+
+    def test_python_package(self):
+        req = PythonPackageRequirement("foo", specs=[(">=", "1.0")])
+        self.assertEqual(repr(req), repr(self.roundtrip(req)))
+
+    def test_cargo_crate(self):
+        req = CargoCrateRequirement("serde", features={"derive", "std"})
+        self.assertEqual(repr(req), repr(self.roundtrip(req)))
+
+    def test_maven_artifact(self):
+        req = MavenArtifactRequirement("org.foo", "bar", "1.0", "pom")
+        self.assertEqual(repr(req), repr(self.roundtrip(req)))
+
+    def test_unknown(self):
+        self.assertRaises(
+            UnknownRequirementFamily, requirement_from_json,
+            {"family": "unknown", "payload": {}})
+
+
+class TryMetTests(TestCase):
+    class FailingSession(object):
+        def __init__(self, exc):
+            self.exc = exc
+
+        def check_output(self, argv, cwd=None, user=None, env=None):
+            raise self.exc
+
+    def test_not_implemented(self):
+        self.assertIs(None, Requirement("unknown").try_met(None))
+
+    def test_missing_tool(self):
+        session = self.FailingSession(FileNotFoundError("which"))
+        self.assertIs(None, BinaryRequirement("make").try_met(session))
+
+    def test_failed_command(self):
+        session = self.FailingSession(
+            subprocess.CalledProcessError(127, ["which", "make"]))
+        self.assertIs(None, BinaryRequirement("make").try_met(session))
+
+
+class VagueDependencyTests(TestCase):
+    def test_perl_module(self):
+        [req] = VagueDependencyRequirement("Foo::Bar").expand()
+        self.assertIsInstance(req, PerlModuleRequirement)
+        self.assertEqual("Foo::Bar", req.module)
+
+    def test_jdk(self):
+        [req] = VagueDependencyRequirement("Java JDK").expand()
+        self.assertIsInstance(req, JDKRequirement)
+
+    def test_go(self):
+        [req] = VagueDependencyRequirement(
+            "golang", minimum_version="1.18").expand()
+        self.assertIsInstance(req, GoRequirement)
+        self.assertEqual("1.18", req.version)
+
+
+class PhpExtensionTests(TestCase):
+    class PhpSession(object):
+        def check_output(self, argv, cwd=None, user=None, env=None):
+            assert argv == ["php", "-m"]
+            return b"[PHP Modules]\nCore\nSimpleXML\nzlib\n"
+
+    def test_met(self):
+        session = self.PhpSession()
+        self.assertTrue(PhpExtensionRequirement("simplexml").met(session))
+        self.assertFalse(PhpExtensionRequirement("intl").met(session))
+
+
+class HaskellPackageTests(TestCase):
+    def test_plain(self):
+        req = HaskellPackageRequirement.from_string("text")
+        self.assertEqual("text", req.package)
+        self.assertIsNone(req.specs)
+
+    def test_range(self):
+        req = HaskellPackageRequirement.from_string("text >= 1.2 && < 2")
+        self.assertEqual("text", req.package)
+        self.assertEqual([">=1.2", "<2"], req.specs)
+
+    def test_no_whitespace(self):
+        req = HaskellPackageRequirement.from_string("base>=4.7&&<5")
+        self.assertEqual("base", req.package)
+        self.assertEqual([">=4.7", "<5"], req.specs)
+
+    def test_major_bound(self):
+        req = HaskellPackageRequirement.from_string("base ^>=4.14.1")
+        self.assertEqual([">=4.14.1", "<4.15"], req.specs)
+
+    def test_union(self):
+        req = HaskellPackageRequirement.from_string(
+            "aeson (>= 1.5 && < 1.6) || ^>= 2.0")
+        self.assertEqual("aeson", req.package)
+        self.assertIsNone(req.specs)
+
+    def test_sublibrary(self):
+        req = HaskellPackageRequirement.from_string("foo:internal == 1.0")
+        self.assertEqual("foo", req.package)
+        self.assertEqual(["==1.0"], req.specs)
+
+
+class HexPackageTests(TestCase):
+    class NoErlangSession(object):
+        def exists(self, path):
+            return False
+
+        def Popen(self, argv, **kwargs):
+            raise FileNotFoundError(argv[0])
+
+    def test_without_erlang(self):
+        self.assertFalse(
+            HexPackageRequirement("jason").met(self.NoErlangSession()))
+
+
+class OctavePackageTests(TestCase):
+    class OctaveSession(object):
+        def __init__(self, returncode):
+            self.returncode = returncode
+            self.argv = []
+
+        def Popen(self, argv, **kwargs):
+            self.argv.append(argv)
+            return mock.Mock(returncode=self.returncode)
+
+    def test_met(self):
+        session = self.OctaveSession(0)
+        self.assertTrue(OctavePackageRequirement("signal").met(session))
+        self.assertEqual(
+            [["octave-cli", "--no-gui", "--eval",
+              "p = pkg('list', 'signal'); failed = isempty(p); "
+              "exit(failed)"]], session.argv)
+
+    def test_minimum_version(self):
+        session = self.OctaveSession(1)
+        self.assertFalse(
+            OctavePackageRequirement("signal", "1.4.0").met(session))
+        self.assertEqual(
+            [["octave-cli", "--no-gui", "--eval",
+              "p = pkg('list', 'signal'); failed = isempty(p); "
+              "failed = failed || ~compare_versions(p{1}.version, '1.4.0', "
+              "'>='); exit(failed)"]], session.argv)
+
+
+class BoostComponentTests(TestCase):
+    class LibrarySession(PlainSession):
+        def __init__(self, libdir, ldconfig):
+            super(BoostComponentTests.LibrarySession, self).__init__()
+            self.libdir = libdir
+            self.ldconfig = ldconfig
+
+        def check_output(self, argv, cwd=None, user=None, env=None):
+            if argv == ["ld", "--verbose"]:
+                return ('SEARCH_DIR("=%s");' % self.libdir).encode()
+            if argv[-1] == "-p":
+                return self.ldconfig.encode()
+            raise AssertionError(argv)
+
+    RUNTIME_LDCONFIG = (
+        "1 libs found in cache `/etc/ld.so.cache'\n"
+        "\tlibboost_regex.so.1.83.0 (libc6,x86-64) => "
+        "/usr/lib/x86_64-linux-gnu/libboost_regex.so.1.83.0\n")
+
+    def setUp(self):
+        super(BoostComponentTests, self).setUp()
+        self.libdir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.libdir)
+
+    def met(self, ldconfig=RUNTIME_LDCONFIG):
+        with self.LibrarySession(self.libdir, ldconfig) as session:
+            return BoostComponentRequirement("regex").met(session)
+
+    def test_versioned_cached(self):
+        session = self.LibrarySession(self.libdir, self.RUNTIME_LDCONFIG)
+        self.assertTrue(shared_library_cached(session, "libboost_regex.so"))
+        self.assertFalse(shared_library_cached(
+            session, "libboost_regex.so", versioned=False))
+
+    def test_runtime_only(self):
+        open(os.path.join(self.libdir, "libboost_regex.so.1.83.0"), "w").close()
+        self.assertFalse(self.met())
+
+    def test_unversioned(self):
+        open(os.path.join(self.libdir, "libboost_regex.so"), "w").close()
+        self.assertTrue(self.met())
+
+    def test_cmake_config(self):
+        path = os.path.join(self.libdir, "cmake", "boost_regex-1.83.0")
+        os.makedirs(path)
+        open(os.path.join(path, "boost_regex-config.cmake"), "w").close()
+        self.assertTrue(self.met())
+
+
+class QtModuleTests(TestCase):
+    def setUp(self):
+        super(QtModuleTests, self).setUp()
+        self.archdata = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.archdata)
+        os.makedirs(os.path.join(self.archdata, "mkspecs", "modules"))
+        for name in ["qt_lib_quickcontrols2.pri", "qt_lib_core_private.pri"]:
+            with open(os.path.join(
+                    self.archdata, "mkspecs", "modules", name), "w"):
+                pass
+
+    def met(self, module, archdata):
+        with PlainSession() as session, mock.patch(
+                "ognibuild.requirements.qt_query", return_value=archdata):
+            return QtModuleRequirement(module).met(session)
+
+    def test_met(self):
+        self.assertTrue(self.met("quickcontrols2", self.archdata))
+        self.assertTrue(self.met("core-private", self.archdata))
+
+    def test_missing(self):
+        self.assertFalse(self.met("xmlpatterns", self.archdata))
+
+    def test_no_qmake(self):
+        self.assertFalse(self.met("quickcontrols2", None))
+
+
+class CMakefileTests(TestCase):
+    def setUp(self):
+        super(CMakefileTests, self).setUp()
+        self.prefix = os.path.join(tempfile.mkdtemp(), "my prefix")
+        self.addCleanup(shutil.rmtree, os.path.dirname(self.prefix))
+        path = os.path.join(
+            self.prefix, "lib", "x86_64-linux-gnu", "cmake", "Foo")
+        os.makedirs(path)
+        open(os.path.join(path, "FooConfig.cmake"), "w").close()
+
+    def met(self, filename, prefix_path=None):
+        with PlainSession() as session:
+            if prefix_path is not None:
+                session.setenv("CMAKE_PREFIX_PATH", prefix_path)
+            return CMakefileRequirement(filename).met(session)
+
+    def test_search_dirs(self):
+        self.assertEqual([
+            "/usr/share/cmake*/Modules",
+            "/opt/lib/cmake/*",
+            "/opt/lib/*/cmake/*",
+            "/opt/share/cmake/*",
+            "/opt/share/*/cmake",
+            "/opt/cmake",
+        ], CMakefileRequirement("FooConfig.cmake").search_dirs(["/opt"]))
+
+    def test_prefix_path(self):
+        self.assertTrue(
+            self.met("FooConfig.cmake", "/nonexistent:" + self.prefix))
+
+    def test_missing(self):
+        self.assertFalse(self.met("BarConfig.cmake", self.prefix))
+        self.assertFalse(self.met("FooConfig.cmake"))
+
+
+class MavenArtifactTests(TestCase):
+    def setUp(self):
+        super(MavenArtifactTests, self).setUp()
+        self.repository = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.repository)
+        path = os.path.join(self.repository, "org", "foo", "foo", "1.0")
+        os.makedirs(path)
+        open(os.path.join(path, "foo-1.0.pom"), "w").close()
+
+    def met(self, req):
+        with PlainSession() as session:
+            session.setenv(
+                "MAVEN_OPTS", "-Xmx1g -Dmaven.repo.local=%s" % self.repository)
+            return req.met(session)
+
+    def test_repository_paths(self):
+        self.assertEqual(
+            ["/repo/org/foo/foo/1.0/foo-1.0.jar"],
+            MavenArtifactRequirement("org.foo", "foo", "1.0").repository_paths(
+                "/repo"))
+        self.assertEqual(
+            ["/repo/org/foo/foo/*/foo-*.pom"],
+            MavenArtifactRequirement(
+                "org.foo", "foo", None, "pom").repository_paths("/repo"))
+
+    def test_met(self):
+        self.assertTrue(self.met(
+            MavenArtifactRequirement("org.foo", "foo", "1.0", "pom")))
+        self.assertTrue(self.met(
+            MavenArtifactRequirement("org.foo", "foo", None, "pom")))
+
+    def test_missing(self):
+        self.assertFalse(self.met(
+            MavenArtifactRequirement("org.foo", "foo", "2.0", "pom")))
+        self.assertFalse(self.met(
+            MavenArtifactRequirement("org.foo", "foo", "1.0", "jar")))
+
+
+class BinaryRequirementTests(TestCase):
+    def setUp(self):
+        super(BinaryRequirementTests, self).setUp()
+        self.bindir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.bindir)
+        path = os.path.join(self.bindir, "ognibuild-test-tool")
+        with open(path, "w") as f:
+            f.write("#!/bin/sh\n")
+        os.chmod(path, 0o755)
+
+    def test_session_path(self):
+        req = BinaryRequirement("ognibuild-test-tool")
+        with PlainSession() as session:
+            self.assertFalse(req.met(session))
+            session.setenv(
+                "PATH", self.bindir + os.pathsep + os.environ["PATH"])
+            self.assertTrue(req.met(session))
+
+    def test_absolute(self):
+        with PlainSession() as session:
+            self.assertTrue(BinaryRequirement(
+                os.path.join(self.bindir, "ognibuild-test-tool")).met(session))
+            self.assertFalse(BinaryRequirement(
+                os.path.join(self.bindir, "missing")).met(session))
@@ -0,0 +1,680 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+import os
+import shutil
+import tempfile
+from unittest import TestCase, mock
+
+from .. import UnidentifiedError
+from ..requirements import (
+    BazelModuleRequirement,
+    BinaryRequirement,
+    CargoCrateRequirement,
+    CHeaderRequirement,
+    CMakefileRequirement,
+    CondaPackageRequirement,
+    ConanPackageRequirement,
+    GoPackageRequirement,
+    LibraryRequirement,
+    NimblePackageRequirement,
+    NuGetPackageRequirement,
+    OctavePackageRequirement,
+    PerlModuleRequirement,
+    PkgConfigRequirement,
+    RPackageRequirement,
+)
+from ..resolver import (
+    BazelResolver,
+    BioconductorResolver,
+    CargoResolver,
+    CondaResolver,
+    CRANResolver,
+    CPANResolver,
+    CpanmResolver,
+    GoResolver,
+    NimbleResolver,
+    NuGetResolver,
+    OctaveForgeResolver,
+    PipxResolver,
+    PackageManager,
+    StackedResolver,
+    UnsatisfiedRequirements,
+)
+from ..resolver.apk import ApkResolver, resolve_requirement_apk
+from ..resolver.conan import (
+    ConanResolver,
+    find_unresolved_packages,
+    generate_conanfile,
+    resolve_requirement_conan,
+)
+from ..resolver.dnf import DnfResolver, resolve_requirement_dnf
+from ..resolver.freebsd import PkgManager, parse_pkg_provides
+from ..resolver.snap import parse_snap_confinement
+from ..resolver.vcpkg import (
+    VcpkgResolver,
+    find_missing_ports,
+    resolve_requirement_vcpkg,
+    vcpkg_triplet,
+)
+from ..resolver.explanation import explain_resolution
+from ..session.plain import PlainSession
+
+
+class DummyPerlModuleRequirement(PerlModuleRequirement):
+    def met(self, session):
+        return False
+
+
+class ExplainResolutionTests(TestCase):
+    def setUp(self):
+        super(ExplainResolutionTests, self).setUp()
+        self.resolver = StackedResolver([
+            GoResolver(None, False), CPANResolver(None)])
+
+    def test_candidate(self):
+        explanation = explain_resolution(
+            None, self.resolver, DummyPerlModuleRequirement("Foo::Bar"))
+        self.assertTrue(explanation.resolvable)
+        self.assertEqual(
+            [("session", "met-check"), ("go", "unsupported"),
+             ("cpan", "candidate")],
+            [(step.source, step.kind) for step in explanation.steps])
+        self.assertEqual(
+            {"command": "cpan -i -T Foo::Bar"},
+            explanation.json()["steps"][-1]["details"])
+
+    def test_unresolvable(self):
+        explanation = explain_resolution(
+            None, StackedResolver([GoResolver(None, False)]),
+            DummyPerlModuleRequirement("Foo::Bar"))
+        self.assertFalse(explanation.resolvable)
+        self.assertEqual(
+            "DummyPerlModuleRequirement('Foo::Bar'):\n"
+            "  [session] requirement is not present\n"
+            "  [go] unable to install perl-module requirements\n"
+            "  No way to install this requirement was found.\n",
+            str(explanation))
+
+
+class CpanmResolverTests(TestCase):
+    def setUp(self):
+        super(CpanmResolverTests, self).setUp()
+        self.session = mock.Mock()
+        self.session.getenv.return_value = None
+        self.session.check_output.side_effect = (
+            lambda argv, cwd=None: b"/home/user\n" if "$HOME" in argv[-1]
+            else b"\n")
+
+    def install(self, resolver, requirements, available=True):
+        with mock.patch(
+                "ognibuild.requirements.BinaryRequirement.met",
+                return_value=available), mock.patch(
+                "ognibuild.resolver.run_detecting_problems") as run:
+            resolver.install(requirements)
+        return [(c.args[1], c.kwargs["user"]) for c in run.call_args_list]
+
+    def test_install(self):
+        self.assertEqual(
+            [(["cpanm", "--notest", "Foo::Bar"], "root")],
+            self.install(
+                CpanmResolver(self.session),
+                [DummyPerlModuleRequirement("Foo::Bar")]))
+
+    def test_install_user_local(self):
+        self.assertEqual(
+            [(["cpanm", "--notest", "--local-lib", "/home/user/perl5",
+               "Foo::Bar"], None)],
+            self.install(
+                CpanmResolver(self.session, user_local=True),
+                [DummyPerlModuleRequirement("Foo::Bar")]))
+        self.session.setenv.assert_called_once_with(
+            "PERL5LIB", "/home/user/perl5/lib/perl5")
+
+    def test_not_available(self):
+        with self.assertRaises(UnsatisfiedRequirements) as cm:
+            self.install(
+                CpanmResolver(self.session),
+                [DummyPerlModuleRequirement("Foo::Bar")], available=False)
+        self.assertEqual(
+            ["Foo::Bar"], [req.module for req in cm.exception.requirements])
+
+    def test_other_requirements(self):
+        req = BinaryRequirement("make")
+        with self.assertRaises(UnsatisfiedRequirements) as cm:
+            self.install(CpanmResolver(self.session, skip_tests=False), [req])
+        self.assertEqual([req], cm.exception.requirements)
+
+    def test_explain(self):
+        resolver = CpanmResolver(self.session, user_local=True)
+        req = DummyPerlModuleRequirement("Foo::Bar")
+        with mock.patch(
+                "ognibuild.requirements.BinaryRequirement.met",
+                return_value=True):
+            self.assertEqual(
+                [(["cpanm", "--notest", "--local-lib", "~/perl5", "Foo::Bar"],
+                  [req])],
+                list(resolver.explain([req, BinaryRequirement("make")])))
+
+
+class ResolveConanTests(TestCase):
+    def test_pkg_config(self):
+        self.assertEqual(
+            "glib/[*]",
+            resolve_requirement_conan(
+                PkgConfigRequirement("glib-2.0")).reference)
+        self.assertEqual(
+            "zlib/[>=1.2]",
+            resolve_requirement_conan(
+                PkgConfigRequirement("zlib", "1.2")).reference)
+
+    def test_cmake(self):
+        self.assertEqual(
+            "fmt", resolve_requirement_conan(
+                CMakefileRequirement("fmtConfig.cmake")).package)
+        self.assertEqual(
+            "zlib", resolve_requirement_conan(
+                CMakefileRequirement("FindZLIB.cmake")).package)
+
+    def test_library(self):
+        self.assertEqual(
+            "openssl", resolve_requirement_conan(
+                LibraryRequirement("ssl")).package)
+
+    def test_conanfile(self):
+        self.assertEqual("""\
+[requires]
+zlib/[*]
+fmt/10.0.0
+
+[generators]
+CMakeDeps
+CMakeToolchain
+PkgConfigDeps
+""", generate_conanfile([
+            ConanPackageRequirement("zlib"),
+            ConanPackageRequirement("fmt", "10.0.0")]))
+
+
+class ConanResolverTests(TestCase):
+    def setUp(self):
+        super(ConanResolverTests, self).setUp()
+        self.conan_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.conan_dir)
+        self.session = PlainSession()
+        self.session.__enter__()
+        self.addCleanup(self.session.__exit__, None, None, None)
+        self.resolver = ConanResolver(self.session, conan_dir=self.conan_dir)
+        self.resolver._profile_detected = True
+        self.runs = []
+
+    def fake_install(self, available):
+        def run_detecting_problems(session, argv, check_success=None):
+            with open(os.path.join(self.conan_dir, "conanfile.txt")) as f:
+                requires = f.read().split("\n\n")[0].splitlines()[1:]
+            self.runs.append(requires)
+            for reference in requires:
+                name = reference.split("/")[0]
+                if name not in available:
+                    lines = [
+                        "ERROR: Package '%s' not resolved: Unable to find "
+                        "'%s' in remotes\n" % (reference, reference)]
+                    check_success(1, lines)
+                    raise UnidentifiedError(1, argv, lines)
+            check_success(0, [])
+        return mock.patch(
+            "ognibuild.resolver.conan.run_detecting_problems",
+            run_detecting_problems)
+
+    def test_find_unresolved_packages(self):
+        self.assertEqual({"foo", "bar"}, find_unresolved_packages([
+            "ERROR: Package 'foo/[*]' not resolved: Unable to find 'foo/[*]' "
+            "in remotes",
+            "ERROR: Version range '>=1.0' from requirement 'bar/[>=1.0]' "
+            "required by 'conanfile' could not be resolved",
+        ]))
+
+    def test_not_found(self):
+        with self.fake_install({"zlib"}):
+            with self.assertRaises(UnsatisfiedRequirements) as cm:
+                self.resolver.install([
+                    PkgConfigRequirement("zlib"), PkgConfigRequirement("unknown")])
+        self.assertEqual(
+            ["unknown"], [req.module for req in cm.exception.requirements])
+        self.assertEqual([["zlib/[*]", "unknown/[*]"], ["zlib/[*]"]], self.runs)
+        self.assertEqual(
+            ["zlib"], [p.package for p in self.resolver._packages])
+
+    def test_user_toolchain(self):
+        self.session.setenv(
+            "CMAKE_TOOLCHAIN_FILE", "/opt/vcpkg/scripts/buildsystems/vcpkg.cmake")
+        self.assertIn(
+            "--conf=tools.cmake.cmaketoolchain:user_toolchain="
+            "['/opt/vcpkg/scripts/buildsystems/vcpkg.cmake']",
+            self.resolver._install_cmd())
+
+    def test_failure_not_recorded(self):
+        def run_detecting_problems(session, argv, check_success=None):
+            raise UnidentifiedError(1, argv, ["compiler exploded"])
+
+        with mock.patch(
+                "ognibuild.resolver.conan.run_detecting_problems",
+                run_detecting_problems):
+            self.assertRaises(
+                UnidentifiedError, self.resolver.install,
+                [PkgConfigRequirement("zlib")])
+        self.assertEqual([], self.resolver._packages)
+
+
+class ResolveVcpkgTests(TestCase):
+    def test_pkg_config(self):
+        self.assertEqual(
+            "curl", resolve_requirement_vcpkg(
+                PkgConfigRequirement("libcurl")).port)
+        self.assertEqual(
+            "zlib", resolve_requirement_vcpkg(
+                PkgConfigRequirement("zlib")).port)
+
+    def test_header(self):
+        self.assertEqual(
+            "openssl", resolve_requirement_vcpkg(
+                CHeaderRequirement("openssl/ssl.h")).port)
+        self.assertEqual(
+            "boost-filesystem", resolve_requirement_vcpkg(
+                CHeaderRequirement("boost/filesystem.hpp")).port)
+        self.assertIs(
+            None, resolve_requirement_vcpkg(CHeaderRequirement("unknown.h")))
+
+
+class VcpkgResolverTests(TestCase):
+    def setUp(self):
+        super(VcpkgResolverTests, self).setUp()
+        self.session = PlainSession()
+        self.session.__enter__()
+        self.addCleanup(self.session.__exit__, None, None, None)
+        self.session.setenv("VCPKG_ROOT", "/opt/vcpkg")
+        self.resolver = VcpkgResolver(self.session, triplet="x64-linux")
+        self.runs = []
+
+    def fake_install(self, available):
+        def run_detecting_problems(session, argv, check_success=None):
+            ports = argv[3:]
+            self.runs.append(ports)
+            lines = [
+                "error: %s does not exist\n" % port for port in ports
+                if port not in available]
+            check_success(1 if lines else 0, lines)
+            if lines:
+                raise UnidentifiedError(1, argv, lines)
+        return mock.patch(
+            "ognibuild.resolver.vcpkg.run_detecting_problems",
+            run_detecting_problems)
+
+    def test_triplet(self):
+        self.assertEqual("x64-linux", vcpkg_triplet("Linux", "x86_64"))
+        self.assertEqual("arm64-osx", vcpkg_triplet("Darwin", "arm64"))
+        self.assertIs(None, vcpkg_triplet("Plan9", "x86_64"))
+
+    def test_find_missing_ports(self):
+        self.assertEqual({"foo", "bar"}, find_missing_ports([
+            "error: foo does not exist",
+            "Error: while loading bar:",
+            "The port directory (/opt/vcpkg/ports/bar) does not exist",
+        ]))
+
+    def test_port_does_not_exist(self):
+        with self.fake_install({"zlib"}):
+            with self.assertRaises(UnsatisfiedRequirements) as cm:
+                self.resolver.install([
+                    PkgConfigRequirement("zlib"), PkgConfigRequirement("unknown")])
+        self.assertEqual(
+            ["unknown"], [req.module for req in cm.exception.requirements])
+        self.assertEqual([["zlib", "unknown"], ["zlib"]], self.runs)
+        self.assertEqual(
+            "/opt/vcpkg/scripts/buildsystems/vcpkg.cmake",
+            self.session.getenv("CMAKE_TOOLCHAIN_FILE"))
+
+    def test_chainload(self):
+        self.session.setenv("CMAKE_TOOLCHAIN_FILE", "/tmp/conan_toolchain.cmake")
+        with self.fake_install({"zlib"}):
+            self.resolver.install([PkgConfigRequirement("zlib")])
+        self.assertEqual(
+            "/opt/vcpkg/scripts/buildsystems/vcpkg.cmake",
+            self.session.getenv("CMAKE_TOOLCHAIN_FILE"))
+        self.assertEqual(
+            "/tmp/conan_toolchain.cmake",
+            self.session.getenv("VCPKG_CHAINLOAD_TOOLCHAIN_FILE"))
+
+
+class DummyPackageManager(PackageManager):
+
+    name = "dummy"
+
+    def __init__(self, provides):
+        super(DummyPackageManager, self).__init__(None)
+        self._packages = provides
+        self.installed = []
+
+    def _provides(self, query):
+        return self._packages.get(query, [])
+
+    def install_command(self, packages):
+        return ["dummy", "install"] + packages
+
+    def install(self, packages):
+        self.installed.extend(packages)
+
+
+class ResolveApkTests(TestCase):
+    def test_binary(self):
+        apk_mgr = DummyPackageManager({"cmd:make": ["make"]})
+        self.assertEqual(
+            "make", resolve_requirement_apk(
+                apk_mgr, BinaryRequirement("make")).package)
+
+    def test_pkg_config(self):
+        apk_mgr = DummyPackageManager({"pc:zlib": ["zlib-dev"]})
+        self.assertEqual(
+            "zlib-dev", resolve_requirement_apk(
+                apk_mgr, PkgConfigRequirement("zlib")).package)
+
+    def test_library(self):
+        apk_mgr = DummyPackageManager({"so:libz.so*": ["zlib"]})
+        self.assertEqual(
+            "zlib", resolve_requirement_apk(
+                apk_mgr, LibraryRequirement("z")).package)
+
+    def test_missing(self):
+        self.assertIs(None, resolve_requirement_apk(
+            DummyPackageManager({}), BinaryRequirement("nonexistent")))
+
+
+class ResolveDnfTests(TestCase):
+    def test_c_header(self):
+        dnf_mgr = DummyPackageManager({"/usr/include/zlib.h": ["zlib-devel"]})
+        self.assertEqual(
+            "zlib-devel", resolve_requirement_dnf(
+                dnf_mgr, CHeaderRequirement("zlib.h")).package)
+
+    def test_pkg_config(self):
+        dnf_mgr = DummyPackageManager({"pkgconfig(glib-2.0)": ["glib2-devel"]})
+        self.assertEqual(
+            "glib2-devel", resolve_requirement_dnf(
+                dnf_mgr, PkgConfigRequirement("glib-2.0")).package)
+
+    def test_library(self):
+        dnf_mgr = DummyPackageManager({"/usr/lib/libfoo.so": ["foo-devel"]})
+        self.assertEqual(
+            "foo-devel", resolve_requirement_dnf(
+                dnf_mgr, LibraryRequirement("foo")).package)
+
+    def test_missing(self):
+        self.assertIs(None, resolve_requirement_dnf(
+            DummyPackageManager({}), CHeaderRequirement("nonexistent.h")))
+
+
+class PackageManagerResolverTests(TestCase):
+    def test_str(self):
+        self.assertEqual("dummy", str(DnfResolver(DummyPackageManager({}))))
+
+    def test_install(self):
+        resolver = DnfResolver(DummyPackageManager({
+            "pkgconfig(zlib)": ["zlib-devel"],
+            "pkgconfig(glib-2.0)": ["glib2-devel"]}))
+        resolver.install([
+            PkgConfigRequirement("zlib"), PkgConfigRequirement("glib-2.0")])
+        self.assertEqual(
+            ["zlib-devel", "glib2-devel"], resolver.manager.installed)
+
+    def test_install_missing(self):
+        resolver = DnfResolver(DummyPackageManager(
+            {"pkgconfig(zlib)": ["zlib-devel"]}))
+        missing = PkgConfigRequirement("nonexistent")
+        with self.assertRaises(UnsatisfiedRequirements) as cm:
+            resolver.install([PkgConfigRequirement("zlib"), missing])
+        self.assertEqual([missing], cm.exception.requirements)
+        self.assertEqual(["zlib-devel"], resolver.manager.installed)
+
+    def test_explain(self):
+        resolver = ApkResolver(DummyPackageManager({"pc:zlib": ["zlib-dev"]}))
+        reqs = [PkgConfigRequirement("zlib"), PkgConfigRequirement("foo")]
+        self.assertEqual(
+            [(["dummy", "install", "zlib-dev"], [reqs[0]])],
+            list(resolver.explain(reqs)))
+
+
+class ParsePkgProvidesTests(TestCase):
+    def test_parse(self):
+        self.assertEqual(["gmake", "py39-foo"], parse_pkg_provides("""\
+Name    : gmake-4.3_2
+Comment : GNU version of 'make' utility
+Repo    : FreeBSD
+Filename: usr/local/bin/gmake
+
+Name    : py39-foo-1.0
+Comment : Foo
+Repo    : FreeBSD
+Filename: usr/local/bin/gmake
+""".splitlines()))
+
+    def test_empty(self):
+        self.assertEqual([], parse_pkg_provides([]))
+
+
+class PkgProvidesSession(object):
+    def __init__(self):
+        self.commands = []
+
+    def check_call(self, argv, cwd=None, user=None):
+        self.commands.append(argv)
+
+    def check_output(self, argv, cwd=None, user=None):
+        self.commands.append(argv)
+        return b"Name    : gmake-4.3_2\nFilename: usr/local/bin/gmake\n"
+
+
+class PkgManagerTests(TestCase):
+    def test_provides(self):
+        session = PkgProvidesSession()
+        pkg = PkgManager(session, prefix=["sudo"])
+        self.assertEqual(["gmake"], pkg.provides("/usr/local/bin/gmake"))
+        self.assertEqual(["gmake"], pkg.provides("/usr/local/bin/gmake"))
+        self.assertEqual(["gmake"], pkg.provides("/usr/local/bin/make"))
+        self.assertEqual([
+            ["sudo", "pkg", "provides", "-u"],
+            ["pkg", "provides", "^usr/local/bin/gmake$"],
+            ["pkg", "provides", "^usr/local/bin/make$"]], session.commands)
+
+
+class ParseSnapConfinementTests(TestCase):
+    def test_strict(self):
+        self.assertEqual("strict", parse_snap_confinement("""\
+name: yq
+channels:
+  latest/stable:    v4.30.8 2023-01-12 (2016) 4MB -
+  latest/candidate: \u2191
+""".splitlines()))
+
+    def test_classic(self):
+        self.assertEqual("classic", parse_snap_confinement("""\
+name: go
+channels:
+  latest/stable:    1.21.5 2023-12-06 (10506) 103MB classic
+""".splitlines()))
+
+    def test_closed(self):
+        self.assertIs(None, parse_snap_confinement("""\
+channels:
+  latest/stable:    \u2013
+""".splitlines()))
+
+
+class GoResolverTests(TestCase):
+    def test_tool(self):
+        resolver = GoResolver(None, False)
+        self.assertEqual(
+            ["go", "install", "golang.org/x/tools/cmd/goimports@latest"],
+            resolver._cmd(BinaryRequirement("goimports")))
+
+    def test_package(self):
+        resolver = GoResolver(None, False)
+        self.assertEqual(
+            ["go", "mod", "download", "github.com/foo/bar@v1.2.0"],
+            resolver._cmd(GoPackageRequirement("github.com/foo/bar", "1.2.0")))
+        self.assertEqual(
+            ["go", "get", "github.com/foo/bar"],
+            resolver._cmd(GoPackageRequirement("github.com/foo/bar")))
+
+    def test_package_gopath(self):
+        resolver = GoResolver(None, False)
+        with mock.patch("ognibuild.resolver.run_detecting_problems") as run:
+            resolver.install([GoPackageRequirement("github.com/foo/bar")])
+        run.assert_called_once_with(
+            None, ["go", "get", "github.com/foo/bar"],
+            env={"GOPATH": "/usr/share/gocode"}, user=None)
+
+
+class CargoResolverTests(TestCase):
+    def test_binary(self):
+        resolver = CargoResolver(None, True)
+        self.assertEqual(
+            [["cargo", "install", "cargo-edit"]],
+            [cmd for (cmd, reqs) in resolver.explain(
+                [BinaryRequirement("cargo-upgrade")])])
+
+    def test_crate(self):
+        resolver = CargoResolver(None, True)
+        self.assertEqual(
+            [["cargo", "install", "--version", "0.4.0", "mdbook"]],
+            [cmd for (cmd, reqs) in resolver.explain(
+                [CargoCrateRequirement("mdbook", version="0.4.0"),
+                 CargoCrateRequirement("serde")])])
+
+
+class PipxResolverTests(TestCase):
+    def test_explain(self):
+        resolver = PipxResolver(None, True)
+        self.assertEqual(
+            [["pipx", "install", "httpie"]],
+            [cmd for (cmd, reqs) in resolver.explain(
+                [BinaryRequirement("http"), BinaryRequirement("gcc")])])
+
+    def test_explain_system(self):
+        resolver = PipxResolver(None, False)
+        self.assertEqual(
+            [["env", "PIPX_BIN_DIR=/usr/local/bin", "PIPX_HOME=/opt/pipx",
+              "pipx", "install", "httpie"]],
+            [cmd for (cmd, reqs) in resolver.explain(
+                [BinaryRequirement("http")])])
+
+    def test_install_system(self):
+        resolver = PipxResolver(None, False)
+        with mock.patch.object(resolver, "_available", return_value=True), \
+                mock.patch("ognibuild.resolver.run_detecting_problems") as run:
+            resolver.install([BinaryRequirement("black")])
+        run.assert_called_once_with(
+            None, ["pipx", "install", "black"],
+            env={"PIPX_HOME": "/opt/pipx", "PIPX_BIN_DIR": "/usr/local/bin"},
+            user="root")
+
+    def test_not_a_pipx_tool(self):
+        resolver = PipxResolver(None, True)
+        self.assertEqual(
+            [], list(resolver.explain([BinaryRequirement("mypy")])))
+
+
+class RResolverTests(TestCase):
+    def test_repository(self):
+        reqs = [
+            RPackageRequirement("limma", repository="bioconductor"),
+            RPackageRequirement("dplyr", repository="cran"),
+            RPackageRequirement("ggplot2")]
+        self.assertEqual(
+            [["dplyr"], ["ggplot2"]],
+            [[r.package for r in rs]
+             for (cmd, rs) in CRANResolver(None).explain(reqs)])
+        self.assertEqual(
+            [["limma"], ["ggplot2"]],
+            [[r.package for r in rs]
+             for (cmd, rs) in BioconductorResolver(None).explain(reqs)])
+
+
+class OctaveForgeResolverTests(TestCase):
+    def install(self, resolver, requirements, octave=True):
+        with mock.patch(
+                "ognibuild.requirements.BinaryRequirement.met",
+                return_value=octave), mock.patch(
+                "ognibuild.requirements.OctavePackageRequirement.met",
+                return_value=False), mock.patch(
+                "ognibuild.resolver.run_detecting_problems") as run:
+            resolver.install(requirements)
+        return [(c.args[1], c.kwargs["user"]) for c in run.call_args_list]
+
+    def test_cmd_global(self):
+        self.assertEqual(
+            ["octave-cli", "--no-gui", "--eval",
+             "pkg install -forge -global signal"],
+            OctaveForgeResolver(None)._cmd(OctavePackageRequirement("signal")))
+
+    def test_cmd_local(self):
+        self.assertEqual(
+            ["octave-cli", "--no-gui", "--eval",
+             "pkg install -forge -local signal"],
+            OctaveForgeResolver(None, user_local=True)._cmd(
+                OctavePackageRequirement("signal")))
+
+    def test_install(self):
+        self.assertEqual(
+            [(["octave-cli", "--no-gui", "--eval",
+               "pkg install -forge -global signal"], "root")],
+            self.install(
+                OctaveForgeResolver(None),
+                [OctavePackageRequirement("signal")]))
+
+    def test_install_without_octave(self):
+        req = OctavePackageRequirement("signal")
+        other = BinaryRequirement("make")
+        with self.assertRaises(UnsatisfiedRequirements) as cm:
+            self.install(OctaveForgeResolver(None), [req, other], octave=False)
+        self.assertEqual([other, req], cm.exception.requirements)
+
+
+class ProjectResolverTests(TestCase):
+    def test_nuget(self):
+        reqs = [NuGetPackageRequirement("Newtonsoft.Json", "13.0.1"),
+                BinaryRequirement("gcc")]
+        self.assertEqual(
+            [(["dotnet", "restore"], reqs[:1])],
+            list(NuGetResolver(None, True).explain(reqs)))
+
+    def test_nimble(self):
+        self.assertEqual(
+            [["nimble", "install", "-y", "jester@>= 0.5"]],
+            [cmd for (cmd, reqs) in NimbleResolver(None, True).explain(
+                [NimblePackageRequirement("jester", ">= 0.5")])])
+
+    def test_conda(self):
+        self.assertEqual(
+            [["conda", "install", "-y", "numpy >=1.20", "scipy"]],
+            [cmd for (cmd, reqs) in CondaResolver(None, True).explain(
+                [CondaPackageRequirement("numpy", ">=1.20"),
+                 CondaPackageRequirement("scipy")])])
+
+    def test_bazel(self):
+        self.assertEqual(
+            [["bazel", "fetch", "//..."]],
+            [cmd for (cmd, reqs) in BazelResolver(None, True).explain(
+                [BazelModuleRequirement("rules_cc", "0.0.9")])])
@@ -0,0 +1,68 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+import shutil
+import tempfile
+from unittest import TestCase
+
+from ..scaffold import create_scaffolding
+
+
+class CreateScaffoldingTests(TestCase):
+    def setUp(self):
+        super(CreateScaffoldingTests, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+        # Trees exported into a session live in a directory called "package"
+        self.path = os.path.join(self.test_dir, "package")
+        os.mkdir(self.path)
+
+    def write(self, name, contents=""):
+        path = os.path.join(self.path, name)
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w") as f:
+            f.write(contents)
+
+    def read(self, name):
+        with open(os.path.join(self.path, name)) as f:
+            return f.read()
+
+    def test_makefile(self):
+        self.write("hello.c", "int main() { return 0; }\n")
+        self.assertEqual(["Makefile"], create_scaffolding(self.path, "hello"))
+        self.assertIn("NAME = hello\n", self.read("Makefile"))
+
+    def test_makefile_directory_name(self):
+        self.write("hello.c", "int main() { return 0; }\n")
+        create_scaffolding(self.path)
+        self.assertIn("NAME = package\n", self.read("Makefile"))
+
+    def test_pyproject_package(self):
+        self.write("frobnicate/__init__.py")
+        self.assertEqual(
+            ["pyproject.toml"], create_scaffolding(self.path, "frob-1.0"))
+        self.assertIn('name = "frobnicate"\n', self.read("pyproject.toml"))
+
+    def test_pyproject_modules(self):
+        self.write("frob.py")
+        create_scaffolding(self.path, "frob tools")
+        self.assertIn('name = "frob-tools"\n', self.read("pyproject.toml"))
+
+    def test_nothing(self):
+        self.write("README")
+        self.assertEqual([], create_scaffolding(self.path, "foo"))
@@ -0,0 +1,898 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+import shutil
+import subprocess
+import tempfile
+import time
+from unittest import TestCase, mock
+
+from ..session import (
+    run_with_callback, run_with_tee, output_lines, prepend_path, SessionLost,
+    TimedOut)
+from ..session.plain import PlainSession
+
+
+class RunWithTeeTests(TestCase):
+    def test_timeout(self):
+        with PlainSession() as session:
+            self.assertRaises(
+                TimedOut, run_with_tee, session, ["sleep", "10"], timeout=0.5
+            )
+
+    def test_timeout_kills_process_group(self):
+        with tempfile.TemporaryDirectory() as td:
+            marker = os.path.join(td, "marker")
+            with PlainSession() as session:
+                self.assertRaises(
+                    TimedOut, run_with_tee, session,
+                    ["sh", "-c", "(sleep 1; touch %s) & sleep 10" % marker],
+                    timeout=0.3)
+            time.sleep(1.5)
+            self.assertFalse(os.path.exists(marker))
+
+    def test_timeout_not_reached(self):
+        with PlainSession() as session:
+            retcode, lines = run_with_tee(session, ["true"], timeout=10)
+        self.assertEqual(0, retcode)
+
+    def test_separate_streams(self):
+        with PlainSession() as session:
+            retcode, lines = run_with_tee(
+                session, ["sh", "-c", "echo out; echo err >&2; exit 1"],
+                separate_streams=True)
+        self.assertEqual(1, retcode)
+        self.assertEqual(
+            [("stderr", "err\n"), ("stdout", "out\n")],
+            sorted([(line.stream, line.line) for line in lines]))
+        self.assertEqual(
+            sorted([line.timestamp for line in lines]),
+            [line.timestamp for line in lines])
+        self.assertEqual(["err"], output_lines(lines, "stderr"))
+
+
+class RunWithCallbackTests(TestCase):
+    def test_streams(self):
+        lines = []
+        with PlainSession() as session:
+            retcode = run_with_callback(
+                session, ["sh", "-c", "echo out; echo err >&2"], lines.append
+            )
+        self.assertEqual(0, retcode)
+        self.assertEqual(
+            [("stderr", "err\n"), ("stdout", "out\n")],
+            sorted([(line.stream, line.line) for line in lines]),
+        )
+
+    def test_buffered_lines_delivered_promptly(self):
+        seen = []
+        start = time.monotonic()
+        with PlainSession() as session:
+            run_with_callback(
+                session, ["sh", "-c", "printf 'a\\nb\\n'; sleep 2"],
+                lambda line: seen.append((line.line, time.monotonic() - start)))
+        self.assertEqual(["a\n", "b\n"], [line for (line, t) in seen])
+        self.assertLess(seen[-1][1], 1.5)
+
+    def test_exit_code(self):
+        with PlainSession() as session:
+            retcode = run_with_callback(session, ["sh", "-c", "exit 3"], print)
+        self.assertEqual(3, retcode)
+
+
+class RecordReplayTests(TestCase):
+    def test_roundtrip(self):
+        import subprocess
+
+        from ..session.record import RecordingSession, ReplaySession, ReplayMismatch
+
+        with RecordingSession(PlainSession()) as session:
+            out = session.check_output(["echo", "hello"])
+            retcode, lines = run_with_tee(
+                session, ["sh", "-c", "echo one; echo two; exit 2"])
+            self.assertRaises(
+                subprocess.CalledProcessError, session.check_call, ["false"])
+        self.assertEqual(b"hello\n", out)
+        self.assertEqual(2, retcode)
+        self.assertEqual("one\ntwo\n", "".join(lines))
+
+        with ReplaySession(session.entries) as replay:
+            self.assertEqual(b"hello\n", replay.check_output(["echo", "hello"]))
+            retcode, lines = run_with_tee(
+                replay, ["sh", "-c", "echo one; echo two; exit 2"])
+            self.assertEqual(2, retcode)
+            self.assertEqual("one\ntwo\n", "".join(lines))
+            self.assertRaises(ReplayMismatch, replay.check_call, ["true"])
+
+    def test_stdin(self):
+        import subprocess
+
+        from ..session.record import RecordingSession
+
+        with RecordingSession(PlainSession()) as session:
+            p = session.Popen(
+                ["cat"], stdin=subprocess.PIPE, stdout=subprocess.PIPE)
+            p.stdin.write(b"piped\n")
+            p.stdin.close()
+            self.assertEqual(b"piped\n", p.stdout.read())
+            p.wait()
+            with tempfile.TemporaryFile() as f:
+                f.write(b"from file\n")
+                f.seek(0)
+                p = session.Popen(["cat"], stdin=f, stdout=subprocess.PIPE)
+                self.assertEqual((b"from file\n", None), p.communicate())
+        self.assertEqual(
+            ["piped\n", "from file\n"],
+            [entry["stdin"] for entry in session.entries])
+
+
+class RunParallelTests(TestCase):
+    def test_run(self):
+        from ..session import CommandSpec, run_parallel
+
+        with PlainSession() as session:
+            results = run_parallel(
+                session,
+                [
+                    CommandSpec(["sh", "-c", "sleep 0.2; echo first"]),
+                    CommandSpec(["sh", "-c", "echo $X >&2; exit 2"], env={"X": "x"}),
+                    CommandSpec(["cat"], stdin=b"input"),
+                    CommandSpec(["nonexistent-ognibuild-command"]),
+                ],
+                max_workers=2,
+            )
+        self.assertEqual(4, len(results))
+        self.assertEqual((0, b"first\n"), (results[0].returncode, results[0].stdout))
+        self.assertEqual((2, b"x\n"), (results[1].returncode, results[1].stderr))
+        self.assertEqual(b"input", results[2].stdout)
+        self.assertIsInstance(results[3], FileNotFoundError)
+
+    def test_thread_safe(self):
+        from ..session.record import RecordingSession
+
+        self.assertTrue(PlainSession.thread_safe)
+        self.assertFalse(RecordingSession(mock.Mock(thread_safe=False)).thread_safe)
+        self.assertTrue(RecordingSession(PlainSession()).thread_safe)
+
+
+class SetenvTests(TestCase):
+    def test_setenv(self):
+        with PlainSession() as session:
+            session.setenv("OGNIBUILD_TEST_A", "a")
+            session.setenv("OGNIBUILD_TEST_B", "b")
+            self.assertEqual(
+                b"a c\n",
+                session.check_output(
+                    ["sh", "-c", "echo $OGNIBUILD_TEST_A $OGNIBUILD_TEST_B"],
+                    env={"OGNIBUILD_TEST_B": "c"}))
+            session.setenv("OGNIBUILD_TEST_A", None)
+            self.assertEqual(
+                b"b\n",
+                session.check_output(
+                    ["sh", "-c", "echo $OGNIBUILD_TEST_A$OGNIBUILD_TEST_B"]))
+
+
+class PrependPathTests(TestCase):
+    def test_path(self):
+        with PlainSession() as session:
+            session.setenv("PATH", "/usr/bin:/bin")
+            prepend_path(session, "/opt/foo/bin")
+            self.assertEqual("/opt/foo/bin:/usr/bin:/bin", session.getenv("PATH"))
+            prepend_path(session, "/usr/bin")
+            self.assertEqual("/opt/foo/bin:/usr/bin:/bin", session.getenv("PATH"))
+
+    def test_inherited(self):
+        with PlainSession() as session, mock.patch.dict(
+                os.environ, {"OGNIBUILD_TEST_PATH": "/a:/b"}):
+            prepend_path(session, "/c", "OGNIBUILD_TEST_PATH")
+            self.assertEqual("/c:/a:/b", session.getenv("OGNIBUILD_TEST_PATH"))
+
+    def test_unset(self):
+        with PlainSession() as session, mock.patch.dict(os.environ):
+            os.environ.pop("OGNIBUILD_TEST_PATH", None)
+            prepend_path(session, "/c", "OGNIBUILD_TEST_PATH")
+            self.assertEqual("/c", session.getenv("OGNIBUILD_TEST_PATH"))
+
+
+class PingTests(TestCase):
+    def test_plain(self):
+        session = PlainSession()
+        self.assertFalse(session.ping())
+        with session:
+            self.assertTrue(session.ping())
+        self.assertFalse(session.ping())
+
+    def test_unshare_root_removed(self):
+        import shutil
+        import tempfile
+        from ..session import SessionLost
+        from ..session.unshare import UnshareSession
+
+        root = tempfile.mkdtemp()
+        session = UnshareSession(root)
+        with session:
+            self.assertTrue(session.ping())
+            shutil.rmtree(root)
+            self.assertFalse(session.ping())
+            self.assertRaises(SessionLost, session.check_call, ["true"])
+
+
+class UnshareNetworkTests(TestCase):
+    def run_argv(self, no_network):
+        import tempfile
+        from ..session.unshare import UnshareSession
+
+        with tempfile.TemporaryDirectory() as root:
+            session = UnshareSession(root, no_network=no_network)
+            with session:
+                return session._run_argv(["make", "check"])
+
+    def test_network(self):
+        argv = self.run_argv(False)
+        self.assertNotIn("--net", argv)
+        self.assertEqual(["make", "check"], argv[-2:])
+
+    def test_no_network(self):
+        argv = self.run_argv(True)
+        self.assertIn("--net", argv[:argv.index("--")])
+        self.assertEqual(
+            ["sh", "-c", "ip link set lo up 2>/dev/null; exec make check"],
+            argv[argv.index("--") + 1:])
+
+
+class BootstrapTests(TestCase):
+    def setUp(self):
+        super(BootstrapTests, self).setUp()
+        self.cache_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.cache_dir)
+        self.argvs = []
+
+    def check_call(self, argv):
+        self.argvs.append(argv)
+        with open(argv[argv.index("unstable") + 1], "w") as f:
+            f.write("tarball")
+
+    def bootstrap(self, **kwargs):
+        from ..session.unshare import bootstrap_tarball
+
+        with mock.patch("subprocess.check_call", self.check_call):
+            return bootstrap_tarball(
+                "unstable", arch="amd64", cache_dir=self.cache_dir, **kwargs)
+
+    def test_argv(self):
+        path = self.bootstrap(
+            include=["git", "build-essential"],
+            mirror="http://deb.example.com/debian")
+        [argv] = self.argvs
+        self.assertEqual(
+            ["mmdebstrap", "--mode=unshare", "--variant=minbase",
+             "--architectures=amd64", "--include=build-essential,git",
+             "unstable"], argv[:6])
+        self.assertEqual(self.cache_dir, os.path.dirname(argv[6]))
+        self.assertEqual(["http://deb.example.com/debian"], argv[7:])
+        self.assertEqual(self.cache_dir, os.path.dirname(path))
+        self.assertTrue(
+            os.path.basename(path).startswith("unstable-amd64-"))
+        self.assertEqual([os.path.basename(path)], os.listdir(self.cache_dir))
+
+    def test_cache_path(self):
+        path = self.bootstrap()
+        self.assertEqual(
+            os.path.join(self.cache_dir, "unstable-amd64.tar.gz"), path)
+        self.assertNotEqual(path, self.bootstrap(include=["git"]))
+        self.assertEqual(2, len(self.argvs))
+
+    def test_cached(self):
+        path = self.bootstrap()
+        self.assertEqual(path, self.bootstrap())
+        self.assertEqual(1, len(self.argvs))
+
+    def test_expired(self):
+        path = self.bootstrap()
+        old = time.time() - 3600
+        os.utime(path, (old, old))
+        self.assertEqual(path, self.bootstrap(max_age=60))
+        self.assertEqual(2, len(self.argvs))
+
+    def test_failure(self):
+        from ..session import SessionSetupFailure
+
+        def check_call(argv):
+            raise subprocess.CalledProcessError(1, argv)
+
+        self.check_call = check_call
+        self.assertRaises(SessionSetupFailure, self.bootstrap)
+        self.assertEqual([], os.listdir(self.cache_dir))
+
+    def test_session(self):
+        from ..session.unshare import BindMount, UnshareSession
+
+        with mock.patch(
+                "ognibuild.session.unshare.bootstrap_tarball",
+                return_value="/cache/unstable-amd64.tar.gz") as bootstrap, \
+                mock.patch.object(UnshareSession, "from_image") as from_image:
+            UnshareSession.bootstrap(
+                "unstable", arch="amd64", no_network=True,
+                bind_mounts=[BindMount("/tmp", "/cache")])
+        bootstrap.assert_called_once_with(
+            "unstable", arch="amd64", mirror=None, include=None,
+            cache_dir=None, max_age=mock.ANY)
+        from_image.assert_called_once_with(
+            "/cache/unstable-amd64.tar.gz", no_network=True,
+            bind_mounts=[BindMount("/dev", "/dev"),
+                         BindMount("/tmp", "/cache")])
+
+
+class ProotSessionTests(TestCase):
+    def run_argv(self, bind_mounts):
+        from ..session.proot import ProotSession
+
+        with tempfile.TemporaryDirectory() as root, mock.patch(
+                "shutil.which", return_value="/usr/bin/proot"):
+            session = ProotSession(root, bind_mounts=bind_mounts)
+            with session:
+                argv = session._run_argv(["make", "check"])
+        return [argv[i + 1] for i, arg in enumerate(argv) if arg == "-b"]
+
+    def test_default_binds(self):
+        self.assertEqual(["/dev", "/proc", "/sys"], self.run_argv([]))
+
+    def test_bind_mounts(self):
+        from ..session.unshare import BindMount
+
+        self.assertEqual(
+            ["/proc", "/sys", "/dev:/dev", "/tmp:/cache"],
+            self.run_argv([
+                BindMount("/dev", "/dev"), BindMount("/tmp", "/cache")]))
+
+
+class ScandirTests(TestCase):
+    def test_plain(self):
+        import os
+        import tempfile
+
+        with tempfile.TemporaryDirectory() as td:
+            os.mkdir(os.path.join(td, "subdir"))
+            with open(os.path.join(td, "afile"), "w") as f:
+                f.write("contents")
+            os.symlink("subdir", os.path.join(td, "link"))
+            os.symlink("missing", os.path.join(td, "dangling"))
+            with PlainSession() as session:
+                entries = {e.name: e for e in session.scandir(td)}
+        self.assertEqual({"subdir", "afile", "link", "dangling"}, set(entries))
+        self.assertEqual("dir", entries["subdir"].file_type)
+        self.assertEqual("file", entries["afile"].file_type)
+        self.assertEqual(8, entries["afile"].stat().st_size)
+        self.assertEqual(os.path.join(td, "afile"), entries["afile"].path)
+        self.assertTrue(entries["link"].is_symlink())
+        self.assertTrue(entries["link"].is_dir())
+        self.assertFalse(entries["link"].is_dir(follow_symlinks=False))
+        self.assertIsNone(entries["dangling"].metadata)
+        self.assertFalse(entries["dangling"].is_file())
+
+
+class OrphanNameTests(TestCase):
+    def test_own(self):
+        from ..session import is_orphan_name, owned_name_prefix
+
+        self.assertFalse(is_orphan_name(owned_name_prefix() + "abc"))
+
+    def test_unrelated(self):
+        from ..session import is_orphan_name
+
+        self.assertFalse(is_orphan_name("tmpabc"))
+
+    def test_dead(self):
+        import subprocess
+
+        from ..session import is_orphan_name, ORPHAN_PREFIX
+
+        p = subprocess.Popen(["true"])
+        p.wait()
+        self.assertTrue(is_orphan_name("%s%d-abc" % (ORPHAN_PREFIX, p.pid)))
+
+
+class OrphanUnshareProcessTests(TestCase):
+    def setUp(self):
+        self.td = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.td)
+        self.proc = os.path.join(self.td, "proc")
+        os.mkdir(self.proc)
+
+    def add_process(self, pid, argv, ppid, root):
+        path = os.path.join(self.proc, str(pid))
+        os.mkdir(path)
+        with open(os.path.join(path, "cmdline"), "wb") as f:
+            f.write("\0".join(argv).encode())
+        with open(os.path.join(path, "stat"), "w") as f:
+            f.write("%d (%s) S %d 0 0" % (pid, os.path.basename(argv[0]), ppid))
+        os.symlink(root, os.path.join(path, "root"))
+
+    def test_orphan(self):
+        from ..session.cleanup import _orphan_unshare_processes
+
+        root = os.path.join(self.td, "chroot")
+        os.mkdir(root)
+        self.add_process(10, ["unshare", "--fork", "--root=" + root], 1, "/")
+        self.add_process(11, ["make"], 10, root)
+        self.assertEqual([10], _orphan_unshare_processes([root], self.proc))
+
+    def test_prefix_of_other_root(self):
+        from ..session.cleanup import _orphan_unshare_processes
+
+        root = os.path.join(self.td, "chroot")
+        os.mkdir(root)
+        os.mkdir(root + "-2")
+        self.add_process(10, ["unshare", "--root=" + root + "-2"], 1, "/")
+        self.add_process(11, ["make"], 10, root + "-2")
+        self.assertEqual([], _orphan_unshare_processes([root], self.proc))
+
+    def test_parent_alive(self):
+        from ..session.cleanup import _orphan_unshare_processes
+
+        root = os.path.join(self.td, "chroot")
+        os.mkdir(root)
+        self.add_process(10, ["unshare", "--root=" + root], 5, "/")
+        self.add_process(11, ["make"], 10, root)
+        self.assertEqual([], _orphan_unshare_processes([root], self.proc))
+
+
+class MetricsTests(TestCase):
+    def test_collect(self):
+        from ..session.metrics import MetricsCollector, metrics_phase
+
+        session = PlainSession()
+        session.set_metrics_collector(MetricsCollector())
+        with session:
+            with metrics_phase(session, "build"):
+                run_with_tee(session, ["sh", "-c", "echo a; exit 3"])
+            run_with_callback(session, ["true"], lambda line: None)
+        self.assertEqual(
+            [(["sh", "-c", "echo a; exit 3"], "build", 3), (["true"], None, 0)],
+            [(m.argv, m.phase, m.returncode) for m in session.metrics.commands])
+        self.assertIsNotNone(session.metrics.commands[0].max_rss)
+        report = session.metrics.report()
+        self.assertEqual(1, report["build"].commands)
+        self.assertEqual(1, report["build"].failures)
+
+
+class CommandWrapperTests(TestCase):
+    def test_parse(self):
+        from ..session.wrappers import (
+            parse_wrapper, EatMyData, IONice, Nice, Timeout)
+
+        self.assertEqual(EatMyData(), parse_wrapper("eatmydata"))
+        self.assertEqual(Nice(5), parse_wrapper("nice=5"))
+        self.assertEqual(IONice("best-effort", 7), parse_wrapper("ionice=best-effort:7"))
+        self.assertEqual(Timeout(60), parse_wrapper("timeout=60"))
+        self.assertRaises(ValueError, parse_wrapper, "timeout")
+        self.assertRaises(ValueError, parse_wrapper, "eatmydata=1")
+        self.assertRaises(ValueError, parse_wrapper, "unknown")
+
+    def test_wrap(self):
+        from ..session.wrappers import Nice, Timeout
+
+        with PlainSession() as session:
+            session.add_command_wrapper(Timeout(60))
+            session.add_command_wrapper(Nice(3))
+            self.assertEqual(
+                ["timeout", "--kill-after=30", "60", "nice", "-n", "3", "ls"],
+                session._wrap_argv(["ls"]))
+            self.assertEqual(b"3\n", session.check_output(["nice"]))
+
+    def test_nix_shell(self):
+        from ..session.wrappers import NixShell, parse_wrapper
+
+        self.assertEqual(
+            NixShell(["gcc", "pkg-config"]), parse_wrapper("nix-shell=gcc,pkg-config"))
+        self.assertEqual(["make"], NixShell().wrap(["make"]))
+        self.assertEqual(
+            ["nix-shell", "-p", "gcc", "--run", "make 'CFLAGS=-O2 -g'"],
+            NixShell(["gcc"]).wrap(["make", "CFLAGS=-O2 -g"]))
+
+
+class TeardownHookTests(TestCase):
+    def test_plain(self):
+        called = []
+        with PlainSession() as session:
+            session.on_teardown(lambda s: called.append(("first", s)))
+            session.on_teardown(lambda s: called.append(("second", s)))
+            self.assertEqual([], called)
+        self.assertEqual([("second", session), ("first", session)], called)
+
+    def test_unshare_failure(self):
+        import tempfile
+        from ..session import SessionTeardownFailure
+        from ..session.unshare import UnshareSession
+
+        def copy_out(session):
+            self.assertTrue(os.path.isdir(session.external_path("/build")))
+            raise OSError("disk full")
+
+        with tempfile.TemporaryDirectory() as root:
+            os.mkdir(os.path.join(root, "build"))
+            session = UnshareSession(root)
+            session.__enter__()
+            session.on_teardown(copy_out)
+            with self.assertRaises(SessionTeardownFailure) as cm:
+                session.close()
+            self.assertEqual(1, len(cm.exception.errors))
+            self.assertFalse(session.ping())
+
+
+class WorkDirTests(TestCase):
+    def setUp(self):
+        super(WorkDirTests, self).setUp()
+        patcher = mock.patch.dict(os.environ)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+        os.environ.pop("OGNIBUILD_WORK_DIR", None)
+        os.environ.pop("OGNIBUILD_PLAIN_WORK_DIR", None)
+
+    def test_default(self):
+        from ..session import default_work_dir
+
+        self.assertIsNone(default_work_dir("plain"))
+        os.environ["OGNIBUILD_WORK_DIR"] = "/srv/scratch"
+        self.assertEqual("/srv/scratch", default_work_dir("plain"))
+        os.environ["OGNIBUILD_PLAIN_WORK_DIR"] = "/srv/plain"
+        self.assertEqual("/srv/plain", default_work_dir("plain"))
+        self.assertEqual("/srv/scratch", default_work_dir("unshare"))
+
+    def test_set_work_dir(self):
+        from tempfile import TemporaryDirectory
+
+        with TemporaryDirectory() as td:
+            with PlainSession() as session:
+                session.set_work_dir(os.path.join(td, "work"))
+                self.assertEqual(
+                    os.path.join(td, "work"), session._temporary_parent())
+                self.assertTrue(os.path.isdir(os.path.join(td, "work")))
+
+    def test_size_limited_tmpfs(self):
+        from ..session import mount_size_limited_tmpfs, unmount_tmpfs
+
+        with tempfile.TemporaryDirectory() as td:
+            path = os.path.join(td, "work")
+            with mock.patch("subprocess.check_call") as check_call:
+                mount_size_limited_tmpfs(path, "2G")
+            self.assertTrue(os.path.isdir(path))
+            check_call.assert_called_once_with(
+                ["mount", "-t", "tmpfs", "-o", "size=2G,mode=1777",
+                 "ognibuild-work", path])
+            with mock.patch("subprocess.call", return_value=0) as call:
+                unmount_tmpfs(path)
+            call.assert_called_once_with(["umount", "--lazy", path])
+
+
+class ImageTests(TestCase):
+    def test_export(self):
+        from ..session import Session
+
+        session = mock.Mock(image_excludes=Session.image_excludes)
+        session.Popen.return_value.wait.return_value = 0
+        with tempfile.TemporaryDirectory() as td:
+            Session.export_image(session, os.path.join(td, "image.tar.gz"))
+        argv = session.Popen.call_args.args[0]
+        self.assertEqual(["-C", "/", "-czf", "-", "."], argv[-5:])
+        self.assertIn("--exclude=./proc", argv)
+        self.assertEqual("root", session.Popen.call_args.kwargs["user"])
+
+    def test_export_failure(self):
+        from ..session import Session
+
+        session = mock.Mock(image_excludes=[])
+        session.Popen.return_value.wait.return_value = 2
+        session.Popen.return_value.returncode = 2
+        with tempfile.TemporaryDirectory() as td:
+            self.assertRaises(
+                subprocess.CalledProcessError, Session.export_image, session,
+                os.path.join(td, "image.tar.gz"))
+
+    def test_from_image(self):
+        import tarfile
+        from ..session.unshare import UnshareSession
+
+        with tempfile.TemporaryDirectory() as td:
+            os.makedirs(os.path.join(td, "src", "etc"))
+            with open(os.path.join(td, "src", "etc", "hostname"), "w") as f:
+                f.write("builder\n")
+            image = os.path.join(td, "image.tar.gz")
+            with tarfile.open(image, "w:gz") as tf:
+                tf.add(os.path.join(td, "src"), arcname=".")
+            session = UnshareSession.from_image(
+                image, work_dir=os.path.join(td, "work"))
+            self.assertEqual(
+                os.path.join(td, "work"), os.path.dirname(session.root))
+            self.assertTrue(session._remove_root)
+            with open(os.path.join(session.root, "etc", "hostname")) as f:
+                self.assertEqual("builder\n", f.read())
+            for name in ["proc", "sys", "dev", "tmp"]:
+                self.assertTrue(
+                    os.path.isdir(os.path.join(session.root, name)))
+            tmp = os.stat(os.path.join(session.root, "tmp"))
+            self.assertEqual(0o1777, tmp.st_mode & 0o7777)
+
+
+class CommandFailedTests(TestCase):
+    def test_check_output(self):
+        from ..session import CommandFailed
+
+        with PlainSession() as session:
+            with self.assertRaises(CommandFailed) as cm:
+                session.check_output(
+                    ["sh", "-c", "echo one; echo two; exit 3"], cwd="/")
+        self.assertEqual(3, cm.exception.returncode)
+        self.assertEqual(["sh", "-c", "echo one; echo two; exit 3"], cm.exception.cmd)
+        self.assertEqual("/", cm.exception.cwd)
+        self.assertEqual(["one", "two"], cm.exception.tail())
+        self.assertIsNone(cm.exception.signal)
+        self.assertEqual(
+            "Command 'sh -c 'echo one; echo two; exit 3'' (in /) returned "
+            "non-zero exit status 3.\nLast output:\none\ntwo",
+            str(cm.exception))
+
+    def test_signal(self):
+        from ..session import CommandFailed
+
+        e = CommandFailed(-9, ["sleep", "100"], cwd="/tmp")
+        self.assertEqual(9, e.signal)
+        self.assertEqual(
+            "Command 'sleep 100' (in /tmp) died with SIGKILL.", str(e))
+
+
+class SchrootConfigTests(TestCase):
+    def test_parse(self):
+        from ..session.schroot import SchrootConfig
+
+        config = SchrootConfig.parse("""\
+# schroot configuration generated by schroot 1.6.13
+
+[unstable-amd64-sbuild]
+type=directory
+directory=/srv/chroot/unstable-amd64-sbuild
+union-type=overlay
+profile=sbuild
+aliases=sid-amd64-sbuild,unstable
+""")
+        self.assertEqual("unstable-amd64-sbuild", config.name)
+        self.assertEqual("directory", config.type)
+        self.assertEqual("overlay", config.union_type)
+        self.assertEqual("/srv/chroot/unstable-amd64-sbuild", config.directory)
+        self.assertEqual("sbuild", config.profile)
+        self.assertEqual(["sid-amd64-sbuild", "unstable"], config.aliases)
+
+    def test_no_union(self):
+        from ..session.schroot import SchrootConfig
+
+        config = SchrootConfig.parse("[foo]\ntype=file\nunion-type=none\n")
+        self.assertIsNone(config.union_type)
+
+
+class BindMountTests(TestCase):
+    def test_parse(self):
+        from ..session.unshare import BindMount
+
+        self.assertEqual(
+            BindMount("/var/cache/ccache", "/var/cache/ccache"),
+            BindMount.parse("/var/cache/ccache"))
+        self.assertEqual(
+            BindMount("/srv/out", "/build/out", read_only=True),
+            BindMount.parse("/srv/out:/build/out:ro"))
+        self.assertRaises(ValueError, BindMount.parse, "/srv/out:relative")
+
+
+class SchrootSessionTests(TestCase):
+    def setUp(self):
+        super(SchrootSessionTests, self).setUp()
+        self.sessions = 0
+        self.check_calls = []
+        self.calls = []
+        # Sessions that have gone away
+        self.lost = set()
+        for name, fn in [
+                ("check_output", self.check_output),
+                ("check_call", self.check_call),
+                ("call", self.call)]:
+            patcher = mock.patch("subprocess." + name, side_effect=fn)
+            patcher.start()
+            self.addCleanup(patcher.stop)
+        patcher = mock.patch("os.getuid", return_value=0)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def check_output(self, argv, **kwargs):
+        if argv[:2] == ["schroot", "--location"]:
+            return ("/run/schroot/mount/%s\n" % argv[-1].split(":")[1]).encode()
+        if "-b" in argv:
+            self.sessions += 1
+            return b"session-%d\n" % self.sessions
+        return b""
+
+    def check_call(self, argv, **kwargs):
+        self.check_calls.append(argv)
+        if argv[:2] == ["schroot", "-r"] and argv[3] in self.lost:
+            raise subprocess.CalledProcessError(1, argv)
+
+    def call(self, argv, **kwargs):
+        self.calls.append(argv)
+        # ping
+        if argv[:2] == ["schroot", "-r"] and argv[3] in self.lost:
+            return 1
+        return 0
+
+    def test_reconnect_not_safe(self):
+        from ..session.schroot import SchrootSession
+
+        session = SchrootSession("unstable-amd64-sbuild", auto_reconnect=True)
+        session.__enter__()
+        self.addCleanup(session.close)
+        self.lost.add("session:session-1")
+        self.assertRaises(SessionLost, session.check_call, ["make", "install"])
+        self.assertEqual("session-2", session.session_id)
+        self.assertEqual(
+            [["schroot", "-r", "-c", "session:session-1", "--",
+              "make", "install"]], self.check_calls)
+
+    def test_reconnect_update_once(self):
+        from ..session.schroot import SchrootSession
+
+        session = SchrootSession(
+            "unstable-amd64-sbuild", auto_reconnect=True, update_chroot=True)
+        session.__enter__()
+        self.addCleanup(session.close)
+        session._session_lost()
+        self.assertEqual(
+            1, len([argv for argv in self.check_calls
+                    if argv[0] == "sbuild-update"]))
+
+    def test_reconnect_safe(self):
+        from ..session.schroot import SchrootSession
+
+        session = SchrootSession(
+            "unstable-amd64-sbuild", auto_reconnect=True,
+            retry_safe=lambda argv: argv[0] == "ls")
+        session.__enter__()
+        self.addCleanup(session.close)
+        self.lost.add("session:session-1")
+        session.check_call(["ls"])
+        self.assertEqual([
+            ["schroot", "-r", "-c", "session:session-1", "--", "ls"],
+            ["schroot", "-r", "-c", "session:session-2", "--", "ls"]],
+            self.check_calls)
+
+    def test_reconnect(self):
+        from ..session.schroot import SchrootSession
+        from ..session.unshare import BindMount
+
+        session = SchrootSession(
+            "unstable-amd64-sbuild", auto_reconnect=True,
+            bind_mounts=[BindMount("/srv/cache", "/var/cache/foo")])
+        session.__enter__()
+        session._session_lost()
+        session.close()
+        self.assertEqual([
+            ["mkdir", "-p", "/run/schroot/mount/session-1/var/cache/foo"],
+            ["mount", "--bind", "/srv/cache",
+             "/run/schroot/mount/session-1/var/cache/foo"],
+            ["mkdir", "-p", "/run/schroot/mount/session-2/var/cache/foo"],
+            ["mount", "--bind", "/srv/cache",
+             "/run/schroot/mount/session-2/var/cache/foo"],
+            ["umount", "/run/schroot/mount/session-2/var/cache/foo"]],
+            self.check_calls)
+        self.assertEqual([
+            ["umount", "--lazy",
+             "/run/schroot/mount/session-1/var/cache/foo"]], self.calls)
+
+
+class OutputForwarderTests(TestCase):
+    def test_format(self):
+        from ..session.output import OutputForwarder
+
+        self.assertEqual(
+            b"foo\n", OutputForwarder().format("stdout", b"foo\n", 0))
+        self.assertEqual(
+            b"1970-01-01T00:00:01.500Z [stderr] foo\n",
+            OutputForwarder(timestamps=True, tags=True).format(
+                "stderr", b"foo\n", 1.5))
+
+    def test_sink(self):
+        from io import BytesIO
+        from ..session.output import OutputForwarder
+
+        sink = BytesIO()
+        with PlainSession() as session:
+            session.set_output_forwarder(
+                OutputForwarder(tags=True, sink=sink, console=False))
+            retcode, lines = run_with_tee(
+                session, ["sh", "-c", "echo out; echo err >&2"],
+                separate_streams=True)
+        self.assertEqual(0, retcode)
+        self.assertEqual(
+            [b"[stderr] err", b"[stdout] out"],
+            sorted(sink.getvalue().splitlines()))
+
+
+class ResourceLimitsTests(TestCase):
+    def setUp(self):
+        super(ResourceLimitsTests, self).setUp()
+        patcher = mock.patch(
+            "ognibuild.session.cgroups_v2_available", return_value=True)
+        self.cgroups_available = patcher.start()
+        self.addCleanup(patcher.stop)
+        patcher = mock.patch("os.getuid", return_value=1000)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_cgroup_argv(self):
+        from ..session import ResourceLimits
+
+        self.assertEqual(
+            ["systemd-run", "--scope", "--quiet", "--collect", "--user",
+             "-p", "MemoryMax=1048576", "-p", "TasksMax=20", "--"],
+            ResourceLimits(memory=1024 * 1024, pids=20).cgroup_argv())
+
+    def test_cgroup_argv_cpu_only(self):
+        from ..session import ResourceLimits
+
+        limits = ResourceLimits(cpu_time=60)
+        self.assertEqual([], limits.cgroup_argv())
+        self.assertEqual(["prlimit", "--cpu=60", "--"], limits.prlimit_argv())
+
+    def test_no_cgroups(self):
+        from ..session import ResourceLimits
+
+        self.cgroups_available.return_value = False
+        limits = ResourceLimits(memory=1024 * 1024)
+        with self.assertLogs(level="WARNING"):
+            self.assertEqual([], limits.cgroup_argv())
+        self.assertEqual([], limits.prlimit_argv())
+
+    def test_setrlimits(self):
+        import resource
+        from ..session import ResourceLimits
+
+        with mock.patch("resource.setrlimit") as setrlimit:
+            ResourceLimits(memory=1024, cpu_time=60, pids=20).setrlimits()
+        setrlimit.assert_called_once_with(resource.RLIMIT_CPU, (60, 60))
+
+    def test_plain(self):
+        from ..session import ResourceLimits
+
+        with PlainSession() as session:
+            session.set_resource_limits(ResourceLimits(pids=20))
+            with mock.patch("subprocess.check_output") as check_output:
+                session.check_output(["true"])
+        self.assertEqual(
+            ["systemd-run", "--scope", "--quiet", "--collect", "--user",
+             "-p", "TasksMax=20", "--", "true"],
+            check_output.call_args.args[0])
+
+    def test_unshare(self):
+        from ..session import ResourceLimits
+        from ..session.unshare import UnshareSession
+
+        with tempfile.TemporaryDirectory() as root:
+            session = UnshareSession(root)
+            session.set_resource_limits(
+                ResourceLimits(memory=1024 * 1024, cpu_time=60))
+            with session:
+                argv = session._run_argv(["make"])
+        self.assertEqual("systemd-run", argv[0])
+        self.assertEqual("unshare", argv[argv.index("--") + 1])
+        self.assertEqual(["prlimit", "--cpu=60", "--", "make"], argv[-4:])
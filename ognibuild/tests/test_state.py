@@ -0,0 +1,113 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+import os
+import shutil
+import tempfile
+from unittest import TestCase
+
+from .. import DetailedFailure
+from ..fix_build import BuildFixer, iterate_with_build_fixers
+from ..state import (
+    ProjectState,
+    RecordingFixer,
+    default_state_dir,
+    detect_buildsystems_with_state,
+    project_fingerprint,
+)
+
+
+class ProjectStateTests(TestCase):
+    def setUp(self):
+        super(ProjectStateTests, self).setUp()
+        self.test_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.test_dir)
+
+    def test_roundtrip(self):
+        state = ProjectState(default_state_dir(self.test_dir))
+        state.detection = {"fingerprint": "abc", "buildsystems": ["Make"]}
+        state.record_dependencies(["make", "make", "gcc"])
+        state.record_fix(("build", ), "missing file /usr/bin/make", "InstallFixer")
+        state.save()
+        loaded = ProjectState.load(default_state_dir(self.test_dir))
+        self.assertEqual(state.detection, loaded.detection)
+        self.assertEqual(["make", "gcc"], loaded.dependencies)
+        self.assertEqual([{
+            "phase": "build", "problem": "missing file /usr/bin/make",
+            "fixer": "InstallFixer"}], loaded.fix_history)
+
+    def test_clear(self):
+        state = ProjectState(default_state_dir(self.test_dir))
+        state.record_dependencies(["make"])
+        state.save()
+        state.clear()
+        self.assertFalse(os.path.exists(state.path))
+        self.assertEqual([], ProjectState.load(state.path).dependencies)
+
+    def test_fingerprint_ignores_state(self):
+        before = project_fingerprint(self.test_dir)
+        ProjectState(default_state_dir(self.test_dir)).save()
+        self.assertEqual(before, project_fingerprint(self.test_dir))
+        with open(os.path.join(self.test_dir, "Makefile"), "w"):
+            pass
+        self.assertNotEqual(before, project_fingerprint(self.test_dir))
+
+    def test_detection_priority(self):
+        with open(os.path.join(self.test_dir, "Makefile"), "w"):
+            pass
+        with open(os.path.join(self.test_dir, "meson.build"), "w") as f:
+            f.write("project('foo')\n")
+        state = ProjectState(default_state_dir(self.test_dir))
+        self.assertEqual(
+            ["Meson", "Make"],
+            [type(bs).__name__ for bs in detect_buildsystems_with_state(
+                self.test_dir, state, priority=[])])
+        # A different priority invalidates the cached detection
+        self.assertEqual(
+            ["Make", "Meson"],
+            [type(bs).__name__ for bs in detect_buildsystems_with_state(
+                self.test_dir, state, priority=["make"])])
+        self.assertEqual(
+            ["Make"],
+            [type(bs).__name__ for bs in detect_buildsystems_with_state(
+                self.test_dir, state, override=["make"])])
+
+
+class RecordingFixerTests(TestCase):
+    class DummyFixer(BuildFixer):
+        def __str__(self):
+            return "DummyFixer"
+
+        def can_fix(self, problem):
+            return problem == "missing-thing"
+
+        def _fix(self, problem, phase):
+            return True
+
+    def test_records_fix(self):
+        state = ProjectState("/nonexistent")
+        fixer = RecordingFixer(self.DummyFixer(), state)
+        failures = [DetailedFailure(1, ["make"], "missing-thing")]
+
+        def cb():
+            if failures:
+                raise failures.pop()
+            return "done"
+
+        self.assertEqual("done", iterate_with_build_fixers([fixer], cb))
+        self.assertEqual([{
+            "phase": None, "problem": "missing-thing",
+            "fixer": "DummyFixer"}], state.fix_history)
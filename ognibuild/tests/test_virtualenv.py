@@ -0,0 +1,98 @@
+#!/usr/bin/python
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+import os
+import subprocess
+from unittest import TestCase
+
+from ..requirements import PythonPackageRequirement
+from ..session.plain import PlainSession
+from ..virtualenv import activate_virtualenv, active_virtualenv
+
+
+class DummySession(PlainSession):
+    def __init__(self, existing=(), fail_with_pip=False):
+        super(DummySession, self).__init__()
+        self.existing = set(existing)
+        self.fail_with_pip = fail_with_pip
+        self.calls = []
+
+    def check_call(self, argv, cwd=None, user=None, env=None, close_fds=True):
+        self.calls.append(argv)
+        if self.fail_with_pip and "--without-pip" not in argv:
+            raise subprocess.CalledProcessError(1, argv)
+        # python3 -m venv --system-site-packages PATH
+        self.existing.add(os.path.join(argv[4], "bin", "python"))
+
+    def check_output(self, argv, cwd=None, user=None, env=None):
+        assert argv == ["sh", "-c", "echo $PATH"], argv
+        return b"/usr/bin:/bin\n"
+
+    def exists(self, path):
+        return path in self.existing
+
+
+class ActivateVirtualenvTests(TestCase):
+    def test_create(self):
+        session = DummySession()
+        activate_virtualenv(session, "/tmp/venv")
+        self.assertEqual(
+            [["python3", "-m", "venv", "--system-site-packages", "/tmp/venv"]],
+            session.calls)
+        self.assertEqual("/tmp/venv", active_virtualenv(session))
+        self.assertEqual("/tmp/venv/bin:/usr/bin:/bin", session.getenv("PATH"))
+
+    def test_without_pip(self):
+        session = DummySession(fail_with_pip=True)
+        activate_virtualenv(session, "/tmp/venv")
+        self.assertEqual(
+            ["python3", "-m", "venv", "--system-site-packages", "/tmp/venv",
+             "--without-pip"], session.calls[-1])
+
+    def test_reuse(self):
+        session = DummySession(existing=["/tmp/venv/bin/python"])
+        activate_virtualenv(session, "/tmp/venv")
+        self.assertEqual([], session.calls)
+        self.assertEqual("/tmp/venv", active_virtualenv(session))
+
+    def test_already_active(self):
+        session = DummySession()
+        activate_virtualenv(session, "/tmp/venv")
+        activate_virtualenv(session, "/tmp/venv")
+        self.assertEqual(1, len(session.calls))
+        self.assertEqual("/tmp/venv/bin:/usr/bin:/bin", session.getenv("PATH"))
+
+
+class PythonPackageMetTests(TestCase):
+    def test_uses_virtualenv_python(self):
+        argvs = []
+
+        class Process(object):
+            returncode = 0
+
+            def communicate(self):
+                return (None, None)
+
+        class Session(PlainSession):
+            def Popen(self, argv, **kwargs):
+                argvs.append(argv)
+                return Process()
+
+        session = Session()
+        session.setenv("VIRTUAL_ENV", "/tmp/venv")
+        self.assertTrue(PythonPackageRequirement("foo").met(session))
+        self.assertEqual("/tmp/venv/bin/python", argvs[0][0])
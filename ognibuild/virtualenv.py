@@ -0,0 +1,75 @@
+#!/usr/bin/python3
+# Copyright (C) 2021 Jelmer Vernooij <jelmer@jelmer.uk>
+#
+# This program is free software; you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation; either version 2 of the License, or
+# (at your option) any later version.
+#
+# This program is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with this program; if not, write to the Free Software
+# Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA
+
+"""Virtual environments for Python builds.
+
+A virtualenv is activated by setting VIRTUAL_ENV and putting its bin
+directory first on PATH in the session, so that python3 and pip run by
+build tools, requirement checks and resolvers all use it. The virtualenv
+has access to the system site packages, so that packages installed with
+apt are still found.
+"""
+
+import logging
+import os
+import subprocess
+from typing import Optional
+
+from .session import Session, create_temporary_directory
+
+
+def active_virtualenv(session: Session) -> Optional[str]:
+    """Return the path of the virtualenv active in a session, if any."""
+    return session.getenv("VIRTUAL_ENV")
+
+
+def create_virtualenv_directory(session: Session) -> str:
+    """Create a directory for a virtualenv inside a session.
+
+    The virtualenv is kept out of the source tree, so that it does not end up
+    in tarballs or confuse build tools; it is removed along with the session.
+    """
+    return create_temporary_directory(session, "ognibuild-venv")
+
+
+def activate_virtualenv(
+    session: Session, path: str, python: str = "python3"
+) -> None:
+    """Create a virtualenv in a session, if it does not exist, and activate it.
+
+    Args:
+      session: Session to activate the virtualenv in
+      path: Absolute path of the virtualenv inside the session
+      python: Interpreter to create the virtualenv with
+    """
+    if active_virtualenv(session) == path:
+        return
+    if session.exists(os.path.join(path, "bin", "python")):
+        logging.info("Reusing virtualenv %s", path)
+    else:
+        logging.info("Creating virtualenv %s", path)
+        argv = [python, "-m", "venv", "--system-site-packages", path]
+        try:
+            session.check_call(argv)
+        except subprocess.CalledProcessError:
+            # Debian ships ensurepip separately (python3-venv); the system pip
+            # installs into the virtualenv just as well
+            session.check_call(argv + ["--without-pip"])
+    base_path = session.getenv("PATH") or session.check_output(
+        ["sh", "-c", "echo $PATH"], cwd="/").decode().strip()
+    session.setenv("VIRTUAL_ENV", path)
+    session.setenv("PATH", "%s/bin:%s" % (path, base_path))